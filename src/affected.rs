@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --affected-by: lists the test binaries that execute code in the given files or functions, so
+// that only the relevant tests need to be run after editing them.
+//
+// The profile data of each binary is found by the binary IDs embedded in profraw files. Merging
+// and exporting it separately for each binary is slow, so the functions executed by each binary
+// are saved to the run manifest and reused until the profraw files change.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fmt::Write as _,
+    path::Path,
+};
+
+use anyhow::{Context as _, Result, bail};
+use serde_derive::Serialize;
+
+use crate::{
+    Format, binary_id, binary_ids,
+    context::Context,
+    make_relative,
+    manifest::{Manifest, ObjectCoverage},
+    merge_profraw_files, profraw_files_modified, write_report,
+};
+
+#[derive(Serialize)]
+struct AffectedObject {
+    object: String,
+    functions: Vec<AffectedFunction>,
+}
+
+#[derive(Serialize)]
+struct AffectedFunction {
+    filename: String,
+    name: String,
+}
+
+pub(crate) fn generate(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    let coverage = object_coverage(cx, object_files, ignore_filename_regex)?;
+    let mut affected = vec![];
+    for (object, files) in &coverage.objects {
+        let mut functions = vec![];
+        for (filename, names) in files {
+            for name in names {
+                if cx.args.cov.affected_by.iter().any(|query| is_match(query, filename, name)) {
+                    functions
+                        .push(AffectedFunction { filename: filename.clone(), name: name.clone() });
+                }
+            }
+        }
+        if !functions.is_empty() {
+            let object = make_relative(cx, Path::new(object)).to_string_lossy().into_owned();
+            affected.push(AffectedObject { object, functions });
+        }
+    }
+    if affected.is_empty() {
+        info!(
+            "--affected-by: no test binaries execute code in {}",
+            cx.args.cov.affected_by.join(", ")
+        );
+    }
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&affected)?
+    } else {
+        let mut out = String::new();
+        for object in &affected {
+            let _ = writeln!(out, "{}", object.object);
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+/// Returns the functions executed by each instrumented object, computing them if the ones
+/// saved in the run manifest are outdated.
+fn object_coverage(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<ObjectCoverage> {
+    let profraw_files: Vec<_> = profraw_files_modified(cx)?.into_keys().collect();
+    let mut manifest = Manifest::load(&cx.ws)?.unwrap_or_default();
+    let key: BTreeSet<_> =
+        profraw_files.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+    if let Some(coverage) = manifest.object_coverage.take().filter(|coverage| {
+        coverage.profraw_files == key
+            && coverage.ignore_filename_regex.as_ref() == ignore_filename_regex
+    }) {
+        return Ok(coverage);
+    }
+
+    let ids = binary_ids(object_files);
+    if ids.is_empty() {
+        bail!(
+            "--affected-by: no instrumented objects with binary IDs found (binary IDs are \
+             currently only available on ELF targets when linked with build IDs)"
+        );
+    }
+    // object file path -> profraw files generated by it
+    let mut object_profraw_files: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for profraw in &profraw_files {
+        let profraw_ids = binary_id::profraw_binary_ids(cx, profraw)?;
+        for (object, id) in &ids {
+            if profraw_ids.contains(id) {
+                object_profraw_files.entry(object).or_default().push(profraw.clone());
+            }
+        }
+    }
+    let profdata_file = &cx.ws.target_dir.join(format!("{}-affected.profdata", cx.ws.name));
+    let input_files_path = &cx.ws.target_dir.join(format!("{}-affected-profraw-list", cx.ws.name));
+    let mut objects = BTreeMap::new();
+    for (object, profraw_files) in object_profraw_files {
+        merge_profraw_files(cx, profraw_files, input_files_path, profdata_file)
+            .with_context(|| format!("failed to merge profile data of {object}"))?;
+        let json = Format::Json
+            .get_json_with_profdata(
+                cx,
+                profdata_file,
+                &[OsString::from(object)],
+                ignore_filename_regex,
+            )
+            .context("failed to get json")?;
+        let functions = json.get_executed_functions(ignore_filename_regex.map(String::as_str))?;
+        objects.insert(object.to_owned(), functions);
+    }
+
+    let coverage = ObjectCoverage {
+        profraw_files: key,
+        ignore_filename_regex: ignore_filename_regex.cloned(),
+        objects,
+    };
+    manifest.object_coverage = Some(coverage);
+    manifest.save(&cx.ws)?;
+    Ok(manifest.object_coverage.unwrap())
+}
+
+/// Returns `true` if the query is the file name (or a suffix of its path), or the name of the
+/// function (or a suffix of its path, e.g., `Type::method`).
+fn is_match(query: &str, filename: &str, function: &str) -> bool {
+    let has_suffix = |s: &str, suffix: &str, separator: &str| {
+        s == suffix || s.strip_suffix(suffix).is_some_and(|rest| rest.ends_with(separator))
+    };
+    let path = query.strip_prefix("./").unwrap_or(query);
+    has_suffix(filename, path, "/")
+        || has_suffix(filename, path, "\\")
+        || has_suffix(function, query, "::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_match;
+
+    #[test]
+    fn test_is_match() {
+        assert!(is_match("src/lib.rs", "src/lib.rs", "a::f"));
+        assert!(is_match("./src/lib.rs", "src/lib.rs", "a::f"));
+        assert!(is_match("lib.rs", "crates/a/src/lib.rs", "a::f"));
+        assert!(!is_match("ib.rs", "crates/a/src/lib.rs", "a::f"));
+        assert!(is_match("a::T::f", "src/lib.rs", "a::T::f"));
+        assert!(is_match("T::f", "src/lib.rs", "a::T::f"));
+        assert!(!is_match("f", "src/lib.rs", "a::T::ff"));
+        assert!(!is_match("src/main.rs", "src/lib.rs", "a::f"));
+    }
+}
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Generates shields.io-like coverage badges as standalone SVG files, so that
+// badges can be served from the repository or CI artifacts without depending
+// on external services.
+
+use std::{collections::BTreeMap, fmt::Write as _, str::FromStr};
+
+use anyhow::{Context as _, Error, Result, bail};
+use camino::Utf8Path;
+use serde_derive::Serialize;
+
+use crate::fs;
+
+/// Coverage below `low` is shown in red, and coverage of at least `high` is shown in green.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Thresholds {
+    low: f64,
+    high: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { low: 40., high: 90. }
+    }
+}
+
+impl FromStr for Thresholds {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((low, high)) = s.split_once(',') else {
+            bail!("--badge-thresholds must be in `LOW,HIGH` format, but found `{s}`")
+        };
+        let parse = |v: &str| -> Result<f64> {
+            v.trim()
+                .parse()
+                .with_context(|| format!("invalid threshold `{v}` in --badge-thresholds"))
+        };
+        let (low, high) = (parse(low)?, parse(high)?);
+        if !(0. ..=100.).contains(&low) || !(0. ..=100.).contains(&high) || low >= high {
+            bail!("--badge-thresholds must satisfy 0 <= LOW < HIGH <= 100, but found `{s}`")
+        }
+        Ok(Self { low, high })
+    }
+}
+
+#[derive(Serialize)]
+struct Index<'a> {
+    total: Entry<'a>,
+    packages: BTreeMap<&'a str, Entry<'a>>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    /// Line coverage in percent.
+    lines: f64,
+    /// File name of the badge, relative to the index file.
+    badge: &'a str,
+}
+
+/// Writes `coverage.svg` for the total line coverage and `<package>.svg` for each
+/// package to `dir`, and writes `index.json` describing them.
+pub(crate) fn write(
+    dir: &Utf8Path,
+    total: f64,
+    packages: &BTreeMap<String, f64>,
+    thresholds: Thresholds,
+) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("coverage.svg"), svg("coverage", total, thresholds))?;
+    let badges: Vec<_> = packages.keys().map(|name| format!("{name}.svg")).collect();
+    for ((name, &percent), badge) in packages.iter().zip(&badges) {
+        fs::write(dir.join(badge), svg(name, percent, thresholds))?;
+    }
+    let index = Index {
+        total: Entry { lines: total, badge: "coverage.svg" },
+        packages: packages
+            .iter()
+            .zip(&badges)
+            .map(|((name, &lines), badge)| (name.as_str(), Entry { lines, badge }))
+            .collect(),
+    };
+    fs::write(dir.join("index.json"), serde_json::to_string_pretty(&index)?)?;
+    Ok(())
+}
+
+fn color(percent: f64, thresholds: Thresholds) -> &'static str {
+    // Map the coverage onto the default scale (40..90).
+    let Thresholds { low, high } = thresholds;
+    match 40. + (percent - low) * 50. / (high - low) {
+        p if p >= 90. => "#4c1",
+        p if p >= 75. => "#97ca00",
+        p if p >= 60. => "#dfb317",
+        p if p >= 40. => "#fe7d37",
+        _ => "#e05d44",
+    }
+}
+
+fn svg(label: &str, percent: f64, thresholds: Thresholds) -> String {
+    // Approximate width of characters in 11px Verdana.
+    fn width(s: &str) -> usize {
+        s.chars().count() * 7 + 10
+    }
+
+    let value = format!("{percent:.0}%");
+    let (label_width, value_width) = (width(label), width(&value));
+    let total_width = label_width + value_width;
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{total_width}" height="20" role="img" aria-label="{label}: {value}"><title>{label}: {value}</title><linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient><clipPath id="r"><rect width="{total_width}" height="20" rx="3" fill="#fff"/></clipPath><g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/><rect width="{total_width}" height="20" fill="url(#s)"/></g><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11"><text x="{label_x}" y="14">{label}</text><text x="{value_x}" y="14">{value}</text></g></svg>"##,
+        color = color(percent, thresholds),
+        label_x = label_width / 2,
+        value_x = label_width + value_width / 2,
+    );
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Thresholds, color};
+
+    #[test]
+    fn test_color() {
+        let default = Thresholds::default();
+        assert_eq!(color(39.9, default), "#e05d44");
+        assert_eq!(color(40., default), "#fe7d37");
+        assert_eq!(color(75., default), "#97ca00");
+        assert_eq!(color(90., default), "#4c1");
+        let custom: Thresholds = "50,80".parse().unwrap();
+        assert_eq!(color(49.9, custom), "#e05d44");
+        assert_eq!(color(50., custom), "#fe7d37");
+        assert_eq!(color(79.9, custom), "#97ca00");
+        assert_eq!(color(80., custom), "#4c1");
+        assert_eq!(color(100., "0,100".parse().unwrap()), "#4c1");
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!("40,90".parse::<Thresholds>().unwrap(), Thresholds::default());
+        assert_eq!("12.5, 67".parse::<Thresholds>().unwrap(), Thresholds { low: 12.5, high: 67. });
+        "90".parse::<Thresholds>().unwrap_err();
+        "a,90".parse::<Thresholds>().unwrap_err();
+        "90,40".parse::<Thresholds>().unwrap_err();
+        "50,50".parse::<Thresholds>().unwrap_err();
+        "-1,90".parse::<Thresholds>().unwrap_err();
+        "40,101".parse::<Thresholds>().unwrap_err();
+    }
+}
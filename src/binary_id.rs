@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Binary IDs are embedded in profraw files by the profiler runtime, so they can be
+// used to check that profraw files came from the current instrumented objects.
+// Refs: https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/compiler-rt/lib/profile/InstrProfilingPlatformLinux.c
+
+use std::{
+    io::{Read as _, Seek as _, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{context::Context, fs};
+
+const PT_NOTE: u32 = 4;
+const NT_GNU_BUILD_ID: u32 = 3;
+// Notes are small; guard against reading huge segments of broken files.
+const MAX_NOTE_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+/// Returns the hex-encoded GNU build ID of the given ELF file.
+///
+/// Returns `None` if the file is not an ELF file or has no build ID.
+pub(crate) fn elf_build_id(path: &Path) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut header = [0; 64];
+    file.read_exact(&mut header[..52]).ok()?;
+    if header[..4] != *b"\x7fELF" {
+        return None;
+    }
+    let is_64 = match header[4] {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let r = Reader {
+        le: match header[5] {
+            1 => true,
+            2 => false,
+            _ => return None,
+        },
+    };
+    let (ph_off, ph_ent_size, ph_num) = if is_64 {
+        file.read_exact(&mut header[52..]).ok()?;
+        (r.u64(&header[0x20..]), r.u16(&header[0x36..]), r.u16(&header[0x38..]))
+    } else {
+        (r.u32(&header[0x1C..]).into(), r.u16(&header[0x2A..]), r.u16(&header[0x2C..]))
+    };
+
+    let mut ph = vec![0; ph_ent_size.into()];
+    for i in 0..u64::from(ph_num) {
+        file.seek(SeekFrom::Start(ph_off + i * u64::from(ph_ent_size))).ok()?;
+        file.read_exact(&mut ph).ok()?;
+        if r.u32(&ph) != PT_NOTE {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (r.u64(&ph[0x08..]), r.u64(&ph[0x20..]))
+        } else {
+            (r.u32(&ph[0x04..]).into(), r.u32(&ph[0x10..]).into())
+        };
+        if size > MAX_NOTE_SEGMENT_SIZE {
+            continue;
+        }
+        let mut notes = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut notes).ok()?;
+        if let Some(id) = find_build_id(r, &notes) {
+            return Some(id);
+        }
+    }
+    None
+}
+
+fn find_build_id(r: Reader, mut notes: &[u8]) -> Option<String> {
+    while notes.len() >= 12 {
+        let name_size = r.u32(notes) as usize;
+        let desc_size = r.u32(&notes[4..]) as usize;
+        let ty = r.u32(&notes[8..]);
+        let desc_start = 12 + name_size.next_multiple_of(4);
+        let desc = notes.get(desc_start..desc_start + desc_size)?;
+        if ty == NT_GNU_BUILD_ID && notes.get(12..12 + name_size)? == b"GNU\0" {
+            return Some(desc.iter().map(|b| format!("{b:02x}")).collect());
+        }
+        notes = notes.get(desc_start + desc_size.next_multiple_of(4)..)?;
+    }
+    None
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Reader {
+    pub(crate) le: bool,
+}
+
+impl Reader {
+    pub(crate) fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        if self.le { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+    }
+    pub(crate) fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if self.le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }
+    }
+    pub(crate) fn u64(self, b: &[u8]) -> u64 {
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        if self.le { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) }
+    }
+}
+
+/// Returns the binary IDs embedded in the given profraw file.
+pub(crate) fn profraw_binary_ids(cx: &Context, profraw: &Path) -> Result<Vec<String>> {
+    let out = cx.process(&cx.llvm_profdata).args(["show", "--binary-ids"]).arg(profraw).read()?;
+    Ok(out
+        .lines()
+        .skip_while(|line| !line.starts_with("Binary IDs:"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && line.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_owned)
+        .collect())
+}
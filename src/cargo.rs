@@ -1,13 +1,14 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use std::ffi::OsStr;
+use std::{ffi::OsStr, mem};
 
 use anyhow::{Context as _, Result, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_config2::Config;
 
 use crate::{
-    cli::{ManifestOptions, Subcommand},
+    cli::{Args, ManifestOptions, Subcommand},
+    config::ProjectConfig,
     context::Context,
     env,
     metadata::Metadata,
@@ -18,6 +19,8 @@ pub(crate) struct Workspace {
     pub(crate) name: String,
     pub(crate) config: Config,
     pub(crate) metadata: Metadata,
+    /// `[workspace.metadata.llvm-cov]` table.
+    pub(crate) project_config: ProjectConfig,
     pub(crate) current_manifest: Utf8PathBuf,
 
     pub(crate) target_dir: Utf8PathBuf,
@@ -25,6 +28,8 @@ pub(crate) struct Workspace {
     pub(crate) output_dir: Utf8PathBuf,
     pub(crate) doctests_dir: Utf8PathBuf,
     pub(crate) profdata_file: Utf8PathBuf,
+    /// Information about the last run of tests. See manifest.rs for more.
+    pub(crate) manifest_file: Utf8PathBuf,
 
     rustc: ProcessBuilder,
     pub(crate) target_for_config: cargo_config2::TargetTriple,
@@ -49,7 +54,16 @@ impl Workspace {
         // Metadata and config
         let config = Config::load()?;
         let current_manifest = package_root(config.cargo(), options.manifest_path.as_deref())?;
-        let metadata = Metadata::new(current_manifest.as_std_path(), config.cargo())?;
+        let mut metadata = Metadata::new(current_manifest.as_std_path(), config.cargo())?;
+        // cargo metadata does not have --target-dir, so it only reflects CARGO_TARGET_DIR and
+        // the cargo configuration.
+        if let Some(target_dir) = &options.target_dir {
+            let base: Utf8PathBuf = env::current_dir()?.try_into()?;
+            metadata.set_target_directory(base.join(target_dir));
+        }
+        let project_config = ProjectConfig::new(&metadata)?;
+        // --target flag takes precedence over the project configuration.
+        let target = target.or(project_config.target.as_deref());
         let mut target_for_config = config.build_target_for_config(target)?;
         if target_for_config.len() != 1 {
             bail!(
@@ -124,17 +138,20 @@ impl Workspace {
 
         let name = metadata.workspace_root.file_name().unwrap_or("default").to_owned();
         let profdata_file = target_dir.join(format!("{name}.profdata"));
+        let manifest_file = target_dir.join("llvm-cov-manifest.json");
 
         Ok(Self {
             name,
             config,
             metadata,
+            project_config,
             current_manifest,
             target_dir,
             build_dir,
             output_dir,
             doctests_dir,
             profdata_file,
+            manifest_file,
             rustc,
             target_for_config,
             target_for_cli,
@@ -179,7 +196,7 @@ impl Workspace {
     }
 }
 
-fn package_root(cargo: &OsStr, manifest_path: Option<&Utf8Path>) -> Result<Utf8PathBuf> {
+pub(crate) fn package_root(cargo: &OsStr, manifest_path: Option<&Utf8Path>) -> Result<Utf8PathBuf> {
     let package_root = if let Some(manifest_path) = manifest_path {
         manifest_path.to_owned()
     } else {
@@ -196,6 +213,16 @@ fn locate_project(cargo: &OsStr) -> Result<String> {
 // https://doc.rust-lang.org/nightly/cargo/commands/cargo-test.html
 // https://doc.rust-lang.org/nightly/cargo/commands/cargo-run.html
 pub(crate) fn test_or_run_args(cx: &Context, cmd: &mut ProcessBuilder) {
+    test_or_run_args_in(cx, cmd, &cx.ws.target_dir, cx.ws.build_dir.as_deref());
+}
+
+/// Same as [`test_or_run_args`], but builds in the given target directory and build directory.
+pub(crate) fn test_or_run_args_in(
+    cx: &Context,
+    cmd: &mut ProcessBuilder,
+    target_dir: &Utf8Path,
+    build_dir: Option<&Utf8Path>,
+) {
     if matches!(cx.args.subcommand, Subcommand::None | Subcommand::Test) && !cx.args.doctests {
         let has_target_selection_options = cx.args.lib
             | cx.args.bins
@@ -244,8 +271,8 @@ pub(crate) fn test_or_run_args(cx: &Context, cmd: &mut ProcessBuilder) {
     } else {
         cmd.arg("--target-dir");
     }
-    cmd.arg(cx.ws.target_dir.as_str());
-    if let Some(build_dir) = &cx.ws.build_dir {
+    cmd.arg(target_dir.as_str());
+    if let Some(build_dir) = build_dir {
         cmd.env("CARGO_BUILD_BUILD_DIR", build_dir.as_str());
     }
 
@@ -293,3 +320,353 @@ pub(crate) fn clean_args(cx: &Context, cmd: &mut ProcessBuilder) {
         cmd.arg(format!("-{}", "v".repeat(cx.args.verbose as usize - 1)));
     }
 }
+
+/// Returns where `panic = "abort"` is set for the binaries built by cargo-llvm-cov, if any.
+///
+/// Processes that abort do not run the exit hook of the profile runtime that writes profile
+/// data, so the counters of processes aborted by panics are lost.
+pub(crate) fn panic_abort(ws: &Workspace, args: &Args) -> Result<Option<String>> {
+    let rustflags = ws.config.rustflags(&ws.target_for_config)?.unwrap_or_default();
+    if let Some((_, value)) =
+        codegen_flags(&rustflags.flags).filter(|(name, _)| *name == "panic").last()
+    {
+        return Ok((value == Some("abort")).then(|| "`-C panic=abort` in RUSTFLAGS".to_owned()));
+    }
+    let Some(profiles) = Profiles::new(args)? else { return Ok(None) };
+    Ok(profiles.get("panic")?.and_then(|(panic, source)| (panic == "abort").then_some(source)))
+}
+
+/// Returns warnings for the build settings that are known to make coverage inaccurate.
+pub(crate) fn distorting_settings(ws: &Workspace, args: &Args) -> Result<Vec<String>> {
+    let rustflags = ws.config.rustflags(&ws.target_for_config)?.unwrap_or_default();
+    // The last one takes precedence, and RUSTFLAGS are passed after the flags from profiles.
+    let codegen: Vec<_> = codegen_flags(&rustflags.flags).collect();
+    // Do not warn about the settings we are not sure about.
+    let Some(profiles) = Profiles::new(args)? else { return Ok(vec![]) };
+    let release = profiles.base() == "release";
+    let setting = |key: &str, default: &str| -> Result<(String, Option<String>)> {
+        if let Some(&(_, value)) = codegen.iter().rev().find(|(name, _)| *name == key) {
+            let flag = value.map_or_else(|| key.to_owned(), |value| format!("{key}={value}"));
+            return Ok((
+                value.unwrap_or_default().to_owned(),
+                Some(format!("`-C {flag}` in RUSTFLAGS")),
+            ));
+        }
+        Ok(match profiles.get(key)? {
+            Some((value, source)) => (value, Some(source)),
+            None => (default.to_owned(), None),
+        })
+    };
+    let source = |source: Option<String>| {
+        source.unwrap_or_else(|| format!("the default of `profile.{}`", profiles.chain[0]))
+    };
+
+    let mut warnings = vec![];
+    let (opt_level, opt_level_source) = setting("opt-level", if release { "3" } else { "0" })?;
+    let optimized = opt_level != "0";
+    if optimized {
+        warnings.push(format!(
+            "`opt-level = {opt_level}` ({}) enables inlining and dead code elimination, which \
+             can merge or remove coverage regions",
+            source(opt_level_source)
+        ));
+    }
+    let (lto, lto_source) = setting("lto", "false")?;
+    // --override-build-settings disables LTO and incremental compilation, unless they are
+    // enabled by RUSTFLAGS.
+    let overridden =
+        |key: &str| args.override_build_settings && !codegen.iter().any(|(name, _)| *name == key);
+    if !overridden("lto") && !matches!(lto.as_str(), "false" | "off" | "no" | "n") {
+        warnings.push(format!(
+            "`lto = {}` ({}) can remove or merge functions across crates, which makes them \
+             missing from the coverage report",
+            if lto.is_empty() { "true" } else { &lto },
+            source(lto_source)
+        ));
+    }
+    let (incremental, incremental_source) = match env::var("CARGO_INCREMENTAL")? {
+        Some(v) => (v, Some("`CARGO_INCREMENTAL` environment variable".to_owned())),
+        None => setting("incremental", if release { "false" } else { "true" })?,
+    };
+    // Incremental compilation is enabled by default for dev profile, so only warn when it
+    // is explicitly enabled.
+    if !overridden("incremental")
+        && incremental_source.is_some()
+        && !matches!(incremental.as_str(), "false" | "0")
+    {
+        warnings.push(format!(
+            "incremental compilation ({}) can reuse coverage mappings of stale artifacts, which \
+             causes mismatched or missing coverage data",
+            source(incremental_source)
+        ));
+    }
+    let (codegen_units, codegen_units_source) = setting("codegen-units", "16")?;
+    if optimized && codegen_units == "1" {
+        warnings.push(format!(
+            "`codegen-units = 1` ({}) together with optimizations allows inlining across the \
+             whole crate, which removes more coverage regions",
+            source(codegen_units_source)
+        ));
+    }
+    let (debug_assertions, debug_assertions_source) =
+        setting("debug-assertions", if release { "false" } else { "true" })?;
+    if matches!(debug_assertions.as_str(), "false" | "off" | "no" | "n") {
+        warnings.push(format!(
+            "debug assertions are disabled ({}), so code guarded by `debug_assert!` and \
+             `cfg!(debug_assertions)` is reported as uncovered",
+            source(debug_assertions_source)
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Returns `true` if the given flags (from RUSTFLAGS or the cargo configuration) already
+/// enable `-C instrument-coverage`, possibly with an option such as `=except-unused-functions`.
+pub(crate) fn enables_instrument_coverage(flags: &[String]) -> bool {
+    // The last one takes precedence.
+    codegen_flags(flags)
+        .filter(|(name, _)| *name == "instrument-coverage")
+        .last()
+        .is_some_and(|(_, value)| !matches!(value, Some("false" | "off" | "no" | "n")))
+}
+
+/// Returns whether the given flags enable a sanitizer by `-Z sanitizer=<value>`.
+pub(crate) fn enables_sanitizer(flags: &[String]) -> bool {
+    let mut flags = flags.iter().map(String::as_str);
+    while let Some(flag) = flags.next() {
+        let unstable = match flag {
+            "-Z" => flags.next(),
+            _ => flag.strip_prefix("-Z"),
+        };
+        if unstable.is_some_and(|unstable| unstable.trim().starts_with("sanitizer=")) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the `FROM` and `TO` of each `--remap-path-prefix FROM=TO` flag in the given flags.
+pub(crate) fn remap_path_prefixes(flags: &[String]) -> Vec<(&str, &str)> {
+    let mut flags = flags.iter().map(String::as_str);
+    let mut prefixes = vec![];
+    while let Some(flag) = flags.next() {
+        let value = match flag {
+            "--remap-path-prefix" => flags.next(),
+            _ => flag.strip_prefix("--remap-path-prefix="),
+        };
+        // rustc splits the value at the last `=`.
+        if let Some((from, to)) = value.and_then(|value| value.rsplit_once('=')) {
+            prefixes.push((from, to));
+        }
+    }
+    prefixes
+}
+
+/// Returns the name and value of each `-C <name>[=<value>]` flag in the given flags.
+fn codegen_flags(flags: &[String]) -> impl Iterator<Item = (&str, Option<&str>)> {
+    let mut flags = flags.iter().map(String::as_str);
+    std::iter::from_fn(move || {
+        loop {
+            let flag = flags.next()?;
+            let codegen = match flag {
+                "-C" | "--codegen" => flags.next(),
+                _ => flag.strip_prefix("-C").or_else(|| flag.strip_prefix("--codegen=")),
+            };
+            if let Some(codegen) = codegen {
+                let codegen = codegen.trim();
+                return Some(match codegen.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim())),
+                    None => (codegen, None),
+                });
+            }
+        }
+    })
+}
+
+/// Returns the dependencies of the first rule in the dep-info file (`<target>: <deps>...` in
+/// Makefile syntax) that rustc and cargo write next to each artifact.
+pub(crate) fn dep_info_dependencies(dep_info: &str) -> Vec<String> {
+    let Some(line) = dep_info.lines().find(|l| !l.is_empty() && !l.starts_with('#')) else {
+        return vec![];
+    };
+    // `: ` is used instead of `:` since Windows paths contain `:` after the drive letter.
+    let Some((_, deps)) = line.split_once(": ") else { return vec![] };
+    // Spaces in paths are escaped by `\`.
+    let mut paths = vec![];
+    let mut path = String::new();
+    let mut chars = deps.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&' ') => path.push(chars.next().unwrap()),
+            ' ' => {
+                if !path.is_empty() {
+                    paths.push(mem::take(&mut path));
+                }
+            }
+            _ => path.push(c),
+        }
+    }
+    if !path.is_empty() {
+        paths.push(path);
+    }
+    paths
+}
+
+/// Settings of profiles from `CARGO_PROFILE_<NAME>_<KEY>` environment variables.
+///
+/// Profiles in `Cargo.toml` are not read, since this crate does not depend on a TOML parser.
+struct Profiles {
+    /// The profile used for the build, followed by the profiles it inherits from.
+    chain: Vec<String>,
+}
+
+/// Returns the name of the profile used for the build.
+fn profile_name(args: &Args) -> String {
+    match &args.cargo_profile {
+        Some(profile) => profile.clone(),
+        None if args.release => "release".to_owned(),
+        None => "dev".to_owned(),
+    }
+}
+
+/// Returns the environment variables that override the build settings of the profiles used for
+/// the build, for `--override-build-settings`.
+///
+/// Optimizations are respected, but LTO (which removes or merges functions across crates) and
+/// incremental compilation (which can reuse coverage mappings of stale artifacts) are disabled.
+pub(crate) fn build_settings_overrides(args: &Args) -> Vec<(String, &'static str)> {
+    let profile = profile_name(args);
+    // Test targets are built with `test` or `bench` profile, which inherit `dev` or `release`
+    // profile but can set their own `lto`.
+    let mut profiles = vec![profile.as_str()];
+    match profile.as_str() {
+        "dev" => profiles.push("test"),
+        "release" => profiles.push("bench"),
+        _ => {}
+    }
+    let mut overrides: Vec<_> =
+        profiles.into_iter().map(|profile| (profile_env_key(profile, "lto"), "false")).collect();
+    overrides.push(("CARGO_INCREMENTAL".to_owned(), "0"));
+    overrides
+}
+
+impl Profiles {
+    /// Returns `None` if the profile used for the build is a custom profile that does not
+    /// inherit a built-in profile by environment variables, since its settings are unknown.
+    fn new(args: &Args) -> Result<Option<Self>> {
+        let mut profile = profile_name(args);
+        let mut chain = vec![profile.clone()];
+        loop {
+            profile = match (env::var(&profile_env_key(&profile, "inherits"))?, profile.as_str()) {
+                (Some(inherits), _) => inherits,
+                (None, "test") => "dev".to_owned(),
+                (None, "bench") => "release".to_owned(),
+                (None, "dev" | "release") => break,
+                (None, _) => return Ok(None),
+            };
+            if chain.contains(&profile) {
+                return Ok(None);
+            }
+            chain.push(profile.clone());
+        }
+        Ok(Some(Self { chain }))
+    }
+
+    /// Returns `dev` or `release`, the built-in profile that the profile is based on.
+    fn base(&self) -> &str {
+        if self.chain.last().unwrap() == "release" { "release" } else { "dev" }
+    }
+
+    /// Follows `inherits` until the profile that sets the given key is found, and returns
+    /// the value and where it is set.
+    fn get(&self, key: &str) -> Result<Option<(String, String)>> {
+        for profile in &self.chain {
+            let env_key = profile_env_key(profile, key);
+            if let Some(value) = env::var(&env_key)? {
+                return Ok(Some((value, format!("`{env_key}` environment variable"))));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn profile_env_key(profile: &str, key: &str) -> String {
+    format!("CARGO_PROFILE_{}_{}", profile.to_uppercase(), key.to_uppercase()).replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        codegen_flags, dep_info_dependencies, enables_instrument_coverage, enables_sanitizer,
+        remap_path_prefixes,
+    };
+
+    #[test]
+    fn test_codegen_flags() {
+        let flags: Vec<_> =
+            ["-C", "opt-level=2", "-Clto", "--codegen=panic=abort", "--cfg=foo", "-C"]
+                .iter()
+                .map(|&s| s.to_owned())
+                .collect();
+        assert_eq!(codegen_flags(&flags).collect::<Vec<_>>(), [
+            ("opt-level", Some("2")),
+            ("lto", None),
+            ("panic", Some("abort"))
+        ]);
+    }
+
+    #[test]
+    fn test_enables_instrument_coverage() {
+        let flags = |flags: &[&str]| flags.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert!(!enables_instrument_coverage(&flags(&["--cfg=foo"])));
+        assert!(enables_instrument_coverage(&flags(&["-C", "instrument-coverage"])));
+        assert!(enables_instrument_coverage(&flags(&["-Cinstrument-coverage=yes"])));
+        assert!(!enables_instrument_coverage(&flags(&[
+            "-Cinstrument-coverage",
+            "-C",
+            "instrument-coverage=off"
+        ])));
+    }
+
+    #[test]
+    fn test_enables_sanitizer() {
+        let flags = |flags: &[&str]| flags.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert!(!enables_sanitizer(&flags(&["-C", "instrument-coverage", "-Zbuild-std"])));
+        assert!(enables_sanitizer(&flags(&["-Z", "sanitizer=address"])));
+        assert!(enables_sanitizer(&flags(&["-Zsanitizer=leak"])));
+    }
+
+    #[test]
+    fn test_remap_path_prefixes() {
+        let flags: Vec<_> = [
+            "--remap-path-prefix",
+            "/home/a/src=/src",
+            "--remap-path-prefix=/a=b=/c",
+            "--cfg=foo",
+            "--remap-path-prefix",
+        ]
+        .iter()
+        .map(|&s| s.to_owned())
+        .collect();
+        assert_eq!(remap_path_prefixes(&flags), [("/home/a/src", "/src"), ("/a=b", "/c")]);
+    }
+
+    #[test]
+    fn test_dep_info_dependencies() {
+        let dep_info = "\
+/t/debug/deps/a-0123: src/lib.rs src/a\\ b.rs /r/dep/src/lib.rs
+
+src/lib.rs:
+src/a\\ b.rs:
+
+# env-dep:CARGO_PKG_NAME=a
+";
+        assert_eq!(dep_info_dependencies(dep_info), [
+            "src/lib.rs",
+            "src/a b.rs",
+            "/r/dep/src/lib.rs"
+        ]);
+        assert_eq!(dep_info_dependencies("C:\\t\\a.exe: C:\\src\\lib.rs\n"), ["C:\\src\\lib.rs"]);
+        assert_eq!(dep_info_dependencies(""), Vec::<String>::new());
+    }
+}
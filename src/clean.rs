@@ -15,6 +15,7 @@ use crate::{
     cli::{self, Args, ManifestOptions},
     context::Context,
     fs,
+    manifest::Manifest,
     metadata::PackageId,
     regex_vec::{RegexVec, RegexVecBuilder},
     term,
@@ -71,6 +72,11 @@ pub(crate) fn clean_partial(cx: &Context) -> Result<()> {
     Ok(())
 }
 
+/// Removes the profraw files of the previous run, but keeps the build artifacts.
+pub(crate) fn clean_profraw(cx: &Context) -> Result<()> {
+    clean_ws_inner(&cx.ws, &cx.workspace_members.included, cx.args.verbose > 1, true)
+}
+
 fn clean_ws(
     ws: &Workspace,
     pkg_ids: &[PackageId],
@@ -118,6 +124,15 @@ fn clean_ws_inner(
     profraw_only: bool,
 ) -> Result<()> {
     clean_profraw_files(ws, verbose)?;
+    // The objects used by the runs that generated the removed profraw files are no longer
+    // needed for reports.
+    if let Some(mut manifest) = Manifest::load(ws)? {
+        if !manifest.object_files.is_empty() || manifest.artifacts.is_some() {
+            manifest.object_files.clear();
+            manifest.artifacts = None;
+            manifest.save(ws)?;
+        }
+    }
 
     if profraw_only {
         return Ok(());
@@ -129,6 +144,7 @@ fn clean_ws_inner(
 
     rm_rf(&ws.doctests_dir, verbose)?;
     rm_rf(&ws.profdata_file, verbose)?;
+    rm_rf(ws.target_dir.join(format!("{}-profraw-list", ws.name)), verbose)?;
 
     clean_trybuild_artifacts(ws, pkg_ids, verbose)?;
     Ok(())
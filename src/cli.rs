@@ -10,9 +10,14 @@ use lexopt::{
 };
 
 use crate::{
-    env,
+    badge, cargo,
+    config::ProjectConfig,
+    dedup, env,
+    metadata::Metadata,
     process::ProcessBuilder,
+    summary,
     term::{self, Coloring},
+    upload,
 };
 
 // TODO: add --config option and passthrough to cargo-config: https://github.com/rust-lang/cargo/pull/10755/
@@ -23,6 +28,12 @@ pub(crate) struct Args {
 
     pub(crate) cov: LlvmCovOptions,
     pub(crate) show_env: ShowEnvOptions,
+    pub(crate) upload: UploadOptions,
+    pub(crate) collect: CollectOptions,
+    pub(crate) fuzz: FuzzOptions,
+    pub(crate) show: ShowOptions,
+    pub(crate) self_update: SelfUpdateOptions,
+    pub(crate) init: InitOptions,
 
     // https://doc.rust-lang.org/nightly/unstable-book/compiler-flags/instrument-coverage.html#including-doc-tests
     /// Including doc tests (unstable)
@@ -42,6 +53,42 @@ pub(crate) struct Args {
     ///
     /// If tests failed but report generation succeeded, exit with a status of 0.
     pub(crate) ignore_run_fail: bool,
+    /// Run the given shell command with coverage environment variables before running tests
+    pub(crate) pre_test_cmd: Option<String>,
+    /// Run the given shell command with coverage environment variables after running tests
+    ///
+    /// This command is also run when tests failed. The target directory in which profraw files
+    /// are searched is passed by the `CARGO_LLVM_COV_TARGET_DIR` environment variable, so this can
+    /// be used to copy profraw files from devices or emulators.
+    pub(crate) post_test_cmd: Option<String>,
+    /// Run up to N test binaries concurrently
+    ///
+    /// Test binaries are built by `cargo test --no-run` and then run directly, each with a
+    /// unique profile file name.
+    pub(crate) test_jobs: Option<usize>,
+    /// Also run tests under Miri after running instrumented tests
+    ///
+    /// Miri cannot collect coverage, so coverage is collected from the native run.
+    pub(crate) miri: bool,
+    /// Also build and run tests without instrumentation and report the overhead of instrumentation
+    pub(crate) measure_overhead: bool,
+    /// Report the compile time of each crate, the size of profraw files, and the time taken to
+    /// merge profile data and generate the report
+    ///
+    /// This flag is also passed to cargo, which generates its own timing report.
+    pub(crate) timings: bool,
+    /// Re-run tests and regenerate the report when files in the workspace are changed
+    pub(crate) watch: bool,
+    /// Run tests once for each feature of the package, and generate a merged report
+    ///
+    /// Tests are run with `--no-default-features`, with `--no-default-features --features <FEATURE>`
+    /// for each feature, and with all features.
+    pub(crate) each_feature: bool,
+    /// Run tests once for each combination of features of the package, and generate a merged
+    /// report
+    pub(crate) feature_powerset: bool,
+    /// Maximum number of features in each combination of --feature-powerset
+    pub(crate) depth: Option<usize>,
     // /// Display one character per test instead of one line
     // pub(crate) quiet: bool,
     /// Test only this package's library unit tests
@@ -79,6 +126,18 @@ pub(crate) struct Args {
     pub(crate) exclude_from_test: Vec<String>,
     /// Exclude packages from the report (but not from the test)
     pub(crate) exclude_from_report: Vec<String>,
+    /// Test and report only a subset of the workspace members
+    ///
+    /// If a fraction (e.g., `0.1`) is specified, a subset of that size is selected in
+    /// rotation, based on `CARGO_LLVM_COV_SAMPLE_SEED` environment variable (default to
+    /// the number of days since the Unix epoch), so that all packages are measured over
+    /// multiple runs. Otherwise, this is a comma-separated list of packages to measure.
+    pub(crate) sample_packages: Option<String>,
+    /// Merge the coverage of the sampled packages into the coverage history in the given
+    /// JSON file
+    ///
+    /// Coverage of packages that were not sampled in this run is kept from the previous runs.
+    pub(crate) sample_history: Option<Utf8PathBuf>,
 
     // /// Number of parallel jobs, defaults to # of CPUs
     // // i32 or string "default": https://github.com/rust-lang/cargo/blob/0.80.0/src/cargo/core/compiler/build_config.rs#L84-L97
@@ -99,7 +158,8 @@ pub(crate) struct Args {
     /// Build for the target triple
     ///
     /// When this option is used, coverage for proc-macro and build script will
-    /// not be displayed because cargo does not pass RUSTFLAGS to them.
+    /// not be displayed because cargo does not pass RUSTFLAGS to them, unless
+    /// --include-proc-macros and --include-build-script are passed.
     pub(crate) target: Option<String>,
     /// Activate coverage reporting only for the target triple
     ///
@@ -108,14 +168,11 @@ pub(crate) struct Args {
     /// bindeps feature, and not all targets can use `instrument-coverage`,
     /// e.g. a microkernel, or an embedded binary.
     pub(crate) coverage_target_only: bool,
-    // TODO: Currently, we are using a subdirectory of the target directory as
-    //       the actual target directory. What effect should this option have
-    //       on its behavior?
-    // /// Directory for all generated artifacts
-    // target_dir: Option<Utf8PathBuf>,
     /// Use verbose output
     ///
     /// Use -vv (-vvv) to propagate verbosity to cargo.
+    /// With -vv, the rustc wrapper used by some flags (e.g., --include-crate) also prints
+    /// how it changes the instrumentation of each crate.
     pub(crate) verbose: u8,
     /// Coloring
     // This flag will be propagated to both cargo and llvm-cov.
@@ -131,6 +188,67 @@ pub(crate) struct Args {
     /// must be set to Clang/LLVM compatible with the LLVM version used in rustc.
     // TODO: support specifying languages like: --include-ffi=c,  --include-ffi=c,c++
     pub(crate) include_ffi: bool,
+    /// Instrument the standard library built by `-Z build-std` and include it in the report
+    ///
+    /// By default, the standard library crates built by `-Z build-std` are not instrumented,
+    /// and the sysroot sources are excluded from the report.
+    pub(crate) include_std: bool,
+    /// Only instrument the given crates
+    ///
+    /// Crates that are not instrumented do not appear in the report. This flag can be
+    /// specified multiple times.
+    pub(crate) include_crate: Vec<String>,
+    /// Do not instrument the given crates
+    ///
+    /// This is useful to reduce build time and noise of crates with generated code or large
+    /// vendored crates. This flag can be specified multiple times.
+    ///
+    /// When --include-crate or --exclude-crate is changed, the crates whose instrumentation
+    /// is changed are rebuilt by the next run (builds with the environment of show-env
+    /// subcommand need `cargo llvm-cov clean` to rebuild them).
+    pub(crate) exclude_crate: Vec<String>,
+    /// Enable continuous mode of the profile runtime
+    ///
+    /// In this mode, counters are continuously synced to the profile files instead of
+    /// being written when the instrumented process exits, so coverage of long-running
+    /// processes can be collected without stopping them.
+    ///
+    /// With `cargo llvm-cov run`, the report is generated even if the binary is killed by a
+    /// signal or aborted, and cargo-llvm-cov exits with an error status after that.
+    ///
+    /// Pass this flag for tests with a custom harness (`harness = false`) that end by
+    /// `std::process::abort` or are killed by a signal. `std::process::exit` writes profile
+    /// data as usual.
+    pub(crate) profile_continuous: bool,
+    /// Build with the given sanitizer in addition to coverage instrumentation (unstable)
+    ///
+    /// Possible values: address, leak
+    ///
+    /// This requires nightly toolchain and --target (or `build.target` in the cargo
+    /// configuration), because cargo cannot run build scripts and proc-macros built with
+    /// sanitizers. They are built without the sanitizer, also when they are instrumented by
+    /// --include-build-script and --include-proc-macros.
+    pub(crate) sanitizer: Option<Sanitizer>,
+    /// How to update the coverage counters
+    ///
+    /// Possible values: atomic, single
+    ///
+    /// `atomic` updates the counters atomically, so that the counts are accurate even when the
+    /// code is executed by multiple threads at the same time. This is the default of rustc
+    /// 1.71+ (older rustc is also made to use it by default). `single` updates the counters
+    /// non-atomically, which makes heavily threaded tests faster, but the counts (not whether
+    /// the code is covered) can be lower than the actual ones.
+    pub(crate) profile_update: Option<ProfileUpdate>,
+    /// Do not warn about build settings that make coverage inaccurate
+    ///
+    /// By default, cargo-llvm-cov warns when optimizations, LTO, incremental compilation, or
+    /// disabled debug assertions are configured for the profile used for the build.
+    pub(crate) no_build_settings_warnings: bool,
+    /// Override build settings that are known to make coverage inaccurate
+    ///
+    /// This disables LTO of the profile used for the build and incremental compilation.
+    /// Optimizations are not changed.
+    pub(crate) override_build_settings: bool,
     /// Build without cleaning any old build artifacts.
     ///
     /// Note that this can cause false positives/false negatives due to old build artifacts.
@@ -158,6 +276,9 @@ pub(crate) enum Subcommand {
     /// Run a binary or example and generate coverage report.
     Run,
 
+    /// Replay the corpus of a fuzz target and generate coverage report.
+    Fuzz,
+
     /// Generate coverage report.
     Report {
         nextest_archive_file: bool,
@@ -169,6 +290,32 @@ pub(crate) enum Subcommand {
     /// Output the environment set by cargo-llvm-cov to build Rust projects.
     ShowEnv,
 
+    /// Check the environment for common problems that cause empty or missing coverage reports.
+    Doctor,
+
+    /// Upload a generated report to an HTTP endpoint.
+    Upload,
+
+    /// Browse coverage interactively in the terminal.
+    Tui,
+
+    /// Print the source of files annotated with coverage in the terminal.
+    Show,
+
+    /// Collect profile data from the memory of a target device.
+    Collect,
+
+    /// Print statistics about the instrumentation.
+    Stats,
+
+    /// Update cargo-llvm-cov to the latest or the specified release.
+    ///
+    /// Only the SHA-256 digest reported by the GitHub API is verified, not signatures.
+    SelfUpdate,
+
+    /// Generate a starter configuration for coverage.
+    Init,
+
     /// Run tests with cargo nextest
     Nextest {
         archive_file: bool,
@@ -184,9 +331,19 @@ pub(crate) enum Subcommand {
 static CARGO_LLVM_COV_USAGE: &str = include_str!("../docs/cargo-llvm-cov.txt");
 static CARGO_LLVM_COV_TEST_USAGE: &str = include_str!("../docs/cargo-llvm-cov-test.txt");
 static CARGO_LLVM_COV_RUN_USAGE: &str = include_str!("../docs/cargo-llvm-cov-run.txt");
+static CARGO_LLVM_COV_FUZZ_USAGE: &str = include_str!("../docs/cargo-llvm-cov-fuzz.txt");
 static CARGO_LLVM_COV_REPORT_USAGE: &str = include_str!("../docs/cargo-llvm-cov-report.txt");
 static CARGO_LLVM_COV_CLEAN_USAGE: &str = include_str!("../docs/cargo-llvm-cov-clean.txt");
 static CARGO_LLVM_COV_SHOW_ENV_USAGE: &str = include_str!("../docs/cargo-llvm-cov-show-env.txt");
+static CARGO_LLVM_COV_DOCTOR_USAGE: &str = include_str!("../docs/cargo-llvm-cov-doctor.txt");
+static CARGO_LLVM_COV_UPLOAD_USAGE: &str = include_str!("../docs/cargo-llvm-cov-upload.txt");
+static CARGO_LLVM_COV_TUI_USAGE: &str = include_str!("../docs/cargo-llvm-cov-tui.txt");
+static CARGO_LLVM_COV_SHOW_USAGE: &str = include_str!("../docs/cargo-llvm-cov-show.txt");
+static CARGO_LLVM_COV_COLLECT_USAGE: &str = include_str!("../docs/cargo-llvm-cov-collect.txt");
+static CARGO_LLVM_COV_STATS_USAGE: &str = include_str!("../docs/cargo-llvm-cov-stats.txt");
+static CARGO_LLVM_COV_SELF_UPDATE_USAGE: &str =
+    include_str!("../docs/cargo-llvm-cov-self-update.txt");
+static CARGO_LLVM_COV_INIT_USAGE: &str = include_str!("../docs/cargo-llvm-cov-init.txt");
 static CARGO_LLVM_COV_NEXTEST_USAGE: &str = include_str!("../docs/cargo-llvm-cov-nextest.txt");
 static CARGO_LLVM_COV_NEXTEST_ARCHIVE_USAGE: &str =
     include_str!("../docs/cargo-llvm-cov-nextest-archive.txt");
@@ -201,9 +358,18 @@ impl Subcommand {
             Self::None => CARGO_LLVM_COV_USAGE,
             Self::Test => CARGO_LLVM_COV_TEST_USAGE,
             Self::Run => CARGO_LLVM_COV_RUN_USAGE,
+            Self::Fuzz => CARGO_LLVM_COV_FUZZ_USAGE,
             Self::Report { .. } => CARGO_LLVM_COV_REPORT_USAGE,
             Self::Clean => CARGO_LLVM_COV_CLEAN_USAGE,
             Self::ShowEnv => CARGO_LLVM_COV_SHOW_ENV_USAGE,
+            Self::Doctor => CARGO_LLVM_COV_DOCTOR_USAGE,
+            Self::Upload => CARGO_LLVM_COV_UPLOAD_USAGE,
+            Self::Tui => CARGO_LLVM_COV_TUI_USAGE,
+            Self::Show => CARGO_LLVM_COV_SHOW_USAGE,
+            Self::Collect => CARGO_LLVM_COV_COLLECT_USAGE,
+            Self::Stats => CARGO_LLVM_COV_STATS_USAGE,
+            Self::SelfUpdate => CARGO_LLVM_COV_SELF_UPDATE_USAGE,
+            Self::Init => CARGO_LLVM_COV_INIT_USAGE,
             Self::Nextest { .. } => CARGO_LLVM_COV_NEXTEST_USAGE,
             Self::NextestArchive => CARGO_LLVM_COV_NEXTEST_ARCHIVE_USAGE,
             Self::Demangle => "", // internal API
@@ -215,9 +381,18 @@ impl Subcommand {
             Self::None => "",
             Self::Test => "test",
             Self::Run => "run",
+            Self::Fuzz => "fuzz",
             Self::Report { .. } => "report",
             Self::Clean => "clean",
             Self::ShowEnv => "show-env",
+            Self::Doctor => "doctor",
+            Self::Upload => "upload",
+            Self::Tui => "tui",
+            Self::Show => "show",
+            Self::Collect => "collect",
+            Self::Stats => "stats",
+            Self::SelfUpdate => "self-update",
+            Self::Init => "init",
             Self::Nextest { .. } => "nextest",
             Self::NextestArchive => "nextest-archive",
             Self::Demangle => "demangle",
@@ -242,12 +417,26 @@ impl FromStr for Subcommand {
         match s {
             "test" | "t" => Ok(Self::Test),
             "run" | "r" => Ok(Self::Run),
+            "fuzz" => Ok(Self::Fuzz),
             "report" => Ok(Self::Report { nextest_archive_file: false }),
             "clean" => Ok(Self::Clean),
             "show-env" => Ok(Self::ShowEnv),
+            "doctor" => Ok(Self::Doctor),
+            "upload" => Ok(Self::Upload),
+            "tui" => Ok(Self::Tui),
+            "show" => Ok(Self::Show),
+            "collect" => Ok(Self::Collect),
+            "stats" => Ok(Self::Stats),
+            "self-update" => Ok(Self::SelfUpdate),
+            "init" => Ok(Self::Init),
             "nextest" => Ok(Self::Nextest { archive_file: false }),
             "nextest-archive" => Ok(Self::NextestArchive),
             "demangle" => Ok(Self::Demangle),
+            "miri" => bail!(
+                "coverage cannot be collected from tests run under Miri; \
+                 use `cargo llvm-cov --miri` to collect coverage from native tests \
+                 and also run tests under Miri"
+            ),
             _ => bail!("unrecognized subcommand {s}"),
         }
     }
@@ -268,13 +457,16 @@ pub(crate) struct LlvmCovOptions {
     ///
     /// This internally calls `llvm-cov export -format=lcov`.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    ///
+    /// The report contains function (`FN` and `FNDA`) and line (`DA`) records, and branch
+    /// (`BRDA`) records if --branch is passed.
     pub(crate) lcov: bool,
 
     /// Export coverage data in "cobertura" XML format
     ///
     /// If --output-path is not specified, the report will be printed to stdout.
     ///
-    /// This internally calls `llvm-cov export -format=lcov` and then converts to cobertura.xml.
+    /// This internally calls `llvm-cov export -format=json` and then converts to cobertura.xml.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) cobertura: bool,
 
@@ -285,6 +477,56 @@ pub(crate) struct LlvmCovOptions {
     /// This internally calls `llvm-cov export -format=json` and then converts to codecov.json.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) codecov: bool,
+    /// Export uncovered lines in reviewdog's "RDJSON" format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to RDJSON.
+    /// See <https://github.com/reviewdog/reviewdog/tree/HEAD/proto/rdf> for more.
+    pub(crate) rdjson: bool,
+    /// Export uncovered regions in "Checkstyle" XML format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to checkstyle.xml.
+    pub(crate) checkstyle: bool,
+    /// Export coverage data in the format of Coveralls' Jobs API
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// The report includes the git metadata of the repository. Use `cargo llvm-cov upload
+    /// --coveralls` to upload the report, which also sends the repository token read from
+    /// COVERALLS_REPO_TOKEN environment variable if it is set.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to Coveralls JSON.
+    /// See <https://docs.coveralls.io/api-reference> for more.
+    pub(crate) coveralls: bool,
+    /// Export uncovered regions in SARIF 2.1.0 format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// The report can be uploaded to GitHub code scanning with the
+    /// `github/codeql-action/upload-sarif` action, or consumed by other tools that support SARIF.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to SARIF.
+    pub(crate) sarif: bool,
+    /// Export coverage data in "JaCoCo" XML format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// Crates are mapped to packages, and files are mapped to classes and source files, so that
+    /// the report can be consumed by tools that support JaCoCo XML, such as SonarQube.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to JaCoCo XML.
+    pub(crate) jacoco: bool,
+    /// Export coverage data in SonarQube's generic test coverage XML format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// The report can be imported with the `sonar.coverageReportPaths` property.
+    ///
+    /// This internally calls `llvm-cov export -format=json` and then converts to SonarQube XML.
+    pub(crate) sonarqube: bool,
 
     /// Generate coverage report in "text" format
     ///
@@ -313,20 +555,149 @@ pub(crate) struct LlvmCovOptions {
 
     /// Specify a file to write coverage data into.
     ///
-    /// This flag can only be used together with --json, --lcov, --cobertura, --codecov, or --text.
-    /// See --output-dir for --html and --open.
+    /// This flag can only be used together with --json, --lcov, --cobertura, --codecov, --rdjson,
+    /// --checkstyle, --coveralls, --sarif, --jacoco, --sonarqube, --csv,
+    /// --tsv, or --text.
+    /// See --output-dir for --html and --open, and multiple format flags.
     pub(crate) output_path: Option<Utf8PathBuf>,
     /// Specify a directory to write coverage report into (default to `target/llvm-cov`).
     ///
-    /// This flag can only be used together with --text, --html, or --open.
-    /// See also --output-path.
+    /// This flag can only be used together with --text, --html, --open, or multiple format
+    /// flags. See also --output-path.
+    ///
+    /// If multiple format flags are passed (e.g., `--lcov --html`), each report is written to
+    /// this directory: `coverage.json`, `lcov.info`, `cobertura.xml`, `codecov.json`,
+    /// `rdjson.json`, `checkstyle.xml`, `coveralls.json`, `coverage.sarif`, `jacoco.xml`,
+    /// `sonarqube.xml`, `summary.csv`, `summary.tsv`, `text`, and `html`. The reports other
+    /// than --text and --html are converted from a single `llvm-cov export`.
     // If the format flag is not specified, this flag is no-op.
     pub(crate) output_dir: Option<Utf8PathBuf>,
+    /// Include the time when tests were run in the run metadata of JSON and HTML reports
+    ///
+    /// This is not included by default so that reports are reproducible.
+    pub(crate) metadata_timestamp: bool,
+    /// Write line coverage badges for the workspace and each package into the given directory
+    ///
+    /// This writes `coverage.svg` for the total coverage, `<package>.svg` for each package,
+    /// and `index.json` listing them, in addition to the report.
+    pub(crate) badges: Option<Utf8PathBuf>,
+    /// Coverage thresholds for the color of badges, in `LOW,HIGH` format (default to `40,90`)
+    ///
+    /// Coverage below LOW is shown in red, coverage of at least HIGH is shown in green, and
+    /// coverage in between is shown in orange, yellow, or yellow-green.
+    pub(crate) badge_thresholds: Option<badge::Thresholds>,
+    /// Write an HTML dashboard with the results of tests from the JUnit report of cargo-nextest
+    /// and the coverage of each package to the given file
+    pub(crate) dashboard: Option<Utf8PathBuf>,
 
     /// Fail if `any` or `all` profiles cannot be merged (default to `any`)
+    ///
+    /// If this flag is not passed and merging fails, profiles that cannot be read (e.g., ones
+    /// truncated by a process that crashed while writing them) are skipped with a warning.
     pub(crate) failure_mode: Option<String>,
+    /// Also merge profraw files that match the given glob pattern
+    ///
+    /// This flag can be specified multiple times.
+    pub(crate) profraw_glob: Vec<String>,
+    /// Show a ranked list of the largest fully or mostly uncovered functions instead of the report
+    pub(crate) debt: bool,
+    /// Show a list of public functions that are not executed by any doctest or example instead of the report
+    pub(crate) missing_examples: bool,
+    /// Show a list of the most executed functions and lines instead of the report
+    pub(crate) hotspots: bool,
+    /// Export coverage summary in CSV format
+    ///
+    /// If --output-path is not specified, the report will be printed to stdout.
+    ///
+    /// The report has a row for each file, each workspace member, and the total, with the
+    /// counts and percentages of all kinds of coverage. The numbers are the same as the ones
+    /// in the table of --file-summary and --package-summary.
+    ///
+    /// With --uncovered-functions, the list of the functions is output in CSV format instead.
+    pub(crate) csv: bool,
+    /// Export coverage summary in TSV format
+    ///
+    /// This is the same as --csv, except that fields are separated by tabs.
+    pub(crate) tsv: bool,
+    /// Show a list of the functions that are never executed, grouped by crate, instead of the
+    /// report
+    ///
+    /// Functions in each crate are sorted in descending order of the number of code regions,
+    /// so that the largest dead or untested functions are listed first. Instantiations of
+    /// generic functions are combined. With --json, --csv, or --tsv, the list is output in JSON,
+    /// CSV, or TSV format.
+    pub(crate) uncovered_functions: bool,
+    /// Show a list of the test binaries that execute code in the given file or function instead
+    /// of the report
+    ///
+    /// The value is matched against file names relative to the workspace root and demangled
+    /// function names, including their suffixes (e.g., `lib.rs` or `Type::method`). This flag
+    /// can be specified multiple times.
+    ///
+    /// Profile data generated by each binary is identified by the binary IDs embedded in
+    /// profraw files, so this is currently only available on ELF targets. The functions
+    /// executed by each binary are saved to the run manifest and reused until tests are run
+    /// again. With --json, the list is output in JSON format.
+    pub(crate) affected_by: Vec<String>,
+    /// Show a list of functions with their execution counts and region coverage instead of the
+    /// report
+    pub(crate) show_functions: bool,
+    /// Only show functions whose demangled names match the given regular expression in
+    /// --show-functions
+    pub(crate) function_filter: Option<String>,
+    /// Show a coverage summary for each module instead of the report
+    pub(crate) module_summary: bool,
+    /// Show a coverage summary for the methods of each type instead of the report
+    pub(crate) type_summary: bool,
+    /// Show a coverage summary for each workspace member with totals instead of the report
+    ///
+    /// Files are grouped by the innermost package that contains them. With --json, the
+    /// summary is output in JSON format without totals.
+    pub(crate) package_summary: bool,
+    /// Show a table of the coverage of each file with totals instead of the report
+    ///
+    /// With --show-missing-lines, uncovered lines of each file are shown in the last column
+    /// instead of after the table.
+    pub(crate) file_summary: bool,
+    /// Sort the files in --file-summary by the coverage of the given kind in ascending order:
+    /// name, lines, functions, regions, or branches (default to name)
+    pub(crate) sort_by: Option<summary::SortBy>,
     /// Skip source code files with file paths that match the given regular expression.
-    pub(crate) ignore_filename_regex: Option<String>,
+    ///
+    /// This flag can be specified multiple times.
+    pub(crate) ignore_filename_regex: Vec<String>,
+    /// Treat files under FROM as the same files as the ones under TO, and merge their coverage.
+    ///
+    /// Files whose paths resolve to the same file (e.g., through symlinks) are always merged
+    /// in the reports converted from the JSON export. This flag can be specified multiple
+    /// times. Rules in `path-equivalence` array of `[workspace.metadata.llvm-cov]` table are
+    /// also used.
+    ///
+    /// llvm-cov itself does not merge files, so this flag may not be used together with
+    /// --text, --html, or the summary printed when no format is specified (use --file-summary
+    /// instead).
+    pub(crate) path_equivalence: Vec<String>,
+    /// Exclude lines that consist solely of a call to `unreachable!`, `todo!`, `unimplemented!`,
+    /// or `panic!` from the line coverage.
+    pub(crate) exclude_panic_lines: bool,
+    /// Exclude bodies of `if cfg!(<PREDICATE>)` from the line coverage, if the predicate is not
+    /// enabled by `--cfg` in RUSTFLAGS.
+    ///
+    /// This flag can be specified multiple times.
+    pub(crate) exclude_cfg: Vec<String>,
+    /// Exclude `#[cfg(test)]` items and `#[test]` functions from the line coverage.
+    pub(crate) exclude_test_code: bool,
+    /// Only include files that have been changed since the given git revision in the report.
+    pub(crate) changed_since: Option<String>,
+    /// Show the coverage of lines that have been added or modified since the given git
+    /// revision ("patch coverage"), including uncommitted changes and untracked files.
+    ///
+    /// If `-` is given, the unified diff read from stdin is used instead.
+    pub(crate) diff: Option<String>,
+    /// Fail if profraw files were not generated by the current instrumented objects.
+    pub(crate) verify_binary_ids: bool,
+    /// Do not fail when no profile data was generated, and skip the report instead
+    pub(crate) allow_empty: bool,
     // For debugging (unstable)
     pub(crate) disable_default_ignore_filename_regex: bool,
     /// Show instantiations in report
@@ -337,23 +708,69 @@ pub(crate) struct LlvmCovOptions {
     pub(crate) no_cfg_coverage_nightly: bool,
     /// Run tests, but don't generate coverage report
     pub(crate) no_report: bool,
-    /// Exit with a status of 1 if the total function coverage is less than MIN percent.
+    /// Exit with a status of 2 if the total function coverage is less than MIN percent.
     pub(crate) fail_under_functions: Option<f64>,
-    /// Exit with a status of 1 if the total line coverage is less than MIN percent.
+    /// Exit with a status of 2 if the total line coverage is less than MIN percent.
     pub(crate) fail_under_lines: Option<f64>,
-    /// Exit with a status of 1 if the total region coverage is less than MIN percent.
+    /// Exit with a status of 2 if the total region coverage is less than MIN percent.
     pub(crate) fail_under_regions: Option<f64>,
-    /// Exit with a status of 1 if the uncovered lines are greater than MAX.
+    /// Exit with a status of 2 if the total branch coverage is less than MIN percent.
+    pub(crate) fail_under_branches: Option<f64>,
+    /// Exit with a status of 2 if the patch coverage is less than MIN percent.
+    pub(crate) fail_under_diff: Option<f64>,
+    /// Emit GitHub Actions warning annotations for the changed lines with no coverage.
+    pub(crate) github_annotations: bool,
+    /// Exit with a status of 2 if the uncovered lines are greater than MAX.
     pub(crate) fail_uncovered_lines: Option<u64>,
-    /// Exit with a status of 1 if the uncovered regions are greater than MAX.
+    /// Exit with a status of 2 if the uncovered regions are greater than MAX.
     pub(crate) fail_uncovered_regions: Option<u64>,
-    /// Exit with a status of 1 if the uncovered functions are greater than MAX.
+    /// Exit with a status of 2 if the uncovered functions are greater than MAX.
     pub(crate) fail_uncovered_functions: Option<u64>,
+    /// Exit with a status of 2 if the total coverage is lower than the coverage recorded in the
+    /// given file, and update the file when the coverage is improved.
+    pub(crate) ratchet: Option<Utf8PathBuf>,
+    /// Allow the coverage to be lower than the recorded coverage by up to PERCENT percentage
+    /// points (default to 0).
+    pub(crate) ratchet_tolerance: Option<f64>,
+    /// Save the coverage of each file and the total coverage to the given file
+    pub(crate) save_snapshot: Option<Utf8PathBuf>,
+    /// Show the changes of the coverage since the snapshot saved by --save-snapshot, and exit
+    /// with a status of 2 if the coverage decreased
+    pub(crate) compare_to: Option<Utf8PathBuf>,
+    /// Allow the coverage to be lower than the coverage in the snapshot by up to PERCENT
+    /// percentage points (default to 0)
+    pub(crate) compare_tolerance: Option<f64>,
+    /// Print the total coverage in JSON format to stderr
+    ///
+    /// The JSON (`totals` object of the JSON report) is printed in a single line after the
+    /// report, so that scripts can read the coverage while the report is written to stdout.
+    pub(crate) summary_json_to_stderr: bool,
     /// Show lines with no coverage.
     pub(crate) show_missing_lines: bool,
+    /// Show lines with no coverage grouped by function.
+    pub(crate) show_missing_lines_by_function: bool,
+    /// Show ranges of consecutive lines with no coverage and the functions that contain them,
+    /// one per line.
+    pub(crate) show_missing_ranges: bool,
+    /// Merge closures and async blocks into their enclosing functions in function-level reports.
+    pub(crate) group_closures: bool,
+    /// Show region coverage weighted by the complexity of each function.
+    pub(crate) complexity_weighted: bool,
     /// Include build script in coverage report.
+    ///
+    /// When --target is used, build scripts in the workspace are instrumented by using
+    /// cargo-llvm-cov as a rustc wrapper.
     pub(crate) include_build_script: bool,
+    /// Instrument proc-macros in the workspace even when --target is used
+    ///
+    /// Cargo does not pass RUSTFLAGS to proc-macros built for the host when --target is used,
+    /// so they are instrumented by using cargo-llvm-cov as a rustc wrapper. Without --target,
+    /// proc-macros are always instrumented.
+    pub(crate) include_proc_macros: bool,
     /// Show coverage of the specified dependency instead of the crates in the current workspace. (unstable)
+    ///
+    /// Dependencies other than the specified one are not instrumented, which reduces build
+    /// time. The specified dependency needs to be from crates.io.
     pub(crate) dep_coverage: Option<String>,
     /// Skip functions in coverage report.
     pub(crate) skip_functions: bool,
@@ -367,6 +784,134 @@ impl LlvmCovOptions {
     pub(crate) const fn show(&self) -> bool {
         self.text || self.html
     }
+
+    /// Returns the report that llvm-cov generates directly instead of converting it from the
+    /// JSON export, if any: --text, --html, or the summary printed when no format is specified.
+    pub(crate) fn llvm_cov_report(&self) -> Option<&'static str> {
+        if self.no_report
+            || self.debt
+            || self.missing_examples
+            || self.hotspots
+            || self.uncovered_functions
+            || !self.affected_by.is_empty()
+            || self.show_functions
+            || self.module_summary
+            || self.type_summary
+            || self.package_summary
+            || self.file_summary
+        {
+            return None;
+        }
+        if self.html || self.open {
+            return Some(if self.open { "--open" } else { "--html" });
+        }
+        if self.text {
+            return Some("--text");
+        }
+        let has_export = self.json
+            || self.lcov
+            || self.cobertura
+            || self.codecov
+            || self.rdjson
+            || self.checkstyle
+            || self.coveralls
+            || self.sarif
+            || self.jacoco
+            || self.sonarqube
+            || self.csv
+            || self.tsv;
+        (!has_export).then_some("the summary printed when no format is specified")
+    }
+
+    /// Returns `true` if multiple format flags are passed, in which case all reports are
+    /// written to --output-dir.
+    pub(crate) fn multiple_formats(&self) -> bool {
+        [
+            self.json,
+            self.lcov,
+            self.cobertura,
+            self.codecov,
+            self.rdjson,
+            self.checkstyle,
+            self.coveralls,
+            self.sarif,
+            self.jacoco,
+            self.sonarqube,
+            self.csv,
+            self.tsv,
+            self.text,
+            self.html || self.open,
+        ]
+        .into_iter()
+        .filter(|&flag| flag)
+        .count()
+            > 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Sanitizer {
+    Address,
+    Leak,
+}
+
+impl Sanitizer {
+    pub(crate) const fn as_str(self) -> &'static str {
+        match self {
+            Self::Address => "address",
+            Self::Leak => "leak",
+        }
+    }
+}
+
+impl FromStr for Sanitizer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(Self::Address),
+            "leak" => Ok(Self::Leak),
+            _ => bail!("--sanitizer must be address or leak, but found `{s}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ProfileUpdate {
+    Atomic,
+    Single,
+}
+
+impl FromStr for ProfileUpdate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atomic" => Ok(Self::Atomic),
+            "single" => Ok(Self::Single),
+            _ => bail!("--profile-update must be atomic or single, but found `{s}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CoverageLevel {
+    Block,
+    Branch,
+    Mcdc,
+}
+
+impl FromStr for CoverageLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Self::Block),
+            "branch" => Ok(Self::Branch),
+            "mcdc" => Ok(Self::Mcdc),
+            _ => bail!("--coverage-level must be block, branch, or mcdc, but found `{s}`"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -378,21 +923,34 @@ pub(crate) enum ShowEnvFormat {
     UnixExport,
     /// Each value: "$env:{key}={value}", where {value} is PowerShell Unicode escaped e.g. "`u{72}".
     Pwsh,
+    /// Each line: "set -gx {key} '{value}'", so that the output is suitable to be sourced by fish.
+    Fish,
+    /// Each line: "{key}={value}" (or multiline syntax with a delimiter if the value contains
+    /// newlines), so that the output is suitable to be appended to `$GITHUB_ENV` file.
+    GitHubEnv,
 }
 
 impl ShowEnvFormat {
-    pub(crate) fn new(export_prefix: bool, with_pwsh_env_prefix: bool) -> Result<Self> {
-        if export_prefix && with_pwsh_env_prefix {
-            conflicts("--export-prefix", "--with-pwsh-env-prefix")?;
+    pub(crate) fn new(
+        export_prefix: bool,
+        with_pwsh_env_prefix: bool,
+        with_fish_set_prefix: bool,
+        github_env: bool,
+    ) -> Result<Self> {
+        let formats = [
+            (export_prefix, "--export-prefix", ShowEnvFormat::UnixExport),
+            (with_pwsh_env_prefix, "--with-pwsh-env-prefix", ShowEnvFormat::Pwsh),
+            (with_fish_set_prefix, "--with-fish-set-prefix", ShowEnvFormat::Fish),
+            (github_env, "--github-env", ShowEnvFormat::GitHubEnv),
+        ];
+        let mut formats = formats.into_iter().filter(|(flag, ..)| *flag);
+        let Some((_, name, format)) = formats.next() else {
+            return Ok(ShowEnvFormat::EscapedKeyValuePair);
+        };
+        if let Some((_, other, _)) = formats.next() {
+            conflicts(name, other)?;
         }
-
-        Ok(if export_prefix {
-            ShowEnvFormat::UnixExport
-        } else if with_pwsh_env_prefix {
-            ShowEnvFormat::Pwsh
-        } else {
-            ShowEnvFormat::EscapedKeyValuePair
-        })
+        Ok(format)
     }
 
     pub(crate) fn export_string(&self, key: &str, value: &str) -> String {
@@ -412,6 +970,23 @@ impl ShowEnvFormat {
                 let value = value.escape_unicode().to_string().replace('\\', "`");
                 format!("$env:{key}=\"{value}\"")
             }
+            ShowEnvFormat::Fish => {
+                // Only `\\` and `\'` are escape sequences in single-quoted strings in fish.
+                let value = value.replace('\\', "\\\\").replace('\'', "\\'");
+                format!("set -gx {key} '{value}'")
+            }
+            ShowEnvFormat::GitHubEnv => {
+                // https://docs.github.com/en/actions/reference/workflows-and-actions/workflow-commands#multiline-strings
+                if value.contains(['\n', '\r']) {
+                    let mut delimiter = "CARGO_LLVM_COV_EOF".to_owned();
+                    while value.contains(&delimiter) {
+                        delimiter.push('_');
+                    }
+                    format!("{key}<<{delimiter}\n{value}\n{delimiter}")
+                } else {
+                    format!("{key}={value}")
+                }
+            }
         }
     }
 }
@@ -421,11 +996,87 @@ pub(crate) struct ShowEnvOptions {
     pub(crate) show_env_format: ShowEnvFormat,
 }
 
+#[derive(Debug, Default)]
+pub(crate) struct UploadOptions {
+    /// URL to upload the report to
+    ///
+    /// `${VAR}` is replaced with the value of the environment variable `VAR`.
+    pub(crate) url: String,
+    /// HTTP method to use
+    pub(crate) method: String,
+    /// Headers to send, in `NAME: VALUE` format
+    ///
+    /// `${VAR}` is replaced with the value of the environment variable `VAR`.
+    pub(crate) headers: Vec<String>,
+    /// Report file to upload
+    pub(crate) file: Utf8PathBuf,
+    /// Upload the report generated by --coveralls to Coveralls
+    pub(crate) coveralls: bool,
+    /// CA certificate bundle to verify the server with, in PEM format
+    pub(crate) cacert: Option<Utf8PathBuf>,
+    /// Write the body of the request to this file instead of uploading it
+    pub(crate) dry_run: Option<Utf8PathBuf>,
+    /// Number of times to retry the upload on transient errors
+    pub(crate) retry: u32,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CollectOptions {
+    /// Target chip, passed to probe-rs
+    pub(crate) chip: String,
+    /// Debug probe to use, passed to probe-rs
+    pub(crate) probe: Option<String>,
+    /// ELF file to look up --symbol in
+    pub(crate) elf: Option<Utf8PathBuf>,
+    /// Symbol of the buffer that contains the profile data
+    pub(crate) symbol: Option<String>,
+    /// Address of the buffer that contains the profile data
+    pub(crate) address: Option<u64>,
+    /// Size of the buffer in bytes
+    pub(crate) size: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FuzzOptions {
+    /// Name of the fuzz target
+    pub(crate) target: String,
+    /// Corpus directories or input files to replay
+    pub(crate) corpus: Vec<Utf8PathBuf>,
+    /// Arguments for libFuzzer
+    pub(crate) args: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ShowOptions {
+    /// Paths or glob patterns of the files to print
+    pub(crate) files: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SelfUpdateOptions {
+    /// Version to install instead of the latest release
+    ///
+    /// Without this flag, the latest release is not installed if it is older than the current
+    /// version. This flag can be used to downgrade.
+    pub(crate) version: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct InitOptions {
+    /// Also generate a GitHub Actions workflow
+    pub(crate) ci_workflow: bool,
+}
+
 // https://doc.rust-lang.org/nightly/cargo/commands/cargo-test.html#manifest-options
 #[derive(Debug, Default)]
 pub(crate) struct ManifestOptions {
     /// Path to Cargo.toml
     pub(crate) manifest_path: Option<Utf8PathBuf>,
+    /// Directory for all generated artifacts
+    ///
+    /// Like `CARGO_TARGET_DIR`, this changes the base directory, and cargo-llvm-cov still builds
+    /// in its `llvm-cov-target` subdirectory.
+    pub(crate) target_dir: Option<Utf8PathBuf>,
     /// Require Cargo.lock and cache are up to date
     pub(crate) frozen: bool,
     /// Require Cargo.lock is up to date
@@ -436,7 +1087,8 @@ pub(crate) struct ManifestOptions {
 
 impl ManifestOptions {
     pub(crate) fn cargo_args(&self, cmd: &mut ProcessBuilder) {
-        // Skip --manifest-path because it is set based on Workspace::current_manifest.
+        // Skip --manifest-path and --target-dir because they are set based on
+        // Workspace::current_manifest and Workspace::target_dir.
         if self.frozen {
             cmd.arg("--frozen");
         }
@@ -467,6 +1119,52 @@ pub(crate) fn merge_config_to_args(
     }
 }
 
+/// Replaces `--preset <NAME>` with the arguments defined in
+/// `[workspace.metadata.llvm-cov.presets]` table.
+fn expand_presets(args: Vec<String>) -> Result<Vec<String>> {
+    fn is_preset(arg: &str) -> bool {
+        arg == "--preset" || arg.starts_with("--preset=")
+    }
+
+    if !args.iter().any(|arg| is_preset(arg)) {
+        return Ok(args);
+    }
+    // --manifest-path needs to be handled before other flags to find the config.
+    let mut manifest_path = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--manifest-path" {
+            manifest_path = args.get(i + 1).map(Utf8Path::new);
+        } else if let Some(path) = arg.strip_prefix("--manifest-path=") {
+            manifest_path = Some(Utf8Path::new(path));
+        }
+    }
+    let config = cargo_config2::Config::load()?;
+    let manifest = cargo::package_root(config.cargo(), manifest_path)?;
+    let metadata = Metadata::new(manifest.as_std_path(), config.cargo())?;
+    let project_config = ProjectConfig::new(&metadata)?;
+
+    let mut expanded = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let name = if arg == "--preset" {
+            args.next().ok_or_else(|| format_err!("missing argument for option '--preset'"))?
+        } else if let Some(name) = arg.strip_prefix("--preset=") {
+            name.to_owned()
+        } else {
+            expanded.push(arg);
+            continue;
+        };
+        let Some(preset) = project_config.presets.get(&name) else {
+            bail!("preset `{name}` is not defined in `[workspace.metadata.llvm-cov.presets]` table")
+        };
+        if preset.iter().any(|arg| is_preset(arg)) {
+            bail!("preset `{name}` may not contain --preset");
+        }
+        expanded.extend(preset.iter().cloned());
+    }
+    Ok(expanded)
+}
+
 impl Args {
     pub(crate) fn parse() -> Result<Option<Self>> {
         const SUBCMD: &str = "llvm-cov";
@@ -502,13 +1200,16 @@ impl Args {
             }
             args.push(arg);
         }
-        let rest = raw_args.collect::<Result<Vec<_>>>()?;
+        let mut rest = raw_args.collect::<Result<Vec<_>>>()?;
+        let args = expand_presets(args)?;
 
         let mut cargo_args = vec![];
+        let mut passthrough_before_subcommand = None;
         let mut subcommand = Subcommand::None;
         let mut after_subcommand = false;
 
         let mut manifest_path = None;
+        let mut target_dir = None;
         let mut frozen = false;
         let mut locked = false;
         let mut offline = false;
@@ -518,6 +1219,16 @@ impl Args {
         let mut no_run = false;
         let mut no_fail_fast = false;
         let mut ignore_run_fail = false;
+        let mut pre_test_cmd = None;
+        let mut post_test_cmd = None;
+        let mut test_jobs = None;
+        let mut miri = false;
+        let mut measure_overhead = false;
+        let mut timings = false;
+        let mut watch = false;
+        let mut each_feature = false;
+        let mut feature_powerset = false;
+        let mut depth = None;
         let mut lib = false;
         let mut bin = vec![];
         let mut bins = false;
@@ -535,20 +1246,55 @@ impl Args {
         let mut exclude = vec![];
         let mut exclude_from_test = vec![];
         let mut exclude_from_report = vec![];
+        let mut sample_packages = None;
+        let mut sample_history = None;
 
         // llvm-cov options
         let mut json = false;
         let mut lcov = false;
         let mut cobertura = false;
         let mut codecov = false;
+        let mut rdjson = false;
+        let mut checkstyle = false;
+        let mut coveralls = false;
+        let mut sarif = false;
+        let mut jacoco = false;
+        let mut sonarqube = false;
         let mut text = false;
         let mut html = false;
         let mut open = false;
         let mut summary_only = false;
         let mut output_path = None;
         let mut output_dir = None;
+        let mut metadata_timestamp = false;
+        let mut badges = None;
+        let mut badge_thresholds = None;
+        let mut dashboard = None;
         let mut failure_mode = None;
-        let mut ignore_filename_regex = None;
+        let mut profraw_glob = vec![];
+        let mut debt = false;
+        let mut missing_examples = false;
+        let mut hotspots = false;
+        let mut show_functions = false;
+        let mut affected_by = vec![];
+        let mut uncovered_functions = false;
+        let mut csv = false;
+        let mut tsv = false;
+        let mut function_filter = None;
+        let mut module_summary = false;
+        let mut type_summary = false;
+        let mut package_summary = false;
+        let mut file_summary = false;
+        let mut sort_by = None;
+        let mut changed_since = None;
+        let mut diff = None;
+        let mut verify_binary_ids = false;
+        let mut allow_empty = false;
+        let mut ignore_filename_regex = vec![];
+        let mut path_equivalence = vec![];
+        let mut exclude_panic_lines = false;
+        let mut exclude_test_code = false;
+        let mut exclude_cfg = vec![];
         let mut disable_default_ignore_filename_regex = false;
         let mut show_instantiations = false;
         let mut no_cfg_coverage = false;
@@ -557,15 +1303,30 @@ impl Args {
         let mut fail_under_functions = None;
         let mut fail_under_lines = None;
         let mut fail_under_regions = None;
+        let mut fail_under_branches = None;
+        let mut fail_under_diff = None;
+        let mut github_annotations = false;
+        let mut ratchet = None;
+        let mut ratchet_tolerance = None;
+        let mut save_snapshot = None;
+        let mut compare_to = None;
+        let mut compare_tolerance = None;
         let mut fail_uncovered_lines = None;
         let mut fail_uncovered_regions = None;
         let mut fail_uncovered_functions = None;
+        let mut summary_json_to_stderr = false;
         let mut show_missing_lines = false;
+        let mut show_missing_lines_by_function = false;
+        let mut show_missing_ranges = false;
+        let mut group_closures = false;
+        let mut complexity_weighted = false;
         let mut include_build_script = false;
+        let mut include_proc_macros = false;
         let mut dep_coverage = None;
         let mut skip_functions = false;
         let mut branch = false;
         let mut mcdc = false;
+        let mut coverage_level: Option<CoverageLevel> = None;
 
         // build options
         let mut release = false;
@@ -573,6 +1334,14 @@ impl Args {
         let mut coverage_target_only = false;
         let mut remap_path_prefix = false;
         let mut include_ffi = false;
+        let mut include_std = false;
+        let mut include_crate = vec![];
+        let mut exclude_crate = vec![];
+        let mut profile_continuous = false;
+        let mut sanitizer = None;
+        let mut profile_update = None;
+        let mut no_build_settings_warnings = false;
+        let mut override_build_settings = false;
         let mut verbose: usize = 0;
         let mut no_clean = false;
 
@@ -582,6 +1351,38 @@ impl Args {
         // show-env options
         let mut export_prefix = false;
         let mut with_pwsh_env_prefix = false;
+        let mut with_fish_set_prefix = false;
+        let mut github_env = false;
+
+        // upload options
+        let mut url = None;
+        let mut method = None;
+        let mut header = vec![];
+        let mut file = None;
+        let mut cacert = None;
+        let mut upload_dry_run = None;
+        let mut retry = None;
+
+        // collect options
+        let mut probe_rs = false;
+        let mut chip = None;
+        let mut probe = None;
+        let mut elf = None;
+        let mut symbol = None;
+        let mut address: Option<String> = None;
+        let mut size: Option<String> = None;
+
+        // fuzz options
+        let mut fuzz_args = vec![];
+
+        // show options
+        let mut show_files = vec![];
+
+        // self-update options
+        let mut version = None;
+
+        // init options
+        let mut ci_workflow = false;
 
         // options ambiguous between nextest-related and others
         let mut profile = None;
@@ -680,12 +1481,23 @@ impl Args {
             match arg {
                 Long("color") => parse_opt_passthrough!(color),
                 Long("manifest-path") => parse_opt!(manifest_path),
+                Long("target-dir") => parse_opt!(target_dir),
                 Long("frozen") => parse_flag_passthrough!(frozen),
                 Long("locked") => parse_flag_passthrough!(locked),
                 Long("offline") => parse_flag_passthrough!(offline),
 
                 Long("doctests") => parse_flag!(doctests),
                 Long("ignore-run-fail") => parse_flag!(ignore_run_fail),
+                Long("pre-test-cmd") => parse_opt!(pre_test_cmd),
+                Long("post-test-cmd") => parse_opt!(post_test_cmd),
+                Long("test-jobs") => parse_opt!(test_jobs),
+                Long("miri") => parse_flag!(miri),
+                Long("measure-overhead") => parse_flag!(measure_overhead),
+                Long("timings") => parse_flag_passthrough!(timings),
+                Long("watch") => parse_flag!(watch),
+                Long("each-feature") => parse_flag!(each_feature),
+                Long("feature-powerset") => parse_flag!(feature_powerset),
+                Long("depth") => parse_opt!(depth),
                 Long("no-run") => parse_flag!(no_run),
                 Long("no-fail-fast") => parse_flag_passthrough!(no_fail_fast),
 
@@ -706,6 +1518,8 @@ impl Args {
                 Long("exclude") => parse_opt_passthrough!(exclude),
                 Long("exclude-from-test") => parse_opt!(exclude_from_test),
                 Long("exclude-from-report") => parse_opt!(exclude_from_report),
+                Long("sample-packages") => parse_opt!(sample_packages),
+                Long("sample-history") => parse_opt!(sample_history),
 
                 // build options
                 Short('r') | Long("release") => parse_flag!(release),
@@ -716,6 +1530,14 @@ impl Args {
                 Long("coverage-target-only") => parse_flag!(coverage_target_only),
                 Long("remap-path-prefix") => parse_flag!(remap_path_prefix),
                 Long("include-ffi") => parse_flag!(include_ffi),
+                Long("include-std") => parse_flag!(include_std),
+                Long("include-crate") => parse_opt!(include_crate),
+                Long("exclude-crate") => parse_opt!(exclude_crate),
+                Long("profile-continuous") => parse_flag!(profile_continuous),
+                Long("sanitizer") => parse_opt!(sanitizer),
+                Long("profile-update") => parse_opt!(profile_update),
+                Long("no-build-settings-warnings") => parse_flag!(no_build_settings_warnings),
+                Long("override-build-settings") => parse_flag!(override_build_settings),
                 Long("no-clean") => parse_flag!(no_clean),
 
                 // clean options
@@ -726,6 +1548,12 @@ impl Args {
                 Long("lcov") => parse_flag!(lcov),
                 Long("cobertura") => parse_flag!(cobertura),
                 Long("codecov") => parse_flag!(codecov),
+                Long("rdjson") => parse_flag!(rdjson),
+                Long("checkstyle") => parse_flag!(checkstyle),
+                Long("coveralls") => parse_flag!(coveralls),
+                Long("sarif") => parse_flag!(sarif),
+                Long("jacoco") => parse_flag!(jacoco),
+                Long("sonarqube") => parse_flag!(sonarqube),
                 Long("text") => parse_flag!(text),
                 Long("html") => parse_flag!(html),
                 Long("open") => parse_flag!(open),
@@ -733,10 +1561,38 @@ impl Args {
                 Long("skip-functions") => parse_flag!(skip_functions),
                 Long("branch") => parse_flag!(branch),
                 Long("mcdc") => parse_flag!(mcdc),
+                Long("coverage-level") => parse_opt!(coverage_level),
                 Long("output-path") => parse_opt!(output_path),
                 Long("output-dir") => parse_opt!(output_dir),
+                Long("metadata-timestamp") => parse_flag!(metadata_timestamp),
+                Long("badges") => parse_opt!(badges),
+                Long("badge-thresholds") => parse_opt!(badge_thresholds),
+                Long("dashboard") => parse_opt!(dashboard),
                 Long("failure-mode") => parse_opt!(failure_mode),
+                Long("profraw-glob") => parse_opt!(profraw_glob),
+                Long("debt") => parse_flag!(debt),
+                Long("missing-examples") => parse_flag!(missing_examples),
+                Long("hotspots") => parse_flag!(hotspots),
+                Long("show-functions") => parse_flag!(show_functions),
+                Long("affected-by") => parse_opt!(affected_by),
+                Long("uncovered-functions") => parse_flag!(uncovered_functions),
+                Long("csv") => parse_flag!(csv),
+                Long("tsv") => parse_flag!(tsv),
+                Long("function-filter") => parse_opt!(function_filter),
+                Long("module-summary") => parse_flag!(module_summary),
+                Long("type-summary") => parse_flag!(type_summary),
+                Long("package-summary") => parse_flag!(package_summary),
+                Long("file-summary") => parse_flag!(file_summary),
+                Long("sort-by") => parse_opt!(sort_by),
+                Long("changed-since") => parse_opt!(changed_since),
+                Long("diff") => parse_opt!(diff),
+                Long("verify-binary-ids") => parse_flag!(verify_binary_ids),
+                Long("allow-empty") => parse_flag!(allow_empty),
                 Long("ignore-filename-regex") => parse_opt!(ignore_filename_regex),
+                Long("path-equivalence") => parse_opt!(path_equivalence),
+                Long("exclude-panic-lines") => parse_flag!(exclude_panic_lines),
+                Long("exclude-test-code") => parse_flag!(exclude_test_code),
+                Long("exclude-cfg") => parse_opt!(exclude_cfg),
                 Long("disable-default-ignore-filename-regex") => {
                     parse_flag!(disable_default_ignore_filename_regex);
                 }
@@ -752,16 +1608,58 @@ impl Args {
                 Long("fail-under-functions") => parse_opt!(fail_under_functions),
                 Long("fail-under-lines") => parse_opt!(fail_under_lines),
                 Long("fail-under-regions") => parse_opt!(fail_under_regions),
+                Long("fail-under-branches") => parse_opt!(fail_under_branches),
+                Long("fail-under-diff") => parse_opt!(fail_under_diff),
+                Long("github-annotations") => parse_flag!(github_annotations),
+                Long("ratchet") => parse_opt!(ratchet),
+                Long("ratchet-tolerance") => parse_opt!(ratchet_tolerance),
+                Long("save-snapshot") => parse_opt!(save_snapshot),
+                Long("compare-to") => parse_opt!(compare_to),
+                Long("compare-tolerance") => parse_opt!(compare_tolerance),
                 Long("fail-uncovered-lines") => parse_opt!(fail_uncovered_lines),
                 Long("fail-uncovered-regions") => parse_opt!(fail_uncovered_regions),
                 Long("fail-uncovered-functions") => parse_opt!(fail_uncovered_functions),
+                Long("summary-json-to-stderr") => parse_flag!(summary_json_to_stderr),
                 Long("show-missing-lines") => parse_flag!(show_missing_lines),
+                Long("show-missing-lines-by-function") => {
+                    parse_flag!(show_missing_lines_by_function);
+                }
+                Long("show-missing-ranges") => parse_flag!(show_missing_ranges),
+                Long("group-closures") => parse_flag!(group_closures),
+                Long("complexity-weighted") => parse_flag!(complexity_weighted),
                 Long("include-build-script") => parse_flag!(include_build_script),
+                Long("include-proc-macros") => parse_flag!(include_proc_macros),
                 Long("dep-coverage") => parse_opt!(dep_coverage),
 
                 // show-env options
                 Long("export-prefix") => parse_flag!(export_prefix),
                 Long("with-pwsh-env-prefix") => parse_flag!(with_pwsh_env_prefix),
+                Long("with-fish-set-prefix") => parse_flag!(with_fish_set_prefix),
+                Long("github-env") => parse_flag!(github_env),
+
+                // upload options
+                Long("url") => parse_opt!(url),
+                Long("method") => parse_opt!(method),
+                Long("header") => parse_opt!(header),
+                Long("file") => parse_opt!(file),
+                Long("cacert") => parse_opt!(cacert),
+                Long("upload-dry-run") => parse_opt!(upload_dry_run),
+                Long("retry") => parse_opt!(retry),
+
+                // collect options
+                Long("probe-rs") => parse_flag!(probe_rs),
+                Long("chip") => parse_opt!(chip),
+                Long("probe") => parse_opt!(probe),
+                Long("elf") => parse_opt!(elf),
+                Long("symbol") => parse_opt!(symbol),
+                Long("address") => parse_opt!(address),
+                Long("size") => parse_opt!(size),
+
+                // self-update options
+                Long("version") if subcommand == Subcommand::SelfUpdate => parse_opt!(version),
+
+                // init options
+                Long("ci-workflow") => parse_flag!(ci_workflow),
 
                 // ambiguous between nextest-related and others will be handled later
                 Long("archive-file") => parse_opt_passthrough!(archive_file),
@@ -783,11 +1681,6 @@ impl Args {
                     unexpected("--version", subcommand)?;
                 }
 
-                // TODO: Currently, we are using a subdirectory of the target directory as
-                //       the actual target directory. What effect should this option have
-                //       on its behavior?
-                Long("target-dir") => unexpected(&format_arg(&arg), subcommand)?,
-
                 // Handle known options for can_passthrough=false subcommands
                 Short('Z') => parse_opt_passthrough!(()),
                 Short('F' | 'j') | Long("features" | "jobs")
@@ -796,6 +1689,7 @@ impl Args {
                         Subcommand::None
                             | Subcommand::Test
                             | Subcommand::Run
+                            | Subcommand::Fuzz
                             | Subcommand::Nextest { .. }
                             | Subcommand::NextestArchive
                     ) =>
@@ -804,15 +1698,13 @@ impl Args {
                 }
                 Short('q') | Long("quiet") => passthrough!(),
                 Long(
-                    "all-features"
-                    | "no-default-features"
-                    | "--keep-going"
-                    | "--ignore-rust-version",
+                    "all-features" | "no-default-features" | "keep-going" | "ignore-rust-version",
                 ) if matches!(
                     subcommand,
                     Subcommand::None
                         | Subcommand::Test
                         | Subcommand::Run
+                        | Subcommand::Fuzz
                         | Subcommand::Nextest { .. }
                         | Subcommand::NextestArchive
                 ) =>
@@ -822,6 +1714,22 @@ impl Args {
 
                 // passthrough
                 Long(_) | Short(_) if Subcommand::can_passthrough(subcommand) => passthrough!(),
+                // Options before the subcommand are passed through to cargo if the subcommand
+                // turns out to be one that runs cargo, e.g., `--future-incompat-report`.
+                Long(_) | Short(_) if subcommand == Subcommand::None => {
+                    passthrough_before_subcommand.get_or_insert_with(|| format_arg(&arg));
+                    passthrough!();
+                }
+                // fuzz target and corpus
+                Value(val) if subcommand == Subcommand::Fuzz => {
+                    fuzz_args.push(val.into_string().unwrap());
+                    after_subcommand = false;
+                }
+                // files to show
+                Value(val) if subcommand == Subcommand::Show => {
+                    show_files.push(val.into_string().unwrap());
+                    after_subcommand = false;
+                }
                 Value(val)
                     if subcommand == Subcommand::None
                         || Subcommand::can_passthrough(subcommand) =>
@@ -829,6 +1737,11 @@ impl Args {
                     let val = val.into_string().unwrap();
                     if subcommand == Subcommand::None {
                         subcommand = val.parse::<Subcommand>()?;
+                        if let Some(arg) = &passthrough_before_subcommand {
+                            if !Subcommand::can_passthrough(subcommand) {
+                                unexpected(arg, subcommand)?;
+                            }
+                        }
                         if subcommand == Subcommand::Demangle && args.len() != 1 {
                             unexpected(
                                 args.iter().find(|&arg| arg != "demangle").unwrap(),
@@ -866,7 +1779,12 @@ impl Args {
 
         // unexpected options
         let show_env_format = match subcommand {
-            Subcommand::ShowEnv => ShowEnvFormat::new(export_prefix, with_pwsh_env_prefix)?,
+            Subcommand::ShowEnv => ShowEnvFormat::new(
+                export_prefix,
+                with_pwsh_env_prefix,
+                with_fish_set_prefix,
+                github_env,
+            )?,
             _ => {
                 if export_prefix {
                     unexpected("--export-prefix", subcommand)?;
@@ -874,14 +1792,219 @@ impl Args {
                 if with_pwsh_env_prefix {
                     unexpected("--with-pwsh-env-prefix", subcommand)?;
                 }
+                if with_fish_set_prefix {
+                    unexpected("--with-fish-set-prefix", subcommand)?;
+                }
+                if github_env {
+                    unexpected("--github-env", subcommand)?;
+                }
                 ShowEnvFormat::default()
             }
         };
+        let upload = match subcommand {
+            Subcommand::Upload => {
+                if coveralls && method.is_some() {
+                    conflicts("--coveralls", "--method")?;
+                }
+                let url = url.or_else(|| coveralls.then(|| upload::COVERALLS_URL.to_owned()));
+                let Some(url) = url else { bail!("--url is required for upload subcommand") };
+                let Some(file) = file else { bail!("--file is required for upload subcommand") };
+                if url.is_empty() {
+                    bail!("empty string is not allowed in --url")
+                }
+                if method.as_deref() == Some("") {
+                    bail!("empty string is not allowed in --method")
+                }
+                if header.iter().any(|h: &String| !h.contains(':')) {
+                    bail!("--header must be in `NAME: VALUE` format")
+                }
+                UploadOptions {
+                    url,
+                    method: method
+                        .unwrap_or_else(|| if coveralls { "POST" } else { "PUT" }.to_owned()),
+                    headers: header,
+                    file,
+                    coveralls,
+                    cacert,
+                    dry_run: upload_dry_run,
+                    retry: retry.unwrap_or(0),
+                }
+            }
+            _ => {
+                if url.is_some() {
+                    unexpected("--url", subcommand)?;
+                }
+                if method.is_some() {
+                    unexpected("--method", subcommand)?;
+                }
+                if !header.is_empty() {
+                    unexpected("--header", subcommand)?;
+                }
+                if file.is_some() {
+                    unexpected("--file", subcommand)?;
+                }
+                if cacert.is_some() {
+                    unexpected("--cacert", subcommand)?;
+                }
+                if upload_dry_run.is_some() {
+                    unexpected("--upload-dry-run", subcommand)?;
+                }
+                if retry.is_some() {
+                    unexpected("--retry", subcommand)?;
+                }
+                UploadOptions::default()
+            }
+        };
+        let collect = match subcommand {
+            Subcommand::Collect => {
+                if !probe_rs {
+                    bail!("--probe-rs is required for collect subcommand");
+                }
+                let Some(chip) = chip else { bail!("--chip is required for collect subcommand") };
+                if symbol.is_some() {
+                    if address.is_some() {
+                        conflicts("--symbol", "--address")?;
+                    }
+                    if elf.is_none() {
+                        requires("--symbol", &["--elf"])?;
+                    }
+                } else if address.is_none() || size.is_none() {
+                    bail!(
+                        "either --symbol or both --address and --size are required for collect \
+                         subcommand"
+                    );
+                }
+                CollectOptions {
+                    chip,
+                    probe,
+                    elf,
+                    symbol,
+                    address: address.as_deref().map(|v| parse_int(v, "--address")).transpose()?,
+                    size: size.as_deref().map(|v| parse_int(v, "--size")).transpose()?,
+                }
+            }
+            _ => {
+                for (present, flag) in [
+                    (probe_rs, "--probe-rs"),
+                    (chip.is_some(), "--chip"),
+                    (probe.is_some(), "--probe"),
+                    (elf.is_some(), "--elf"),
+                    (symbol.is_some(), "--symbol"),
+                    (address.is_some(), "--address"),
+                    (size.is_some(), "--size"),
+                ] {
+                    if present {
+                        unexpected(flag, subcommand)?;
+                    }
+                }
+                CollectOptions::default()
+            }
+        };
+        let fuzz = match subcommand {
+            Subcommand::Fuzz => {
+                let mut fuzz_args = fuzz_args.into_iter();
+                let Some(target) = fuzz_args.next() else {
+                    bail!("the name of the fuzz target is required for fuzz subcommand")
+                };
+                if target.is_empty() {
+                    bail!("empty string is not allowed in the name of the fuzz target")
+                }
+                for (present, flag) in [
+                    (!bin.is_empty(), "--bin"),
+                    (!example.is_empty(), "--example"),
+                    (ignore_run_fail, "--ignore-run-fail"),
+                ] {
+                    if present {
+                        unexpected(flag, subcommand)?;
+                    }
+                }
+                FuzzOptions {
+                    target,
+                    corpus: fuzz_args.map(Utf8PathBuf::from).collect(),
+                    // Arguments after `--` are passed to libFuzzer, not to cargo.
+                    args: mem::take(&mut rest),
+                }
+            }
+            _ => FuzzOptions::default(),
+        };
+        let show = match subcommand {
+            Subcommand::Show => {
+                if show_files.is_empty() {
+                    bail!("at least one file is required for show subcommand")
+                }
+                if show_files.iter().any(String::is_empty) {
+                    bail!("empty string is not allowed in the files of show subcommand")
+                }
+                ShowOptions { files: show_files }
+            }
+            _ => ShowOptions::default(),
+        };
+        let self_update = match subcommand {
+            Subcommand::SelfUpdate => {
+                if version.as_deref() == Some("") {
+                    bail!("empty string is not allowed in --version")
+                }
+                SelfUpdateOptions { version }
+            }
+            _ => SelfUpdateOptions::default(),
+        };
+        if ci_workflow && subcommand != Subcommand::Init {
+            unexpected("--ci-workflow", subcommand)?;
+        }
+        match subcommand {
+            Subcommand::Nextest { .. } => {}
+            _ => {
+                if dashboard.is_some() {
+                    unexpected("--dashboard", subcommand)?;
+                }
+            }
+        }
+        match subcommand {
+            Subcommand::None | Subcommand::Test => {}
+            _ => {
+                if test_jobs.is_some() {
+                    unexpected("--test-jobs", subcommand)?;
+                }
+                if miri {
+                    unexpected("--miri", subcommand)?;
+                }
+                if measure_overhead {
+                    unexpected("--measure-overhead", subcommand)?;
+                }
+                if watch {
+                    unexpected("--watch", subcommand)?;
+                }
+                if each_feature {
+                    unexpected("--each-feature", subcommand)?;
+                }
+                if feature_powerset {
+                    unexpected("--feature-powerset", subcommand)?;
+                }
+                if depth.is_some() {
+                    unexpected("--depth", subcommand)?;
+                }
+            }
+        }
+        match subcommand {
+            Subcommand::None | Subcommand::Test => {}
+            // --timings is only passed to cargo, e.g., for `cargo llvm-cov run --timings`.
+            _ if Subcommand::can_passthrough(subcommand) => timings = false,
+            _ => {
+                if timings {
+                    unexpected("--timings", subcommand)?;
+                }
+            }
+        }
         if doc || doctests {
             let flag = if doc { "--doc" } else { "--doctests" };
             match subcommand {
                 Subcommand::None | Subcommand::Test => {}
-                Subcommand::ShowEnv | Subcommand::Report { .. } if doctests => {}
+                Subcommand::ShowEnv
+                | Subcommand::Report { .. }
+                | Subcommand::Tui
+                | Subcommand::Show
+                | Subcommand::Stats
+                    if doctests => {}
                 Subcommand::Nextest { .. } | Subcommand::NextestArchive => {
                     bail!("doctest is not supported for nextest")
                 }
@@ -938,6 +2061,7 @@ impl Args {
             Subcommand::None
             | Subcommand::Test
             | Subcommand::Run
+            | Subcommand::Fuzz
             | Subcommand::Nextest { .. }
             | Subcommand::NextestArchive => {}
             _ => {
@@ -961,10 +2085,28 @@ impl Args {
                 }
             }
         }
+        match subcommand {
+            Subcommand::None | Subcommand::Test | Subcommand::Run | Subcommand::Nextest { .. } => {}
+            _ => {
+                if pre_test_cmd.is_some() {
+                    unexpected("--pre-test-cmd", subcommand)?;
+                }
+                if post_test_cmd.is_some() {
+                    unexpected("--post-test-cmd", subcommand)?;
+                }
+                if sample_packages.is_some() {
+                    unexpected("--sample-packages", subcommand)?;
+                }
+                if sample_history.is_some() {
+                    unexpected("--sample-history", subcommand)?;
+                }
+            }
+        }
         match subcommand {
             Subcommand::None
             | Subcommand::Test
             | Subcommand::Run
+            | Subcommand::Fuzz
             | Subcommand::Nextest { .. }
             | Subcommand::NextestArchive
             | Subcommand::ShowEnv => {}
@@ -975,6 +2117,30 @@ impl Args {
                 if no_cfg_coverage_nightly {
                     unexpected("--no-cfg-coverage-nightly", subcommand)?;
                 }
+                if profile_continuous {
+                    unexpected("--profile-continuous", subcommand)?;
+                }
+                if sanitizer.is_some() {
+                    unexpected("--sanitizer", subcommand)?;
+                }
+                if profile_update.is_some() {
+                    unexpected("--profile-update", subcommand)?;
+                }
+                if !include_crate.is_empty() {
+                    unexpected("--include-crate", subcommand)?;
+                }
+                if !exclude_crate.is_empty() {
+                    unexpected("--exclude-crate", subcommand)?;
+                }
+                if include_proc_macros {
+                    unexpected("--include-proc-macros", subcommand)?;
+                }
+                if no_build_settings_warnings {
+                    unexpected("--no-build-settings-warnings", subcommand)?;
+                }
+                if override_build_settings {
+                    unexpected("--override-build-settings", subcommand)?;
+                }
             }
         }
         match subcommand {
@@ -989,6 +2155,143 @@ impl Args {
                 }
             }
         }
+        match subcommand {
+            Subcommand::Report { .. } => {}
+            _ => {
+                if !profraw_glob.is_empty() {
+                    unexpected("--profraw-glob", subcommand)?;
+                }
+                if debt {
+                    unexpected("--debt", subcommand)?;
+                }
+                if missing_examples {
+                    unexpected("--missing-examples", subcommand)?;
+                }
+                if hotspots {
+                    unexpected("--hotspots", subcommand)?;
+                }
+                if show_functions {
+                    unexpected("--show-functions", subcommand)?;
+                }
+                if !affected_by.is_empty() {
+                    unexpected("--affected-by", subcommand)?;
+                }
+                if uncovered_functions {
+                    unexpected("--uncovered-functions", subcommand)?;
+                }
+                if module_summary {
+                    unexpected("--module-summary", subcommand)?;
+                }
+                if type_summary {
+                    unexpected("--type-summary", subcommand)?;
+                }
+                if package_summary {
+                    unexpected("--package-summary", subcommand)?;
+                }
+            }
+        }
+        match subcommand {
+            Subcommand::ShowEnv
+            | Subcommand::Clean
+            | Subcommand::Doctor
+            | Subcommand::Upload
+            | Subcommand::Collect
+            | Subcommand::SelfUpdate
+            | Subcommand::Init => {
+                if changed_since.is_some() {
+                    unexpected("--changed-since", subcommand)?;
+                }
+                if diff.is_some() {
+                    unexpected("--diff", subcommand)?;
+                }
+                if exclude_panic_lines {
+                    unexpected("--exclude-panic-lines", subcommand)?;
+                }
+                if !exclude_cfg.is_empty() {
+                    unexpected("--exclude-cfg", subcommand)?;
+                }
+                if exclude_test_code {
+                    unexpected("--exclude-test-code", subcommand)?;
+                }
+                if group_closures {
+                    unexpected("--group-closures", subcommand)?;
+                }
+                if metadata_timestamp {
+                    unexpected("--metadata-timestamp", subcommand)?;
+                }
+                if badges.is_some() {
+                    unexpected("--badges", subcommand)?;
+                }
+                if file_summary {
+                    unexpected("--file-summary", subcommand)?;
+                }
+                if ratchet.is_some() {
+                    unexpected("--ratchet", subcommand)?;
+                }
+                if save_snapshot.is_some() {
+                    unexpected("--save-snapshot", subcommand)?;
+                }
+                if compare_to.is_some() {
+                    unexpected("--compare-to", subcommand)?;
+                }
+                if verify_binary_ids {
+                    unexpected("--verify-binary-ids", subcommand)?;
+                }
+                if allow_empty {
+                    unexpected("--allow-empty", subcommand)?;
+                }
+            }
+            _ => {}
+        }
+        if matches!(subcommand, Subcommand::Tui | Subcommand::Show | Subcommand::Stats) {
+            // These subcommands don't write reports and don't check coverage.
+            for (flag, name) in [
+                (json, "--json"),
+                (lcov, "--lcov"),
+                (cobertura, "--cobertura"),
+                (codecov, "--codecov"),
+                (rdjson, "--rdjson"),
+                (checkstyle, "--checkstyle"),
+                (coveralls, "--coveralls"),
+                (sarif, "--sarif"),
+                (jacoco, "--jacoco"),
+                (sonarqube, "--sonarqube"),
+                (csv, "--csv"),
+                (tsv, "--tsv"),
+                (text, "--text"),
+                (html, "--html"),
+                (open, "--open"),
+                (summary_only, "--summary-only"),
+                (output_path.is_some(), "--output-path"),
+                (output_dir.is_some(), "--output-dir"),
+                (metadata_timestamp, "--metadata-timestamp"),
+                (badges.is_some(), "--badges"),
+                (file_summary, "--file-summary"),
+                (ratchet.is_some(), "--ratchet"),
+                (save_snapshot.is_some(), "--save-snapshot"),
+                (compare_to.is_some(), "--compare-to"),
+                (allow_empty, "--allow-empty"),
+                (fail_under_functions.is_some(), "--fail-under-functions"),
+                (fail_under_lines.is_some(), "--fail-under-lines"),
+                (fail_under_regions.is_some(), "--fail-under-regions"),
+                (fail_under_branches.is_some(), "--fail-under-branches"),
+                (diff.is_some(), "--diff"),
+                (fail_under_diff.is_some(), "--fail-under-diff"),
+                (github_annotations, "--github-annotations"),
+                (fail_uncovered_lines.is_some(), "--fail-uncovered-lines"),
+                (fail_uncovered_regions.is_some(), "--fail-uncovered-regions"),
+                (fail_uncovered_functions.is_some(), "--fail-uncovered-functions"),
+                (summary_json_to_stderr, "--summary-json-to-stderr"),
+                (show_missing_lines, "--show-missing-lines"),
+                (show_missing_lines_by_function, "--show-missing-lines-by-function"),
+                (show_missing_ranges, "--show-missing-ranges"),
+                (complexity_weighted, "--complexity-weighted"),
+            ] {
+                if flag {
+                    unexpected(name, subcommand)?;
+                }
+            }
+        }
         // TODO: check more
 
         // requires
@@ -1001,12 +2304,61 @@ impl Args {
             if !exclude_from_test.is_empty() {
                 requires("--exclude-from-test", &["--workspace"])?;
             }
+            if sample_packages.is_some() {
+                requires("--sample-packages", &["--workspace"])?;
+            }
+        }
+        if sample_history.is_some() && sample_packages.is_none() {
+            requires("--sample-history", &["--sample-packages"])?;
+        }
+        if badge_thresholds.is_some() && badges.is_none() {
+            requires("--badge-thresholds", &["--badges"])?;
+        }
+        if function_filter.is_some() && !show_functions {
+            requires("--function-filter", &["--show-functions"])?;
+        }
+        if sort_by.is_some() && !file_summary {
+            requires("--sort-by", &["--file-summary"])?;
         }
         if coverage_target_only && target.is_none() {
             requires("--coverage-target-only", &["--target"])?;
         }
+        // --coverage-level is another way to spell --branch and --mcdc.
+        if let Some(level) = coverage_level {
+            if branch {
+                conflicts("--coverage-level", "--branch")?;
+            }
+            if mcdc {
+                conflicts("--coverage-level", "--mcdc")?;
+            }
+            branch = level == CoverageLevel::Branch;
+            mcdc = level == CoverageLevel::Mcdc;
+        }
+        if fail_under_branches.is_some() && !branch && !mcdc {
+            requires("--fail-under-branches", &["--branch", "--mcdc"])?;
+        }
+        if ratchet_tolerance.is_some() && ratchet.is_none() {
+            requires("--ratchet-tolerance", &["--ratchet"])?;
+        }
+        if compare_tolerance.is_some() && compare_to.is_none() {
+            requires("--compare-tolerance", &["--compare-to"])?;
+        }
+        if fail_under_diff.is_some() && diff.is_none() {
+            requires("--fail-under-diff", &["--diff"])?;
+        }
+        if github_annotations && diff.is_none() {
+            requires("--github-annotations", &["--diff"])?;
+        }
 
         // conflicts
+        if ratchet.is_some() && changed_since.is_some() {
+            // Coverage of changed files is not comparable with the recorded total coverage.
+            conflicts("--ratchet", "--changed-since")?;
+        }
+        if compare_to.is_some() && changed_since.is_some() {
+            // Coverage of changed files is not comparable with the snapshot.
+            conflicts("--compare-to", "--changed-since")?;
+        }
         if no_report && no_run {
             conflicts("--no-report", "--no-run")?;
         }
@@ -1021,6 +2373,59 @@ impl Args {
             // --ignore-run-fail implicitly enable --no-fail-fast.
             conflicts("--ignore-run-fail", "--no-fail-fast")?;
         }
+        if test_jobs.is_some() {
+            let flag = "--test-jobs";
+            if no_run {
+                conflicts(flag, "--no-run")?;
+            }
+            if doc || doctests {
+                // Doctests are not compiled into test binaries.
+                conflicts(flag, if doc { "--doc" } else { "--doctests" })?;
+            }
+        }
+        if miri && no_run {
+            conflicts("--miri", "--no-run")?;
+        }
+        if measure_overhead && no_run {
+            conflicts("--measure-overhead", "--no-run")?;
+        }
+        if watch {
+            if no_run {
+                conflicts("--watch", "--no-run")?;
+            }
+            if measure_overhead {
+                // The uninstrumented build would be repeated on every change.
+                conflicts("--watch", "--measure-overhead")?;
+            }
+        }
+        if depth.is_some() && !feature_powerset {
+            requires("--depth", &["--feature-powerset"])?;
+        }
+        if each_feature || feature_powerset {
+            if each_feature && feature_powerset {
+                conflicts("--each-feature", "--feature-powerset")?;
+            }
+            let flag = if each_feature { "--each-feature" } else { "--feature-powerset" };
+            if no_run {
+                conflicts(flag, "--no-run")?;
+            }
+            if test_jobs.is_some() {
+                conflicts(flag, "--test-jobs")?;
+            }
+            if measure_overhead {
+                conflicts(flag, "--measure-overhead")?;
+            }
+            // The features of each run are selected by cargo-llvm-cov.
+            for arg in &cargo_args {
+                let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+                if name.starts_with("-F") || name == "--features" {
+                    conflicts(flag, "--features")?;
+                }
+                if matches!(name, "--all-features" | "--no-default-features") {
+                    conflicts(flag, name)?;
+                }
+            }
+        }
         if doc || doctests {
             let flag = if doc { "--doc" } else { "--doctests" };
             if lib {
@@ -1059,77 +2464,175 @@ impl Args {
             // we reject it because the situation where both flags are specified is odd.
             conflicts("--package", "--workspace")?;
         }
-        // TODO: handle these mutual exclusions elegantly.
-        if lcov {
-            let flag = "--lcov";
-            if json {
-                conflicts(flag, "--json")?;
+        // Multiple format flags can be passed to generate multiple reports at once, and the
+        // reports are written to --output-dir in that case (--open implies --html). Flags that
+        // generate their own report only support one format.
+        let formats = [
+            (json, "--json"),
+            (lcov, "--lcov"),
+            (cobertura, "--cobertura"),
+            (codecov, "--codecov"),
+            (rdjson, "--rdjson"),
+            (checkstyle, "--checkstyle"),
+            (coveralls, "--coveralls"),
+            (sarif, "--sarif"),
+            (jacoco, "--jacoco"),
+            (sonarqube, "--sonarqube"),
+            (csv, "--csv"),
+            (tsv, "--tsv"),
+            (text, "--text"),
+            (html || open, if html { "--html" } else { "--open" }),
+        ];
+        let formats: Vec<_> =
+            formats.iter().filter(|&&(flag, _)| flag).map(|&(_, name)| name).collect();
+        let multiple_formats = formats.len() > 1;
+        if multiple_formats
+            && (!affected_by.is_empty()
+                || debt
+                || uncovered_functions
+                || missing_examples
+                || hotspots
+                || show_functions
+                || module_summary
+                || type_summary
+                || package_summary
+                || file_summary)
+        {
+            conflicts(formats[1], formats[0])?;
+        }
+        if multiple_formats && output_path.is_some() {
+            bail!(
+                "--output-path may not be used together with multiple format flags ({}); use \
+                 --output-dir instead",
+                formats.join(", ")
+            );
+        }
+        if summary_only || output_path.is_some() {
+            let flag = if summary_only { "--summary-only" } else { "--output-path" };
+            if html {
+                conflicts(flag, "--html")?;
+            }
+            if open {
+                conflicts(flag, "--open")?;
             }
         }
-        if cobertura {
-            let flag = "--cobertura";
-            if json {
-                conflicts(flag, "--json")?;
+        // Flags that generate their own report. They may not be used together with each other,
+        // and the former one in this list is reported first. The flags other than
+        // --file-summary support --json, and --uncovered-functions also supports --csv and --tsv.
+        // (flag, name, allow --json, allow --csv and --tsv)
+        let report_modes = [
+            (uncovered_functions, "--uncovered-functions", true, true),
+            (!affected_by.is_empty(), "--affected-by", true, false),
+            (show_functions, "--show-functions", true, false),
+            (file_summary, "--file-summary", false, false),
+            (package_summary, "--package-summary", true, false),
+            (type_summary, "--type-summary", true, false),
+            (module_summary, "--module-summary", true, false),
+            (hotspots, "--hotspots", true, false),
+            (missing_examples, "--missing-examples", true, false),
+            (debt, "--debt", true, false),
+        ];
+        for (i, &(mode, flag, allow_json, allow_delimited)) in report_modes.iter().enumerate() {
+            if !mode {
+                continue;
             }
-            if lcov {
-                conflicts(flag, "--lcov")?;
+            for (other, name) in [
+                (json && !allow_json, "--json"),
+                (lcov, "--lcov"),
+                (cobertura, "--cobertura"),
+                (codecov, "--codecov"),
+                (rdjson, "--rdjson"),
+                (checkstyle, "--checkstyle"),
+                (coveralls, "--coveralls"),
+                (sarif, "--sarif"),
+                (jacoco, "--jacoco"),
+                (sonarqube, "--sonarqube"),
+                (csv && !allow_delimited, "--csv"),
+                (tsv && !allow_delimited, "--tsv"),
+                (text, "--text"),
+                (html, "--html"),
+                (open, "--open"),
+                (summary_only, "--summary-only"),
+            ] {
+                if other {
+                    conflicts(flag, name)?;
+                }
             }
-            if codecov {
-                conflicts(flag, "--codecov")?;
+            if let Some(&(_, name, ..)) = report_modes[i + 1..].iter().find(|&&(other, ..)| other) {
+                conflicts(flag, name)?;
             }
         }
-        if codecov {
-            let flag = "--codecov";
-            if json {
-                conflicts(flag, "--json")?;
-            }
-            if lcov {
-                conflicts(flag, "--lcov")?;
+        // The list of functions is output instead of the report, so the coverage checked by these
+        // flags is not computed.
+        for (mode, flag) in [(debt, "--debt"), (missing_examples, "--missing-examples")] {
+            if !mode {
+                continue;
             }
-            if cobertura {
-                conflicts(flag, "--cobertura")?;
+            for (other, name) in [
+                (fail_under_functions.is_some(), "--fail-under-functions"),
+                (fail_under_lines.is_some(), "--fail-under-lines"),
+                (fail_under_regions.is_some(), "--fail-under-regions"),
+                (fail_under_branches.is_some(), "--fail-under-branches"),
+                (fail_under_diff.is_some(), "--fail-under-diff"),
+                (fail_uncovered_lines.is_some(), "--fail-uncovered-lines"),
+                (fail_uncovered_regions.is_some(), "--fail-uncovered-regions"),
+                (fail_uncovered_functions.is_some(), "--fail-uncovered-functions"),
+                (ratchet.is_some(), "--ratchet"),
+            ] {
+                if other {
+                    conflicts(flag, name)?;
+                }
             }
         }
-        if text {
-            let flag = "--text";
-            if json {
-                conflicts(flag, "--json")?;
+        if summary_only {
+            let flag = "--summary-only";
+            if rdjson {
+                conflicts("--rdjson", flag)?;
             }
-            if lcov {
-                conflicts(flag, "--lcov")?;
+            if checkstyle {
+                conflicts("--checkstyle", flag)?;
             }
-            if cobertura {
-                conflicts(flag, "--cobertura")?;
+            if coveralls {
+                conflicts("--coveralls", flag)?;
             }
-            if codecov {
-                conflicts(flag, "--codecov")?;
+            if sarif {
+                conflicts("--sarif", flag)?;
             }
-        }
-        if html || open {
-            let flag = if html { "--html" } else { "--open" };
-            if json {
-                conflicts(flag, "--json")?;
+            if jacoco {
+                conflicts("--jacoco", flag)?;
             }
-            if lcov {
-                conflicts(flag, "--lcov")?;
+            if sonarqube {
+                conflicts("--sonarqube", flag)?;
             }
-            if cobertura {
-                conflicts(flag, "--cobertura")?;
+            if csv {
+                conflicts("--csv", flag)?;
             }
-            if codecov {
-                conflicts(flag, "--codecov")?;
+            if tsv {
+                conflicts("--tsv", flag)?;
             }
-            if text {
-                conflicts(flag, "--text")?;
+            if complexity_weighted {
+                conflicts("--complexity-weighted", flag)?;
             }
         }
-        if summary_only || output_path.is_some() {
-            let flag = if summary_only { "--summary-only" } else { "--output-path" };
-            if html {
-                conflicts(flag, "--html")?;
-            }
-            if open {
-                conflicts(flag, "--open")?;
+        if exclude_panic_lines || !exclude_cfg.is_empty() || exclude_test_code {
+            // llvm-cov show output cannot be modified, and summary-only export does not
+            // contain line coverage data.
+            let flag = if exclude_panic_lines {
+                "--exclude-panic-lines"
+            } else if exclude_test_code {
+                "--exclude-test-code"
+            } else {
+                "--exclude-cfg"
+            };
+            for (other, name) in [
+                (text, "--text"),
+                (html, "--html"),
+                (open, "--open"),
+                (summary_only, "--summary-only"),
+            ] {
+                if other {
+                    conflicts(flag, name)?;
+                }
             }
         }
         if skip_functions {
@@ -1138,7 +2641,7 @@ impl Args {
                 conflicts(flag, "--html")?;
             }
         }
-        if output_dir.is_some() {
+        if output_dir.is_some() && !multiple_formats {
             let flag = "--output-dir";
             if json {
                 conflicts(flag, "--json")?;
@@ -1158,15 +2661,81 @@ impl Args {
         }
 
         // forbid_empty_values
-        if ignore_filename_regex.as_deref() == Some("") {
+        if ignore_filename_regex.iter().any(String::is_empty) {
             bail!("empty string is not allowed in --ignore-filename-regex")
         }
+        if let Some(rule) = path_equivalence.iter().find(|rule| dedup::parse_rule(rule).is_none()) {
+            bail!("--path-equivalence must be <FROM>,<TO>, but found `{rule}`")
+        }
+        if changed_since.as_deref() == Some("") {
+            bail!("empty string is not allowed in --changed-since")
+        }
+        if diff.as_deref() == Some("") {
+            bail!("empty string is not allowed in --diff")
+        }
+        if test_jobs == Some(0) {
+            bail!("--test-jobs must be greater than 0")
+        }
+        if depth == Some(0) {
+            bail!("--depth must be greater than 0")
+        }
+        if pre_test_cmd.as_deref() == Some("") {
+            bail!("empty string is not allowed in --pre-test-cmd")
+        }
+        if post_test_cmd.as_deref() == Some("") {
+            bail!("empty string is not allowed in --post-test-cmd")
+        }
+        if include_crate.iter().any(String::is_empty) {
+            bail!("empty string is not allowed in --include-crate")
+        }
+        if exclude_crate.iter().any(String::is_empty) {
+            bail!("empty string is not allowed in --exclude-crate")
+        }
+        if profraw_glob.iter().any(String::is_empty) {
+            bail!("empty string is not allowed in --profraw-glob")
+        }
+        if affected_by.iter().any(String::is_empty) {
+            bail!("empty string is not allowed in --affected-by")
+        }
+        if exclude_cfg.iter().any(|cfg| cfg.trim().is_empty()) {
+            bail!("empty string is not allowed in --exclude-cfg")
+        }
         if output_path.as_deref() == Some(Utf8Path::new("")) {
             bail!("empty string is not allowed in --output-path")
         }
         if output_dir.as_deref() == Some(Utf8Path::new("")) {
             bail!("empty string is not allowed in --output-dir")
         }
+        if target_dir.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --target-dir")
+        }
+        if badges.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --badges")
+        }
+        if dashboard.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --dashboard")
+        }
+        if ratchet.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --ratchet")
+        }
+        if ratchet_tolerance.is_some_and(|t: f64| t < 0. || t.is_nan()) {
+            bail!("--ratchet-tolerance must be a non-negative number")
+        }
+        if save_snapshot.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --save-snapshot")
+        }
+        if compare_to.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --compare-to")
+        }
+        if compare_tolerance.is_some_and(|t: f64| t < 0. || t.is_nan()) {
+            bail!("--compare-tolerance must be a non-negative number")
+        }
+        if sample_packages.as_deref() == Some("") {
+            bail!("empty string is not allowed in --sample-packages")
+        }
+        if sample_history.as_deref() == Some(Utf8Path::new("")) {
+            bail!("empty string is not allowed in --sample-history")
+        }
 
         if no_run {
             // The following warnings should not be promoted to an error.
@@ -1248,14 +2817,47 @@ impl Args {
                 lcov,
                 cobertura,
                 codecov,
+                rdjson,
+                checkstyle,
+                coveralls,
+                sarif,
+                jacoco,
+                sonarqube,
                 text,
                 html,
                 open,
                 summary_only,
                 output_path,
                 output_dir,
+                metadata_timestamp,
+                badges,
+                badge_thresholds,
+                dashboard,
                 failure_mode,
+                profraw_glob,
+                debt,
+                missing_examples,
+                hotspots,
+                show_functions,
+                affected_by,
+                uncovered_functions,
+                csv,
+                tsv,
+                function_filter,
+                module_summary,
+                type_summary,
+                package_summary,
+                file_summary,
+                sort_by,
                 ignore_filename_regex,
+                path_equivalence,
+                exclude_panic_lines,
+                exclude_test_code,
+                exclude_cfg,
+                changed_since,
+                diff,
+                verify_binary_ids,
+                allow_empty,
                 disable_default_ignore_filename_regex,
                 show_instantiations,
                 no_cfg_coverage,
@@ -1264,19 +2866,49 @@ impl Args {
                 fail_under_functions,
                 fail_under_lines,
                 fail_under_regions,
+                fail_under_branches,
+                fail_under_diff,
+                github_annotations,
+                ratchet,
+                ratchet_tolerance,
+                save_snapshot,
+                compare_to,
+                compare_tolerance,
                 fail_uncovered_lines,
                 fail_uncovered_regions,
                 fail_uncovered_functions,
+                summary_json_to_stderr,
                 show_missing_lines,
+                show_missing_lines_by_function,
+                show_missing_ranges,
+                group_closures,
+                complexity_weighted,
                 include_build_script,
+                include_proc_macros,
                 dep_coverage,
                 skip_functions,
                 branch,
                 mcdc,
             },
             show_env: ShowEnvOptions { show_env_format },
+            upload,
+            collect,
+            fuzz,
+            show,
+            self_update,
+            init: InitOptions { ci_workflow },
             doctests,
             ignore_run_fail,
+            pre_test_cmd,
+            post_test_cmd,
+            test_jobs,
+            miri,
+            measure_overhead,
+            timings,
+            watch,
+            each_feature,
+            feature_powerset,
+            depth,
             lib,
             bin,
             bins,
@@ -1292,6 +2924,8 @@ impl Args {
             exclude,
             exclude_from_test,
             exclude_from_report,
+            sample_packages,
+            sample_history,
             release,
             cargo_profile,
             target,
@@ -1300,9 +2934,17 @@ impl Args {
             color,
             remap_path_prefix,
             include_ffi,
+            include_std,
+            include_crate,
+            exclude_crate,
+            profile_continuous,
+            sanitizer,
+            profile_update,
+            no_build_settings_warnings,
+            override_build_settings,
             no_clean,
             profraw_only,
-            manifest: ManifestOptions { manifest_path, frozen, locked, offline },
+            manifest: ManifestOptions { manifest_path, target_dir, frozen, locked, offline },
             nextest_archive_file,
             cargo_args,
             rest,
@@ -1386,6 +3028,15 @@ fn conflicts(a: &str, b: &str) -> Result<()> {
     bail!("{a} may not be used together with {b}");
 }
 
+/// Parses an integer in decimal or, with `0x` prefix, hexadecimal.
+fn parse_int(v: &str, flag: &str) -> Result<u64> {
+    match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => v.parse(),
+    }
+    .map_err(|e| format_err!("invalid value '{v}' for {flag}: {e}"))
+}
+
 #[cold]
 #[inline(never)]
 fn unexpected(arg: &str, subcommand: Subcommand) -> Result<()> {
@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Retrieves profile data from the memory of a bare-metal target device.
+//
+// On bare-metal targets, there is no file system to write profraw files to, so the firmware
+// serializes the profile data into a buffer in RAM (e.g., with the minicov crate) and this
+// reads the buffer via a debug probe using the probe-rs CLI. The buffer is written as a
+// profraw file in the target directory, so the normal report pipeline can use it.
+
+use anyhow::{Context as _, Result, bail, format_err};
+
+use crate::{
+    binary_id::Reader,
+    cargo::Workspace,
+    cli::{self, Args},
+    fs, term,
+};
+
+const SHT_SYMTAB: u32 = 2;
+// Refs: https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/include/llvm/ProfileData/InstrProfData.inc
+const RAW_PROFILE_MAGIC_64: u64 = u64::from_be_bytes(*b"\xfflprofr\x81");
+const RAW_PROFILE_MAGIC_32: u64 = u64::from_be_bytes(*b"\xfflprofR\x81");
+
+pub(crate) fn run(args: &mut Args) -> Result<()> {
+    let ws = Workspace::new(&args.manifest, None, false, false, false, false)?;
+    cli::merge_config_to_args(&ws, &mut None, &mut args.verbose, &mut args.color);
+    term::set_coloring(&mut args.color);
+    let options = &args.collect;
+
+    let (address, size) = match (&options.symbol, &options.elf) {
+        (Some(symbol), Some(elf)) => {
+            let buf = fs::read(elf)?;
+            let Some((address, size)) = find_symbol(&buf, symbol) else {
+                bail!(
+                    "symbol `{symbol}` not found in {elf}; make sure the buffer is a \
+                     #[no_mangle] static and the ELF file is not stripped"
+                );
+            };
+            (address, options.size.unwrap_or(size))
+        }
+        _ => (options.address.unwrap(), options.size.unwrap()),
+    };
+    if size == 0 {
+        bail!("size of the buffer at {address:#x} is zero; consider passing --size");
+    }
+
+    let mut cmd = cmd!("probe-rs", "read", "--chip", &options.chip);
+    if let Some(probe) = &options.probe {
+        cmd.args(["--probe", probe]);
+    }
+    cmd.arg("b8").arg(format!("{address:#x}")).arg(size.to_string());
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    let out = cmd.read().with_context(|| {
+        format!(
+            "failed to read profile data from {}; make sure probe-rs is installed",
+            options.chip
+        )
+    })?;
+    let mut data = parse_hex_bytes(&out)?;
+    if !is_raw_profile(&data) {
+        bail!(
+            "data at {address:#x} is not a raw profile; make sure the firmware has written \
+             the profile data to the buffer before collecting it"
+        );
+    }
+    trim_trailing_zeros(&mut data);
+
+    fs::create_dir_all(&ws.target_dir)?;
+    let path = ws.target_dir.join(format!("{}-probe-rs-{address:x}.profraw", ws.name));
+    fs::write(&path, data)?;
+    status!("Collected", "profile data from {} to {path}", options.chip);
+    Ok(())
+}
+
+/// Returns the address and size of the given symbol in the given ELF file.
+fn find_symbol(elf: &[u8], name: &str) -> Option<(u64, u64)> {
+    if elf.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+    let is_64 = match elf.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let r = Reader {
+        le: match elf.get(5)? {
+            1 => true,
+            2 => false,
+            _ => return None,
+        },
+    };
+    let (sh_off, sh_ent_size, sh_num) = if is_64 {
+        (r.u64(elf.get(0x28..0x30)?), r.u16(elf.get(0x3A..0x3C)?), r.u16(elf.get(0x3C..0x3E)?))
+    } else {
+        (
+            r.u32(elf.get(0x20..0x24)?).into(),
+            r.u16(elf.get(0x2E..0x30)?),
+            r.u16(elf.get(0x30..0x32)?),
+        )
+    };
+    let section = |i: u64| {
+        let start = usize::try_from(sh_off + i * u64::from(sh_ent_size)).ok()?;
+        elf.get(start..start + usize::from(sh_ent_size))
+    };
+    // Returns (type, offset, size, link, entry size) of the section header.
+    let parse_section = |sh: &[u8]| {
+        Some(if is_64 {
+            (
+                r.u32(sh.get(0x04..)?),
+                r.u64(sh.get(0x18..)?),
+                r.u64(sh.get(0x20..)?),
+                r.u32(sh.get(0x28..)?),
+                r.u64(sh.get(0x38..)?),
+            )
+        } else {
+            (
+                r.u32(sh.get(0x04..)?),
+                r.u32(sh.get(0x10..)?).into(),
+                r.u32(sh.get(0x14..)?).into(),
+                r.u32(sh.get(0x18..)?),
+                r.u32(sh.get(0x24..)?).into(),
+            )
+        })
+    };
+    let slice = |offset: u64, size: u64| {
+        let start = usize::try_from(offset).ok()?;
+        elf.get(start..start.checked_add(usize::try_from(size).ok()?)?)
+    };
+
+    for i in 0..u64::from(sh_num) {
+        let (ty, offset, size, link, ent_size) = parse_section(section(i)?)?;
+        if ty != SHT_SYMTAB || ent_size == 0 {
+            continue;
+        }
+        let (_, str_offset, str_size, _, _) = parse_section(section(link.into())?)?;
+        let strtab = slice(str_offset, str_size)?;
+        let symtab = slice(offset, size)?;
+        for sym in symtab.chunks_exact(usize::try_from(ent_size).ok()?) {
+            let (sym_name, value, size) = if is_64 {
+                (r.u32(sym), r.u64(sym.get(0x08..)?), r.u64(sym.get(0x10..)?))
+            } else {
+                (r.u32(sym), r.u32(sym.get(0x04..)?).into(), r.u32(sym.get(0x08..)?).into())
+            };
+            let sym_name = strtab.get(sym_name as usize..)?;
+            let len = sym_name.iter().position(|&b| b == 0)?;
+            if &sym_name[..len] == name.as_bytes() {
+                return Some((value, size));
+            }
+        }
+    }
+    None
+}
+
+/// Parses the output of `probe-rs read b8`, whitespace-separated hex bytes.
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>> {
+    s.split_ascii_whitespace()
+        .map(|b| {
+            let hex = b.strip_prefix("0x").unwrap_or(b);
+            u8::from_str_radix(hex, 16)
+                .map_err(|e| format_err!("unexpected output from probe-rs `{b}`: {e}"))
+        })
+        .collect()
+}
+
+fn is_raw_profile(data: &[u8]) -> bool {
+    let Some(magic) = data.get(..8) else { return false };
+    let magic: [u8; 8] = magic.try_into().unwrap();
+    [u64::from_le_bytes(magic), u64::from_be_bytes(magic)]
+        .iter()
+        .any(|m| matches!(*m, RAW_PROFILE_MAGIC_64 | RAW_PROFILE_MAGIC_32))
+}
+
+/// Removes the unused space at the end of the buffer.
+///
+/// The profile data written by the profiler runtime is padded to a multiple of 8 bytes,
+/// so trailing zeros after the last 8-byte word that contains non-zero bytes are unused.
+fn trim_trailing_zeros(data: &mut Vec<u8>) {
+    let len = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    data.truncate(len.next_multiple_of(8).min(data.len()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_symbol, is_raw_profile, parse_hex_bytes, trim_trailing_zeros};
+
+    // A minimal 32-bit little-endian ELF file that contains only the section headers,
+    // a symbol table with `COVERAGE` at 0x2000_0000 (size 0x400), and its string table.
+    fn elf32() -> Vec<u8> {
+        let mut elf = vec![0; 0x34];
+        elf[..6].copy_from_slice(b"\x7fELF\x01\x01");
+        let strtab = b"\0COVERAGE\0";
+        let strtab_off = elf.len();
+        elf.extend_from_slice(strtab);
+        elf.resize(elf.len().next_multiple_of(4), 0);
+        let symtab_off = elf.len();
+        elf.extend_from_slice(&[0; 16]);
+        for v in [1_u32, 0x2000_0000, 0x400, 0] {
+            elf.extend_from_slice(&v.to_le_bytes());
+        }
+        let sh_off = elf.len();
+        elf.extend_from_slice(&[0; 40]);
+        for (ty, offset, size, link, ent_size) in
+            [(2_u32, symtab_off, 32, 2_u32, 16_u32), (3, strtab_off, strtab.len(), 0, 0)]
+        {
+            for v in [0, ty, 0, 0, offset as u32, size as u32, link, 0, 0, ent_size] {
+                elf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        elf[0x20..0x24].copy_from_slice(&(sh_off as u32).to_le_bytes());
+        elf[0x2E..0x30].copy_from_slice(&40_u16.to_le_bytes());
+        elf[0x30..0x32].copy_from_slice(&3_u16.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn test_find_symbol() {
+        let elf = elf32();
+        assert_eq!(find_symbol(&elf, "COVERAGE"), Some((0x2000_0000, 0x400)));
+        assert_eq!(find_symbol(&elf, "COVERAGE_LEN"), None);
+        assert_eq!(find_symbol(&elf, "COVER"), None);
+        assert_eq!(find_symbol(b"not an elf file", "COVERAGE"), None);
+    }
+
+    #[test]
+    fn test_parse_hex_bytes() {
+        assert_eq!(parse_hex_bytes("81 72 66 6f\nff 0x00 0a\n").unwrap(), [
+            0x81, 0x72, 0x66, 0x6F, 0xFF, 0x00, 0x0A
+        ]);
+        parse_hex_bytes("81 xx").unwrap_err();
+        parse_hex_bytes("100").unwrap_err();
+    }
+
+    #[test]
+    fn test_is_raw_profile() {
+        assert!(is_raw_profile(b"\x81rforpl\xff\x0a\0\0\0"));
+        assert!(is_raw_profile(b"\x81Rforpl\xff"));
+        assert!(is_raw_profile(b"\xfflprofr\x81"));
+        assert!(!is_raw_profile(b"\0\0\0\0\0\0\0\0"));
+        assert!(!is_raw_profile(b"\x81rfor"));
+    }
+
+    #[test]
+    fn test_trim_trailing_zeros() {
+        let mut data = vec![1; 12];
+        data.extend_from_slice(&[0; 20]);
+        trim_trailing_zeros(&mut data);
+        assert_eq!(data.len(), 16);
+        let mut data = vec![1; 8];
+        trim_trailing_zeros(&mut data);
+        assert_eq!(data.len(), 8);
+        let mut data = vec![0; 8];
+        trim_trailing_zeros(&mut data);
+        assert_eq!(data.len(), 0);
+    }
+}
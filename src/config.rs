@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context as _, Result, bail};
+use serde_derive::Deserialize;
+
+use crate::{
+    cli::{Args, LlvmCovOptions, Subcommand},
+    dedup,
+    metadata::Metadata,
+};
+
+/// Configuration in `[workspace.metadata.llvm-cov]` table (or `[package.metadata.llvm-cov]`
+/// table of the root package) of Cargo.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct ProjectConfig {
+    /// Named sets of arguments, which can be used by `--preset <NAME>`.
+    #[serde(default)]
+    pub(crate) presets: BTreeMap<String, Vec<String>>,
+    /// Minimum coverage percentages, which are used unless `--fail-under-*` flags are passed.
+    #[serde(default)]
+    pub(crate) fail_under: FailUnder,
+    /// Regular expressions of file paths to skip, which are used in addition to
+    /// `--ignore-filename-regex` flags.
+    #[serde(default)]
+    pub(crate) ignore_filename_regex: Vec<String>,
+    /// `<FROM>,<TO>` rules of the same files under different paths, which are used in addition
+    /// to `--path-equivalence` flags.
+    #[serde(default)]
+    pub(crate) path_equivalence: Vec<String>,
+    /// Report format, which is used unless a format flag is passed.
+    pub(crate) format: Option<Format>,
+    /// Packages to exclude from the report, which are used in addition to
+    /// `--exclude-from-report` flags.
+    #[serde(default)]
+    pub(crate) exclude_from_report: Vec<String>,
+    /// Cargo profile, which is used unless `--release` or `--profile` is passed.
+    pub(crate) profile: Option<String>,
+    /// Target triple, which is used unless `--target` is passed.
+    pub(crate) target: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FailUnder {
+    pub(crate) functions: Option<f64>,
+    pub(crate) lines: Option<f64>,
+    pub(crate) regions: Option<f64>,
+    /// This is only used when branch coverage is enabled by `--branch` or `--mcdc`.
+    pub(crate) branches: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Format {
+    Json,
+    Lcov,
+    Cobertura,
+    Codecov,
+    Text,
+    Html,
+}
+
+impl ProjectConfig {
+    pub(crate) fn new(metadata: &Metadata) -> Result<Self> {
+        match &metadata.llvm_cov_config {
+            Some(config) => Self::from_value(config.clone())
+                .context("failed to parse `[workspace.metadata.llvm-cov]` table in Cargo.toml"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn from_value(value: serde_json::Value) -> Result<Self> {
+        let config: Self = serde_json::from_value(value)?;
+        for (key, empty) in [
+            ("ignore-filename-regex", config.ignore_filename_regex.iter().any(String::is_empty)),
+            ("exclude-from-report", config.exclude_from_report.iter().any(String::is_empty)),
+            ("profile", config.profile.as_deref() == Some("")),
+            ("target", config.target.as_deref() == Some("")),
+        ] {
+            if empty {
+                bail!("empty string is not allowed in `{key}`");
+            }
+        }
+        if let Some(rule) =
+            config.path_equivalence.iter().find(|rule| dedup::parse_rule(rule).is_none())
+        {
+            bail!("`path-equivalence` must be <FROM>,<TO>, but found `{rule}`");
+        }
+        Ok(config)
+    }
+
+    /// Applies the configuration to the arguments; flags take precedence over it.
+    ///
+    /// `target` is applied when the workspace is loaded, since it is needed to resolve the
+    /// cargo configuration.
+    pub(crate) fn apply(&self, args: &mut Args) {
+        // Patterns in the project configuration are used in addition to the ones passed by
+        // flags, so that all reports (including the TUI) skip the same files.
+        args.cov.ignore_filename_regex.extend(self.ignore_filename_regex.iter().cloned());
+        args.cov.path_equivalence.extend(self.path_equivalence.iter().cloned());
+        if matches!(
+            args.subcommand,
+            Subcommand::None
+                | Subcommand::Test
+                | Subcommand::Run
+                | Subcommand::Fuzz
+                | Subcommand::Nextest { .. }
+                | Subcommand::NextestArchive
+        ) {
+            for package in &self.exclude_from_report {
+                if !args.exclude_from_report.contains(package) {
+                    args.exclude_from_report.push(package.clone());
+                }
+            }
+        }
+        if !args.release && args.cargo_profile.is_none() {
+            args.cargo_profile.clone_from(&self.profile);
+        }
+        if !matches!(
+            args.subcommand,
+            Subcommand::Tui
+                | Subcommand::Show
+                | Subcommand::Stats
+                | Subcommand::ShowEnv
+                | Subcommand::NextestArchive
+        ) {
+            self.fail_under.apply(&mut args.cov);
+        }
+        if let Some(format) = self.format {
+            if matches!(
+                args.subcommand,
+                Subcommand::None
+                    | Subcommand::Test
+                    | Subcommand::Run
+                    | Subcommand::Fuzz
+                    | Subcommand::Report { .. }
+                    | Subcommand::Nextest { .. }
+            ) {
+                format.apply(&mut args.cov);
+            }
+        }
+    }
+}
+
+impl FailUnder {
+    fn apply(&self, cov: &mut LlvmCovOptions) {
+        cov.fail_under_functions = cov.fail_under_functions.or(self.functions);
+        cov.fail_under_lines = cov.fail_under_lines.or(self.lines);
+        cov.fail_under_regions = cov.fail_under_regions.or(self.regions);
+        if cov.branch || cov.mcdc {
+            cov.fail_under_branches = cov.fail_under_branches.or(self.branches);
+        }
+    }
+}
+
+impl Format {
+    fn apply(self, cov: &mut LlvmCovOptions) {
+        let has_format = cov.json
+            || cov.lcov
+            || cov.cobertura
+            || cov.codecov
+            || cov.rdjson
+            || cov.checkstyle
+            || cov.coveralls
+            || cov.sarif
+            || cov.jacoco
+            || cov.sonarqube
+            || cov.csv
+            || cov.tsv
+            || cov.text
+            || cov.html
+            || cov.open;
+        // These print their own reports, or do not print reports at all.
+        if has_format || cov.summary_only || cov.file_summary || cov.no_report {
+            return;
+        }
+        match self {
+            Self::Json => cov.json = true,
+            Self::Lcov => cov.lcov = true,
+            Self::Cobertura => cov.cobertura = true,
+            Self::Codecov => cov.codecov = true,
+            Self::Text => cov.text = true,
+            Self::Html => cov.html = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, ProjectConfig};
+    use crate::cli::LlvmCovOptions;
+
+    #[test]
+    fn test_from_value() {
+        let config = ProjectConfig::from_value(serde_json::json!({
+            "format": "lcov",
+            "fail-under": { "lines": 80.0 },
+            "exclude-from-report": ["xtask"],
+            "target": "x86_64-unknown-linux-gnu",
+        }))
+        .unwrap();
+        assert_eq!(config.format, Some(Format::Lcov));
+        assert_eq!(config.fail_under.lines, Some(80.0));
+        assert_eq!(config.exclude_from_report, ["xtask"]);
+        assert_eq!(config.target.as_deref(), Some("x86_64-unknown-linux-gnu"));
+        assert_eq!(config.profile, None);
+
+        assert!(ProjectConfig::from_value(serde_json::json!({ "format": "xml" })).is_err());
+        // Typos are rejected rather than silently ignored.
+        assert!(
+            ProjectConfig::from_value(serde_json::json!({ "fail_under": { "lines": 80.0 } }))
+                .unwrap_err()
+                .to_string()
+                .contains("unknown field `fail_under`")
+        );
+        assert!(
+            ProjectConfig::from_value(serde_json::json!({ "fail-under": { "l": 1 } })).is_err()
+        );
+        assert_eq!(
+            ProjectConfig::from_value(serde_json::json!({ "profile": "" }))
+                .unwrap_err()
+                .to_string(),
+            "empty string is not allowed in `profile`"
+        );
+        assert_eq!(
+            ProjectConfig::from_value(serde_json::json!({ "path-equivalence": ["vendor/foo"] }))
+                .unwrap_err()
+                .to_string(),
+            "`path-equivalence` must be <FROM>,<TO>, but found `vendor/foo`"
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let mut cov = LlvmCovOptions::default();
+        Format::Html.apply(&mut cov);
+        assert!(cov.html);
+
+        // Flags take precedence over the configuration.
+        let mut cov = LlvmCovOptions { json: true, ..Default::default() };
+        Format::Lcov.apply(&mut cov);
+        assert!(cov.json && !cov.lcov);
+        let mut cov = LlvmCovOptions { summary_only: true, ..Default::default() };
+        Format::Lcov.apply(&mut cov);
+        assert!(!cov.lcov);
+    }
+}
@@ -1,22 +1,25 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 use std::{
+    cell::RefCell,
     ffi::OsString,
     io::{self, Write as _},
     path::PathBuf,
 };
 
-use anyhow::{Result, bail};
+use anyhow::{Context as _, Result, bail};
 use camino::Utf8PathBuf;
 
 use crate::{
-    cargo::Workspace,
+    cargo::{self, Workspace},
     cli::{self, Args, Subcommand},
-    env,
+    diff::{self, ChangedLines},
+    env, fuzz,
     metadata::{Metadata, PackageId},
     process::ProcessBuilder,
     regex_vec::{RegexVec, RegexVecBuilder},
-    term,
+    sample,
+    term::{self, ExitStatus},
 };
 
 pub(crate) struct Context {
@@ -27,6 +30,11 @@ pub(crate) struct Context {
     pub(crate) workspace_members: WorkspaceMembers,
     pub(crate) build_script_re: RegexVec,
     pub(crate) current_dir: PathBuf,
+    /// Rust source files changed since the revision specified by `--changed-since`. Files that
+    /// are not in the coverage mapping are removed from this at report time.
+    pub(crate) changed_files: Option<RefCell<Vec<Utf8PathBuf>>>,
+    /// Lines changed since the revision specified by `--diff`.
+    pub(crate) changed_lines: Option<ChangedLines>,
 
     // Paths to executables.
     pub(crate) current_exe: PathBuf,
@@ -36,16 +44,25 @@ pub(crate) struct Context {
     pub(crate) llvm_profdata: PathBuf,
 
     /// `LLVM_COV_FLAGS` environment variable to pass additional flags to llvm-cov.
-    /// (value: space-separated list)
-    pub(crate) llvm_cov_flags: Option<String>,
+    /// (value: space-separated list, or `\x1f`-separated list like `CARGO_ENCODED_RUSTFLAGS`)
+    pub(crate) llvm_cov_flags: Option<Vec<String>>,
     /// `LLVM_PROFDATA_FLAGS` environment variable to pass additional flags to llvm-profdata.
-    /// (value: space-separated list)
-    pub(crate) llvm_profdata_flags: Option<String>,
+    /// (value: space-separated list, or `\x1f`-separated list like `CARGO_ENCODED_RUSTFLAGS`)
+    pub(crate) llvm_profdata_flags: Option<Vec<String>>,
+    /// `-path-equivalence` flags passed to llvm-cov to map the source paths remapped by
+    /// `--remap-path-prefix` in the user's rustflags back to the local paths.
+    pub(crate) path_equivalence: Vec<String>,
+    /// Whether `--profile-continuous` was passed by the user, as opposed to being enabled
+    /// automatically (e.g., by `panic = "abort"`).
+    pub(crate) profile_continuous_passed: bool,
 }
 
 impl Context {
     pub(crate) fn new(mut args: Args) -> Result<Self> {
         let show_env = args.subcommand == Subcommand::ShowEnv;
+        if args.subcommand == Subcommand::Fuzz && args.manifest.manifest_path.is_none() {
+            args.manifest.manifest_path = fuzz::default_manifest_path();
+        }
         let ws = Workspace::new(
             &args.manifest,
             args.target.as_deref(),
@@ -55,11 +72,32 @@ impl Context {
             show_env,
         )?;
         cli::merge_config_to_args(&ws, &mut args.target, &mut args.verbose, &mut args.color);
+        ws.project_config.apply(&mut args);
         term::set_coloring(&mut args.color);
         term::verbose::set(args.verbose != 0);
 
+        if !args.cov.path_equivalence.is_empty()
+            && matches!(
+                args.subcommand,
+                Subcommand::None
+                    | Subcommand::Test
+                    | Subcommand::Run
+                    | Subcommand::Fuzz
+                    | Subcommand::Report { .. }
+                    | Subcommand::Nextest { .. }
+            )
+        {
+            // llvm-cov does not merge the files mapped to the same path, so the rules are only
+            // applied to the reports converted from the JSON export (see dedup.rs).
+            if let Some(report) = args.cov.llvm_cov_report() {
+                bail!(
+                    "--path-equivalence (or `path-equivalence` in `[workspace.metadata.llvm-cov]`) \
+                     may not be used together with {report}"
+                );
+            }
+        }
         args.cov.html |= args.cov.open;
-        if args.cov.output_dir.is_some() && !args.cov.show() {
+        if args.cov.output_dir.is_some() && !args.cov.show() && !args.cov.multiple_formats() {
             // If the format flag is not specified, this flag is no-op.
             args.cov.output_dir = None;
         }
@@ -87,15 +125,47 @@ impl Context {
                 warn!("--doctests option is unstable");
             }
         }
-        if args.target.is_some() {
+        if args.target.is_some()
+            && !args.coverage_target_only
+            && !(args.cov.include_build_script && args.cov.include_proc_macros)
+        {
             info!(
                 "when --target option is used, coverage for proc-macro and build script will \
-                 not be displayed because cargo does not pass RUSTFLAGS to them"
+                 not be displayed because cargo does not pass RUSTFLAGS to them; pass \
+                 --include-proc-macros and --include-build-script to instrument them"
             );
         }
-        if !matches!(args.subcommand, Subcommand::Report { .. } | Subcommand::Clean)
-            && (!args.cov.no_cfg_coverage
-                || ws.rustc_version.nightly && !args.cov.no_cfg_coverage_nightly)
+        if let Some(sanitizer) = args.sanitizer {
+            if args.target.is_none() {
+                // Without --target, cargo passes RUSTFLAGS to build scripts and proc-macros,
+                // which cannot be run or loaded when they are built with sanitizers.
+                bail!(
+                    "--sanitizer requires --target; consider passing `--target {}`",
+                    ws.target_for_config.triple()
+                );
+            }
+            let rustflags = ws.config.rustflags(&ws.target_for_config)?.unwrap_or_default();
+            if cargo::enables_sanitizer(&rustflags.flags) {
+                bail!(
+                    "--sanitizer={} may not be used together with `-Z sanitizer` in RUSTFLAGS",
+                    sanitizer.as_str()
+                );
+            }
+            if !ws.rustc_version.nightly {
+                warn!(
+                    "--sanitizer flag requires nightly toolchain; consider using `cargo +nightly llvm-cov`"
+                );
+            }
+        }
+        if !matches!(
+            args.subcommand,
+            Subcommand::Report { .. }
+                | Subcommand::Clean
+                | Subcommand::Tui
+                | Subcommand::Show
+                | Subcommand::Stats
+        ) && (!args.cov.no_cfg_coverage
+            || ws.rustc_version.nightly && !args.cov.no_cfg_coverage_nightly)
         {
             let mut cfgs = String::new();
             let mut flags = String::new();
@@ -114,11 +184,17 @@ impl Context {
             }
             info!("cargo-llvm-cov currently setting {cfgs}; you can opt-out it by passing {flags}");
         }
-        if args.cov.output_dir.is_none() && args.cov.html {
+        if args.cov.output_dir.is_none() && (args.cov.html || args.cov.multiple_formats()) {
             args.cov.output_dir = Some(ws.output_dir.clone());
         }
-        if !matches!(args.subcommand, Subcommand::Report { .. } | Subcommand::Clean)
-            && env::var_os("CARGO_LLVM_COV_SHOW_ENV").is_some()
+        if !matches!(
+            args.subcommand,
+            Subcommand::Report { .. }
+                | Subcommand::Clean
+                | Subcommand::Tui
+                | Subcommand::Show
+                | Subcommand::Stats
+        ) && env::var_os("CARGO_LLVM_COV_SHOW_ENV").is_some()
         {
             warn!(
                 "cargo-llvm-cov subcommands other than report and clean may not work correctly \
@@ -135,6 +211,51 @@ impl Context {
         {
             warn!("nextest archive may not work with Cargo build-dir");
         }
+        let profile_continuous_passed = args.profile_continuous;
+        if args.subcommand == Subcommand::Fuzz {
+            // libFuzzer exits by `_exit` when an input crashes the fuzz target, so the exit hook
+            // of the profile runtime does not write the counters of the inputs run so far.
+            args.profile_continuous = true;
+        }
+        if !args.profile_continuous
+            && !matches!(
+                args.subcommand,
+                Subcommand::Report { .. }
+                    | Subcommand::Clean
+                    | Subcommand::Tui
+                    | Subcommand::Show
+                    | Subcommand::Stats
+            )
+        {
+            if let Some(source) = cargo::panic_abort(&ws, &args)? {
+                // In continuous mode, counters are written to the profraw files while the
+                // process is running, so they are not lost even if the process aborts.
+                info!(
+                    "{source} sets `panic = \"abort\"`; enabling --profile-continuous so that \
+                     coverage of processes aborted by panics is not lost"
+                );
+                args.profile_continuous = true;
+            }
+        }
+        if !args.no_build_settings_warnings
+            && matches!(
+                args.subcommand,
+                Subcommand::None
+                    | Subcommand::Test
+                    | Subcommand::Run
+                    | Subcommand::Fuzz
+                    | Subcommand::Nextest { .. }
+                    | Subcommand::NextestArchive
+                    | Subcommand::ShowEnv
+            )
+        {
+            for warning in cargo::distorting_settings(&ws, &args)? {
+                warn!(
+                    "{warning}; you can silence this warning by passing \
+                     --no-build-settings-warnings"
+                );
+            }
+        }
 
         let (llvm_cov, llvm_profdata): (PathBuf, PathBuf) = match (
             env::var_os("LLVM_COV").map(PathBuf::from),
@@ -192,6 +313,7 @@ impl Context {
                                         "CARGO_LLVM_COV_SETUP must be yes or no, but found `{v:?}`"
                                     );
                                 }
+                                term::fail(ExitStatus::ToolchainMissing);
                                 bail!(
                                     "failed to find llvm-tools-preview, please install llvm-tools-preview \
                                      with `rustup component add llvm-tools-preview --toolchain {toolchain}`",
@@ -202,8 +324,10 @@ impl Context {
                             &cmd,
                             ask,
                             "install the `llvm-tools-preview` component for the selected toolchain",
-                        )?;
+                        )
+                        .inspect_err(|_| term::fail(ExitStatus::ToolchainMissing))?;
                     } else {
+                        term::fail(ExitStatus::ToolchainMissing);
                         bail!(
                             "failed to find llvm-tools-preview, please install llvm-tools-preview, or set LLVM_COV and LLVM_PROFDATA environment variables",
                         );
@@ -213,6 +337,7 @@ impl Context {
             }
         };
 
+        sample::apply(&mut args, &ws.metadata)?;
         let workspace_members =
             WorkspaceMembers::new(&args.exclude, &args.exclude_from_report, &ws.metadata);
         if workspace_members.included.is_empty() {
@@ -221,6 +346,15 @@ impl Context {
 
         let build_script_re = pkg_hash_re(&ws, &workspace_members.included);
 
+        let changed_files = match &args.cov.changed_since {
+            Some(rev) => Some(RefCell::new(changed_files(&ws, rev)?)),
+            None => None,
+        };
+        let changed_lines = match &args.cov.diff {
+            Some(rev) => Some(diff::changed_lines(&ws, rev)?),
+            None => None,
+        };
+
         let mut llvm_cov_flags = env::var("LLVM_COV_FLAGS")?;
         if llvm_cov_flags.is_none() {
             llvm_cov_flags = env::var("CARGO_LLVM_COV_FLAGS")?;
@@ -238,12 +372,28 @@ impl Context {
             }
         }
 
+        // Paths remapped to an empty prefix (e.g., by --remap-path-prefix flag of
+        // cargo-llvm-cov) are relative to the workspace root, and do not need to be mapped.
+        let path_equivalence = ws
+            .config
+            .rustflags(&ws.target_for_config)?
+            .map(|rustflags| {
+                cargo::remap_path_prefixes(&rustflags.flags)
+                    .into_iter()
+                    .filter(|(_, to)| !to.is_empty())
+                    .map(|(from, to)| format!("-path-equivalence={to},{from}"))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Ok(Self {
             ws,
             args,
             workspace_members,
             build_script_re,
             current_dir: env::current_dir().unwrap(),
+            changed_files,
+            changed_lines,
             current_exe: match env::current_exe() {
                 Ok(exe) => exe,
                 Err(e) => {
@@ -256,8 +406,10 @@ impl Context {
             },
             llvm_cov,
             llvm_profdata,
-            llvm_cov_flags,
-            llvm_profdata_flags,
+            llvm_cov_flags: llvm_cov_flags.as_deref().map(split_flags),
+            llvm_profdata_flags: llvm_profdata_flags.as_deref().map(split_flags),
+            path_equivalence,
+            profile_continuous_passed,
         })
     }
 
@@ -283,6 +435,37 @@ fn pkg_hash_re(ws: &Workspace, pkg_ids: &[PackageId]) -> RegexVec {
     re.build().unwrap()
 }
 
+/// Returns absolute paths of Rust source files that have been changed since the given git
+/// revision, including uncommitted and untracked files.
+fn changed_files(ws: &Workspace, rev: &str) -> Result<Vec<Utf8PathBuf>> {
+    let root = ws.metadata.workspace_root.as_std_path();
+    let toplevel = cmd!("git", "rev-parse", "--show-toplevel")
+        .dir(root)
+        .read()
+        .context("--changed-since requires the workspace to be in a git repository")?;
+    let toplevel = Utf8PathBuf::from(toplevel.trim());
+    let commit = cmd!("git", "rev-parse", "--verify", "--quiet", format!("{rev}^{{commit}}"))
+        .dir(root)
+        .read()
+        .with_context(|| format!("failed to resolve git revision `{rev}`"))?;
+    let diff = cmd!("git", "diff", "--name-only", "--no-renames", commit, "--")
+        .dir(toplevel.as_std_path())
+        .read()?;
+    let untracked = cmd!("git", "ls-files", "--others", "--exclude-standard")
+        .dir(toplevel.as_std_path())
+        .read()?;
+    let mut files: Vec<_> = diff
+        .lines()
+        .chain(untracked.lines())
+        .map(|file| toplevel.join(file))
+        // Files that have been removed since the revision are not in the report anyway.
+        .filter(|file| file.extension() == Some("rs") && file.is_file())
+        .collect();
+    files.sort_unstable();
+    files.dedup();
+    Ok(files)
+}
+
 pub(crate) struct WorkspaceMembers {
     pub(crate) excluded: Vec<PackageId>,
     pub(crate) included: Vec<PackageId>,
@@ -313,6 +496,15 @@ impl WorkspaceMembers {
     }
 }
 
+/// Splits the value of `LLVM_COV_FLAGS`/`LLVM_PROFDATA_FLAGS` into flags.
+///
+/// Flags are separated by `\x1f` (ASCII Unit Separator) if the value contains it, so that flags
+/// can contain spaces (e.g., paths passed by `-path-equivalence`), otherwise by spaces.
+fn split_flags(flags: &str) -> Vec<String> {
+    let separator = if flags.contains('\x1f') { '\x1f' } else { ' ' };
+    flags.split(separator).filter(|s| !s.trim_start().is_empty()).map(str::to_owned).collect()
+}
+
 // Adapted from https://github.com/rust-lang/miri/blob/dba35d2be72f4b78343d1a0f0b4737306f310672/cargo-miri/src/util.rs#L181-L204
 fn ask_to_run(cmd: &ProcessBuilder, ask: bool, text: &str) -> Result<()> {
     // Disable interactive prompts in CI (GitHub Actions, Travis, AppVeyor, etc).
@@ -337,3 +529,18 @@ fn ask_to_run(cmd: &ProcessBuilder, ask: bool, text: &str) -> Result<()> {
     cmd.run()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::split_flags;
+
+    #[test]
+    fn test_split_flags() {
+        assert_eq!(split_flags("-a  -b=c "), ["-a", "-b=c"]);
+        assert_eq!(split_flags("-path-equivalence=/a b,/c\x1f-d"), [
+            "-path-equivalence=/a b,/c",
+            "-d"
+        ]);
+        assert!(split_flags("\x1f").is_empty());
+    }
+}
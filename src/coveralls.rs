@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --coveralls: payload of the Coveralls Jobs API.
+//
+// Refs: https://docs.coveralls.io/api-reference
+//
+// Coveralls identifies the build by the CI service and git metadata in the payload, so they are
+// collected here instead of requiring grcov or coveralls' uploader. Use
+// `cargo llvm-cov upload --coveralls` to send the generated file. The repository token is sent by
+// the upload instead of being written to the file, which may be printed or kept as an artifact.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use serde_derive::Serialize;
+
+use crate::{
+    context::Context,
+    digest::{md5, to_hex},
+    env, fs,
+};
+
+#[derive(Serialize)]
+struct Job {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_job_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git: Option<Git>,
+    source_files: Vec<SourceFile>,
+}
+
+#[derive(Serialize)]
+struct SourceFile {
+    /// Path relative to the repository root.
+    name: String,
+    /// MD5 digest of the source code.
+    source_digest: String,
+    /// Execution count of each line, or `null` if the line has no coverage mapping.
+    coverage: Vec<Option<u64>>,
+}
+
+#[derive(Serialize)]
+struct Git {
+    head: Head,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    remotes: Vec<Remote>,
+}
+
+#[derive(Serialize)]
+struct Head {
+    id: String,
+    author_name: String,
+    author_email: String,
+    committer_name: String,
+    committer_email: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Remote {
+    name: String,
+    url: String,
+}
+
+pub(crate) fn export(cx: &Context, json: &LlvmCovJsonExport) -> Result<String> {
+    let root = &cx.ws.metadata.workspace_root;
+    // Coveralls expects paths relative to the repository root, which may be a parent
+    // directory of the workspace root.
+    let toplevel = git(root, &["rev-parse", "--show-toplevel"]).map(Utf8PathBuf::from);
+    let base = toplevel.as_deref().unwrap_or(root);
+
+    let mut source_files = vec![];
+    for (filename, lines) in json.get_line_execution_counts() {
+        // File names are relative if --remap-path-prefix is used.
+        let path = root.join(filename);
+        let Ok(source) = fs::read(&path) else {
+            warn!("failed to read {path}; it is omitted from Coveralls report");
+            continue;
+        };
+        source_files.push(SourceFile {
+            name: path.strip_prefix(base).unwrap_or(&path).to_string(),
+            source_digest: to_hex(&md5(&source)),
+            coverage: line_coverage(&source, &lines),
+        });
+    }
+
+    let github_actions = env::var("GITHUB_ACTIONS")?.as_deref() == Some("true");
+    let service_name = match env::var("COVERALLS_SERVICE_NAME")? {
+        Some(name) => Some(name),
+        None => github_actions.then(|| "github".to_owned()),
+    };
+    let service_job_id = if github_actions { env::var("GITHUB_RUN_ID")? } else { None };
+    let job = Job { service_name, service_job_id, git: git_info(base)?, source_files };
+    Ok(serde_json::to_string(&job)?)
+}
+
+/// Returns the execution count of each line of the source, or `None` for lines without
+/// coverage mapping.
+fn line_coverage(source: &[u8], lines: &BTreeMap<u64, u64>) -> Vec<Option<u64>> {
+    let mut len = source.iter().filter(|&&b| b == b'\n').count();
+    if !source.is_empty() && !source.ends_with(b"\n") {
+        len += 1;
+    }
+    let mut coverage = vec![None; len];
+    for (&line, &count) in lines {
+        let Some(i) = usize::try_from(line).ok().and_then(|line| line.checked_sub(1)) else {
+            continue;
+        };
+        // Lines out of range can appear if the file has been modified after the build.
+        if i >= coverage.len() {
+            coverage.resize(i + 1, None);
+        }
+        coverage[i] = Some(count);
+    }
+    coverage
+}
+
+fn git_info(dir: &Utf8Path) -> Result<Option<Git>> {
+    let Some(head) = git(dir, &["log", "-1", "--format=%H%n%an%n%ae%n%cn%n%ce%n%s"]) else {
+        return Ok(None);
+    };
+    let mut head = head.lines().map(str::to_owned);
+    let mut next = || head.next().unwrap_or_default();
+    let head = Head {
+        id: next(),
+        author_name: next(),
+        author_email: next(),
+        committer_name: next(),
+        committer_email: next(),
+        message: next(),
+    };
+    // HEAD is detached when CI checks out a pull request.
+    let branch = match git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]) {
+        Some(branch) if branch != "HEAD" => Some(branch),
+        _ => match env::var("GITHUB_HEAD_REF")? {
+            Some(branch) => Some(branch),
+            None => env::var("GITHUB_REF_NAME")?,
+        },
+    };
+    let remotes = git(dir, &["remote", "-v"]).map(|s| parse_remotes(&s)).unwrap_or_default();
+    Ok(Some(Git { head, branch, remotes }))
+}
+
+/// Parses the output of `git remote -v`.
+fn parse_remotes(s: &str) -> Vec<Remote> {
+    s.lines()
+        .filter_map(|line| {
+            // `<name>\t<url> (fetch)`
+            let (name, rest) = line.split_once('\t')?;
+            let url = rest.strip_suffix(" (fetch)")?;
+            Some(Remote { name: name.to_owned(), url: url.to_owned() })
+        })
+        .collect()
+}
+
+fn git(dir: &Utf8Path, args: &[&str]) -> Option<String> {
+    cmd!("git")
+        .args(args.iter().copied())
+        .dir(dir.as_std_path())
+        .read()
+        .ok()
+        .map(|s| s.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{line_coverage, parse_remotes};
+
+    #[test]
+    fn test_line_coverage() {
+        let lines = BTreeMap::from([(1, 2), (3, 0)]);
+        assert_eq!(line_coverage(b"a\nb\nc\nd\n", &lines), [Some(2), None, Some(0), None]);
+        assert_eq!(line_coverage(b"a\nb\nc", &lines), [Some(2), None, Some(0)]);
+        assert_eq!(line_coverage(b"a\n", &lines), [Some(2), None, Some(0)]);
+    }
+
+    #[test]
+    fn test_parse_remotes() {
+        let remotes = parse_remotes(
+            "origin\thttps://github.com/a/b.git (fetch)\n\
+             origin\thttps://github.com/a/b.git (push)\n\
+             fork\tgit@github.com:c/b.git (fetch)\n\
+             fork\tgit@github.com:c/b.git (push)\n",
+        );
+        let remotes: Vec<_> = remotes.iter().map(|r| (r.name.as_str(), r.url.as_str())).collect();
+        assert_eq!(remotes, [
+            ("origin", "https://github.com/a/b.git"),
+            ("fork", "git@github.com:c/b.git")
+        ]);
+    }
+}
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Generates a single HTML page with the results of tests from the JUnit report of
+// cargo-nextest and the coverage of each package.
+//
+// The JUnit report is parsed with regular expressions instead of an XML parser; only
+// the subset of JUnit XML generated by cargo-nextest needs to be supported.
+
+use std::{collections::BTreeMap, fmt::Write as _, sync::LazyLock, time::SystemTime};
+
+use anyhow::{Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::{context::Context, env, fs};
+
+#[derive(Debug, PartialEq)]
+struct TestCase {
+    /// Binary ID of the test, such as `crate::bin/test`.
+    suite: String,
+    name: String,
+    /// Duration in seconds.
+    time: Option<f64>,
+    status: Status,
+}
+
+#[derive(Debug, PartialEq)]
+enum Status {
+    Passed,
+    Skipped,
+    /// Failed or errored, with the failure message.
+    Failed(String),
+}
+
+/// Writes the dashboard to `path`.
+///
+/// `packages` is package name -> (lines, covered lines).
+pub(crate) fn write(
+    cx: &Context,
+    path: &Utf8Path,
+    total: f64,
+    packages: &BTreeMap<&str, (u64, u64)>,
+) -> Result<()> {
+    let junit = find_junit(cx)?;
+    let tests = parse_junit(&fs::read_to_string(&junit)?);
+    if tests.is_empty() {
+        warn!("--dashboard: no test cases found in {junit}");
+    }
+    if let Some(dir) = path.parent() {
+        if !dir.as_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    fs::write(path, html(&tests, total, packages))?;
+    status!("Finished", "dashboard saved to {path}");
+    Ok(())
+}
+
+/// Finds the latest JUnit report in the store directory of the current nextest profile.
+///
+/// The file name of the JUnit report is configured in the nextest config, so this
+/// looks for XML files in the directory instead of reading the config.
+fn find_junit(cx: &Context) -> Result<Utf8PathBuf> {
+    let profile = nextest_profile(cx)?;
+    let dir = cx.ws.target_dir.join("nextest").join(&profile);
+    let mut latest: Option<(SystemTime, Utf8PathBuf)> = None;
+    for entry in WalkDir::new(&dir).into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "xml") {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            let path = Utf8PathBuf::try_from(path.to_owned())?;
+            latest = Some((modified, path));
+        }
+    }
+    match latest {
+        Some((_, path)) => Ok(path),
+        None => bail!(
+            "--dashboard: JUnit report not found in {dir}; enable JUnit output for nextest \
+             profile '{profile}' with `[profile.{profile}.junit] path = \"junit.xml\"` in \
+             .config/nextest.toml"
+        ),
+    }
+}
+
+fn nextest_profile(cx: &Context) -> Result<String> {
+    let mut profile = None;
+    let mut args = cx.args.cargo_args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" || arg == "-P" {
+            profile = args.next().cloned();
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            profile = Some(value.to_owned());
+        }
+    }
+    match profile {
+        Some(profile) => Ok(profile),
+        None => Ok(env::var("NEXTEST_PROFILE")?.unwrap_or_else(|| "default".to_owned())),
+    }
+}
+
+fn parse_junit(xml: &str) -> Vec<TestCase> {
+    static SUITE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)<testsuite(?-u:\b)([^>]*?)(?:/>|>(.*?)</testsuite>)").unwrap()
+    });
+    static CASE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)<testcase(?-u:\b)([^>]*?)(?:/>|>(.*?)</testcase>)").unwrap()
+    });
+    static FAILURE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)<(?:failure|error)(?-u:\b)([^>]*?)(?:/>|>(.*?)</(?:failure|error)>)")
+            .unwrap()
+    });
+
+    let mut tests = vec![];
+    for suite in SUITE.captures_iter(xml) {
+        let suite_name = attr(&suite[1], "name").unwrap_or_default();
+        let Some(body) = suite.get(2) else { continue };
+        for case in CASE.captures_iter(body.as_str()) {
+            let body = case.get(2).map_or("", |m| m.as_str());
+            let status = if let Some(failure) = FAILURE.captures(body) {
+                let message = attr(&failure[1], "message")
+                    .filter(|m| !m.is_empty())
+                    .or_else(|| failure.get(2).map(|m| unescape(m.as_str().trim())))
+                    .unwrap_or_default();
+                Status::Failed(message)
+            } else if body.contains("<skipped") {
+                Status::Skipped
+            } else {
+                Status::Passed
+            };
+            tests.push(TestCase {
+                suite: suite_name.clone(),
+                name: attr(&case[1], "name").unwrap_or_default(),
+                time: attr(&case[1], "time").and_then(|t| t.parse().ok()),
+                status,
+            });
+        }
+    }
+    tests
+}
+
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    static ATTR: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"([0-9A-Za-z_:-]+)(?-u:\s)*=(?-u:\s)*"([^"]*)""#).unwrap());
+    ATTR.captures_iter(attrs).find(|caps| &caps[1] == name).map(|caps| unescape(&caps[2]))
+}
+
+fn unescape(s: &str) -> String {
+    static ENTITY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&(lt|gt|amp|quot|apos|#[0-9]+|#x[0-9a-fA-F]+);").unwrap());
+    ENTITY
+        .replace_all(s, |caps: &regex::Captures<'_>| {
+            let entity = &caps[1];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .unwrap_or_else(|| entity[1..].parse())
+                    .ok()
+                    .and_then(char::from_u32),
+            };
+            c.map_or_else(|| caps[0].to_owned(), String::from)
+        })
+        .into_owned()
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html(tests: &[TestCase], total: f64, packages: &BTreeMap<&str, (u64, u64)>) -> String {
+    let passed = tests.iter().filter(|t| t.status == Status::Passed).count();
+    let skipped = tests.iter().filter(|t| t.status == Status::Skipped).count();
+    let failed = tests.len() - passed - skipped;
+
+    let mut out = String::new();
+    out.push_str(
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Test and coverage dashboard</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 2em; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }\n\
+         td.num { text-align: right; }\n\
+         .passed { color: #2e7d32; }\n.failed { color: #c62828; }\n.skipped { color: #757575; }\n\
+         pre { white-space: pre-wrap; margin: 0; }\n\
+         </style>\n</head>\n<body>\n<h1>Test and coverage dashboard</h1>\n",
+    );
+    let _ = writeln!(
+        out,
+        "<p>{} tests: <span class=\"passed\">{passed} passed</span>, \
+         <span class=\"failed\">{failed} failed</span>, \
+         <span class=\"skipped\">{skipped} skipped</span>. Line coverage: {total:.2}%</p>",
+        tests.len()
+    );
+
+    if failed != 0 {
+        out.push_str("<h2>Failures</h2>\n<table>\n<tr><th>Test</th><th>Message</th></tr>\n");
+        for test in tests {
+            if let Status::Failed(message) = &test.status {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{} {}</td><td><pre>{}</pre></td></tr>",
+                    escape(&test.suite),
+                    escape(&test.name),
+                    escape(message)
+                );
+            }
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str(
+        "<h2>Coverage</h2>\n<table>\n\
+         <tr><th>Package</th><th>Lines</th><th>Covered</th><th>Cover</th></tr>\n",
+    );
+    for (name, &(count, covered)) in packages {
+        let percent = if count == 0 { 0_f64 } else { covered as f64 * 100_f64 / count as f64 };
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"num\">{count}</td><td class=\"num\">{covered}</td>\
+             <td class=\"num\">{percent:.2}%</td></tr>",
+            escape(name)
+        );
+    }
+    out.push_str("</table>\n");
+
+    out.push_str(
+        "<h2>Tests</h2>\n<table>\n<tr><th>Binary</th><th>Test</th><th>Status</th><th>Time</th></tr>\n",
+    );
+    for test in tests {
+        let status = match test.status {
+            Status::Passed => "passed",
+            Status::Skipped => "skipped",
+            Status::Failed(_) => "failed",
+        };
+        let time = test.time.map(|t| format!("{t:.3}s")).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td class=\"{status}\">{status}</td>\
+             <td class=\"num\">{time}</td></tr>",
+            escape(&test.suite),
+            escape(&test.name)
+        );
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Status, TestCase, parse_junit};
+
+    #[test]
+    fn test_parse_junit() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="nextest-run" tests="3" failures="1" errors="0" uuid="0" timestamp="2026-01-01T00:00:00Z" time="0.1">
+    <testsuite name="a::b" tests="3" disabled="0" errors="0" failures="1">
+        <testcase name="ok" classname="a::b" timestamp="2026-01-01T00:00:00Z" time="0.001">
+        </testcase>
+        <testcase name="fail" classname="a::b" timestamp="2026-01-01T00:00:00Z" time="0.002">
+            <failure type="test failure">thread &apos;fail&apos; panicked &amp; &lt;exit&gt;</failure>
+            <system-out>out</system-out>
+        </testcase>
+        <testcase name="ignored" classname="a::b" time="0">
+            <skipped/>
+        </testcase>
+    </testsuite>
+</testsuites>
+"#;
+        let expected = vec![
+            TestCase {
+                suite: "a::b".to_owned(),
+                name: "ok".to_owned(),
+                time: Some(0.001),
+                status: Status::Passed,
+            },
+            TestCase {
+                suite: "a::b".to_owned(),
+                name: "fail".to_owned(),
+                time: Some(0.002),
+                status: Status::Failed("thread 'fail' panicked & <exit>".to_owned()),
+            },
+            TestCase {
+                suite: "a::b".to_owned(),
+                name: "ignored".to_owned(),
+                time: Some(0.),
+                status: Status::Skipped,
+            },
+        ];
+        assert_eq!(parse_junit(xml), expected);
+    }
+}
@@ -0,0 +1,327 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Merges the coverage of the same source file that appears in the report under multiple paths.
+//
+// Symlinked workspaces and `path` dependencies referred to by different paths make the same
+// file appear multiple times with different coverage, so files whose paths resolve to the same
+// canonical path are merged into one. Vendored copies of a crate are different files, so they
+// are only merged when --path-equivalence (or `path-equivalence` array of the project
+// configuration) maps them to the same path.
+//
+// Like the excluded lines (see exclude.rs), files are merged in the JSON and LCOV exports after
+// llvm-cov generates them. llvm-cov's -path-equivalence only changes the paths used to read the
+// sources, and does not merge files with the same path, so files cannot be merged in --text,
+// --html, and the summary of `llvm-cov report`. --path-equivalence is rejected together with
+// them (see Context::new), rather than silently generating reports inconsistent with the others.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, btree_map},
+    fmt::Write as _,
+    mem,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+use crate::{context::Context, fs};
+
+/// File name in the report -> file name to merge it into.
+type Renames = BTreeMap<String, String>;
+
+/// Parses `<FROM>,<TO>` of --path-equivalence.
+pub(crate) fn parse_rule(rule: &str) -> Option<(&str, &str)> {
+    rule.split_once(',').filter(|(from, to)| !from.is_empty() && !to.is_empty())
+}
+
+/// Merges the files with the same canonical path in the JSON export.
+pub(crate) fn json(cx: &Context, cov: &mut LlvmCovJsonExport) -> Result<()> {
+    let renames =
+        renames(cx, cov.data.iter().flat_map(|data| &data.files).map(|f| f.filename.as_str()));
+    if renames.is_empty() {
+        return Ok(());
+    }
+    cov.merge_files(&renames)
+}
+
+/// Merges the files with the same canonical path in the LCOV export.
+pub(crate) fn lcov(cx: &Context, lcov: String) -> String {
+    let renames = renames(cx, lcov.lines().filter_map(|line| line.strip_prefix("SF:")));
+    if renames.is_empty() {
+        return lcov;
+    }
+    merge_lcov(&lcov, &renames)
+}
+
+/// Returns the files to merge into other files.
+///
+/// Relative paths are resolved from the workspace root. Files that resolve to the same path
+/// are merged into the one whose path is already canonical, or the one with the shortest name.
+fn renames<'a>(cx: &Context, filenames: impl IntoIterator<Item = &'a str>) -> Renames {
+    let root = cx.ws.metadata.workspace_root.as_std_path();
+    let rules: Vec<_> = cx
+        .args
+        .cov
+        .path_equivalence
+        .iter()
+        .filter_map(|rule| parse_rule(rule))
+        .map(|(from, to)| (root.join(from), root.join(to)))
+        .collect();
+    let mut groups: BTreeMap<PathBuf, Vec<(&str, bool)>> = BTreeMap::new();
+    for filename in filenames {
+        let path = root.join(filename);
+        let canonical = canonicalize(&path, &rules);
+        let group = groups.entry(canonical.clone()).or_default();
+        if !group.iter().any(|&(f, _)| f == filename) {
+            group.push((filename, canonical == path));
+        }
+    }
+    let mut renames = BTreeMap::new();
+    for mut group in groups.into_values().filter(|group| group.len() > 1) {
+        group.sort_by_key(|&(filename, is_canonical)| (!is_canonical, filename.len(), filename));
+        let (to, _) = group[0];
+        for &(from, _) in &group[1..] {
+            renames.insert(from.to_owned(), to.to_owned());
+        }
+    }
+    renames
+}
+
+/// Applies the first matching --path-equivalence rule, and resolves symlinks. Files that no
+/// longer exist (e.g., generated during the build) are compared by their paths as is.
+fn canonicalize(path: &Path, rules: &[(PathBuf, PathBuf)]) -> PathBuf {
+    let path = rules
+        .iter()
+        .find_map(|(from, to)| path.strip_prefix(from).ok().map(|rest| to.join(rest)))
+        .unwrap_or_else(|| path.to_owned());
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// Records of a file in LCOV format.
+#[derive(Default)]
+struct LcovRecord<'a> {
+    /// Lines before `SF:`, such as `TN:`.
+    head: Vec<&'a str>,
+    functions: Vec<&'a str>,
+    /// Function name -> execution count
+    function_counts: BTreeMap<&'a str, u64>,
+    /// (line, block, branch) -> taken count, or `None` if the block was never executed.
+    branches: BTreeMap<(u64, &'a str, &'a str), Option<u64>>,
+    /// Line -> execution count
+    lines: BTreeMap<u64, u64>,
+    /// Other records, such as `FNL:` and `MCDC:`.
+    other: Vec<&'a str>,
+}
+
+impl<'a> LcovRecord<'a> {
+    fn add(&mut self, line: &'a str) {
+        if let Some(f) = line.strip_prefix("FN:") {
+            if !self.functions.contains(&f) {
+                self.functions.push(f);
+            }
+        } else if let Some(fnda) = line.strip_prefix("FNDA:") {
+            // FNDA:<execution count>,<function name>
+            if let Some((count, name)) = fnda.split_once(',') {
+                *self.function_counts.entry(name).or_default() += count.parse().unwrap_or(0);
+            }
+        } else if let Some(brda) = line.strip_prefix("BRDA:") {
+            // BRDA:<line number>,<block number>,<branch number>,<taken>
+            let fields: Vec<_> = brda.splitn(4, ',').collect();
+            if let &[n, block, branch, taken] = &*fields {
+                let taken = taken.parse::<u64>().ok();
+                let entry = self.branches.entry((n.parse().unwrap_or(0), block, branch));
+                match entry {
+                    btree_map::Entry::Vacant(entry) => {
+                        entry.insert(taken);
+                    }
+                    btree_map::Entry::Occupied(mut entry) => {
+                        let total = entry.get_mut();
+                        if let Some(taken) = taken {
+                            *total = Some(total.unwrap_or(0) + taken);
+                        }
+                    }
+                }
+            }
+        } else if let Some(da) = line.strip_prefix("DA:") {
+            // DA:<line number>,<execution count>[,<checksum>]
+            let mut fields = da.split(',');
+            if let (Some(n), Some(count)) = (
+                fields.next().and_then(|n| n.parse().ok()),
+                fields.next().and_then(|count| count.parse::<u64>().ok()),
+            ) {
+                *self.lines.entry(n).or_default() += count;
+            }
+        } else if !["FNF:", "FNH:", "BRF:", "BRH:", "LF:", "LH:"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+            && !self.other.contains(&line)
+        {
+            // Summaries are recomputed from the merged records.
+            self.other.push(line);
+        }
+    }
+
+    fn write(&self, out: &mut String, filename: &str) {
+        for line in &self.head {
+            let _ = writeln!(out, "{line}");
+        }
+        let _ = writeln!(out, "SF:{filename}");
+        for f in &self.functions {
+            let _ = writeln!(out, "FN:{f}");
+        }
+        for (name, count) in &self.function_counts {
+            let _ = writeln!(out, "FNDA:{count},{name}");
+        }
+        let _ = writeln!(out, "FNF:{}", self.functions.len());
+        let hit = self.function_counts.values().filter(|&&count| count > 0).count();
+        let _ = writeln!(out, "FNH:{hit}");
+        for ((n, block, branch), taken) in &self.branches {
+            let taken = taken.map_or_else(|| "-".to_owned(), |taken| taken.to_string());
+            let _ = writeln!(out, "BRDA:{n},{block},{branch},{taken}");
+        }
+        let _ = writeln!(out, "BRF:{}", self.branches.len());
+        let hit = self.branches.values().filter(|taken| taken.is_some_and(|t| t > 0)).count();
+        let _ = writeln!(out, "BRH:{hit}");
+        for (n, count) in &self.lines {
+            let _ = writeln!(out, "DA:{n},{count}");
+        }
+        let _ = writeln!(out, "LF:{}", self.lines.len());
+        let hit = self.lines.values().filter(|&&count| count > 0).count();
+        let _ = writeln!(out, "LH:{hit}");
+        for line in &self.other {
+            let _ = writeln!(out, "{line}");
+        }
+        let _ = writeln!(out, "end_of_record");
+    }
+}
+
+/// Merges the records of the files in `renames` into the records of the files they are
+/// mapped to, in LCOV format.
+///
+/// Merged files are written at the position of the first record of them, and other files are
+/// written as is.
+fn merge_lcov(lcov: &str, renames: &Renames) -> String {
+    enum Record<'a> {
+        Raw(Vec<&'a str>),
+        Merged(String),
+    }
+    let merged_files: BTreeSet<&str> =
+        renames.iter().flat_map(|(from, to)| [from.as_str(), to.as_str()]).collect();
+    let mut records = vec![];
+    let mut merged: BTreeMap<String, LcovRecord<'_>> = BTreeMap::new();
+    let mut head = vec![];
+    let mut current: Option<&str> = None;
+    let mut raw = vec![];
+    for line in lcov.lines() {
+        if let Some(filename) = line.strip_prefix("SF:") {
+            if merged_files.contains(filename) {
+                let to = renames.get(filename).map_or(filename, String::as_str);
+                current = Some(to);
+                if let btree_map::Entry::Vacant(entry) = merged.entry(to.to_owned()) {
+                    entry.insert(LcovRecord { head: mem::take(&mut head), ..Default::default() });
+                    records.push(Record::Merged(to.to_owned()));
+                } else {
+                    head.clear();
+                }
+            } else {
+                raw = mem::take(&mut head);
+                raw.push(line);
+            }
+        } else if line == "end_of_record" {
+            if current.take().is_none() {
+                raw.push(line);
+                records.push(Record::Raw(mem::take(&mut raw)));
+            }
+        } else if let Some(to) = current {
+            merged.get_mut(to).unwrap().add(line);
+        } else if raw.is_empty() {
+            head.push(line);
+        } else {
+            raw.push(line);
+        }
+    }
+    let mut out = String::with_capacity(lcov.len());
+    for record in records {
+        match record {
+            Record::Raw(lines) => {
+                for line in lines {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+            Record::Merged(filename) => merged[&filename].write(&mut out, &filename),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::merge_lcov;
+
+    #[test]
+    fn test_merge_lcov() {
+        let input = "\
+SF:src/a.rs
+FN:1,f
+FNDA:0,f
+FNF:1
+FNH:0
+BRDA:2,0,0,-
+BRDA:2,0,1,-
+BRF:2
+BRH:0
+DA:1,0
+DA:2,0
+LF:2
+LH:0
+end_of_record
+SF:src/b.rs
+DA:2,0
+LF:1
+LH:0
+end_of_record
+SF:/symlink/src/a.rs
+FN:1,f
+FNDA:3,f
+FNF:1
+FNH:1
+BRDA:2,0,0,3
+BRDA:2,0,1,0
+BRF:2
+BRH:1
+DA:1,3
+DA:2,3
+DA:3,0
+LF:3
+LH:2
+end_of_record
+";
+        let renames = BTreeMap::from([("/symlink/src/a.rs".to_owned(), "src/a.rs".to_owned())]);
+        let expected = "\
+SF:src/a.rs
+FN:1,f
+FNDA:3,f
+FNF:1
+FNH:1
+BRDA:2,0,0,3
+BRDA:2,0,1,0
+BRF:2
+BRH:1
+DA:1,3
+DA:2,3
+DA:3,0
+LF:3
+LH:2
+end_of_record
+SF:src/b.rs
+DA:2,0
+LF:1
+LH:0
+end_of_record
+";
+        assert_eq!(merge_lcov(input, &renames), expected);
+    }
+}
@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --diff: coverage of the lines added or modified by a change ("patch coverage").
+//
+// Only changed lines that have coverage mapping are counted, so changes to comments,
+// blank lines, and files that are not in the report do not affect the result.
+//
+// --github-annotations: the changed lines with no coverage are also emitted as workflow
+// commands, which GitHub Actions shows as warnings in the "Files changed" tab of pull requests.
+// https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions#setting-a-warning-message
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufWriter, Read as _, Write as _},
+};
+
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+use crate::{
+    cargo::Workspace,
+    context::Context,
+    env, fs, summary,
+    term::{self, ExitStatus},
+};
+
+/// Absolute file path -> changed lines.
+pub(crate) type ChangedLines = BTreeMap<Utf8PathBuf, BTreeSet<u64>>;
+
+/// Returns the lines that have been added or modified since the given git revision,
+/// including uncommitted changes and untracked files, or in the unified diff read from
+/// stdin if `rev` is `-`.
+pub(crate) fn changed_lines(ws: &Workspace, rev: &str) -> Result<ChangedLines> {
+    let root = ws.metadata.workspace_root.as_std_path();
+    // Paths in diffs generated by git are relative to the top-level directory.
+    let toplevel = cmd!("git", "rev-parse", "--show-toplevel")
+        .dir(root)
+        .read()
+        .ok()
+        .map(|toplevel| Utf8PathBuf::from(toplevel.trim()));
+    if rev == "-" {
+        let mut diff = String::new();
+        io::stdin().read_to_string(&mut diff).context("failed to read diff from stdin")?;
+        return Ok(parse(&diff, toplevel.as_deref().unwrap_or(&ws.metadata.workspace_root)));
+    }
+
+    let toplevel = toplevel.context("--diff requires the workspace to be in a git repository")?;
+    let commit = cmd!("git", "rev-parse", "--verify", "--quiet", format!("{rev}^{{commit}}"))
+        .dir(root)
+        .read()
+        .with_context(|| format!("failed to resolve git revision `{rev}`"))?;
+    // Override diff.noprefix, diff.mnemonicPrefix, etc. in the user's git config.
+    let diff = cmd!("git", "diff", "-U0", "--no-renames", "--no-color", "--no-ext-diff")
+        .args(["--src-prefix=a/", "--dst-prefix=b/", commit.trim(), "--"])
+        .dir(toplevel.as_std_path())
+        .read()?;
+    let mut lines = parse(&diff, &toplevel);
+    let untracked = cmd!("git", "ls-files", "--others", "--exclude-standard")
+        .dir(toplevel.as_std_path())
+        .read()?;
+    for file in untracked.lines() {
+        let file = toplevel.join(file);
+        // Non-UTF-8 files are not source files.
+        let Ok(content) = fs::read_to_string(&file) else { continue };
+        lines.insert(file, (1..=content.lines().count() as u64).collect());
+    }
+    Ok(lines)
+}
+
+/// Parses added lines in the unified diff. Paths in the diff are relative to `root`.
+fn parse(diff: &str, root: &Utf8Path) -> ChangedLines {
+    let mut lines = ChangedLines::new();
+    let mut file: Option<Utf8PathBuf> = None;
+    // Line number of the next line in the new file.
+    let mut line = 0;
+    // Number of the remaining lines of the current hunk in the old and new file. Lines in
+    // hunks are distinguished from headers by these because they can start with `---`/`+++`.
+    let (mut old, mut new) = (0_u64, 0_u64);
+    for s in diff.lines() {
+        if old > 0 || new > 0 {
+            match s.as_bytes().first() {
+                Some(b'+') => {
+                    if let Some(file) = &file {
+                        lines.entry(file.clone()).or_default().insert(line);
+                    }
+                    line += 1;
+                    new = new.saturating_sub(1);
+                }
+                Some(b'-') => old = old.saturating_sub(1),
+                // `\ No newline at end of file`
+                Some(b'\\') => {}
+                _ => {
+                    line += 1;
+                    old = old.saturating_sub(1);
+                    new = new.saturating_sub(1);
+                }
+            }
+        } else if let Some(path) = s.strip_prefix("+++ ") {
+            // `+++ b/path\t<timestamp>` (timestamp is included by diff(1))
+            let path = path.split('\t').next().unwrap_or_default().trim_end();
+            let path = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')).unwrap_or(path);
+            file =
+                (path != "/dev/null").then(|| root.join(path.strip_prefix("b/").unwrap_or(path)));
+        } else if let Some(hunk) = s.strip_prefix("@@ ") {
+            // `@@ -<start>[,<count>] +<start>[,<count>] @@`
+            let range = |prefix| -> Option<(u64, u64)> {
+                let range = hunk.split(' ').find_map(|range| range.strip_prefix(prefix))?;
+                Some(match range.split_once(',') {
+                    Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+                    None => (range.parse().ok()?, 1),
+                })
+            };
+            if let (Some((_, old_count)), Some((start, new_count))) = (range('-'), range('+')) {
+                (old, new, line) = (old_count, new_count, start);
+            }
+        }
+    }
+    lines
+}
+
+/// Prints the patch coverage and the changed lines with no coverage, and sets the exit
+/// status if it is less than --fail-under-diff.
+pub(crate) fn report(cx: &Context, json: &LlvmCovJsonExport, changed: &ChangedLines) -> Result<()> {
+    let root = &cx.ws.metadata.workspace_root;
+    let mut count = 0;
+    // file name relative to the workspace root -> uncovered lines
+    let mut uncovered: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for (filename, lines) in json.get_line_execution_counts() {
+        // File names are relative if --remap-path-prefix is used.
+        let path = root.join(filename);
+        let Some(changed) = changed.get(&path) else { continue };
+        for (line, execution_count) in lines {
+            if !changed.contains(&line) {
+                continue;
+            }
+            count += 1;
+            if execution_count == 0 {
+                let name = path.strip_prefix(root).unwrap_or(&path);
+                uncovered.entry(name.to_string()).or_default().push(line);
+            }
+        }
+    }
+
+    let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+    if count == 0 {
+        writeln!(stdout, "Patch Coverage: no changed lines with coverage data")?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    let missed = uncovered.values().map(|lines| lines.len() as u64).sum::<u64>();
+    let percent = (count - missed) as f64 * 100. / count as f64;
+    writeln!(
+        stdout,
+        "Patch Coverage: {percent:.2}% ({} of {count} changed lines)",
+        count - missed
+    )?;
+    if !uncovered.is_empty() {
+        writeln!(stdout, "Uncovered Changed Lines:")?;
+        for (file, lines) in &uncovered {
+            let lines: Vec<_> = lines.iter().map(ToString::to_string).collect();
+            writeln!(stdout, "{file}: {}", lines.join(", "))?;
+        }
+    }
+    if cx.args.cov.github_annotations {
+        // Paths in annotations are relative to the repository checked out by actions/checkout.
+        let base = env::var("GITHUB_WORKSPACE")?.map_or_else(|| root.clone(), Utf8PathBuf::from);
+        for (file, lines) in &uncovered {
+            let path = root.join(file);
+            let path = path.strip_prefix(&base).unwrap_or(&path);
+            for range in summary::coalesce(lines) {
+                writeln!(
+                    stdout,
+                    "::warning file={},line={},endLine={},title=Patch Coverage::{}",
+                    escape_property(path.as_str()),
+                    range.start(),
+                    range.end(),
+                    escape_data(if range.start() == range.end() {
+                        "Changed line is not covered by tests"
+                    } else {
+                        "Changed lines are not covered by tests"
+                    }),
+                )?;
+            }
+        }
+    }
+    stdout.flush()?;
+
+    if cx.args.cov.fail_under_diff.is_some_and(|min| percent < min) {
+        term::fail(ExitStatus::Threshold);
+    }
+    Ok(())
+}
+
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use camino::Utf8Path;
+
+    use super::{escape_data, escape_property, parse};
+
+    #[test]
+    fn test_parse() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 0000000..1111111 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,0 +3,2 @@ fn a() {
++    b();
++    c();
+@@ -10 +12 @@ fn d() {
+-    e();
++    f();
+@@ -20 +22 @@ fn d() {
+---- foo
++++- bar
+diff --git a/old.rs b/old.rs
+deleted file mode 100644
+--- a/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn g() {}
+--- src/main.rs\t2024-01-01 00:00:00.000000000 +0000
++++ src/main.rs\t2024-01-01 00:00:01.000000000 +0000
+@@ -1,3 +1,4 @@
+ fn main() {
+-    h();
++    i();
++    j();
+ }
+\\ No newline at end of file
+";
+        let lines = parse(diff, Utf8Path::new("/ws"));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[Utf8Path::new("/ws/src/lib.rs")], BTreeSet::from([3, 4, 12, 22]));
+        assert_eq!(lines[Utf8Path::new("/ws/src/main.rs")], BTreeSet::from([2, 3]));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_data("50% covered: a,b\r\n"), "50%25 covered: a,b%0D%0A");
+        assert_eq!(escape_property("C:\\src\\a,b.rs"), "C%3A\\src\\a%2Cb.rs");
+    }
+}
@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Digests of downloaded or reported files.
+//
+// These are implemented here to avoid depending on crypto crates for the few digests needed:
+// the SHA-256 digest of release assets verified by self-update, the MD5 digest of source
+// files in Coveralls reports, and the SHA-1 digest used by the WebSocket handshake of the
+// live reload server of --watch.
+
+use std::fmt::Write as _;
+
+/// Returns the lowercase hexadecimal representation of the given bytes.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+    s
+}
+
+/// Returns the base64 representation (with padding) of the given bytes.
+// Refs: https://www.rfc-editor.org/rfc/rfc4648#section-4
+pub(crate) fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = u32::from(chunk[0]) << 16
+            | u32::from(chunk.get(1).copied().unwrap_or(0)) << 8
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Computes the SHA-1 digest of the given data.
+// Refs: https://csrc.nist.gov/pubs/fips/180-4/upd1/final
+#[allow(clippy::many_single_char_names)] // Follow the names in the specification.
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0_u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.into_iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5A82_7999),
+                1 => (b ^ c ^ d, 0x6ED9_EBA1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t =
+                a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0; 20];
+    for (out, h) in out.chunks_exact_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+/// Computes the SHA-256 digest of the given data.
+// Refs: https://csrc.nist.gov/pubs/fips/180-4/upd1/final
+#[allow(clippy::many_single_char_names)] // Follow the names in the specification.
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428A_2F98,
+        0x7137_4491,
+        0xB5C0_FBCF,
+        0xE9B5_DBA5,
+        0x3956_C25B,
+        0x59F1_11F1,
+        0x923F_82A4,
+        0xAB1C_5ED5,
+        0xD807_AA98,
+        0x1283_5B01,
+        0x2431_85BE,
+        0x550C_7DC3,
+        0x72BE_5D74,
+        0x80DE_B1FE,
+        0x9BDC_06A7,
+        0xC19B_F174,
+        0xE49B_69C1,
+        0xEFBE_4786,
+        0x0FC1_9DC6,
+        0x240C_A1CC,
+        0x2DE9_2C6F,
+        0x4A74_84AA,
+        0x5CB0_A9DC,
+        0x76F9_88DA,
+        0x983E_5152,
+        0xA831_C66D,
+        0xB003_27C8,
+        0xBF59_7FC7,
+        0xC6E0_0BF3,
+        0xD5A7_9147,
+        0x06CA_6351,
+        0x1429_2967,
+        0x27B7_0A85,
+        0x2E1B_2138,
+        0x4D2C_6DFC,
+        0x5338_0D13,
+        0x650A_7354,
+        0x766A_0ABB,
+        0x81C2_C92E,
+        0x9272_2C85,
+        0xA2BF_E8A1,
+        0xA81A_664B,
+        0xC24B_8B70,
+        0xC76C_51A3,
+        0xD192_E819,
+        0xD699_0624,
+        0xF40E_3585,
+        0x106A_A070,
+        0x19A4_C116,
+        0x1E37_6C08,
+        0x2748_774C,
+        0x34B0_BCB5,
+        0x391C_0CB3,
+        0x4ED8_AA4A,
+        0x5B9C_CA4F,
+        0x682E_6FF3,
+        0x748F_82EE,
+        0x78A5_636F,
+        0x84C8_7814,
+        0x8CC7_0208,
+        0x90BE_FFFA,
+        0xA450_6CEB,
+        0xBEF9_A3F7,
+        0xC671_78F2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6A09_E667,
+        0xBB67_AE85,
+        0x3C6E_F372,
+        0xA54F_F53A,
+        0x510E_527F,
+        0x9B05_688C,
+        0x1F83_D9AB,
+        0x5BE0_CD19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0_u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for (k, w) in K.into_iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0; 32];
+    for (out, h) in out.chunks_exact_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+/// Computes the MD5 digest of the given data.
+// Refs: https://www.rfc-editor.org/rfc/rfc1321
+#[allow(clippy::many_single_char_names)] // Follow the names in the specification.
+pub(crate) fn md5(data: &[u8]) -> [u8; 16] {
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    const K: [u32; 64] = [
+        0xD76A_A478,
+        0xE8C7_B756,
+        0x2420_70DB,
+        0xC1BD_CEEE,
+        0xF57C_0FAF,
+        0x4787_C62A,
+        0xA830_4613,
+        0xFD46_9501,
+        0x6980_98D8,
+        0x8B44_F7AF,
+        0xFFFF_5BB1,
+        0x895C_D7BE,
+        0x6B90_1122,
+        0xFD98_7193,
+        0xA679_438E,
+        0x49B4_0821,
+        0xF61E_2562,
+        0xC040_B340,
+        0x265E_5A51,
+        0xE9B6_C7AA,
+        0xD62F_105D,
+        0x0244_1453,
+        0xD8A1_E681,
+        0xE7D3_FBC8,
+        0x21E1_CDE6,
+        0xC337_07D6,
+        0xF4D5_0D87,
+        0x455A_14ED,
+        0xA9E3_E905,
+        0xFCEF_A3F8,
+        0x676F_02D9,
+        0x8D2A_4C8A,
+        0xFFFA_3942,
+        0x8771_F681,
+        0x6D9D_6122,
+        0xFDE5_380C,
+        0xA4BE_EA44,
+        0x4BDE_CFA9,
+        0xF6BB_4B60,
+        0xBEBF_BC70,
+        0x289B_7EC6,
+        0xEAA1_27FA,
+        0xD4EF_3085,
+        0x0488_1D05,
+        0xD9D4_D039,
+        0xE6DB_99E5,
+        0x1FA2_7CF8,
+        0xC4AC_5665,
+        0xF429_2244,
+        0x432A_FF97,
+        0xAB94_23A7,
+        0xFC93_A039,
+        0x655B_59C3,
+        0x8F0C_CC92,
+        0xFFEF_F47D,
+        0x8584_5DD1,
+        0x6FA8_7E4F,
+        0xFE2C_E6E0,
+        0xA301_4314,
+        0x4E08_11A1,
+        0xF753_7E82,
+        0xBD3A_F235,
+        0x2AD7_D2BB,
+        0xEB86_D391,
+    ];
+    let mut h: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64).wrapping_mul(8).to_le_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut m = [0_u32; 16];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            m[i] = u32::from_le_bytes(word.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = h;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(K[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(S[i]));
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0; 16];
+    for (out, h) in out.chunks_exact_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{md5, sha1, sha256, to_base64, to_hex};
+
+    #[test]
+    fn test_to_base64() {
+        assert_eq!(to_base64(b""), "");
+        assert_eq!(to_base64(b"f"), "Zg==");
+        assert_eq!(to_base64(b"fo"), "Zm8=");
+        assert_eq!(to_base64(b"foo"), "Zm9v");
+        assert_eq!(to_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(to_base64(&[0xFB, 0xFF]), "+/8=");
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(to_hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(to_hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // Two blocks after padding.
+        assert_eq!(
+            to_hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks after padding.
+        assert_eq!(
+            to_hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            to_hex(&sha256(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn test_md5() {
+        assert_eq!(to_hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(to_hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(to_hex(&md5(b"message digest")), "f96b697d7cb7938d525a2f31aaf161d0");
+        // Two blocks after padding.
+        assert_eq!(
+            to_hex(&md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            )),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Checks the environment for common causes of missing or empty coverage reports.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+use camino::Utf8Path;
+
+use crate::{
+    cargo::{self, Workspace},
+    cli::{self, Args},
+    env, fs, term,
+};
+
+struct Doctor {
+    problems: usize,
+}
+
+impl Doctor {
+    /// Reports a problem found in the environment, with an actionable fix.
+    fn problem(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        // The problems are summarized at the end, so they should not be promoted to an error.
+        let _guard = term::warn::ignore();
+        warn!("{}\n  fix: {}", message.as_ref(), fix.as_ref());
+        self.problems += 1;
+    }
+}
+
+pub(crate) fn run(args: &mut Args) -> Result<()> {
+    let ws = Workspace::new(&args.manifest, args.target.as_deref(), false, false, false, false)?;
+    cli::merge_config_to_args(&ws, &mut args.target, &mut args.verbose, &mut args.color);
+    term::set_coloring(&mut args.color);
+
+    let doctor = &mut Doctor { problems: 0 };
+    let target = ws.target_for_config.triple();
+    let sysroot = PathBuf::from(ws.rustc_print("sysroot")?);
+    let toolchain = sysroot.file_name().map(|s| s.to_string_lossy().into_owned());
+    let toolchain_flag =
+        toolchain.as_ref().map(|t| format!(" --toolchain {t}")).unwrap_or_default();
+
+    // Toolchain
+    let version = &ws.rustc_version;
+    if ws.stable_coverage || version.nightly {
+        let (major, minor) = version.major_minor();
+        ok(&format!(
+            "rustc {major}.{minor}{} supports instrument-coverage",
+            if version.nightly { " (nightly)" } else { "" }
+        ));
+    } else {
+        doctor.problem(
+            "rustc does not support `-C instrument-coverage`",
+            "update the toolchain with `rustup update`",
+        );
+    }
+    if !version.nightly {
+        info!(
+            "--doctests, --branch, and --mcdc require nightly toolchain; use \
+             `cargo +nightly llvm-cov` if you need them"
+        );
+    }
+
+    // llvm-tools
+    let (llvm_cov, llvm_profdata): (PathBuf, PathBuf) =
+        match (env::var_os("LLVM_COV"), env::var_os("LLVM_PROFDATA")) {
+            (Some(llvm_cov), Some(llvm_profdata)) => (llvm_cov.into(), llvm_profdata.into()),
+            (llvm_cov_env, llvm_profdata_env) => {
+                if llvm_cov_env.is_some() || llvm_profdata_env.is_some() {
+                    doctor.problem(
+                        "only one of LLVM_COV and LLVM_PROFDATA environment variables is set",
+                        "set both LLVM_COV and LLVM_PROFDATA environment variables, or neither",
+                    );
+                }
+                // See Context::new for the location of llvm-tools.
+                let mut rustlib: PathBuf = ws.rustc_print("target-libdir")?.into();
+                rustlib.pop(); // lib
+                rustlib.push("bin");
+                (
+                    rustlib.join(format!("llvm-cov{}", env::consts::EXE_SUFFIX)),
+                    rustlib.join(format!("llvm-profdata{}", env::consts::EXE_SUFFIX)),
+                )
+            }
+        };
+    // LLVM_COV and LLVM_PROFDATA may be program names in PATH.
+    let llvm_cov_version = cmd!(&llvm_cov).arg("--version").read().ok();
+    let llvm_profdata_found =
+        llvm_profdata.exists() || cmd!(&llvm_profdata).arg("--version").read().is_ok();
+    if llvm_cov_version.is_some() && llvm_profdata_found {
+        ok(&format!("llvm-cov found at {}", llvm_cov.display()));
+        let rustc_llvm = ws.rustc().arg("-vV").read().ok().as_deref().and_then(llvm_major_version);
+        let tools_llvm = llvm_cov_version.as_deref().and_then(llvm_major_version);
+        match (rustc_llvm, tools_llvm) {
+            (Some(rustc_llvm), Some(tools_llvm)) if rustc_llvm != tools_llvm => doctor.problem(
+                format!(
+                    "llvm-tools use LLVM {tools_llvm}, but rustc uses LLVM {rustc_llvm}; \
+                     profile data may fail to be read"
+                ),
+                if env::var_os("LLVM_COV").is_some() {
+                    "set LLVM_COV and LLVM_PROFDATA to llvm-tools of the same LLVM version as rustc"
+                        .to_owned()
+                } else {
+                    format!("reinstall llvm-tools with `rustup component add llvm-tools-preview{toolchain_flag}`")
+                },
+            ),
+            (Some(llvm), Some(_)) => ok(&format!("llvm-tools and rustc use LLVM {llvm}")),
+            _ => {}
+        }
+    } else {
+        doctor.problem(
+            format!("llvm-tools not found: {}", llvm_cov.display()),
+            format!(
+                "install llvm-tools with `rustup component add llvm-tools-preview{toolchain_flag}`"
+            ),
+        );
+    }
+
+    // Profiler runtime
+    let target_libdir = sysroot.join("lib/rustlib").join(target).join("lib");
+    if has_profiler_builtins(&target_libdir) {
+        ok(&format!("profiler runtime is available for {target}"));
+    } else {
+        doctor.problem(
+            format!("profiler runtime (profiler_builtins) not found for {target}"),
+            if target_libdir.exists() {
+                "use a toolchain built with the profiler runtime enabled (`profiler = true` in \
+                 bootstrap config), or build it with `-Z build-std=std,profiler_builtins`"
+                    .to_owned()
+            } else {
+                format!(
+                    "install the standard library with `rustup target add {target}{toolchain_flag}`"
+                )
+            },
+        );
+    }
+
+    // Environment variables and config that conflict with cargo-llvm-cov
+    for wrapper in [&ws.config.build.rustc_wrapper, &ws.config.build.rustc_workspace_wrapper]
+        .into_iter()
+        .flatten()
+    {
+        let name = wrapper.file_stem().unwrap_or_default().to_string_lossy();
+        if name.contains("sccache") || name.contains("cachepot") {
+            doctor.problem(
+                format!(
+                    "rustc wrapper {} is used; compiler caches may return artifacts that are \
+                     not instrumented or have stale coverage mapping",
+                    wrapper.display()
+                ),
+                "unset RUSTC_WRAPPER (or build.rustc-wrapper in cargo config) when running \
+                 cargo-llvm-cov",
+            );
+        }
+    }
+    if let Some(rustflags) = ws.config.rustflags(&ws.target_for_config)? {
+        // `-C instrument-coverage=...` that enables coverage is respected.
+        if rustflags.flags.iter().any(|f| f.contains("instrument-coverage"))
+            && !cargo::enables_instrument_coverage(&rustflags.flags)
+        {
+            doctor.problem(
+                "RUSTFLAGS contains `instrument-coverage` that does not enable coverage",
+                "remove `instrument-coverage` from RUSTFLAGS; cargo-llvm-cov sets it",
+            );
+        }
+        if rustflags.flags.iter().any(|f| f.contains("profile-generate")) {
+            doctor.problem(
+                "RUSTFLAGS contains `profile-generate`, which conflicts with instrument-coverage",
+                "remove `-C profile-generate` from RUSTFLAGS",
+            );
+        }
+    }
+    if env::var_os("CARGO_LLVM_COV_SHOW_ENV").is_some() {
+        doctor.problem(
+            "environment variables set by `cargo llvm-cov show-env` are active",
+            "use normal cargo commands in this environment, or run cargo-llvm-cov in a fresh shell",
+        );
+    }
+
+    // Target directory
+    match check_writable(&ws.target_dir) {
+        Ok(()) => ok(&format!("target directory {} is writable", ws.target_dir)),
+        Err(e) => doctor.problem(
+            format!("target directory {} is not writable: {e:#}", ws.target_dir),
+            "fix permissions of the target directory, or set CARGO_LLVM_COV_TARGET_DIR",
+        ),
+    }
+
+    if doctor.problems == 0 {
+        status!("Finished", "no problems found");
+        return Ok(());
+    }
+    bail!(
+        "found {} problem{}; see the warnings above for how to fix them",
+        doctor.problems,
+        if doctor.problems == 1 { "" } else { "s" }
+    )
+}
+
+fn ok(msg: &str) {
+    status!("Ok", "{msg}");
+}
+
+/// Parses the major version from the "LLVM version" line of `rustc -vV` or
+/// `llvm-cov --version`.
+pub(crate) fn llvm_major_version(output: &str) -> Option<u32> {
+    let line = output.lines().find_map(|line| line.trim().strip_prefix("LLVM version"))?;
+    let version = line.trim_start_matches(':').trim();
+    version.split('.').next()?.parse().ok()
+}
+
+pub(crate) fn has_profiler_builtins(target_libdir: &Path) -> bool {
+    fs::read_dir(target_libdir).is_ok_and(|entries| {
+        entries
+            .filter_map(Result::ok)
+            .any(|e| e.file_name().to_string_lossy().starts_with("libprofiler_builtins-"))
+    })
+}
+
+fn check_writable(dir: &Utf8Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let file = dir.join(".cargo-llvm-cov-doctor");
+    fs::write(&file, "")?;
+    fs::remove_file(file)?;
+    Ok(())
+}
@@ -0,0 +1,431 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Excludes lines that are intentionally not covered from the coverage report.
+//
+// llvm-cov has no way to exclude individual lines, so the excluded lines are
+// removed from the JSON and LCOV exports after llvm-cov generates them.
+//
+// Lines marked with `// llvm-cov: ignore` comments are always excluded. Other
+// exclusions are enabled by --exclude-panic-lines, --exclude-cfg, and --exclude-test-code.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    sync::{LazyLock, Once},
+};
+
+use anyhow::Result;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use regex::Regex;
+
+use crate::{context::Context, dedup, fs};
+
+/// File name -> lines to exclude.
+type ExcludedLines = BTreeMap<String, BTreeSet<u64>>;
+
+/// Merges the same files under different paths, and removes the excluded lines from the JSON
+/// export.
+pub(crate) fn json(cx: &Context, cov: &mut LlvmCovJsonExport) -> Result<()> {
+    dedup::json(cx, cov)?;
+    // Files in summary-only export, which does not contain line coverage data, are omitted.
+    // --exclude-panic-lines, --exclude-cfg, and --exclude-test-code cannot be used with
+    // --summary-only, so this only skips comment markers.
+    let excluded = collect(cx, cov.get_line_execution_counts().into_keys())?;
+    if excluded.is_empty() {
+        return Ok(());
+    }
+    cov.exclude_lines(&excluded)
+}
+
+/// Merges the same files under different paths, and removes the excluded lines from the LCOV
+/// export.
+pub(crate) fn lcov(cx: &Context, lcov: String) -> Result<String> {
+    let lcov = dedup::lcov(cx, lcov);
+    let excluded = collect(cx, lcov.lines().filter_map(|line| line.strip_prefix("SF:")))?;
+    if excluded.is_empty() {
+        return Ok(lcov);
+    }
+    Ok(remove_from_lcov(&lcov, &excluded))
+}
+
+/// Collects lines to exclude from the given files.
+///
+/// File names are the ones in the report, and relative paths are resolved from the
+/// workspace root.
+fn collect<'a>(
+    cx: &Context,
+    filenames: impl IntoIterator<Item = &'a str>,
+) -> Result<ExcludedLines> {
+    let cfgs = inactive_cfgs(cx)?;
+    let mut excluded = BTreeMap::new();
+    for filename in filenames {
+        if excluded.contains_key(filename) {
+            continue;
+        }
+        // Files generated during the build may no longer exist.
+        let Ok(source) = fs::read_to_string(cx.ws.metadata.workspace_root.join(filename)) else {
+            continue;
+        };
+        let mut lines = BTreeSet::new();
+        if source.contains("llvm-cov:") {
+            let (marked, unterminated) = marker_lines(&source);
+            if let Some(start) = unterminated {
+                warn!(
+                    "{filename}:{start}: `llvm-cov: ignore-start` without matching \
+                     `llvm-cov: ignore-end`; lines until the end of the file are excluded"
+                );
+            }
+            lines.extend(marked);
+        }
+        if cx.args.cov.exclude_panic_lines {
+            lines.extend(panic_lines(&source));
+        }
+        if !cfgs.is_empty() {
+            lines.extend(cfg_lines(&source, &cfgs));
+        }
+        if cx.args.cov.exclude_test_code {
+            lines.extend(test_lines(&source));
+        }
+        if !lines.is_empty() {
+            excluded.insert(filename.to_owned(), lines);
+        }
+    }
+    Ok(excluded)
+}
+
+/// Returns the lines excluded by comment markers, and the line of `ignore-start` without
+/// matching `ignore-end`, if any.
+///
+/// - `// llvm-cov: ignore` excludes the line containing it.
+/// - `// llvm-cov: ignore-start` and `// llvm-cov: ignore-end` exclude the lines between
+///   them, including the lines containing them.
+fn marker_lines(source: &str) -> (BTreeSet<u64>, Option<u64>) {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"//[ \t]*llvm-cov:[ \t]*(ignore-start|ignore-end|ignore)([ \t]|$)").unwrap()
+    });
+    let mut lines = BTreeSet::new();
+    let mut start = None;
+    for (line, n) in source.lines().zip(1..) {
+        match RE.captures(line).as_ref().map(|caps| &caps[1]) {
+            Some("ignore-start") => {
+                start.get_or_insert(n);
+            }
+            Some("ignore-end") => {
+                if let Some(start) = start.take() {
+                    lines.extend(start..=n);
+                }
+            }
+            Some(_) => {
+                lines.insert(n);
+            }
+            None => {}
+        }
+    }
+    let total = source.lines().count() as u64;
+    if let Some(start) = start {
+        lines.extend(start..=total);
+    }
+    (lines, start)
+}
+
+/// Returns the lines that consist solely of a call to a panicking macro, such as
+/// `unreachable!()`.
+fn panic_lines(source: &str) -> impl Iterator<Item = u64> + '_ {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"^[ \t]*((::)?(core|std)::)?(unreachable|todo|unimplemented|panic)![ \t]*(\(.*\)|\[.*\]|\{.*\})[ \t]*[;,]?[ \t]*$",
+        )
+        .unwrap()
+    });
+    source.lines().zip(1..).filter(|(line, _)| RE.is_match(line)).map(|(_, n)| n)
+}
+
+/// Returns the predicates specified by --exclude-cfg, with whitespace removed, except for
+/// the ones enabled by `--cfg` in RUSTFLAGS.
+fn inactive_cfgs(cx: &Context) -> Result<Vec<String>> {
+    if cx.args.cov.exclude_cfg.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut active = BTreeSet::new();
+    if let Some(rustflags) = cx.ws.config.rustflags(&cx.ws.target_for_config)? {
+        let mut flags = rustflags.flags.iter();
+        while let Some(flag) = flags.next() {
+            let cfg = match flag.strip_prefix("--cfg") {
+                Some("") => flags.next().map(String::as_str),
+                Some(cfg) => cfg.strip_prefix('='),
+                None => None,
+            };
+            if let Some(cfg) = cfg {
+                active.insert(normalize_cfg(cfg));
+            }
+        }
+    }
+    let (enabled, cfgs): (Vec<_>, Vec<_>) = cx
+        .args
+        .cov
+        .exclude_cfg
+        .iter()
+        .map(|cfg| normalize_cfg(cfg))
+        .partition(|cfg| active.contains(cfg));
+    // This is called for each report, so warn only once.
+    static WARN: Once = Once::new();
+    if !enabled.is_empty() {
+        WARN.call_once(|| {
+            warn!(
+                "--exclude-cfg: {} enabled by `--cfg` in RUSTFLAGS; code behind them is not excluded",
+                enabled.join(", ")
+            );
+        });
+    }
+    Ok(cfgs)
+}
+
+fn normalize_cfg(cfg: &str) -> String {
+    cfg.split_whitespace().collect()
+}
+
+/// Returns the lines in bodies of `if cfg!(<predicate>) { .. }` with the given predicates.
+///
+/// Code behind `#[cfg(<predicate>)]` is not compiled when the predicate is not enabled, so
+/// it does not appear in the report in the first place.
+fn cfg_lines(source: &str, cfgs: &[String]) -> BTreeSet<u64> {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?-u:\b)if(?-u:\s)+cfg!(?-u:\s)*\(([^()]*)\)(?-u:\s)*(&&[^{;]*)?\{").unwrap()
+    });
+    let mut lines = BTreeSet::new();
+    for caps in RE.captures_iter(source) {
+        if !cfgs.contains(&normalize_cfg(&caps[1])) {
+            continue;
+        }
+        let open = caps.get(0).unwrap().end();
+        let Some(close) = closing_brace(source, open) else { continue };
+        let (start, mut end) = (line_of(source, open) + 1, line_of(source, close));
+        // The line of the closing brace is executed if it is followed by other code,
+        // like `} else {`.
+        let line_start = source[..close].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[close..].find('\n').map_or(source.len(), |i| close + i);
+        if source[line_start..line_end].trim().trim_end_matches([';', ',']) != "}" {
+            end -= 1;
+        }
+        lines.extend(start..=end);
+    }
+    lines
+}
+
+/// Returns the lines of `#[cfg(test)]` items and `#[test]` functions, from the attribute to
+/// the closing brace of the item.
+///
+/// Test attributes of other crates, such as `#[tokio::test]`, are also recognized. Items
+/// without a body, such as `#[cfg(test)] mod tests;`, are skipped.
+fn test_lines(source: &str) -> BTreeSet<u64> {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?m)^[ \t]*#\[(cfg\(test\)|([A-Za-z0-9_]+::)*test(\(.*\))?)\]").unwrap()
+    });
+    let mut lines = BTreeSet::new();
+    for m in RE.find_iter(source) {
+        let rest = &source[m.end()..];
+        let Some(i) = rest.find(['{', ';']).filter(|&i| rest.as_bytes()[i] == b'{') else {
+            continue;
+        };
+        let Some(close) = closing_brace(source, m.end() + i + 1) else { continue };
+        lines.extend(line_of(source, m.start())..=line_of(source, close));
+    }
+    lines
+}
+
+/// Returns the position of the closing brace that matches the opening brace just before
+/// `open`.
+///
+/// This does not take braces in string literals and comments into account.
+fn closing_brace(source: &str, open: usize) -> Option<usize> {
+    let mut depth = 1_usize;
+    let len = source[open..].find(|c| {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        depth == 0
+    })?;
+    Some(open + len)
+}
+
+/// Returns the 1-based line number of the given position.
+fn line_of(source: &str, pos: usize) -> u64 {
+    source[..pos].matches('\n').count() as u64 + 1
+}
+
+/// Removes the excluded lines from the line coverage in LCOV format.
+fn remove_from_lcov(lcov: &str, excluded: &ExcludedLines) -> String {
+    let mut out = String::with_capacity(lcov.len());
+    let mut lines = None;
+    // Number of lines and covered lines of the current file.
+    let mut found = 0_u64;
+    let mut hit = 0_u64;
+    for line in lcov.lines() {
+        if let Some(filename) = line.strip_prefix("SF:") {
+            lines = excluded.get(filename);
+            found = 0;
+            hit = 0;
+        } else if let (Some(lines), Some(da)) = (lines, line.strip_prefix("DA:")) {
+            // DA:<line number>,<execution count>[,<checksum>]
+            let mut fields = da.split(',');
+            let n = fields.next().and_then(|n| n.parse::<u64>().ok());
+            if n.is_some_and(|n| lines.contains(&n)) {
+                continue;
+            }
+            found += 1;
+            hit += u64::from(fields.next().is_some_and(|count| count != "0"));
+        } else if lines.is_some() && line.starts_with("LF:") {
+            let _ = writeln!(out, "LF:{found}");
+            continue;
+        } else if lines.is_some() && line.starts_with("LH:") {
+            let _ = writeln!(out, "LH:{hit}");
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::{cfg_lines, marker_lines, panic_lines, remove_from_lcov, test_lines};
+
+    #[test]
+    fn test_marker_lines() {
+        let source = "\
+fn f(x: u8) -> u8 {
+    if x == 0 {
+        return g(); // llvm-cov: ignore
+    }
+    // llvm-cov: ignore-start
+    match x {
+        1 => 1,
+        _ => 2,
+    }
+    // llvm-cov: ignore-end
+    // llvm-cov: ignored
+    // llvm-cov: ignore-end
+    h() //llvm-cov:ignore
+}
+";
+        assert_eq!(marker_lines(source), (BTreeSet::from([3, 5, 6, 7, 8, 9, 10, 13]), None));
+        let source = "\
+fn f() {
+    a();
+    // llvm-cov: ignore-start
+    b();
+    // llvm-cov: ignore-start
+}
+";
+        assert_eq!(marker_lines(source), (BTreeSet::from([3, 4, 5, 6]), Some(3)));
+    }
+
+    #[test]
+    fn test_panic_lines() {
+        let source = "\
+fn f(x: u8) -> u8 {
+    match x {
+        0 => 1,
+        _ => unreachable!(),
+    }
+    unreachable!()
+    todo!();
+    std::unimplemented!(\"a\"),
+    panic!(\"{x}\");
+    if x == 0 { panic!() }
+    panic!(
+        \"multi-line\"
+    );
+}
+";
+        assert_eq!(panic_lines(source).collect::<Vec<_>>(), vec![6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_cfg_lines() {
+        let source = "\
+fn f() {
+    if cfg!(fuzzing) {
+        a();
+        b();
+    }
+    if cfg!(feature = \"x\") && f() {
+        if g() { c(); }
+    } else {
+        d();
+    }
+    if cfg!(loom) {
+        e();
+    }
+    if cfg!(fuzzing) { f() }
+}
+";
+        let cfgs = ["fuzzing".to_owned(), "feature=\"x\"".to_owned()];
+        assert_eq!(cfg_lines(source, &cfgs), BTreeSet::from([3, 4, 5, 7]));
+    }
+
+    #[test]
+    fn test_test_lines() {
+        let source = "\
+fn f() {}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn a() {
+        if true { f(); }
+    }
+}
+
+#[tokio::test(flavor = \"multi_thread\")]
+async fn b() {
+    f();
+}
+
+#[cfg(test)]
+mod other;
+fn g() {}
+";
+        assert_eq!(test_lines(source), (3..=9).chain(11..=14).collect::<BTreeSet<_>>());
+    }
+
+    #[test]
+    fn test_remove_from_lcov() {
+        let input = "\
+SF:src/a.rs
+FN:1,f
+DA:1,1
+DA:2,0
+DA:3,0
+LF:3
+LH:1
+end_of_record
+SF:src/b.rs
+DA:2,0
+LF:1
+LH:0
+end_of_record
+";
+        let excluded = BTreeMap::from([("src/a.rs".to_owned(), BTreeSet::from([2]))]);
+        let expected = "\
+SF:src/a.rs
+FN:1,f
+DA:1,1
+DA:3,0
+LF:2
+LH:1
+end_of_record
+SF:src/b.rs
+DA:2,0
+LF:1
+LH:0
+end_of_record
+";
+        assert_eq!(remove_from_lcov(input, &excluded), expected);
+    }
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --each-feature/--feature-powerset: run tests once per feature combination of the package
+// (like cargo-hack), so that code gated by `cfg(feature = "...")` is covered by one of the runs.
+//
+// All runs write profile data to the same directory, so the report is merged from all of them.
+
+use anyhow::{Result, bail};
+
+use crate::context::Context;
+
+/// Returns the cargo arguments of each run.
+pub(crate) fn runs(cx: &Context) -> Result<Vec<Vec<String>>> {
+    let Some(package) = cx
+        .ws
+        .metadata
+        .packages
+        .values()
+        .find(|package| package.manifest_path == cx.ws.current_manifest)
+    else {
+        let flag = if cx.args.each_feature { "--each-feature" } else { "--feature-powerset" };
+        bail!(
+            "{flag} requires the manifest of a package, but {} is a virtual manifest; \
+             use --manifest-path to select a package",
+            cx.ws.current_manifest
+        );
+    };
+    let mut features: Vec<_> =
+        package.features.iter().filter(|f| *f != "default").map(String::as_str).collect();
+    features.sort_unstable();
+
+    let sets = if cx.args.each_feature {
+        each_feature(&features)
+    } else {
+        powerset(&features, cx.args.depth.unwrap_or(features.len()))
+    };
+    Ok(sets
+        .into_iter()
+        .map(|set| {
+            let mut args = vec!["--no-default-features".to_owned()];
+            if !set.is_empty() {
+                args.push("--features".to_owned());
+                args.push(set.join(","));
+            }
+            args
+        })
+        .collect())
+}
+
+/// No features, each feature, and all features.
+fn each_feature<'a>(features: &[&'a str]) -> Vec<Vec<&'a str>> {
+    let mut sets = vec![vec![]];
+    sets.extend(features.iter().map(|&f| vec![f]));
+    if features.len() > 1 {
+        sets.push(features.to_vec());
+    }
+    sets
+}
+
+/// All combinations of up to `depth` features, in order of size.
+fn powerset<'a>(features: &[&'a str], depth: usize) -> Vec<Vec<&'a str>> {
+    let mut sets: Vec<Vec<&str>> = vec![vec![]];
+    let mut last = 0..1;
+    for _ in 0..depth.min(features.len()) {
+        let start = sets.len();
+        for i in last.clone() {
+            // Extend each combination of the previous size with the features after its last one.
+            let next =
+                sets[i].last().map_or(0, |f| features.iter().position(|x| x == f).unwrap() + 1);
+            for &f in &features[next..] {
+                let mut set = sets[i].clone();
+                set.push(f);
+                sets.push(set);
+            }
+        }
+        last = start..sets.len();
+    }
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{each_feature, powerset};
+
+    #[test]
+    fn test_each_feature() {
+        assert_eq!(each_feature(&[]), [vec![]] as [Vec<&str>; 1]);
+        assert_eq!(each_feature(&["a"]), [vec![], vec!["a"]]);
+        assert_eq!(each_feature(&["a", "b"]), [vec![], vec!["a"], vec!["b"], vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn test_powerset() {
+        assert_eq!(powerset(&["a", "b", "c"], 3), [
+            vec![],
+            vec!["a"],
+            vec!["b"],
+            vec!["c"],
+            vec!["a", "b"],
+            vec!["a", "c"],
+            vec!["b", "c"],
+            vec!["a", "b", "c"],
+        ]);
+        assert_eq!(powerset(&["a", "b", "c"], 1), [vec![], vec!["a"], vec!["b"], vec!["c"]]);
+        assert_eq!(powerset(&["a"], 5), [vec![], vec!["a"]]);
+    }
+}
@@ -3,7 +3,9 @@
 pub(crate) use std::fs::Metadata;
 use std::{ffi::OsStr, io, path::Path};
 
-pub(crate) use fs_err::{File, create_dir_all, read_dir, write};
+pub(crate) use fs_err::{
+    File, canonicalize, create_dir_all, metadata, read, read_dir, read_to_string, rename, write,
+};
 
 /// Removes a file from the filesystem **if exists**. (Similar to `rm -f`)
 pub(crate) fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
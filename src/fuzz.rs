@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Replays the corpus of a cargo-fuzz target through a build instrumented for coverage.
+//
+// The fuzz target is built with `--cfg fuzzing` like cargo-fuzz does, but without the
+// sanitizer coverage instrumentation that libFuzzer uses to guide fuzzing. When `-runs=0` is
+// passed, libFuzzer executes each input of the corpus once and exits without fuzzing, so the
+// report shows which code the corpus reaches.
+
+use anyhow::{Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{IsNextest, cargo, context::Context, set_env, stdout_to_stderr, term};
+
+/// Returns `fuzz/Cargo.toml` in the current directory if it exists, which is where
+/// `cargo fuzz init` creates the fuzz crate.
+pub(crate) fn default_manifest_path() -> Option<Utf8PathBuf> {
+    let path = Utf8Path::new("fuzz/Cargo.toml");
+    path.is_file().then(|| path.to_owned())
+}
+
+pub(crate) fn run(cx: &Context) -> Result<()> {
+    let options = &cx.args.fuzz;
+    let metadata = &cx.ws.metadata;
+    if !metadata
+        .workspace_members
+        .iter()
+        .any(|id| metadata.packages[id].targets.iter().any(|target| target.name == options.target))
+    {
+        bail!(
+            "fuzz target `{}` not found in {}; use --manifest-path to select the fuzz crate if \
+             it is not in `fuzz` directory",
+            options.target,
+            cx.ws.current_manifest
+        );
+    }
+    let corpus = if options.corpus.is_empty() {
+        // The default corpus directory of cargo-fuzz.
+        let dir = cx.ws.current_manifest.parent().unwrap().join("corpus").join(&options.target);
+        if !dir.is_dir() {
+            bail!(
+                "corpus directory {dir} does not exist; pass corpus directories or input files \
+                 after the name of the fuzz target"
+            );
+        }
+        vec![dir]
+    } else {
+        options.corpus.clone()
+    };
+
+    let mut cargo = cx.cargo();
+    set_env(cx, &mut cargo, IsNextest(false))?;
+
+    // Build separately so that build failures are not ignored.
+    {
+        let mut cargo = cargo.clone();
+        cargo.arg("build").arg("--bin").arg(&options.target);
+        cargo::test_or_run_args(cx, &mut cargo);
+        if term::verbose() {
+            status!("Running", "{cargo}");
+            cargo.stdout_to_stderr().run()?;
+        } else {
+            // Capture output to prevent duplicate warnings from appearing in two runs.
+            cargo.run_with_output()?;
+        }
+    }
+
+    cargo.arg("run").arg("--bin").arg(&options.target);
+    cargo::test_or_run_args(cx, &mut cargo);
+    cargo.arg("--");
+    cargo.arg("-runs=0");
+    cargo.args(&options.args);
+    cargo.args(corpus.iter().map(Utf8PathBuf::as_str));
+    if term::verbose() {
+        status!("Running", "{cargo}");
+    }
+    stdout_to_stderr(cx, &mut cargo);
+    if let Err(e) = cargo.run() {
+        // An input crashed the fuzz target. Continuous mode (which is always enabled for fuzz
+        // subcommand) keeps the counters of the inputs run until then, so generate the report.
+        warn!("{e:#}");
+    }
+    Ok(())
+}
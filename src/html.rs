@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Improves accessibility of the HTML report generated by llvm-cov.
+//
+// llvm-cov indicates coverage only by colors and does not support keyboard
+// navigation, so this adds symbols and patterns via the stylesheet, and a script
+// that adds ARIA roles and keyboard shortcuts to each page. The script also makes
+// the columns of the index page sortable.
+
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use camino::Utf8Path;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::fs;
+
+const SCRIPT_FILE: &str = "cargo-llvm-cov-a11y.js";
+
+const STYLE: &str = r#"
+/* Added by cargo-llvm-cov: indicate coverage by symbols and patterns in addition to colors. */
+td.uncovered-line pre::before { content: "\2717\00a0"; }
+.red { text-decoration: underline wavy; text-decoration-skip-ink: none; }
+.column-entry-red::after { content: "\00a0\2717"; }
+.column-entry-yellow::after { content: "\00a0\25cb"; }
+.column-entry-green::after { content: "\00a0\2713"; }
+:focus { outline: 3px solid #1a73e8; outline-offset: 1px; }
+.cargo-llvm-cov-sr-only {
+  position: absolute; width: 1px; height: 1px;
+  overflow: hidden; clip: rect(0 0 0 0); white-space: nowrap;
+}
+.cargo-llvm-cov-help { font-size: small; }
+.cargo-llvm-cov-sortable { cursor: pointer; }
+"#;
+
+const SCRIPT: &str = r#"// Added by cargo-llvm-cov: ARIA roles and keyboard navigation for the coverage report.
+(function () {
+  'use strict';
+  const KEY = 'cargo-llvm-cov-files';
+
+  for (const table of document.querySelectorAll('table')) {
+    table.setAttribute('role', 'table');
+    for (const row of table.rows) {
+      row.setAttribute('role', 'row');
+      for (const cell of row.cells) {
+        const header = cell.classList.contains('column-entry-bold') || cell.tagName === 'TH';
+        cell.setAttribute('role', header ? 'columnheader' : 'cell');
+      }
+    }
+  }
+
+  // Uncovered lines and lines that contain uncovered regions.
+  const uncovered = [];
+  for (const row of document.querySelectorAll('tr')) {
+    if (row.querySelector('td.uncovered-line, span.red')) {
+      row.tabIndex = -1;
+      const label = document.createElement('span');
+      label.className = 'cargo-llvm-cov-sr-only';
+      label.textContent = 'Not covered: ';
+      const first = row.cells[0];
+      if (first) first.prepend(label);
+      uncovered.push(row);
+    }
+  }
+
+  // Remember the order of files on the index page to move between files.
+  const saveFiles = () => {
+    const links = Array.from(document.querySelectorAll('td a[href$=".html"]'));
+    if (uncovered.length === 0 && links.length !== 0) {
+      try {
+        sessionStorage.setItem(KEY, JSON.stringify(links.map((a) => a.href)));
+      } catch (_) {}
+    }
+  };
+  saveFiles();
+
+  // Sort the files on the index page by the clicked column. Coverage is sorted in
+  // ascending order first (least covered first), and files without coverage of the
+  // kind (`- (0/0)`) are placed last. The totals row is kept at the end.
+  let sortable = false;
+  for (const table of document.querySelectorAll('table')) {
+    const header = table.rows[0];
+    const rows = Array.from(table.rows).filter((row) => row.querySelector('a[href$=".html"]'));
+    if (uncovered.length !== 0 || !header || rows.length === 0) continue;
+    sortable = true;
+    Array.from(header.cells).forEach((cell, column) => {
+      cell.tabIndex = 0;
+      cell.classList.add('cargo-llvm-cov-sortable');
+      const sort = () => {
+        const ascending = cell.getAttribute('aria-sort') !== 'ascending';
+        for (const other of header.cells) other.removeAttribute('aria-sort');
+        cell.setAttribute('aria-sort', ascending ? 'ascending' : 'descending');
+        const key = (row) => {
+          const text = row.cells[column] ? row.cells[column].textContent.trim() : '';
+          return column === 0 ? text : parseFloat(text);
+        };
+        const sorted = rows.slice().sort((a, b) => {
+          const [x, y] = [key(a), key(b)];
+          if (column === 0) return ascending ? x.localeCompare(y) : y.localeCompare(x);
+          if (Number.isNaN(x) || Number.isNaN(y)) return Number.isNaN(x) - Number.isNaN(y);
+          return ascending ? x - y : y - x;
+        });
+        const next = rows[rows.length - 1].nextSibling;
+        for (const row of sorted) row.parentNode.insertBefore(row, next);
+        rows.splice(0, rows.length, ...sorted);
+        saveFiles();
+      };
+      cell.addEventListener('click', sort);
+      cell.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter' || e.key === ' ') {
+          sort();
+          e.preventDefault();
+        }
+      });
+    });
+  }
+  let files = [];
+  try {
+    files = JSON.parse(sessionStorage.getItem(KEY) || '[]');
+  } catch (_) {}
+
+  const help = document.createElement('p');
+  help.className = 'cargo-llvm-cov-help';
+  help.setAttribute('role', 'note');
+  help.textContent =
+    'Keyboard shortcuts: n / p: next / previous uncovered line; ] / [: next / previous file. ' +
+    'Symbols: ✗ not covered or low coverage, ○ medium coverage, ✓ high coverage.' +
+    (sortable ? ' Click a column header to sort the files.' : '');
+  document.body.prepend(help);
+
+  let current = -1;
+  document.addEventListener('keydown', (e) => {
+    if (e.altKey || e.ctrlKey || e.metaKey) return;
+    const target = e.target;
+    if (target instanceof HTMLInputElement || target instanceof HTMLTextAreaElement) return;
+    if ((e.key === 'n' || e.key === 'p') && uncovered.length !== 0) {
+      const step = e.key === 'n' ? 1 : -1;
+      current = (current + step + uncovered.length) % uncovered.length;
+      uncovered[current].focus();
+      uncovered[current].scrollIntoView({ block: 'center' });
+      e.preventDefault();
+    } else if ((e.key === ']' || e.key === '[') && files.length !== 0) {
+      const index = files.indexOf(location.href.split('#')[0]);
+      const next = index + (e.key === ']' ? 1 : -1);
+      if (index !== -1 && next >= 0 && next < files.length) {
+        location.href = files[next];
+      } else if (index === -1) {
+        location.href = files[e.key === ']' ? 0 : files.length - 1];
+      }
+      e.preventDefault();
+    }
+  });
+})();
+"#;
+
+/// Adds accessibility improvements to the HTML report in `dir`.
+pub(crate) fn improve_accessibility(dir: &Utf8Path) -> Result<()> {
+    static STYLESHEET: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"<link [^>]*href=['"]([^'"]*)style\.css['"][^>]*>"#).unwrap()
+    });
+
+    let style = dir.join("style.css");
+    if style.exists() {
+        let mut css = fs::read_to_string(&style)?;
+        if !css.contains("cargo-llvm-cov") {
+            css.push_str(STYLE);
+            fs::write(&style, css)?;
+        }
+    }
+    fs::write(dir.join(SCRIPT_FILE), SCRIPT)?;
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let mut html = fs::read_to_string(path)?;
+        if html.contains(SCRIPT_FILE) {
+            continue;
+        }
+        // Use the same relative path as the stylesheet, which is at the root of the report.
+        let Some(prefix) = STYLESHEET.captures(&html).map(|caps| caps[1].to_owned()) else {
+            continue;
+        };
+        let script = format!("<script src='{prefix}{SCRIPT_FILE}' defer></script>");
+        match html.find("</head>") {
+            Some(pos) => html.insert_str(pos, &script),
+            None => continue,
+        }
+        fs::write(path, html)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Generates a starter configuration for new projects.
+//
+// The configuration is appended to Cargo.toml as text (instead of parsing and re-serializing
+// it) to preserve the formatting and comments of the existing manifest.
+
+use std::fmt::Write as _;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    cargo::Workspace,
+    cli::{self, Args},
+    fs, term,
+};
+
+// Files generated by build scripts in OUT_DIR and included with `include!`.
+const GENERATED_CODE_REGEX: &str = "/build/[^/]+-[0-9a-f]+/out/";
+const FAIL_UNDER_LINES: u32 = 80;
+
+pub(crate) fn run(args: &mut Args) -> Result<()> {
+    let ws = Workspace::new(&args.manifest, None, false, false, false, false)?;
+    cli::merge_config_to_args(&ws, &mut None, &mut args.verbose, &mut args.color);
+    term::set_coloring(&mut args.color);
+
+    let workspace_root = &ws.metadata.workspace_root;
+    let manifest_path = workspace_root.join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_path)?;
+    if has_config(&manifest) {
+        bail!("{manifest_path} already has cargo-llvm-cov configuration");
+    }
+    let workflow_path = workspace_root.join(".github/workflows/coverage.yml");
+    if args.init.ci_workflow && workflow_path.exists() {
+        bail!("{workflow_path} already exists");
+    }
+
+    let table = if is_workspace(&manifest) { "workspace" } else { "package" };
+    let mut new_manifest = manifest;
+    if !new_manifest.is_empty() && !new_manifest.ends_with('\n') {
+        new_manifest.push('\n');
+    }
+    new_manifest.push_str(&config(table));
+    fs::write(&manifest_path, new_manifest)?;
+    status!("Added", "[{table}.metadata.llvm-cov] table to {manifest_path}");
+
+    if args.init.ci_workflow {
+        fs::create_dir_all(workflow_path.parent().unwrap())?;
+        fs::write(&workflow_path, WORKFLOW)?;
+        status!("Created", "{workflow_path}");
+    }
+    info!("run `cargo llvm-cov --preset dev` or `cargo llvm-cov --preset ci` to collect coverage");
+    Ok(())
+}
+
+/// Returns `true` if the manifest already has `[workspace.metadata.llvm-cov]` or
+/// `[package.metadata.llvm-cov]` table (or its sub-tables).
+fn has_config(manifest: &str) -> bool {
+    manifest.lines().any(|line| {
+        let line = line.trim();
+        ["[workspace.metadata.llvm-cov", "[package.metadata.llvm-cov"]
+            .iter()
+            .any(|prefix| line.strip_prefix(prefix).is_some_and(|s| s.starts_with([']', '.'])))
+    })
+}
+
+fn is_workspace(manifest: &str) -> bool {
+    manifest.lines().any(|line| {
+        let line = line.trim();
+        line == "[workspace]" || line.starts_with("[workspace.")
+    })
+}
+
+fn config(table: &str) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out);
+    let _ = writeln!(out, "# Generated by `cargo llvm-cov init`.");
+    let _ = writeln!(out, "# Use with `cargo llvm-cov --preset <NAME>`.");
+    let _ = writeln!(out, "[{table}.metadata.llvm-cov.presets]");
+    let _ = writeln!(out, "# Show a summary and open the HTML report.");
+    let _ = writeln!(
+        out,
+        "dev = [\"--workspace\", \"--ignore-filename-regex\", \"{GENERATED_CODE_REGEX}\", \
+         \"--open\"]"
+    );
+    let _ = writeln!(
+        out,
+        "# Write a report for coverage services and fail if line coverage is below the threshold."
+    );
+    let _ = writeln!(out, "ci = [");
+    let _ = writeln!(out, "    \"--workspace\",");
+    let _ = writeln!(out, "    \"--ignore-filename-regex\", \"{GENERATED_CODE_REGEX}\",");
+    let _ = writeln!(out, "    \"--lcov\", \"--output-path\", \"lcov.info\",");
+    let _ = writeln!(out, "    \"--fail-under-lines\", \"{FAIL_UNDER_LINES}\",");
+    let _ = writeln!(out, "]");
+    out
+}
+
+const WORKFLOW: &str = "\
+name: Coverage
+
+on: [pull_request, push]
+
+jobs:
+  coverage:
+    runs-on: ubuntu-latest
+    env:
+      CARGO_TERM_COLOR: always
+    steps:
+      - uses: actions/checkout@v5
+      - name: Install Rust
+        run: rustup update stable
+      - name: Install cargo-llvm-cov
+        uses: taiki-e/install-action@cargo-llvm-cov
+      - name: Generate code coverage
+        run: cargo llvm-cov --preset ci
+      - name: Upload coverage report
+        uses: actions/upload-artifact@v4
+        with:
+          name: lcov.info
+          path: lcov.info
+";
+
+#[cfg(test)]
+mod tests {
+    use super::{config, has_config, is_workspace};
+
+    #[test]
+    fn test_has_config() {
+        assert!(has_config("[package]\nname = \"a\"\n[package.metadata.llvm-cov]\n"));
+        assert!(has_config("[workspace.metadata.llvm-cov.presets]\nfast = [\"--lib\"]\n"));
+        assert!(!has_config("[package]\nname = \"a\"\n[package.metadata.llvm-cov-foo]\n"));
+        assert!(!has_config("[package]\nname = \"a\"\n"));
+    }
+
+    #[test]
+    fn test_is_workspace() {
+        assert!(is_workspace("[workspace]\nmembers = [\"a\"]\n"));
+        assert!(is_workspace("[package]\nname = \"a\"\n\n[workspace.dependencies]\n"));
+        assert!(!is_workspace("[package]\nname = \"a\"\n"));
+    }
+
+    #[test]
+    fn test_config() {
+        let config = config("package");
+        assert!(config.contains("[package.metadata.llvm-cov.presets]\n"));
+        assert!(config.contains("\"--fail-under-lines\", \"80\""));
+    }
+}
@@ -4,17 +4,18 @@
 // TODO: reflect https://github.com/llvm/llvm-project/commit/8ecbb0404d740d1ab173554e47cef39cd5e3ef8c#diff-e5de2b538138d03e13b43901f61adc61992516c742991ebaf1a13f2f8623910a?
 
 use std::{
-    collections::{BTreeMap, HashMap},
-    fmt,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, hash_map},
+    fmt::{self, Write as _},
 };
 
-use anyhow::{Context as _, Result};
+use anyhow::{Context as _, Result, bail};
 use camino::Utf8PathBuf;
 use regex::Regex;
 use serde::ser::{Serialize, SerializeMap as _, Serializer};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct LlvmCovJsonExport {
     /// List of one or more export objects
@@ -26,6 +27,9 @@ pub struct LlvmCovJsonExport {
     /// Additional information injected into the export data.
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     cargo_llvm_cov: Option<CargoLlvmCov>,
+    /// Whether closures are merged into their enclosing functions in function-level reports.
+    #[serde(skip)]
+    group_closures: bool,
 }
 
 /// <https://docs.codecov.com/docs/codecov-custom-coverage-format>
@@ -78,22 +82,22 @@ impl CodeCovJsonExport {
         let mut regions = HashMap::new();
 
         for func in functions {
-            for filename in func.filenames {
+            for region in &func.regions {
+                // Regions in macro expansions and included files point to other files than
+                // the one in which the function is defined.
+                let Some(filename) = func.filenames.get(region.file_id() as usize) else {
+                    continue;
+                };
                 if let Some(re) = ignore_filename_regex {
-                    if re.is_match(&filename) {
+                    if re.is_match(filename) {
                         continue;
                     }
                 }
                 // region location to covered
                 let coverage: &mut HashMap<RegionLocation, bool> =
-                    regions.entry(filename).or_default();
-                for region in &func.regions {
-                    let loc = RegionLocation::from(region);
-
-                    let covered = coverage.entry(loc).or_default();
-
-                    *covered = *covered || region.execution_count() > 0;
-                }
+                    regions.entry(filename.clone()).or_default();
+                let covered = coverage.entry(RegionLocation::from(region)).or_default();
+                *covered = *covered || region.execution_count() > 0;
             }
         }
 
@@ -114,12 +118,11 @@ impl CodeCovJsonExport {
         Self { coverage }
     }
 
-    #[must_use]
     pub fn from_llvm_cov_json_export(
         value: LlvmCovJsonExport,
         ignore_filename_regex: Option<&str>,
-    ) -> Self {
-        let re = ignore_filename_regex.map(|s| Regex::new(s).unwrap());
+    ) -> Result<Self> {
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
         let exports: Vec<_> =
             value.data.into_iter().map(|v| Self::from_export(v, re.as_ref())).collect();
 
@@ -140,13 +143,969 @@ impl CodeCovJsonExport {
             }
         }
 
-        combined
+        Ok(combined)
+    }
+}
+
+/// Uncovered lines in [reviewdog's RDJSON format](https://github.com/reviewdog/reviewdog/tree/HEAD/proto/rdf).
+#[derive(Serialize)]
+pub struct RdJsonExport {
+    source: RdJsonSource,
+    diagnostics: Vec<RdJsonDiagnostic>,
+}
+
+#[derive(Serialize)]
+struct RdJsonSource {
+    name: &'static str,
+    url: &'static str,
+}
+
+#[derive(Serialize)]
+struct RdJsonDiagnostic {
+    message: String,
+    location: RdJsonLocation,
+    severity: &'static str,
+}
+
+#[derive(Serialize)]
+struct RdJsonLocation {
+    path: String,
+    range: RdJsonRange,
+}
+
+#[derive(Serialize)]
+struct RdJsonRange {
+    start: RdJsonPosition,
+    end: RdJsonPosition,
+}
+
+#[derive(Serialize)]
+struct RdJsonPosition {
+    line: u64,
+}
+
+impl RdJsonExport {
+    /// Creates a diagnostic for each range of consecutive uncovered lines.
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Self> {
+        let mut diagnostics = vec![];
+        for (path, lines) in value.get_uncovered_lines(ignore_filename_regex)? {
+            for (start, end) in line_ranges(&lines) {
+                let message = if start == end {
+                    format!("line {start} is not covered by tests")
+                } else {
+                    format!("lines {start}-{end} are not covered by tests")
+                };
+                diagnostics.push(RdJsonDiagnostic {
+                    message,
+                    location: RdJsonLocation {
+                        path: path.clone(),
+                        range: RdJsonRange {
+                            start: RdJsonPosition { line: start },
+                            end: RdJsonPosition { line: end },
+                        },
+                    },
+                    severity: "WARNING",
+                });
+            }
+        }
+        Ok(Self {
+            source: RdJsonSource {
+                name: "cargo-llvm-cov",
+                url: "https://github.com/taiki-e/cargo-llvm-cov",
+            },
+            diagnostics,
+        })
+    }
+}
+
+/// Uncovered regions in [Checkstyle](https://checkstyle.org) XML format.
+#[derive(Default)]
+pub struct CheckstyleExport {
+    /// filename -> list of uncovered regions.
+    files: BTreeMap<String, BTreeSet<RegionLocation>>,
+}
+
+impl CheckstyleExport {
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self { files: uncovered_regions(value, ignore_filename_regex)? })
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<checkstyle version=\"4.3\">\n");
+        for (filename, regions) in &self.files {
+            let _ = writeln!(out, "  <file name=\"{}\">", xml_escape(filename));
+            for loc in regions {
+                let _ = writeln!(
+                    out,
+                    "    <error line=\"{}\" column=\"{}\" severity=\"warning\" \
+                     message=\"region {}:{}-{}:{} is not covered by tests\" \
+                     source=\"cargo-llvm-cov\"/>",
+                    loc.start_line,
+                    loc.start_column,
+                    loc.start_line,
+                    loc.start_column,
+                    loc.end_line,
+                    loc.end_column
+                );
+            }
+            out.push_str("  </file>\n");
+        }
+        out.push_str("</checkstyle>\n");
+        out
+    }
+}
+
+/// Uncovered regions in [SARIF](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+/// 2.1.0 format, which can be uploaded to GitHub code scanning.
+#[derive(Serialize)]
+pub struct SarifExport {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Serialize)]
+struct SarifRun {
+    tool: SarifTool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    information_uri: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    short_description: SarifMessage,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: u64,
+    start_column: u64,
+    end_line: u64,
+    end_column: u64,
+}
+
+const SARIF_RULE_ID: &str = "uncovered-region";
+
+impl SarifExport {
+    /// Creates a result for each uncovered region.
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Self> {
+        let mut results = vec![];
+        for (filename, regions) in uncovered_regions(value, ignore_filename_regex)? {
+            let uri = file_uri(&filename);
+            for loc in regions {
+                results.push(SarifResult {
+                    rule_id: SARIF_RULE_ID,
+                    level: "warning",
+                    message: SarifMessage {
+                        text: format!(
+                            "region {}:{}-{}:{} is not covered by tests",
+                            loc.start_line, loc.start_column, loc.end_line, loc.end_column
+                        ),
+                    },
+                    locations: vec![SarifLocation {
+                        physical_location: SarifPhysicalLocation {
+                            artifact_location: SarifArtifactLocation { uri: uri.clone() },
+                            region: SarifRegion {
+                                start_line: loc.start_line,
+                                start_column: loc.start_column,
+                                end_line: loc.end_line,
+                                end_column: loc.end_column,
+                            },
+                        },
+                    }],
+                });
+            }
+        }
+        Ok(Self {
+            schema: "https://json.schemastore.org/sarif-2.1.0.json",
+            version: "2.1.0",
+            runs: vec![SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: "cargo-llvm-cov",
+                        information_uri: "https://github.com/taiki-e/cargo-llvm-cov",
+                        rules: vec![SarifRule {
+                            id: SARIF_RULE_ID,
+                            short_description: SarifMessage {
+                                text: "Region is not covered by tests".to_owned(),
+                            },
+                        }],
+                    },
+                },
+                results,
+            }],
+        })
+    }
+}
+
+/// Converts a file name to a URI reference. Absolute paths are converted to `file:` URIs
+/// (GitHub code scanning makes them relative to the repository checkout), and relative paths
+/// (e.g., when --remap-path-prefix is used) are kept relative.
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::with_capacity(path.len());
+    let windows_drive = path.as_bytes().get(1) == Some(&b':');
+    if path.starts_with('/') {
+        uri.push_str("file://");
+    } else if windows_drive {
+        uri.push_str("file:///");
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char);
+            }
+            b':' if windows_drive => uri.push(':'),
+            _ => {
+                let _ = write!(uri, "%{b:02X}");
+            }
+        }
+    }
+    uri
+}
+
+/// Compiles the regex passed to llvm-cov by `-ignore-filename-regex`, to filter the files in
+/// the JSON export in the same way.
+fn compile_ignore_filename_regex(ignore_filename_regex: Option<&str>) -> Result<Option<Regex>> {
+    ignore_filename_regex
+        .map(|s| Regex::new(s).with_context(|| format!("invalid ignore-filename-regex `{s}`")))
+        .transpose()
+}
+
+/// Returns the uncovered code regions of each file.
+fn uncovered_regions(
+    value: &LlvmCovJsonExport,
+    ignore_filename_regex: Option<&str>,
+) -> Result<BTreeMap<String, BTreeSet<RegionLocation>>> {
+    let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+    // filename -> region location -> covered
+    let mut regions: BTreeMap<&str, HashMap<RegionLocation, bool>> = BTreeMap::new();
+    for data in &value.data {
+        let Some(ref functions) = data.functions else { continue };
+        for function in functions {
+            for region in function.regions.iter().filter(|r| r.kind() == CODE_REGION) {
+                let Some(filename) = function.filenames.get(region.file_id() as usize) else {
+                    continue;
+                };
+                if let Some(re) = &re {
+                    if re.is_match(filename) {
+                        continue;
+                    }
+                }
+                let covered =
+                    regions.entry(filename).or_default().entry(region.into()).or_default();
+                *covered = *covered || region.execution_count() > 0;
+            }
+        }
+    }
+
+    let mut files = BTreeMap::new();
+    for (filename, regions) in regions {
+        let uncovered: BTreeSet<_> =
+            regions.into_iter().filter(|&(_, covered)| !covered).map(|(loc, _)| loc).collect();
+        if !uncovered.is_empty() {
+            files.insert(filename.to_owned(), uncovered);
+        }
+    }
+    Ok(files)
+}
+
+/// Coverage in [Cobertura](https://cobertura.github.io/cobertura/) XML format.
+///
+/// Packages are crates and classes are files, named by the module path of the functions
+/// defined in them. Methods are functions, with instantiations of generic functions merged.
+pub struct CoberturaExport {
+    source: String,
+    timestamp: u64,
+    /// crate name -> file name -> class
+    packages: BTreeMap<String, BTreeMap<String, CoberturaClass>>,
+}
+
+struct CoberturaClass {
+    name: String,
+    lines: BTreeMap<u64, CoberturaLine>,
+    methods: Vec<CoberturaMethod>,
+}
+
+struct CoberturaMethod {
+    name: String,
+    lines: BTreeMap<u64, CoberturaLine>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct CoberturaLine {
+    hits: u64,
+    /// Number of conditions (two per branch) and covered conditions.
+    conditions: (u64, u64),
+}
+
+#[derive(Default, Clone, Copy)]
+struct CoberturaCounts {
+    lines: (u64, u64),
+    conditions: (u64, u64),
+}
+
+impl CoberturaCounts {
+    fn new<'a>(lines: impl IntoIterator<Item = &'a CoberturaLine>) -> Self {
+        let mut counts = Self::default();
+        for line in lines {
+            counts.add(Self { lines: (1, u64::from(line.hits > 0)), conditions: line.conditions });
+        }
+        counts
+    }
+
+    fn add(&mut self, other: Self) {
+        self.lines.0 += other.lines.0;
+        self.lines.1 += other.lines.1;
+        self.conditions.0 += other.conditions.0;
+        self.conditions.1 += other.conditions.1;
+    }
+
+    fn rates(self) -> String {
+        let rate = |(count, covered): (u64, u64)| percent(count, covered) / 100.;
+        format!(
+            "line-rate=\"{:.4}\" branch-rate=\"{:.4}\"",
+            rate(self.lines),
+            rate(self.conditions)
+        )
     }
 }
 
+impl CoberturaExport {
+    /// Creates a report of the given coverage data.
+    ///
+    /// File names are made relative to `source` (usually the workspace root).
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+        source: &str,
+        timestamp: u64,
+    ) -> Result<Self> {
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        // file name -> class
+        let mut classes: BTreeMap<&str, (Option<String>, CoberturaClass)> = BTreeMap::new();
+        for data in &value.data {
+            for file in &data.files {
+                if re.as_ref().is_some_and(|re| re.is_match(&file.filename)) {
+                    continue;
+                }
+                let (_, class) = classes.entry(&file.filename).or_insert_with(|| {
+                    (None, CoberturaClass {
+                        name: String::new(),
+                        lines: BTreeMap::new(),
+                        methods: vec![],
+                    })
+                });
+                if let Some(segments) = &file.segments {
+                    for (line, count) in line_execution_counts(segments) {
+                        class.lines.entry(line).or_default().hits += count;
+                    }
+                }
+                // [line start, column start, line end, column end, true count, false count, ...]
+                for branch in file.branches.iter().flatten() {
+                    let Some(branch) = branch.as_array() else { continue };
+                    let (Some(line), Some(t), Some(f)) = (
+                        branch.first().and_then(serde_json::Value::as_u64),
+                        branch.get(4).and_then(serde_json::Value::as_u64),
+                        branch.get(5).and_then(serde_json::Value::as_u64),
+                    ) else {
+                        continue;
+                    };
+                    let conditions = &mut class.lines.entry(line).or_default().conditions;
+                    conditions.0 += 2;
+                    conditions.1 += u64::from(t > 0) + u64::from(f > 0);
+                }
+            }
+        }
+        for group in value.function_groups(re.as_ref()) {
+            let Some((module, class)) = classes.get_mut(group.filename) else { continue };
+            let name = group.name();
+            let path = module_path(&name);
+            if module.as_ref().is_none_or(|module| path.len() < module.len()) {
+                *module = Some(path.to_owned());
+            }
+            // Use the line coverage of the file, so that the lines of methods are consistent
+            // with the lines of the class.
+            let mut lines = BTreeMap::new();
+            for function in &group.instantiations {
+                for region in
+                    function.regions.iter().filter(|r| r.kind() == CODE_REGION && r.file_id() == 0)
+                {
+                    lines.extend(
+                        class
+                            .lines
+                            .range(region.line_start()..=region.line_end())
+                            .map(|(&l, &c)| (l, c)),
+                    );
+                }
+            }
+            class.methods.push(CoberturaMethod { name, lines });
+        }
+
+        let mut packages: BTreeMap<String, BTreeMap<String, CoberturaClass>> = BTreeMap::new();
+        for (filename, (module, mut class)) in classes {
+            let filename = filename
+                .strip_prefix(source)
+                .and_then(|f| f.strip_prefix(['/', '\\']))
+                .unwrap_or(filename)
+                .to_owned();
+            let krate = match &module {
+                Some(module) => module.split_once("::").map_or(&**module, |(krate, _)| krate),
+                None => "(other)",
+            };
+            class.name = module.clone().unwrap_or_else(|| filename.clone());
+            packages.entry(krate.to_owned()).or_default().insert(filename, class);
+        }
+        Ok(Self { source: source.to_owned(), timestamp, packages })
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        fn write_lines(out: &mut String, indent: &str, lines: &BTreeMap<u64, CoberturaLine>) {
+            let _ = writeln!(out, "{indent}<lines>");
+            for (number, line) in lines {
+                let _ = write!(out, "{indent}  <line number=\"{number}\" hits=\"{}\"", line.hits);
+                let (count, covered) = line.conditions;
+                if count == 0 {
+                    out.push_str(" branch=\"false\"/>\n");
+                } else {
+                    let _ = writeln!(
+                        out,
+                        " branch=\"true\" condition-coverage=\"{:.0}% ({covered}/{count})\"/>",
+                        percent(count, covered)
+                    );
+                }
+            }
+            let _ = writeln!(out, "{indent}</lines>");
+        }
+
+        let mut total = CoberturaCounts::default();
+        let mut packages = String::new();
+        for (name, classes) in &self.packages {
+            let mut package_counts = CoberturaCounts::default();
+            let mut out = String::new();
+            for (filename, class) in classes {
+                let counts = CoberturaCounts::new(class.lines.values());
+                package_counts.add(counts);
+                let _ = writeln!(
+                    out,
+                    "        <class name=\"{}\" filename=\"{}\" {} complexity=\"0\">",
+                    xml_escape(&class.name),
+                    xml_escape(filename),
+                    counts.rates()
+                );
+                out.push_str("          <methods>\n");
+                for method in &class.methods {
+                    let _ = writeln!(
+                        out,
+                        "            <method name=\"{}\" signature=\"\" {} complexity=\"0\">",
+                        xml_escape(&method.name),
+                        CoberturaCounts::new(method.lines.values()).rates()
+                    );
+                    write_lines(&mut out, "              ", &method.lines);
+                    out.push_str("            </method>\n");
+                }
+                out.push_str("          </methods>\n");
+                write_lines(&mut out, "          ", &class.lines);
+                out.push_str("        </class>\n");
+            }
+            total.add(package_counts);
+            let _ = writeln!(
+                packages,
+                "    <package name=\"{}\" {} complexity=\"0\">",
+                xml_escape(name),
+                package_counts.rates()
+            );
+            packages.push_str("      <classes>\n");
+            packages.push_str(&out);
+            packages.push_str("      </classes>\n");
+            packages.push_str("    </package>\n");
+        }
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" ?>\n");
+        out.push_str(
+            "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n",
+        );
+        let _ = writeln!(
+            out,
+            "<coverage lines-valid=\"{}\" lines-covered=\"{}\" branches-valid=\"{}\" \
+             branches-covered=\"{}\" {} complexity=\"0\" version=\"{}\" timestamp=\"{}\">",
+            total.lines.0,
+            total.lines.1,
+            total.conditions.0,
+            total.conditions.1,
+            total.rates(),
+            env!("CARGO_PKG_VERSION"),
+            self.timestamp
+        );
+        let _ = writeln!(
+            out,
+            "  <sources>\n    <source>{}</source>\n  </sources>",
+            xml_escape(&self.source)
+        );
+        out.push_str("  <packages>\n");
+        out.push_str(&packages);
+        out.push_str("  </packages>\n");
+        out.push_str("</coverage>\n");
+        out
+    }
+}
+
+/// Coverage in [JaCoCo](https://www.jacoco.org/jacoco/trunk/doc/) XML format.
+///
+/// This has the same structure as [`CoberturaExport`]: packages are crates, classes and source
+/// files are files, and methods are functions.
+pub struct JacocoExport {
+    cobertura: CoberturaExport,
+}
+
+#[derive(Default, Clone, Copy)]
+struct JacocoCounters {
+    lines: CoberturaCounts,
+    methods: (u64, u64),
+    classes: (u64, u64),
+}
+
+impl JacocoCounters {
+    fn add(&mut self, other: Self) {
+        self.lines.add(other.lines);
+        self.methods.0 += other.methods.0;
+        self.methods.1 += other.methods.1;
+        self.classes.0 += other.classes.0;
+        self.classes.1 += other.classes.1;
+    }
+
+    fn write(self, out: &mut String, indent: &str) {
+        for (kind, (count, covered)) in [
+            ("LINE", self.lines.lines),
+            ("BRANCH", self.lines.conditions),
+            ("METHOD", self.methods),
+            ("CLASS", self.classes),
+        ] {
+            if count != 0 {
+                let _ = writeln!(
+                    out,
+                    "{indent}<counter type=\"{kind}\" missed=\"{}\" covered=\"{covered}\"/>",
+                    count - covered
+                );
+            }
+        }
+    }
+}
+
+impl JacocoExport {
+    /// Creates a report of the given coverage data.
+    ///
+    /// File names are made relative to `source` (usually the workspace root), and the report is
+    /// named after its last component.
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+        source: &str,
+        timestamp: u64,
+    ) -> Result<Self> {
+        Ok(Self {
+            cobertura: CoberturaExport::from_llvm_cov_json_export(
+                value,
+                ignore_filename_regex,
+                source,
+                timestamp,
+            )?,
+        })
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let is_covered = |lines: &BTreeMap<u64, CoberturaLine>| lines.values().any(|l| l.hits > 0);
+
+        let mut total = JacocoCounters::default();
+        let mut packages = String::new();
+        for (name, classes) in &self.cobertura.packages {
+            let mut package_counters = JacocoCounters::default();
+            let mut sourcefiles = String::new();
+            let _ = writeln!(packages, "  <package name=\"{}\">", xml_escape(name));
+            for (filename, class) in classes {
+                let mut counters = JacocoCounters {
+                    lines: CoberturaCounts::new(class.lines.values()),
+                    methods: (0, 0),
+                    classes: (1, u64::from(is_covered(&class.lines))),
+                };
+                let _ = writeln!(
+                    packages,
+                    "    <class name=\"{}\" sourcefilename=\"{}\">",
+                    xml_escape(&class.name),
+                    xml_escape(filename)
+                );
+                for method in &class.methods {
+                    let method_counters = JacocoCounters {
+                        lines: CoberturaCounts::new(method.lines.values()),
+                        methods: (1, u64::from(is_covered(&method.lines))),
+                        classes: (0, 0),
+                    };
+                    counters.methods.0 += 1;
+                    counters.methods.1 += method_counters.methods.1;
+                    let _ = write!(
+                        packages,
+                        "      <method name=\"{}\" desc=\"()\"",
+                        xml_escape(&method.name)
+                    );
+                    if let Some(line) = method.lines.keys().next() {
+                        let _ = write!(packages, " line=\"{line}\"");
+                    }
+                    packages.push_str(">\n");
+                    method_counters.write(&mut packages, "        ");
+                    packages.push_str("      </method>\n");
+                }
+                counters.write(&mut packages, "      ");
+                packages.push_str("    </class>\n");
+
+                let _ = writeln!(sourcefiles, "    <sourcefile name=\"{}\">", xml_escape(filename));
+                for (number, line) in &class.lines {
+                    let (count, covered) = line.conditions;
+                    let _ = writeln!(
+                        sourcefiles,
+                        "      <line nr=\"{number}\" mi=\"{}\" ci=\"{}\" mb=\"{}\" cb=\"{covered}\"/>",
+                        u64::from(line.hits == 0),
+                        u64::from(line.hits > 0),
+                        count - covered
+                    );
+                }
+                counters.write(&mut sourcefiles, "      ");
+                sourcefiles.push_str("    </sourcefile>\n");
+                package_counters.add(counters);
+            }
+            packages.push_str(&sourcefiles);
+            package_counters.write(&mut packages, "    ");
+            packages.push_str("  </package>\n");
+            total.add(package_counters);
+        }
+
+        let source = &self.cobertura.source;
+        let name = source.rsplit(['/', '\\']).find(|s| !s.is_empty()).unwrap_or(source);
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+        out.push_str("<!DOCTYPE report PUBLIC \"-//JACOCO//DTD Report 1.1//EN\" \"report.dtd\">\n");
+        let _ = writeln!(out, "<report name=\"{}\">", xml_escape(name));
+        // JaCoCo timestamps are in milliseconds.
+        let timestamp = self.cobertura.timestamp * 1000;
+        let _ = writeln!(
+            out,
+            "  <sessioninfo id=\"cargo-llvm-cov\" start=\"{timestamp}\" dump=\"{timestamp}\"/>"
+        );
+        out.push_str(&packages);
+        total.write(&mut out, "  ");
+        out.push_str("</report>\n");
+        out
+    }
+}
+
+/// Coverage in SonarQube's [generic test coverage](https://docs.sonarsource.com/sonarqube/latest/analyzing-source-code/test-coverage/generic-test-data/)
+/// XML format.
+pub struct SonarQubeExport {
+    cobertura: CoberturaExport,
+}
+
+impl SonarQubeExport {
+    /// Creates a report of the given coverage data.
+    ///
+    /// File names are made relative to `source` (usually the workspace root).
+    pub fn from_llvm_cov_json_export(
+        value: &LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+        source: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            cobertura: CoberturaExport::from_llvm_cov_json_export(
+                value,
+                ignore_filename_regex,
+                source,
+                0,
+            )?,
+        })
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<coverage version=\"1\">\n");
+        for (filename, class) in self.cobertura.packages.values().flatten() {
+            let _ = writeln!(out, "  <file path=\"{}\">", xml_escape(filename));
+            for (number, line) in &class.lines {
+                let _ = write!(
+                    out,
+                    "    <lineToCover lineNumber=\"{number}\" covered=\"{}\"",
+                    line.hits > 0
+                );
+                let (count, covered) = line.conditions;
+                if count != 0 {
+                    let _ =
+                        write!(out, " branchesToCover=\"{count}\" coveredBranches=\"{covered}\"");
+                }
+                out.push_str("/>\n");
+            }
+            out.push_str("  </file>\n");
+        }
+        out.push_str("</coverage>\n");
+        out
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Groups sorted lines into ranges of consecutive lines.
+fn line_ranges(lines: &[u64]) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = vec![];
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+}
+
 /// Files -> list of uncovered lines.
 type UncoveredLines = BTreeMap<String, Vec<u64>>;
 
+/// The name and location of a function.
+///
+/// Instantiations of the same generic function share the name and location. This is flattened
+/// into the reports of functions in JSON, and displayed as `name (filename:line)`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FunctionLocation {
+    /// Demangled name of the function, without generic arguments.
+    pub name: String,
+    /// File the function is defined in.
+    pub filename: String,
+    /// Line the function starts at.
+    pub line: u64,
+}
+
+impl FunctionLocation {
+    /// Compares the locations by file name and line, ignoring the name.
+    fn cmp_position(&self, other: &Self) -> Ordering {
+        self.filename.cmp(&other.filename).then(self.line.cmp(&other.line))
+    }
+}
+
+impl fmt::Display for FunctionLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}:{})", self.name, self.filename, self.line)
+    }
+}
+
+/// Uncovered lines of a single function.
+#[derive(Debug, PartialEq)]
+pub struct FunctionUncoveredLines {
+    pub location: FunctionLocation,
+    /// Lines of the function with no coverage.
+    pub uncovered_lines: Vec<u64>,
+}
+
+/// A function that is fully or mostly uncovered.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DebtFunction {
+    #[serde(flatten)]
+    pub location: FunctionLocation,
+    /// Number of code regions in the function.
+    pub regions: u64,
+    /// Number of code regions with no coverage.
+    pub uncovered_regions: u64,
+}
+
+/// A function that is not executed at all, with the crate it belongs to.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UncoveredFunction {
+    /// Name of the crate, which is the first segment of the module path of the function.
+    #[serde(rename = "crate")]
+    pub krate: String,
+    #[serde(flatten)]
+    pub location: FunctionLocation,
+    /// Number of code regions in the function.
+    pub regions: u64,
+}
+
+impl UncoveredFunction {
+    pub const FIELDS: [&'static str; 5] = ["crate", "name", "filename", "line", "regions"];
+
+    /// Returns the function as a record of [`Self::FIELDS`] separated by the given separator
+    /// (`,` for CSV or `\t` for TSV), without a trailing newline.
+    #[must_use]
+    pub fn to_record(&self, separator: char) -> String {
+        [
+            escape_field(&self.krate, separator),
+            escape_field(&self.location.name, separator),
+            escape_field(&self.location.filename, separator),
+            self.location.line.to_string(),
+            self.regions.to_string(),
+        ]
+        .join(separator.encode_utf8(&mut [0; 4]))
+    }
+}
+
+/// Escapes a field of a CSV (if `separator` is `,`) or TSV (if `separator` is `\t`) record.
+///
+/// CSV fields that contain special characters are quoted. TSV fields cannot contain tabs or
+/// newlines, so they are replaced with spaces.
+#[must_use]
+pub fn escape_field(s: &str, separator: char) -> String {
+    if separator == '\t' {
+        s.replace(['\t', '\n', '\r'], " ")
+    } else if s.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Coverage of a file, of the functions in a module, or of the methods of a type.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct GroupCoverage {
+    /// File name, module path such as `crate::net::tcp`, or type path such as
+    /// `crate::net::Pool`.
+    pub name: String,
+    pub functions: u64,
+    pub covered_functions: u64,
+    /// Number of lines of code regions in the functions.
+    pub lines: u64,
+    pub covered_lines: u64,
+    pub regions: u64,
+    pub covered_regions: u64,
+    /// Number of branch outcomes (two per branch) in the functions.
+    pub branches: u64,
+    pub covered_branches: u64,
+}
+
+/// The most executed functions and lines.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Hotspots {
+    pub functions: Vec<HotFunction>,
+    pub lines: Vec<HotLine>,
+}
+
+/// A function with its execution count.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HotFunction {
+    #[serde(flatten)]
+    pub location: FunctionLocation,
+    /// Number of times the function is executed, summed over all instantiations.
+    pub count: u64,
+}
+
+/// A function with its execution count and region coverage.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FunctionCoverage {
+    #[serde(flatten)]
+    pub location: FunctionLocation,
+    /// Number of times the function is executed, summed over all instantiations.
+    pub count: u64,
+    /// Number of code regions in the function.
+    pub regions: u64,
+    /// Number of code regions with coverage.
+    pub covered_regions: u64,
+    /// Coverage of each instantiation (e.g., of generic functions), if the function has more
+    /// than one instantiation. Closures merged into the function are also listed here.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub instantiations: Vec<InstantiationCoverage>,
+}
+
+/// An instantiation of a function with its execution count and region coverage.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InstantiationCoverage {
+    /// Demangled name of the instantiation, with generic arguments.
+    pub name: String,
+    /// Number of times the instantiation is executed.
+    pub count: u64,
+    /// Number of code regions in the instantiation.
+    pub regions: u64,
+    /// Number of code regions covered by the instantiation.
+    pub covered_regions: u64,
+}
+
+/// A line with its execution count.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HotLine {
+    pub filename: String,
+    pub line: u64,
+    /// Largest execution count of the code regions starting at the line.
+    pub count: u64,
+}
+
 #[non_exhaustive]
 #[derive(Clone, Copy)]
 #[cfg_attr(test, derive(Debug))]
@@ -154,6 +1113,7 @@ pub enum CoverageKind {
     Functions,
     Lines,
     Regions,
+    Branches,
 }
 
 impl CoverageKind {
@@ -162,6 +1122,7 @@ impl CoverageKind {
             Self::Functions => "functions",
             Self::Lines => "lines",
             Self::Regions => "regions",
+            Self::Branches => "branches",
         }
     }
 }
@@ -177,13 +1138,35 @@ impl LlvmCovJsonExport {
         }
     }
 
+    /// Merges closures and async blocks into their enclosing functions in function-level
+    /// reports, such as [`Self::get_coverage_debt`].
+    pub fn group_closures(&mut self) {
+        self.group_closures = true;
+    }
+
     pub fn inject(&mut self, manifest_path: Utf8PathBuf) {
         self.cargo_llvm_cov = Some(CargoLlvmCov {
             version: env!("CARGO_PKG_VERSION"),
             manifest_path: manifest_path.into_string(),
+            complexity_weighted_coverage: None,
+            metadata: None,
         });
     }
 
+    /// Injects information about the run that generated the coverage data.
+    pub fn inject_metadata(&mut self, metadata: RunMetadata) {
+        if let Some(cargo_llvm_cov) = &mut self.cargo_llvm_cov {
+            cargo_llvm_cov.metadata = Some(metadata);
+        }
+    }
+
+    /// Gets the total coverage, in the same format as `totals` of the JSON report.
+    #[must_use]
+    pub fn get_totals(&self) -> Option<&serde_json::Value> {
+        // llvm-cov export always outputs a single export object.
+        self.data.first().map(|data| &data.totals)
+    }
+
     /// Gets the minimal lines coverage of all files.
     pub fn get_coverage_percent(&self, kind: CoverageKind) -> Result<f64> {
         let mut count = 0_f64;
@@ -203,12 +1186,283 @@ impl LlvmCovJsonExport {
         Ok(covered * 100_f64 / count)
     }
 
-    /// Gets the list of uncovered lines of all files.
+    /// Excludes the given lines of each file from the line coverage.
+    ///
+    /// Code regions are trimmed so that they do not start or end at excluded lines, and
+    /// removed if they only span excluded lines, so that excluded lines are not reported as
+    /// uncovered.
+    pub fn exclude_lines(&mut self, excluded: &BTreeMap<String, BTreeSet<u64>>) -> Result<()> {
+        for data in &mut self.data {
+            // Number of excluded lines and excluded covered lines.
+            let mut total_removed = (0, 0);
+            for file in &mut data.files {
+                let Some(lines) = excluded.get(&file.filename) else { continue };
+                let segments = file
+                    .segments
+                    .as_deref()
+                    .context("excluding lines requires per-file coverage data")?;
+                let mut removed = (0, 0);
+                for (line, count) in line_execution_counts(segments) {
+                    if lines.contains(&line) {
+                        removed.0 += 1;
+                        removed.1 += u64::from(count != 0);
+                    }
+                }
+                let lines = &mut file.summary.lines;
+                lines.count -= removed.0;
+                lines.covered -= removed.1;
+                lines.percent = percent(lines.count, lines.covered);
+                total_removed.0 += removed.0;
+                total_removed.1 += removed.1;
+            }
+            if let Some(functions) = &mut data.functions {
+                for function in functions {
+                    let filenames = &function.filenames;
+                    function.regions.retain_mut(|region| {
+                        let Some(lines) = usize::try_from(region.file_id())
+                            .ok()
+                            .and_then(|id| filenames.get(id))
+                            .and_then(|filename| excluded.get(filename))
+                        else {
+                            return true;
+                        };
+                        if !lines.contains(&region.line_start())
+                            && !lines.contains(&region.line_end())
+                        {
+                            return true;
+                        }
+                        let range = region.line_start()..=region.line_end();
+                        let (Some(start), Some(end)) = (
+                            range.clone().find(|line| !lines.contains(line)),
+                            range.rev().find(|line| !lines.contains(line)),
+                        ) else {
+                            return false;
+                        };
+                        if start != region.line_start() {
+                            (region.0, region.1) = (start, 1);
+                        }
+                        if end != region.line_end() {
+                            // Use the largest column so that the region covers the whole line.
+                            (region.2, region.3) = (end, u64::from(u32::MAX));
+                        }
+                        true
+                    });
+                }
+            }
+            let totals = data.totals.as_object_mut().context("totals is not an object")?;
+            let lines = totals
+                .get_mut("lines")
+                .and_then(serde_json::Value::as_object_mut)
+                .context("no lines")?;
+            let count =
+                lines["count"].as_u64().context("no count")?.saturating_sub(total_removed.0);
+            let covered =
+                lines["covered"].as_u64().context("no covered")?.saturating_sub(total_removed.1);
+            lines.insert("count".to_owned(), count.into());
+            lines.insert("covered".to_owned(), covered.into());
+            lines.insert("percent".to_owned(), percent(count, covered).into());
+        }
+        Ok(())
+    }
+
+    /// Merges the coverage of files in `history` that are not in this export and for which
+    /// `keep` returns `true` into this export.
+    pub fn merge_history(&mut self, history: Self, keep: impl Fn(&str) -> bool) -> Result<()> {
+        let current: BTreeSet<_> =
+            self.data.iter().flat_map(|data| &data.files).map(|f| f.filename.clone()).collect();
+        let keep = |filename: &str| !current.contains(filename) && keep(filename);
+        let Some(data) = self.data.first_mut() else { return Ok(()) };
+        let totals = data.totals.as_object_mut().context("totals is not an object")?;
+        for old in history.data {
+            for file in old.files {
+                if !keep(&file.filename) {
+                    continue;
+                }
+                update_totals(totals, &file.summary, true)?;
+                data.files.push(file);
+            }
+            if let (Some(functions), Some(old)) = (&mut data.functions, old.functions) {
+                functions.extend(
+                    old.into_iter().filter(|f| f.filenames.first().is_some_and(|f| keep(f))),
+                );
+            }
+        }
+        data.files.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(())
+    }
+
+    /// Merges the coverage of each file in `renames` into the file it is mapped to, for the
+    /// same file that appears in the report under multiple paths.
+    ///
+    /// Execution counts of the same lines, regions, and branches are summed up. Files in
+    /// summary-only export, which does not contain per-file coverage data, are not merged.
+    pub fn merge_files(&mut self, renames: &BTreeMap<String, String>) -> Result<()> {
+        for data in &mut self.data {
+            if data.files.iter().any(|file| file.segments.is_none()) {
+                continue;
+            }
+            let functions = data.functions.as_deref_mut().unwrap_or_default();
+            for function in &mut *functions {
+                for filename in &mut function.filenames {
+                    if let Some(to) = renames.get(filename) {
+                        filename.clone_from(to);
+                    }
+                }
+            }
+            let mut groups: BTreeMap<String, Vec<File>> = BTreeMap::new();
+            for mut file in std::mem::take(&mut data.files) {
+                if let Some(to) = renames.get(&file.filename) {
+                    file.filename.clone_from(to);
+                }
+                groups.entry(file.filename.clone()).or_default().push(file);
+            }
+            let totals = data.totals.as_object_mut().context("totals is not an object")?;
+            for (filename, files) in groups {
+                if files.len() == 1 {
+                    data.files.extend(files);
+                    continue;
+                }
+                for file in &files {
+                    update_totals(totals, &file.summary, false)?;
+                }
+                let file_functions: Vec<_> =
+                    functions.iter().filter(|f| f.filenames.first() == Some(&filename)).collect();
+                let file = merge_files(filename, &files, &file_functions);
+                update_totals(totals, &file.summary, true)?;
+                data.files.push(file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the number of lines and the number of covered lines of each file.
     #[must_use]
-    pub fn get_uncovered_lines(&self, ignore_filename_regex: Option<&str>) -> UncoveredLines {
+    pub fn get_line_counts(&self) -> BTreeMap<&str, (u64, u64)> {
+        let mut counts = BTreeMap::new();
+        for data in &self.data {
+            for file in &data.files {
+                let (count, covered) = counts.entry(file.filename.as_str()).or_insert((0, 0));
+                *count += file.summary.lines.count;
+                *covered += file.summary.lines.covered;
+            }
+        }
+        counts
+    }
+
+    /// Gets the execution count of each line that has coverage mapping, for each file.
+    ///
+    /// Files are omitted if the report is summary-only.
+    #[must_use]
+    pub fn get_line_execution_counts(&self) -> BTreeMap<&str, BTreeMap<u64, u64>> {
+        let mut counts: BTreeMap<&str, BTreeMap<u64, u64>> = BTreeMap::new();
+        for data in &self.data {
+            for file in &data.files {
+                let Some(segments) = &file.segments else { continue };
+                let lines = counts.entry(file.filename.as_str()).or_default();
+                for (line, count) in line_execution_counts(segments) {
+                    *lines.entry(line).or_insert(0) += count;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Converts the coverage data to LCOV format, in the same way as `llvm-cov export -format=lcov`.
+    ///
+    /// Only the summaries are written if the report is summary-only.
+    // https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/tools/llvm-cov/CoverageExporterLcov.cpp
+    #[must_use]
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for data in &self.data {
+            for file in &data.files {
+                let _ = writeln!(out, "SF:{}", file.filename);
+                let Some(segments) = &file.segments else {
+                    let summary = &file.summary;
+                    let _ = writeln!(out, "FNF:{}", summary.functions.count);
+                    let _ = writeln!(out, "FNH:{}", summary.functions.covered);
+                    let _ = writeln!(out, "BRF:{}", summary.branches.count);
+                    let _ = writeln!(out, "BRH:{}", summary.branches.covered);
+                    let _ = writeln!(out, "LF:{}", summary.lines.count);
+                    let _ = writeln!(out, "LH:{}", summary.lines.covered);
+                    out.push_str("end_of_record\n");
+                    continue;
+                };
+
+                let functions: Vec<_> = data
+                    .functions
+                    .iter()
+                    .flatten()
+                    .filter(|f| f.filenames.first() == Some(&file.filename))
+                    .filter(|f| !f.regions.is_empty())
+                    .collect();
+                for function in &functions {
+                    let _ =
+                        writeln!(out, "FN:{},{}", function.regions[0].line_start(), function.name);
+                }
+                for function in &functions {
+                    let _ = writeln!(out, "FNDA:{},{}", function.count, function.name);
+                }
+                let _ = writeln!(out, "FNF:{}", functions.len());
+                let _ = writeln!(out, "FNH:{}", functions.iter().filter(|f| f.count > 0).count());
+
+                // line -> [(true count, false count)]
+                let mut branches: BTreeMap<u64, Vec<(u64, u64)>> = BTreeMap::new();
+                // [line start, column start, line end, column end, true count, false count, ...]
+                for branch in file.branches.iter().flatten() {
+                    let Some(branch) = branch.as_array() else { continue };
+                    let (Some(line), Some(t), Some(f)) = (
+                        branch.first().and_then(serde_json::Value::as_u64),
+                        branch.get(4).and_then(serde_json::Value::as_u64),
+                        branch.get(5).and_then(serde_json::Value::as_u64),
+                    ) else {
+                        continue;
+                    };
+                    branches.entry(line).or_default().push((t, f));
+                }
+                let (mut branch_count, mut branch_hit) = (0, 0);
+                for (line, branches) in &branches {
+                    // The taken count is `-` if neither side of the branch was executed.
+                    let counts = branches.iter().flat_map(|&(t, f)| {
+                        let executed = t > 0 || f > 0;
+                        [executed.then_some(t), executed.then_some(f)]
+                    });
+                    for (i, count) in counts.enumerate() {
+                        match count {
+                            Some(count) => {
+                                let _ = writeln!(out, "BRDA:{line},0,{i},{count}");
+                            }
+                            None => {
+                                let _ = writeln!(out, "BRDA:{line},0,{i},-");
+                            }
+                        }
+                        branch_count += 1;
+                        branch_hit += u64::from(count.is_some_and(|count| count > 0));
+                    }
+                }
+                let _ = writeln!(out, "BRF:{branch_count}");
+                let _ = writeln!(out, "BRH:{branch_hit}");
+
+                let lines = line_execution_counts(segments);
+                for (line, count) in &lines {
+                    let _ = writeln!(out, "DA:{line},{count}");
+                }
+                let _ = writeln!(out, "LF:{}", lines.len());
+                let _ = writeln!(out, "LH:{}", lines.values().filter(|&&count| count > 0).count());
+                out.push_str("end_of_record\n");
+            }
+        }
+        out
+    }
+
+    /// Gets the list of uncovered lines of all files.
+    pub fn get_uncovered_lines(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<UncoveredLines> {
         let mut uncovered_files: UncoveredLines = BTreeMap::new();
         let mut covered_files: UncoveredLines = BTreeMap::new();
-        let re = ignore_filename_regex.map(|s| Regex::new(s).unwrap());
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
         for data in &self.data {
             if let Some(ref functions) = data.functions {
                 // Iterate over all functions inside the coverage data.
@@ -277,7 +1531,414 @@ impl LlvmCovJsonExport {
         // Remove empty keys.
         uncovered_files.retain(|_, v| !v.is_empty());
 
-        uncovered_files
+        Ok(uncovered_files)
+    }
+
+    /// Gets the list of uncovered lines grouped by function.
+    ///
+    /// Instantiations of the same generic function are merged into one entry.
+    /// Functions are sorted in descending order of the number of uncovered lines.
+    pub fn get_uncovered_lines_by_function(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<FunctionUncoveredLines>> {
+        // Lines that are covered by another function (e.g., a line with a macro that expands
+        // to multiple functions) are not treated as uncovered, as in get_uncovered_lines.
+        let uncovered_files = self.get_uncovered_lines(ignore_filename_regex)?;
+        let mut uncovered_functions: Vec<_> = self
+            .function_groups(None)
+            .into_iter()
+            .filter(|group| uncovered_files.contains_key(group.filename))
+            .filter_map(|group| {
+                let mut lines: BTreeMap<u64, u64> = BTreeMap::new();
+                for function in &group.instantiations {
+                    // Regions with other file IDs are in the files of the macros expanded in
+                    // the function, not in `group.filename`.
+                    for region in function.regions.iter().filter(|r| r.file_id() == 0) {
+                        for line in region.line_start()..=region.line_end() {
+                            *lines.entry(line).or_insert(0) += region.execution_count();
+                        }
+                    }
+                }
+                let uncovered_lines_in_file = &uncovered_files[group.filename];
+                let uncovered_lines: Vec<u64> = lines
+                    .into_iter()
+                    .filter(|&(line, exec_count)| {
+                        exec_count == 0 && uncovered_lines_in_file.binary_search(&line).is_ok()
+                    })
+                    .map(|(line, _exec_count)| line)
+                    .collect();
+                if uncovered_lines.is_empty() {
+                    return None;
+                }
+                Some(FunctionUncoveredLines { location: group.location(), uncovered_lines })
+            })
+            .collect();
+        uncovered_functions.sort_by(|a, b| {
+            b.uncovered_lines
+                .len()
+                .cmp(&a.uncovered_lines.len())
+                .then_with(|| a.location.cmp_position(&b.location))
+        });
+        Ok(uncovered_functions)
+    }
+
+    /// Gets the region coverage where each function is weighted by its complexity.
+    ///
+    /// The complexity of a function is the number of its decision points plus one
+    /// (similar to cyclomatic complexity). If branch coverage is not enabled, the number
+    /// of decision points is approximated by the number of code regions minus one.
+    pub fn get_complexity_weighted_coverage_percent(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<f64> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("complexity-weighted coverage requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut weighted_covered = 0_f64;
+        let mut total_complexity = 0_f64;
+        for group in self.function_groups(re.as_ref()) {
+            let (count, covered) = group.count_code_regions();
+            if count == 0 {
+                continue;
+            }
+            let branches =
+                group.instantiations.iter().map(|function| function.branches.len()).max();
+            let decisions = match branches {
+                Some(branches) if branches != 0 => branches as u64,
+                _ => count - 1,
+            };
+            let complexity = (decisions + 1) as f64;
+            weighted_covered += complexity * covered as f64 / count as f64;
+            total_complexity += complexity;
+        }
+
+        if total_complexity == 0_f64 {
+            return Ok(0_f64);
+        }
+
+        Ok(weighted_covered * 100_f64 / total_complexity)
+    }
+
+    /// Gets the functions that are fully or mostly (less than half of the regions are
+    /// covered) uncovered, sorted in descending order of the number of uncovered regions.
+    pub fn get_coverage_debt(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<DebtFunction>> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("coverage debt report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut debt: Vec<_> = self
+            .function_groups(re.as_ref())
+            .into_iter()
+            .filter_map(|group| {
+                let (regions, covered) = group.count_code_regions();
+                if regions == 0 || covered * 2 >= regions {
+                    return None;
+                }
+                Some(DebtFunction {
+                    location: group.location(),
+                    regions,
+                    uncovered_regions: regions - covered,
+                })
+            })
+            .collect();
+        debt.sort_by(|a, b| {
+            b.uncovered_regions
+                .cmp(&a.uncovered_regions)
+                .then(b.regions.cmp(&a.regions))
+                .then_with(|| a.location.cmp_position(&b.location))
+        });
+        Ok(debt)
+    }
+
+    /// Gets the functions that none of the instantiations are executed, sorted by location.
+    pub fn get_unexecuted_functions(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<FunctionLocation>> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("unexecuted functions report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        Ok(self
+            .function_groups(re.as_ref())
+            .into_iter()
+            .filter(|group| group.instantiations.iter().all(|function| function.count == 0))
+            .map(|group| group.location())
+            .collect())
+    }
+
+    /// Gets the functions that none of the instantiations are executed, grouped by crate and
+    /// sorted in descending order of the number of code regions in each crate.
+    pub fn get_uncovered_functions(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<UncoveredFunction>> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("uncovered functions report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut functions: Vec<_> = self
+            .function_groups(re.as_ref())
+            .into_iter()
+            .filter(|group| group.instantiations.iter().all(|function| function.count == 0))
+            .map(|group| {
+                let location = group.location();
+                let module = module_path(&location.name);
+                let krate = module.split_once("::").map_or(module, |(krate, _)| krate).to_owned();
+                UncoveredFunction { krate, location, regions: group.count_code_regions().0 }
+            })
+            .collect();
+        functions.sort_by(|a, b| {
+            a.krate
+                .cmp(&b.krate)
+                .then(b.regions.cmp(&a.regions))
+                .then_with(|| a.location.cmp_position(&b.location))
+        });
+        Ok(functions)
+    }
+
+    /// Gets file name -> names of the functions that any of the instantiations are executed.
+    pub fn get_executed_functions(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("executed functions report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut files: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for group in self.function_groups(re.as_ref()) {
+            if group.instantiations.iter().any(|function| function.count > 0) {
+                files.entry(group.filename.to_owned()).or_default().insert(group.name());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Gets the coverage of each file, sorted by file name.
+    #[must_use]
+    pub fn get_file_coverage(&self) -> Vec<GroupCoverage> {
+        let mut files: BTreeMap<&str, GroupCoverage> = BTreeMap::new();
+        for data in &self.data {
+            for file in &data.files {
+                let f = files.entry(&file.filename).or_insert_with(|| GroupCoverage {
+                    name: file.filename.clone(),
+                    functions: 0,
+                    covered_functions: 0,
+                    lines: 0,
+                    covered_lines: 0,
+                    regions: 0,
+                    covered_regions: 0,
+                    branches: 0,
+                    covered_branches: 0,
+                });
+                let summary = &file.summary;
+                f.functions += summary.functions.count;
+                f.covered_functions += summary.functions.covered;
+                f.lines += summary.lines.count;
+                f.covered_lines += summary.lines.covered;
+                f.regions += summary.regions.count;
+                f.covered_regions += summary.regions.covered;
+                f.branches += summary.branches.count;
+                f.covered_branches += summary.branches.covered;
+            }
+        }
+        files.into_values().collect()
+    }
+
+    /// Gets the coverage of each module, sorted by module path.
+    ///
+    /// Module paths are derived from the function names, and methods belong to the
+    /// module the type is defined in.
+    pub fn get_module_coverage(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<GroupCoverage>> {
+        self.get_group_coverage(ignore_filename_regex, |name| Some(module_path(name)))
+    }
+
+    /// Gets the coverage of the methods of each type, sorted by type path.
+    ///
+    /// Methods in inherent impls and trait impls of the same type are grouped together,
+    /// even if they are defined in different files. Free functions are not included.
+    pub fn get_type_coverage(
+        &self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<Vec<GroupCoverage>> {
+        self.get_group_coverage(ignore_filename_regex, self_type)
+    }
+
+    fn get_group_coverage(
+        &self,
+        ignore_filename_regex: Option<&str>,
+        group_name: impl Fn(&str) -> Option<&str>,
+    ) -> Result<Vec<GroupCoverage>> {
+        #[derive(Default)]
+        struct Counts<'a> {
+            functions: (u64, u64),
+            regions: (u64, u64),
+            branches: (u64, u64),
+            // (file name, line) -> covered
+            lines: BTreeMap<(&'a str, u64), bool>,
+        }
+
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("grouped coverage requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut groups: BTreeMap<String, Counts<'_>> = BTreeMap::new();
+        for group in self.function_groups(re.as_ref()) {
+            let name = group.name();
+            let Some(group_name) = group_name(&name) else { continue };
+            let counts = groups.entry(group_name.to_owned()).or_default();
+            counts.functions.0 += 1;
+            counts.functions.1 +=
+                u64::from(group.instantiations.iter().any(|function| function.count != 0));
+            let (regions, covered) = group.count_code_regions();
+            counts.regions.0 += regions;
+            counts.regions.1 += covered;
+            let (branches, covered) = group.count_branches();
+            counts.branches.0 += branches;
+            counts.branches.1 += covered;
+            for function in &group.instantiations {
+                for region in
+                    function.regions.iter().filter(|r| r.kind() == CODE_REGION && r.file_id() == 0)
+                {
+                    for line in region.line_start()..=region.line_end() {
+                        let covered = counts.lines.entry((group.filename, line)).or_default();
+                        *covered = *covered || region.execution_count() > 0;
+                    }
+                }
+            }
+        }
+        Ok(groups
+            .into_iter()
+            .map(|(name, counts)| GroupCoverage {
+                name,
+                functions: counts.functions.0,
+                covered_functions: counts.functions.1,
+                lines: counts.lines.len() as u64,
+                covered_lines: counts.lines.values().filter(|&&covered| covered).count() as u64,
+                regions: counts.regions.0,
+                covered_regions: counts.regions.1,
+                branches: counts.branches.0,
+                covered_branches: counts.branches.1,
+            })
+            .collect())
+    }
+
+    /// Gets up to `limit` most executed functions and lines, sorted in descending
+    /// order of the execution count.
+    pub fn get_hotspots(
+        &self,
+        ignore_filename_regex: Option<&str>,
+        limit: usize,
+    ) -> Result<Hotspots> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("hotspots report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        let mut functions = vec![];
+        // (file name, line) -> execution count
+        let mut lines: BTreeMap<(&str, u64), u64> = BTreeMap::new();
+        for group in self.function_groups(re.as_ref()) {
+            let count = group.instantiations.iter().map(|function| function.count).sum();
+            if count == 0 {
+                continue;
+            }
+            functions.push(HotFunction { location: group.location(), count });
+            // Code regions are shared between instantiations, so sum the counts of the same
+            // region first.
+            let mut regions: HashMap<RegionLocation, u64> = HashMap::new();
+            for function in &group.instantiations {
+                for region in function.regions.iter().filter(|r| {
+                    r.kind() == CODE_REGION && r.file_id() == 0 && r.expanded_file_id() == 0
+                }) {
+                    *regions.entry(RegionLocation::from(region)).or_default() +=
+                        region.execution_count();
+                }
+            }
+            for (region, count) in regions {
+                let entry = lines.entry((group.filename, region.start_line)).or_default();
+                *entry = (*entry).max(count);
+            }
+        }
+        functions.sort_by(|a, b| {
+            b.count.cmp(&a.count).then_with(|| a.location.cmp_position(&b.location))
+        });
+        functions.truncate(limit);
+        let mut lines: Vec<_> = lines
+            .into_iter()
+            .filter(|&(_, count)| count != 0)
+            .map(|((filename, line), count)| HotLine { filename: filename.to_owned(), line, count })
+            .collect();
+        // The sort is stable, so lines with the same count remain sorted by location.
+        lines.sort_by(|a, b| b.count.cmp(&a.count));
+        lines.truncate(limit);
+        Ok(Hotspots { functions, lines })
+    }
+
+    /// Gets the functions with their execution counts and region coverage, sorted by location.
+    ///
+    /// If `filter` is given, only functions whose names match it are included.
+    pub fn get_function_coverage(
+        &self,
+        ignore_filename_regex: Option<&str>,
+        filter: Option<&Regex>,
+    ) -> Result<Vec<FunctionCoverage>> {
+        if self.data.iter().any(|data| data.functions.is_none()) {
+            bail!("function coverage report requires per-function coverage data");
+        }
+        let re = compile_ignore_filename_regex(ignore_filename_regex)?;
+        Ok(self
+            .function_groups(re.as_ref())
+            .into_iter()
+            .filter_map(|group| {
+                let location = group.location();
+                if filter.is_some_and(|filter| !filter.is_match(&location.name)) {
+                    return None;
+                }
+                let (regions, covered_regions) = group.count_code_regions();
+                let mut instantiations = vec![];
+                if group.instantiations.len() > 1 {
+                    for function in &group.instantiations {
+                        let (regions, covered_regions) = count_code_regions([*function]);
+                        instantiations.push(InstantiationCoverage {
+                            name: format!("{:#}", rustc_demangle::demangle(&function.name)),
+                            count: function.count,
+                            regions,
+                            covered_regions,
+                        });
+                    }
+                }
+                Some(FunctionCoverage {
+                    location,
+                    count: group.instantiations.iter().map(|function| function.count).sum(),
+                    regions,
+                    covered_regions,
+                    instantiations,
+                })
+            })
+            .collect())
+    }
+
+    /// Injects the complexity-weighted coverage into the additional information.
+    ///
+    /// See [`Self::get_complexity_weighted_coverage_percent`] for more.
+    pub fn inject_complexity_weighted_coverage(
+        &mut self,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<()> {
+        let percent = self.get_complexity_weighted_coverage_percent(ignore_filename_regex)?;
+        if let Some(cargo_llvm_cov) = &mut self.cargo_llvm_cov {
+            cargo_llvm_cov.complexity_weighted_coverage = Some(percent);
+        }
+        Ok(())
     }
 
     pub fn count_uncovered_functions(&self) -> Result<u64> {
@@ -304,21 +1965,436 @@ impl LlvmCovJsonExport {
         Ok(count.saturating_sub(covered))
     }
 
-    pub fn count_uncovered_regions(&self) -> Result<u64> {
-        let mut count = 0_u64;
-        let mut covered = 0_u64;
-        for data in &self.data {
-            let totals = &data.totals.as_object().context("totals is not an object")?;
-            let regions = &totals["regions"].as_object().context("no regions")?;
-            count += regions["count"].as_u64().context("no count")?;
-            covered += regions["covered"].as_u64().context("no covered")?;
+    pub fn count_uncovered_regions(&self) -> Result<u64> {
+        let mut count = 0_u64;
+        let mut covered = 0_u64;
+        for data in &self.data {
+            let totals = &data.totals.as_object().context("totals is not an object")?;
+            let regions = &totals["regions"].as_object().context("no regions")?;
+            count += regions["count"].as_u64().context("no count")?;
+            covered += regions["covered"].as_u64().context("no covered")?;
+        }
+        Ok(count.saturating_sub(covered))
+    }
+
+    /// Groups functions by their location, so that instantiations of the same
+    /// generic function are merged into one group.
+    fn function_groups(&self, ignore_filename_regex: Option<&Regex>) -> Vec<FunctionGroup<'_>> {
+        // (file name, line start, column start) -> instantiations
+        let mut groups: BTreeMap<(&str, u64, u64), Vec<&Function>> = BTreeMap::new();
+        for data in &self.data {
+            let Some(ref functions) = data.functions else { continue };
+            for function in functions {
+                let Some(file_name) = function.filenames.first() else { continue };
+                if let Some(re) = ignore_filename_regex {
+                    if re.is_match(file_name) {
+                        continue;
+                    }
+                }
+                let Some((line_start, column_start)) =
+                    function.regions.iter().map(|r| (r.line_start(), r.column_start())).min()
+                else {
+                    continue;
+                };
+                groups.entry((file_name, line_start, column_start)).or_default().push(function);
+            }
+        }
+        let groups = groups.into_iter().map(|((filename, line, _), instantiations)| {
+            FunctionGroup { filename, line, instantiations, closures_merged: self.group_closures }
+        });
+        if !self.group_closures {
+            return groups.collect();
+        }
+
+        // Handle the enclosing functions first, so that closures are merged into them.
+        let (closures, functions): (Vec<_>, Vec<_>) = groups.partition(|group| {
+            enclosing_function(&demangle_function_name(&group.instantiations[0].name)).1
+        });
+        let mut merged: Vec<FunctionGroup<'_>> = vec![];
+        // (file name, name of the enclosing function) -> index in `merged`
+        let mut indices = HashMap::new();
+        for mut group in functions.into_iter().chain(closures) {
+            match indices.entry((group.filename, group.name())) {
+                hash_map::Entry::Occupied(e) => {
+                    merged[*e.get()].instantiations.append(&mut group.instantiations);
+                }
+                hash_map::Entry::Vacant(e) => {
+                    e.insert(merged.len());
+                    merged.push(group);
+                }
+            }
+        }
+        merged.sort_by_key(|group| (group.filename, group.line));
+        merged
+    }
+}
+
+/// Returns line -> execution count of the lines that have coverage mapping.
+///
+/// This is the same as how llvm-cov computes the line coverage from the segments.
+// https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/lib/ProfileData/Coverage/CoverageMapping.cpp
+// (LineCoverageStats::LineCoverageStats)
+fn line_execution_counts(segments: &[Segment]) -> BTreeMap<u64, u64> {
+    fn is_start_of_region(segment: &&Segment) -> bool {
+        !segment.is_gap_region() && segment.has_count() && segment.is_region_entry()
+    }
+
+    let mut counts = BTreeMap::new();
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else { return counts };
+    let mut wrapped: Option<&Segment> = None;
+    let mut next = 0;
+    for line in first.line()..=last.line() {
+        let start = next;
+        while next < segments.len() && segments[next].line() == line {
+            next += 1;
+        }
+        let line_segments = &segments[start..next];
+        let start_of_skipped_region =
+            line_segments.first().is_some_and(|s| !s.has_count() && s.is_region_entry());
+        let mut region_counts =
+            line_segments.iter().filter(is_start_of_region).map(Segment::count).peekable();
+        let mapped = !start_of_skipped_region
+            && (wrapped.is_some_and(Segment::has_count) || region_counts.peek().is_some());
+        if mapped {
+            counts.insert(line, region_counts.fold(wrapped.map_or(0, Segment::count), u64::max));
+        }
+        if let Some(segment) = line_segments.last() {
+            wrapped = Some(segment);
+        }
+    }
+    counts
+}
+
+/// Adds the counts in the summary of a file to the totals, or subtracts them if `add` is
+/// `false`.
+fn update_totals(
+    totals: &mut serde_json::Map<String, serde_json::Value>,
+    summary: &Summary,
+    add: bool,
+) -> Result<()> {
+    for (kind, counts) in [
+        ("branches", Some(&summary.branches)),
+        ("mcdc", summary.mcdc.as_ref()),
+        ("functions", Some(&summary.functions)),
+        ("instantiations", Some(&summary.instantiations)),
+        ("lines", Some(&summary.lines)),
+        ("regions", Some(&summary.regions)),
+    ] {
+        let (Some(counts), Some(total)) =
+            (counts, totals.get_mut(kind).and_then(serde_json::Value::as_object_mut))
+        else {
+            continue;
+        };
+        for (key, value) in [
+            ("count", Some(counts.count)),
+            ("covered", Some(counts.covered)),
+            ("notcovered", counts.notcovered),
+        ] {
+            if let (Some(value), Some(v)) = (value, total.get(key)) {
+                let v = v.as_u64().context(format!("no {key}"))?;
+                let v = if add { v + value } else { v.saturating_sub(value) };
+                total.insert(key.to_owned(), v.into());
+            }
+        }
+        let count = total["count"].as_u64().context("no count")?;
+        let covered = total["covered"].as_u64().context("no covered")?;
+        total.insert("percent".to_owned(), percent(count, covered).into());
+    }
+    Ok(())
+}
+
+/// Merges the coverage of the same file under different paths into one file, and recomputes
+/// its summary. `functions` are the functions in the merged file.
+fn merge_files(filename: String, files: &[File], functions: &[&Function]) -> File {
+    let segments = merge_segments(files.iter().filter_map(|file| file.segments.as_deref()));
+    let lines = line_execution_counts(&segments);
+
+    // [line start, column start, line end, column end, true count, false count, file id, ...]
+    let mut branches: BTreeMap<Vec<u64>, (u64, u64)> = BTreeMap::new();
+    let mut expansions = vec![];
+    let mut mcdc_records = None::<Vec<_>>;
+    for file in files {
+        for branch in file.branches.iter().flatten().filter_map(serde_json::Value::as_array) {
+            let Some(mut location) =
+                branch.iter().map(serde_json::Value::as_u64).collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            if location.len() < 6 {
+                continue;
+            }
+            let (t, f) = (location[4], location[5]);
+            location.drain(4..6);
+            let counts = branches.entry(location).or_default();
+            *counts = (counts.0 + t, counts.1 + f);
+        }
+        expansions.extend(file.expansions.iter().flatten().cloned());
+        if let Some(records) = &file.mcdc_records {
+            mcdc_records.get_or_insert_with(Vec::new).extend(records.iter().cloned());
+        }
+    }
+    let branches: Vec<_> = branches
+        .into_iter()
+        .map(|(mut branch, (t, f))| {
+            branch.splice(4..4, [t, f]);
+            serde_json::Value::from(branch)
+        })
+        .collect();
+
+    let mut function_locations: HashMap<RegionLocation, bool> = HashMap::new();
+    for function in functions {
+        if let Some(region) = function.regions.first() {
+            let covered = function_locations.entry(RegionLocation::from(region)).or_default();
+            *covered = *covered || function.count > 0;
+        }
+    }
+    let counts = |count: u64, covered: u64, notcovered: bool| CoverageCounts {
+        count,
+        covered,
+        notcovered: notcovered.then_some(count - covered),
+        percent: percent(count, covered),
+    };
+    let (regions, covered_regions) = count_code_regions(functions.iter().copied());
+    let covered_branches = branches
+        .iter()
+        .filter_map(serde_json::Value::as_array)
+        .map(|b| {
+            let side = |i: usize| u64::from(b[i].as_u64().is_some_and(|c| c > 0));
+            side(4) + side(5)
+        })
+        .sum();
+    let summary = Summary {
+        branches: counts(branches.len() as u64 * 2, covered_branches, true),
+        // MC/DC records cannot be merged by location, so use the file with the most covered
+        // conditions.
+        mcdc: files
+            .iter()
+            .filter_map(|file| file.summary.mcdc.as_ref())
+            .max_by_key(|mcdc| mcdc.covered)
+            .map(|mcdc| counts(mcdc.count, mcdc.covered, mcdc.notcovered.is_some())),
+        functions: counts(
+            function_locations.len() as u64,
+            function_locations.values().filter(|&&covered| covered).count() as u64,
+            false,
+        ),
+        instantiations: counts(
+            files.iter().map(|file| file.summary.instantiations.count).sum(),
+            files.iter().map(|file| file.summary.instantiations.covered).sum(),
+            false,
+        ),
+        lines: counts(
+            lines.len() as u64,
+            lines.values().filter(|&&count| count > 0).count() as u64,
+            false,
+        ),
+        regions: counts(regions, covered_regions, true),
+    };
+    File {
+        branches: Some(branches),
+        mcdc_records,
+        expansions: Some(expansions),
+        filename,
+        segments: Some(segments),
+        summary,
+    }
+}
+
+/// Merges the segments of the same file, summing up the execution counts of the regions
+/// active at each position.
+fn merge_segments<'a>(files: impl IntoIterator<Item = &'a [Segment]>) -> Vec<Segment> {
+    let files: Vec<_> = files.into_iter().collect();
+    let positions: BTreeSet<_> = files.iter().copied().flatten().map(|s| (s.0, s.1)).collect();
+    let mut next = vec![0; files.len()];
+    let mut active: Vec<Option<&Segment>> = vec![None; files.len()];
+    let mut merged = Vec::with_capacity(positions.len());
+    for (line, col) in positions {
+        let mut segment = Segment(line, col, 0, false, false, true);
+        for (i, segments) in files.iter().enumerate() {
+            let start = next[i];
+            while segments.get(next[i]).is_some_and(|s| (s.0, s.1) == (line, col)) {
+                active[i] = Some(&segments[next[i]]);
+                next[i] += 1;
+            }
+            let Some(s) = active[i] else { continue };
+            if next[i] != start {
+                segment.4 |= s.is_region_entry();
+            }
+            if s.has_count() {
+                segment.2 += s.count();
+                segment.3 = true;
+                segment.5 &= s.is_gap_region();
+            }
+        }
+        segment.5 &= segment.3;
+        merged.push(segment);
+    }
+    merged
+}
+
+fn percent(count: u64, covered: u64) -> f64 {
+    if count == 0 { 0_f64 } else { covered as f64 * 100_f64 / count as f64 }
+}
+
+/// Instantiations of a function, grouped by the location of the function.
+struct FunctionGroup<'a> {
+    filename: &'a str,
+    line: u64,
+    instantiations: Vec<&'a Function>,
+    /// Whether closures in the function are merged into this group.
+    closures_merged: bool,
+}
+
+impl FunctionGroup<'_> {
+    fn location(&self) -> FunctionLocation {
+        FunctionLocation { name: self.name(), filename: self.filename.to_owned(), line: self.line }
+    }
+
+    fn name(&self) -> String {
+        let name = demangle_function_name(&self.instantiations[0].name);
+        if self.closures_merged {
+            return enclosing_function(&name).0.to_owned();
+        }
+        readable_closure_name(&name, self.line)
+    }
+
+    /// Returns the number of code regions and the number of covered code regions.
+    ///
+    /// Code regions are shared between instantiations; a region is covered if any
+    /// instantiation covers it.
+    fn count_code_regions(&self) -> (u64, u64) {
+        count_code_regions(self.instantiations.iter().copied())
+    }
+
+    /// Returns the number of branch outcomes and the number of covered branch outcomes.
+    ///
+    /// As in llvm-cov, each branch has two outcomes (true and false). Branches are shared
+    /// between instantiations in the same way as code regions.
+    fn count_branches(&self) -> (u64, u64) {
+        // [line start, column start, line end, column end, true count, false count, file id, ...]
+        let mut branches: HashMap<[u64; 5], (bool, bool)> = HashMap::new();
+        for function in &self.instantiations {
+            for branch in &function.branches {
+                let Some(branch) = branch.as_array() else { continue };
+                let Some(values) = branch
+                    .iter()
+                    .take(7)
+                    .map(serde_json::Value::as_u64)
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    continue;
+                };
+                let &[ls, cs, le, ce, t, f, file_id] = &*values else { continue };
+                let covered = branches.entry([file_id, ls, cs, le, ce]).or_default();
+                covered.0 = covered.0 || t > 0;
+                covered.1 = covered.1 || f > 0;
+            }
+        }
+        let covered: usize = branches.values().map(|&(t, f)| usize::from(t) + usize::from(f)).sum();
+        (branches.len() as u64 * 2, covered as u64)
+    }
+}
+
+/// Returns the number of code regions and the number of covered code regions of the given
+/// functions, counting regions at the same location once.
+fn count_code_regions<'a>(functions: impl IntoIterator<Item = &'a Function>) -> (u64, u64) {
+    let mut regions: HashMap<RegionLocation, bool> = HashMap::new();
+    for function in functions {
+        for region in function.regions.iter().filter(|r| r.kind() == CODE_REGION) {
+            let covered = regions.entry(RegionLocation::from(region)).or_default();
+            *covered = *covered || region.execution_count() > 0;
+        }
+    }
+    let covered = regions.values().filter(|&&covered| covered).count();
+    (regions.len() as u64, covered as u64)
+}
+
+/// Demangles the function name and removes generic arguments from it, so that
+/// instantiations of the same generic function have the same name.
+fn demangle_function_name(name: &str) -> String {
+    let name = format!("{:#}", rustc_demangle::demangle(name));
+    strip_generic_args(&name).to_owned()
+}
+
+/// Returns the name of the enclosing function of closures and async blocks, and whether the
+/// given name is of a closure or an async block.
+///
+/// `a::f::{closure#0}::{closure#0}` -> (`a::f`, true), `a::f` -> (`a::f`, false)
+fn enclosing_function(name: &str) -> (&str, bool) {
+    let mut name = name;
+    let mut is_closure = false;
+    while let Some((parent, last)) = name.rsplit_once("::") {
+        if !last.starts_with('{') {
+            break;
+        }
+        name = parent;
+        is_closure = true;
+    }
+    (name, is_closure)
+}
+
+/// Replaces the disambiguator of the closure or async block with its line.
+///
+/// `a::f::{closure#0}` -> `a::f::{closure at line 3}`,
+/// `a::f::{async_block#1}` -> `a::f::{async block at line 3}`
+fn readable_closure_name(name: &str, line: u64) -> String {
+    let Some((parent, last)) = name.rsplit_once("::") else { return name.to_owned() };
+    // `{closure#0}` (v0 mangling) or `{{closure}}` (legacy mangling)
+    let Some(kind) = last
+        .strip_prefix("{{")
+        .and_then(|s| s.strip_suffix("}}"))
+        .or_else(|| last.strip_prefix('{')?.split_once('#').map(|(kind, _)| kind))
+    else {
+        return name.to_owned();
+    };
+    format!("{parent}::{{{} at line {line}}}", kind.replace('_', " "))
+}
+
+/// Returns the path of the module the function is defined in.
+///
+/// `a::b::f` -> `a::b`, `<a::b::T as c::U>::f` -> `a::b`, `a::f::{closure#0}` -> `a`
+fn module_path(name: &str) -> &str {
+    let path = self_type(name).unwrap_or_else(|| enclosing_function(name).0);
+    path.rsplit_once("::").map_or(path, |(module, _)| module)
+}
+
+/// Returns the path of the self type of the method, without generic arguments.
+///
+/// `<a::T<U> as b::Trait>::f` -> `a::T`, `a::f` -> `None`
+fn self_type(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix('<')?;
+    let rest = rest.trim_start_matches(['&', '*']);
+    let rest = rest.strip_prefix("mut ").or_else(|| rest.strip_prefix("dyn ")).unwrap_or(rest);
+    if rest.starts_with(['[', '(']) {
+        // Slices, arrays, and tuples.
+        return None;
+    }
+    let end = rest.find([' ', '<', '>']).unwrap_or(rest.len());
+    Some(&rest[..end])
+}
+
+/// `a::b::<T>` -> `a::b`
+fn strip_generic_args(name: &str) -> &str {
+    if !name.ends_with('>') {
+        return name;
+    }
+    let mut depth = 0_usize;
+    for (i, c) in name.char_indices().rev() {
+        match c {
+            '>' => depth += 1,
+            '<' => {
+                depth -= 1;
+                if depth == 0 {
+                    return name[..i].strip_suffix("::").unwrap_or(name);
+                }
+            }
+            _ => {}
         }
-        Ok(count.saturating_sub(covered))
     }
+    name
 }
 
 /// Json representation of one `CoverageMapping`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct Export {
     /// List of objects describing coverage for files
@@ -332,7 +2408,7 @@ pub struct Export {
 }
 
 /// Coverage for a single file
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct File {
     /// List of Branches in the file
@@ -361,7 +2437,7 @@ pub struct File {
 }
 
 /// Describes a segment of the file with a counter
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Segment(
     /* Line */ u64,
@@ -407,7 +2483,7 @@ impl fmt::Debug for Segment {
 }
 
 /// Coverage info for a single function
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Function {
     branches: Vec<serde_json::Value>,
@@ -420,6 +2496,9 @@ struct Function {
     regions: Vec<Region>,
 }
 
+// https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/include/llvm/ProfileData/Coverage/CoverageMapping.h
+const CODE_REGION: u64 = 0;
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Region(
@@ -476,11 +2555,11 @@ impl fmt::Debug for Region {
 }
 
 /// The location of a region
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
 struct RegionLocation {
     start_line: u64,
-    end_line: u64,
     start_column: u64,
+    end_line: u64,
     end_column: u64,
 }
 
@@ -502,7 +2581,7 @@ impl RegionLocation {
 }
 
 /// Object summarizing the coverage for this file
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Summary {
     /// Object summarizing branch coverage
@@ -519,7 +2598,7 @@ struct Summary {
     regions: CoverageCounts,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct CoverageCounts {
     count: u64,
@@ -531,7 +2610,7 @@ struct CoverageCounts {
 }
 
 /// Information that is not part of the llvm-cov JSON export, but instead injected afterwards by us.
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[cfg_attr(test, derive(PartialEq))]
 struct CargoLlvmCov {
     /// Version of this project, which allows projects that depend on it, to express and verify
@@ -539,6 +2618,47 @@ struct CargoLlvmCov {
     version: &'static str,
     /// Resolved path to the `Cargo.toml` manifest.
     manifest_path: String,
+    /// Region coverage weighted by the complexity of each function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    complexity_weighted_coverage: Option<f64>,
+    /// Information about the run that generated the coverage data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<RunMetadata>,
+}
+
+/// Information about the run that generated the coverage data, which makes archived
+/// reports self-describing.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct RunMetadata {
+    /// SHA of the `HEAD` commit of the git repository that contains the workspace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Current branch of the git repository, if not in detached `HEAD` state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Output of `rustc -V`.
+    pub rustc_version: String,
+    /// LLVM version used by rustc.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llvm_version: Option<String>,
+    /// Target triple of the host.
+    pub host: String,
+    /// Target triple that tests were built for.
+    pub target: String,
+    /// Features passed to cargo via `--features`.
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub all_features: bool,
+    #[serde(default)]
+    pub no_default_features: bool,
+    /// Unix time in seconds when tests were run.
+    ///
+    /// This is only included in reports when `--metadata-timestamp` flag is passed (but
+    /// always recorded), so that reports are reproducible by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
 }
 
 #[cfg(test)]
@@ -549,6 +2669,10 @@ mod tests {
 
     use super::*;
 
+    fn location(name: &str, filename: &str, line: u64) -> FunctionLocation {
+        FunctionLocation { name: name.to_owned(), filename: filename.to_owned(), line }
+    }
+
     #[test]
     fn test_parse_llvm_cov_json() {
         let files: Vec<_> = glob::glob(&format!(
@@ -635,12 +2759,830 @@ mod tests {
 
         // When finding uncovered lines in that report:
         let ignore_filename_regex = None;
-        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex);
+        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex).unwrap();
 
         // Then make sure the file / line data matches the `llvm-cov report` output:
         let expected: UncoveredLines =
             vec![("src/lib.rs".to_owned(), vec![7, 8, 9])].into_iter().collect();
         assert_eq!(uncovered_lines, expected);
+
+        let err = json.get_uncovered_lines(Some("lib(")).unwrap_err();
+        assert_eq!(err.to_string(), "invalid ignore-filename-regex `lib(`");
+    }
+
+    #[test]
+    fn test_get_uncovered_lines_by_function() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-multi-missing.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let ignore_filename_regex = None;
+        let uncovered = json.get_uncovered_lines_by_function(ignore_filename_regex).unwrap();
+
+        // Line 11 is not reported because some of the functions generated by the serde
+        // macro on that line are covered.
+        let expected = vec![
+            FunctionUncoveredLines {
+                location: location("t::baz", "src/lib.rs", 15),
+                uncovered_lines: vec![15],
+            },
+            FunctionUncoveredLines {
+                location: location("t::blah", "src/lib.rs", 17),
+                uncovered_lines: vec![17],
+            },
+        ];
+        assert_eq!(uncovered, expected);
+
+        // `a.rs` calls a macro defined in `b.rs`.
+        let s = r#"{
+            "data": [{
+                "files": [],
+                "functions": [{
+                    "branches": [],
+                    "count": 1,
+                    "filenames": ["a.rs", "b.rs"],
+                    "name": "f",
+                    "regions": [
+                        [1, 1, 2, 2, 1, 0, 0, 0],
+                        [2, 5, 2, 10, 1, 0, 1, 1],
+                        [3, 1, 3, 10, 0, 0, 0, 0],
+                        [10, 1, 11, 2, 0, 1, 0, 0]
+                    ]
+                }],
+                "totals": {}
+            }],
+            "type": "llvm.coverage.json.export",
+            "version": "2.0.1"
+        }"#;
+        let json = serde_json::from_str::<LlvmCovJsonExport>(s).unwrap();
+        assert_eq!(json.get_uncovered_lines_by_function(None).unwrap(), vec![
+            FunctionUncoveredLines { location: location("f", "a.rs", 1), uncovered_lines: vec![3] }
+        ]);
+    }
+
+    #[test]
+    fn test_get_complexity_weighted_coverage_percent() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        // `bar` has 3 code regions (complexity 3) and is not covered; the other 4 functions
+        // have 1 code region (complexity 1) and are covered.
+        let actual = json.get_complexity_weighted_coverage_percent(None).unwrap();
+        assert_eq!(actual, 400_f64 / 7_f64);
+
+        let file = format!(
+            "{}/tests/fixtures/coverage-reports/no_coverage/no_coverage.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        // summary-only export doesn't have per-function data.
+        json.get_complexity_weighted_coverage_percent(None).unwrap_err();
+    }
+
+    #[test]
+    fn test_get_coverage_debt() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_coverage_debt(None).unwrap();
+        let expected = vec![DebtFunction {
+            location: location("t::bar", "src/lib.rs", 7),
+            regions: 3,
+            uncovered_regions: 3,
+        }];
+        assert_eq!(actual, expected);
+
+        let actual = json.get_coverage_debt(Some("lib.rs")).unwrap();
+        assert_eq!(actual, vec![]);
+    }
+
+    #[test]
+    fn test_get_line_counts() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_line_counts();
+        let expected = BTreeMap::from([("src/lib.rs", (11, 8))]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_get_line_execution_counts() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let counts = json.get_line_execution_counts();
+        let lines = &counts["src/lib.rs"];
+        assert_eq!(lines.len(), 11);
+        let uncovered: Vec<_> = lines.iter().filter(|&(_, &c)| c == 0).map(|(&l, _)| l).collect();
+        assert_eq!(uncovered, [7, 8, 9]);
+    }
+
+    #[test]
+    fn test_get_unexecuted_functions() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_unexecuted_functions(None).unwrap();
+        let expected = vec![location("t::bar", "src/lib.rs", 7)];
+        assert_eq!(actual, expected);
+
+        let actual = json.get_unexecuted_functions(Some("lib.rs")).unwrap();
+        assert_eq!(actual, vec![]);
+    }
+
+    #[test]
+    fn test_get_uncovered_functions() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_uncovered_functions(None).unwrap();
+        let expected = vec![UncoveredFunction {
+            krate: "t".to_owned(),
+            location: location("t::bar", "src/lib.rs", 7),
+            regions: 3,
+        }];
+        assert_eq!(actual, expected);
+        assert_eq!(actual[0].to_record(','), "t,t::bar,src/lib.rs,7,3");
+        assert_eq!(actual[0].to_record('\t'), "t\tt::bar\tsrc/lib.rs\t7\t3");
+
+        assert_eq!(json.get_uncovered_functions(Some("lib.rs")).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("a::b", ','), "a::b");
+        assert_eq!(escape_field("<(A, B) as T>::f", ','), "\"<(A, B) as T>::f\"");
+        assert_eq!(escape_field("a\"b", ','), "\"a\"\"b\"");
+        assert_eq!(escape_field("<(A, B) as T>::f", '\t'), "<(A, B) as T>::f");
+        assert_eq!(escape_field("a\tb\nc", '\t'), "a b c");
+    }
+
+    #[test]
+    fn test_get_executed_functions() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_executed_functions(None).unwrap();
+        let expected = BTreeMap::from([(
+            "src/lib.rs".to_owned(),
+            BTreeSet::from(
+                [
+                    "t::foo",
+                    "t::main",
+                    "t::tests::it_works",
+                    "t::tests::it_works::{closure at line 14}",
+                ]
+                .map(str::to_owned),
+            ),
+        )]);
+        assert_eq!(actual, expected);
+
+        assert!(json.get_executed_functions(Some("lib.rs")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_exclude_lines() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let excluded = BTreeMap::from([("src/lib.rs".to_owned(), BTreeSet::from([8, 9, 10]))]);
+        json.exclude_lines(&excluded).unwrap();
+        assert_eq!(json.get_line_counts(), BTreeMap::from([("src/lib.rs", (9, 8))]));
+        assert_eq!(json.count_uncovered_lines().unwrap(), 1);
+        let expected: UncoveredLines =
+            vec![("src/lib.rs".to_owned(), vec![7])].into_iter().collect();
+        assert_eq!(json.get_uncovered_lines(None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_merge_history() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        let lines = json.get_line_counts()["src/lib.rs"];
+        let percent = json.get_coverage_percent(CoverageKind::Lines).unwrap();
+
+        let mut history = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        json.merge_history(history, |_| true).unwrap();
+        assert_eq!(json.get_line_counts(), BTreeMap::from([("src/lib.rs", lines)]));
+
+        history = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        for data in &mut history.data {
+            for file in &mut data.files {
+                file.filename = "src/a.rs".to_owned();
+            }
+            for function in data.functions.iter_mut().flatten() {
+                function.filenames = vec!["src/a.rs".to_owned()];
+            }
+        }
+        json.merge_history(history, |_| true).unwrap();
+        assert_eq!(
+            json.get_line_counts(),
+            BTreeMap::from([("src/a.rs", lines), ("src/lib.rs", lines)])
+        );
+        assert_eq!(json.get_coverage_percent(CoverageKind::Lines).unwrap(), percent);
+    }
+
+    #[test]
+    fn test_merge_files() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        let lines = json.get_line_counts()["src/lib.rs"];
+        let counts = json.get_line_execution_counts()["src/lib.rs"].clone();
+        let percent = json.get_coverage_percent(CoverageKind::Lines).unwrap();
+
+        // The same file as src/lib.rs under another path.
+        let other = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        for (data, other) in json.data.iter_mut().zip(other.data) {
+            for mut file in other.files {
+                file.filename = "/symlink/src/lib.rs".to_owned();
+                data.files.push(file);
+            }
+            for mut function in other.functions.into_iter().flatten() {
+                function.filenames = vec!["/symlink/src/lib.rs".to_owned()];
+                data.functions.as_mut().unwrap().push(function);
+            }
+            let totals = data.totals.as_object_mut().unwrap();
+            for (key, value) in [("count", lines.0), ("covered", lines.1)] {
+                let v = &mut totals["lines"][key];
+                *v = (v.as_u64().unwrap() + value).into();
+            }
+        }
+        assert_eq!(
+            json.get_line_counts(),
+            BTreeMap::from([("/symlink/src/lib.rs", lines), ("src/lib.rs", lines)])
+        );
+
+        let renames = BTreeMap::from([("/symlink/src/lib.rs".to_owned(), "src/lib.rs".to_owned())]);
+        json.merge_files(&renames).unwrap();
+        assert_eq!(json.get_line_counts(), BTreeMap::from([("src/lib.rs", lines)]));
+        let doubled: BTreeMap<_, _> =
+            counts.iter().map(|(&line, &count)| (line, count * 2)).collect();
+        assert_eq!(json.get_line_execution_counts()["src/lib.rs"], doubled);
+        assert_eq!(json.get_coverage_percent(CoverageKind::Lines).unwrap(), percent);
+        assert_eq!(json.count_uncovered_lines().unwrap(), lines.0 - lines.1);
+    }
+
+    #[test]
+    fn test_to_lcov() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        assert_eq!(
+            json.to_lcov(),
+            "SF:src/lib.rs\n\
+             FN:7,_RNvCsaBRreq0qC0g_1t3bar\n\
+             FN:14,_RNCNvNtCsaBRreq0qC0g_1t5tests8it_works0B5_\n\
+             FN:15,_RNvNtCsaBRreq0qC0g_1t5testss_8it_works\n\
+             FN:3,_RNvCsaBRreq0qC0g_1t3foo\n\
+             FN:1,_RNvCsaBRreq0qC0g_1t4main\n\
+             FNDA:0,_RNvCsaBRreq0qC0g_1t3bar\n\
+             FNDA:1,_RNCNvNtCsaBRreq0qC0g_1t5tests8it_works0B5_\n\
+             FNDA:1,_RNvNtCsaBRreq0qC0g_1t5testss_8it_works\n\
+             FNDA:1,_RNvCsaBRreq0qC0g_1t3foo\n\
+             FNDA:1,_RNvCsaBRreq0qC0g_1t4main\n\
+             FNF:5\n\
+             FNH:4\n\
+             BRF:0\n\
+             BRH:0\n\
+             DA:1,1\n\
+             DA:3,1\n\
+             DA:4,1\n\
+             DA:5,1\n\
+             DA:7,0\n\
+             DA:8,0\n\
+             DA:9,0\n\
+             DA:14,1\n\
+             DA:15,1\n\
+             DA:16,1\n\
+             DA:17,1\n\
+             LF:11\n\
+             LH:8\n\
+             end_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_get_module_coverage() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_module_coverage(None).unwrap();
+        let expected = vec![
+            GroupCoverage {
+                name: "t".to_owned(),
+                functions: 3,
+                covered_functions: 2,
+                lines: 7,
+                covered_lines: 4,
+                regions: 5,
+                covered_regions: 2,
+                branches: 0,
+                covered_branches: 0,
+            },
+            GroupCoverage {
+                name: "t::tests".to_owned(),
+                functions: 2,
+                covered_functions: 2,
+                lines: 4,
+                covered_lines: 4,
+                regions: 2,
+                covered_regions: 2,
+                branches: 0,
+                covered_branches: 0,
+            },
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_get_file_coverage() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_file_coverage();
+        let expected = vec![GroupCoverage {
+            name: "src/lib.rs".to_owned(),
+            functions: 5,
+            covered_functions: 4,
+            lines: 11,
+            covered_lines: 8,
+            regions: 7,
+            covered_regions: 4,
+            branches: 0,
+            covered_branches: 0,
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_module_path() {
+        assert_eq!(module_path("a::b::f"), "a::b");
+        assert_eq!(module_path("a::f::{closure#0}"), "a");
+        assert_eq!(module_path("<a::b::T>::f"), "a::b");
+        assert_eq!(module_path("<a::b::T as c::U>::f"), "a::b");
+        assert_eq!(module_path("<&mut a::T as c::U>::f::{closure#0}"), "a");
+    }
+
+    #[test]
+    fn test_closure_names() {
+        assert_eq!(enclosing_function("a::f::{closure#0}::{closure#1}"), ("a::f", true));
+        assert_eq!(enclosing_function("a::f"), ("a::f", false));
+        assert_eq!(readable_closure_name("a::f", 3), "a::f");
+        assert_eq!(readable_closure_name("a::f::{closure#0}", 3), "a::f::{closure at line 3}");
+        assert_eq!(readable_closure_name("a::f::{{closure}}", 3), "a::f::{closure at line 3}");
+        assert_eq!(
+            readable_closure_name("a::f::{async_block#1}", 3),
+            "a::f::{async block at line 3}"
+        );
+
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        let hotspots = json.get_hotspots(None, 10).unwrap();
+        assert!(
+            hotspots
+                .functions
+                .iter()
+                .any(|f| f.location.name == "t::tests::it_works::{closure at line 14}"
+                    && f.location.line == 14)
+        );
+
+        json.group_closures();
+        let hotspots = json.get_hotspots(None, 1).unwrap();
+        let expected = vec![HotFunction {
+            location: location("t::tests::it_works", "src/lib.rs", 15),
+            count: 2,
+        }];
+        assert_eq!(hotspots.functions, expected);
+    }
+
+    #[test]
+    fn test_self_type() {
+        assert_eq!(self_type("a::b::f"), None);
+        assert_eq!(self_type("<a::b::T>::f"), Some("a::b::T"));
+        assert_eq!(self_type("<a::T<u8> as c::U>::f"), Some("a::T"));
+        assert_eq!(self_type("<&mut a::T as c::U>::f::{closure#0}"), Some("a::T"));
+        assert_eq!(self_type("<[u8]>::f"), None);
+    }
+
+    #[test]
+    fn test_get_function_coverage() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_function_coverage(None, None).unwrap();
+        let names: Vec<_> =
+            actual.iter().map(|f| (f.location.name.as_str(), f.location.line, f.count)).collect();
+        assert_eq!(names, [
+            ("t::main", 1, 1),
+            ("t::foo", 3, 1),
+            ("t::bar", 7, 0),
+            ("t::tests::it_works::{closure at line 14}", 14, 1),
+            ("t::tests::it_works", 15, 1),
+        ]);
+        let bar = &actual[2];
+        assert_eq!((bar.regions, bar.covered_regions), (3, 0));
+
+        let filter = Regex::new("ba").unwrap();
+        let actual = json.get_function_coverage(None, Some(&filter)).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].location.name, "t::bar");
+
+        let actual = json.get_function_coverage(Some("lib.rs"), None).unwrap();
+        assert_eq!(actual, vec![]);
+
+        // Functions with a single instantiation do not list instantiations.
+        let actual = json.get_function_coverage(None, None).unwrap();
+        assert!(actual.iter().all(|f| f.instantiations.is_empty()));
+
+        // Functions generated by `#[derive(Deserialize)]` share the location of the derive.
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-multi-missing.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+        let actual = json.get_function_coverage(None, None).unwrap();
+        let derive = actual.iter().find(|f| f.location.line == 11).unwrap();
+        assert_eq!(derive.instantiations.len(), 9);
+        let executed: Vec<_> = derive.instantiations.iter().filter(|i| i.count != 0).collect();
+        assert_eq!(executed.len(), 2);
+        assert_eq!(derive.count, 2);
+    }
+
+    #[test]
+    fn test_get_hotspots() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = json.get_hotspots(None, 2).unwrap();
+        let expected = Hotspots {
+            functions: vec![
+                HotFunction { location: location("t::main", "src/lib.rs", 1), count: 1 },
+                HotFunction { location: location("t::foo", "src/lib.rs", 3), count: 1 },
+            ],
+            lines: vec![
+                HotLine { filename: "src/lib.rs".to_owned(), line: 1, count: 1 },
+                HotLine { filename: "src/lib.rs".to_owned(), line: 3, count: 1 },
+            ],
+        };
+        assert_eq!(actual, expected);
+
+        let actual = json.get_hotspots(Some("lib.rs"), 2).unwrap();
+        assert_eq!(actual, Hotspots { functions: vec![], lines: vec![] });
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        assert_eq!(line_ranges(&[]), vec![]);
+        assert_eq!(line_ranges(&[1]), vec![(1, 1)]);
+        assert_eq!(line_ranges(&[1, 2, 3, 5, 7, 8]), vec![(1, 3), (5, 5), (7, 8)]);
+    }
+
+    #[test]
+    fn test_rdjson_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let rdjson = RdJsonExport::from_llvm_cov_json_export(&json, None).unwrap();
+        let actual = serde_json::to_value(&rdjson).unwrap();
+        assert_eq!(actual["source"]["name"], "cargo-llvm-cov");
+        assert_eq!(
+            actual["diagnostics"],
+            serde_json::json!([{
+                "message": "lines 7-9 are not covered by tests",
+                "location": {
+                    "path": "src/lib.rs",
+                    "range": { "start": { "line": 7 }, "end": { "line": 9 } },
+                },
+                "severity": "WARNING",
+            }])
+        );
+    }
+
+    #[test]
+    fn test_sarif_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let sarif = SarifExport::from_llvm_cov_json_export(&json, None).unwrap();
+        let actual = serde_json::to_value(&sarif).unwrap();
+        assert_eq!(actual["version"], "2.1.0");
+        assert_eq!(actual["runs"][0]["tool"]["driver"]["name"], "cargo-llvm-cov");
+        let results = actual["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0],
+            serde_json::json!({
+                "ruleId": "uncovered-region",
+                "level": "warning",
+                "message": { "text": "region 7:1-8:13 is not covered by tests" },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "src/lib.rs" },
+                        "region": { "startLine": 7, "startColumn": 1, "endLine": 8, "endColumn": 13 },
+                    },
+                }],
+            })
+        );
+
+        let sarif = SarifExport::from_llvm_cov_json_export(&json, Some("lib.rs")).unwrap();
+        let actual = serde_json::to_value(&sarif).unwrap();
+        assert_eq!(actual["runs"][0]["results"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(file_uri("src/lib.rs"), "src/lib.rs");
+        assert_eq!(
+            file_uri("/home/me/my crate/src/lib.rs"),
+            "file:///home/me/my%20crate/src/lib.rs"
+        );
+        assert_eq!(file_uri("C:\\a\\src\\lib.rs"), "file:///C:/a/src/lib.rs");
+    }
+
+    #[test]
+    fn test_checkstyle_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual = CheckstyleExport::from_llvm_cov_json_export(&json, None).unwrap().to_xml();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<checkstyle version="4.3">
+  <file name="src/lib.rs">
+    <error line="7" column="1" severity="warning" message="region 7:1-8:13 is not covered by tests" source="cargo-llvm-cov"/>
+    <error line="8" column="13" severity="warning" message="region 8:13-8:14 is not covered by tests" source="cargo-llvm-cov"/>
+    <error line="9" column="1" severity="warning" message="region 9:1-9:2 is not covered by tests" source="cargo-llvm-cov"/>
+  </file>
+</checkstyle>
+"#;
+        assert_eq!(actual, expected);
+
+        let actual =
+            CheckstyleExport::from_llvm_cov_json_export(&json, Some("lib.rs")).unwrap().to_xml();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<checkstyle version="4.3">
+</checkstyle>
+"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_codecov_export_macro_expansion() {
+        // `a.rs` calls a macro defined in `b.rs`.
+        let s = r#"{
+            "data": [{
+                "files": [],
+                "functions": [{
+                    "branches": [],
+                    "count": 1,
+                    "filenames": ["a.rs", "b.rs"],
+                    "name": "f",
+                    "regions": [
+                        [1, 1, 3, 2, 1, 0, 0, 0],
+                        [2, 5, 2, 10, 1, 0, 1, 1],
+                        [10, 1, 11, 2, 0, 1, 0, 0]
+                    ]
+                }],
+                "totals": {}
+            }],
+            "type": "llvm.coverage.json.export",
+            "version": "2.0.1"
+        }"#;
+        let json = serde_json::from_str::<LlvmCovJsonExport>(s).unwrap();
+        let actual = serde_json::to_string(
+            &CodeCovJsonExport::from_llvm_cov_json_export(json, None).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            actual,
+            r#"{"coverage":{"a.rs":{"1":"1/1","2":"2/2","3":"1/1"},"b.rs":{"10":"0/1","11":"0/1"}}}"#
+        );
+    }
+
+    #[test]
+    fn test_cobertura_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual =
+            CoberturaExport::from_llvm_cov_json_export(&json, None, "/ws", 0).unwrap().to_xml();
+        let expected = format!(
+            r#"<?xml version="1.0" ?>
+<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">
+<coverage lines-valid="11" lines-covered="8" branches-valid="0" branches-covered="0" line-rate="0.7273" branch-rate="0.0000" complexity="0" version="{}" timestamp="0">
+  <sources>
+    <source>/ws</source>
+  </sources>
+  <packages>
+    <package name="t" line-rate="0.7273" branch-rate="0.0000" complexity="0">
+      <classes>
+        <class name="t" filename="src/lib.rs" line-rate="0.7273" branch-rate="0.0000" complexity="0">
+          <methods>
+            <method name="t::main" signature="" line-rate="1.0000" branch-rate="0.0000" complexity="0">
+              <lines>
+                <line number="1" hits="1" branch="false"/>
+              </lines>
+            </method>
+            <method name="t::foo" signature="" line-rate="1.0000" branch-rate="0.0000" complexity="0">
+              <lines>
+                <line number="3" hits="1" branch="false"/>
+                <line number="4" hits="1" branch="false"/>
+                <line number="5" hits="1" branch="false"/>
+              </lines>
+            </method>
+            <method name="t::bar" signature="" line-rate="0.0000" branch-rate="0.0000" complexity="0">
+              <lines>
+                <line number="7" hits="0" branch="false"/>
+                <line number="8" hits="0" branch="false"/>
+                <line number="9" hits="0" branch="false"/>
+              </lines>
+            </method>
+            <method name="t::tests::it_works::{{closure at line 14}}" signature="" line-rate="1.0000" branch-rate="0.0000" complexity="0">
+              <lines>
+                <line number="14" hits="1" branch="false"/>
+              </lines>
+            </method>
+            <method name="t::tests::it_works" signature="" line-rate="1.0000" branch-rate="0.0000" complexity="0">
+              <lines>
+                <line number="15" hits="1" branch="false"/>
+                <line number="16" hits="1" branch="false"/>
+                <line number="17" hits="1" branch="false"/>
+              </lines>
+            </method>
+          </methods>
+          <lines>
+            <line number="1" hits="1" branch="false"/>
+            <line number="3" hits="1" branch="false"/>
+            <line number="4" hits="1" branch="false"/>
+            <line number="5" hits="1" branch="false"/>
+            <line number="7" hits="0" branch="false"/>
+            <line number="8" hits="0" branch="false"/>
+            <line number="9" hits="0" branch="false"/>
+            <line number="14" hits="1" branch="false"/>
+            <line number="15" hits="1" branch="false"/>
+            <line number="16" hits="1" branch="false"/>
+            <line number="17" hits="1" branch="false"/>
+          </lines>
+        </class>
+      </classes>
+    </package>
+  </packages>
+</coverage>
+"#,
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(actual, expected);
+
+        let actual = CoberturaExport::from_llvm_cov_json_export(&json, Some("lib.rs"), "/ws", 0)
+            .unwrap()
+            .to_xml();
+        assert!(actual.contains(r#"<coverage lines-valid="0" lines-covered="0""#));
+        assert!(actual.contains("  <packages>\n  </packages>\n"));
+    }
+
+    #[test]
+    fn test_jacoco_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual =
+            JacocoExport::from_llvm_cov_json_export(&json, None, "/ws", 1).unwrap().to_xml();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<!DOCTYPE report PUBLIC "-//JACOCO//DTD Report 1.1//EN" "report.dtd">
+<report name="ws">
+  <sessioninfo id="cargo-llvm-cov" start="1000" dump="1000"/>
+  <package name="t">
+    <class name="t" sourcefilename="src/lib.rs">
+      <method name="t::main" desc="()" line="1">
+        <counter type="LINE" missed="0" covered="1"/>
+        <counter type="METHOD" missed="0" covered="1"/>
+      </method>
+      <method name="t::foo" desc="()" line="3">
+        <counter type="LINE" missed="0" covered="3"/>
+        <counter type="METHOD" missed="0" covered="1"/>
+      </method>
+      <method name="t::bar" desc="()" line="7">
+        <counter type="LINE" missed="3" covered="0"/>
+        <counter type="METHOD" missed="1" covered="0"/>
+      </method>
+      <method name="t::tests::it_works::{closure at line 14}" desc="()" line="14">
+        <counter type="LINE" missed="0" covered="1"/>
+        <counter type="METHOD" missed="0" covered="1"/>
+      </method>
+      <method name="t::tests::it_works" desc="()" line="15">
+        <counter type="LINE" missed="0" covered="3"/>
+        <counter type="METHOD" missed="0" covered="1"/>
+      </method>
+      <counter type="LINE" missed="3" covered="8"/>
+      <counter type="METHOD" missed="1" covered="4"/>
+      <counter type="CLASS" missed="0" covered="1"/>
+    </class>
+    <sourcefile name="src/lib.rs">
+      <line nr="1" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="3" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="4" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="5" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="7" mi="1" ci="0" mb="0" cb="0"/>
+      <line nr="8" mi="1" ci="0" mb="0" cb="0"/>
+      <line nr="9" mi="1" ci="0" mb="0" cb="0"/>
+      <line nr="14" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="15" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="16" mi="0" ci="1" mb="0" cb="0"/>
+      <line nr="17" mi="0" ci="1" mb="0" cb="0"/>
+      <counter type="LINE" missed="3" covered="8"/>
+      <counter type="METHOD" missed="1" covered="4"/>
+      <counter type="CLASS" missed="0" covered="1"/>
+    </sourcefile>
+    <counter type="LINE" missed="3" covered="8"/>
+    <counter type="METHOD" missed="1" covered="4"/>
+    <counter type="CLASS" missed="0" covered="1"/>
+  </package>
+  <counter type="LINE" missed="3" covered="8"/>
+  <counter type="METHOD" missed="1" covered="4"/>
+  <counter type="CLASS" missed="0" covered="1"/>
+</report>
+"#;
+        assert_eq!(actual, expected);
+
+        let actual = JacocoExport::from_llvm_cov_json_export(&json, Some("lib.rs"), "/ws", 0)
+            .unwrap()
+            .to_xml();
+        assert!(actual.contains(
+            "  <sessioninfo id=\"cargo-llvm-cov\" start=\"0\" dump=\"0\"/>\n</report>\n"
+        ));
+    }
+
+    #[test]
+    fn test_sonarqube_export() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        let actual =
+            SonarQubeExport::from_llvm_cov_json_export(&json, None, "/ws").unwrap().to_xml();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<coverage version="1">
+  <file path="src/lib.rs">
+    <lineToCover lineNumber="1" covered="true"/>
+    <lineToCover lineNumber="3" covered="true"/>
+    <lineToCover lineNumber="4" covered="true"/>
+    <lineToCover lineNumber="5" covered="true"/>
+    <lineToCover lineNumber="7" covered="false"/>
+    <lineToCover lineNumber="8" covered="false"/>
+    <lineToCover lineNumber="9" covered="false"/>
+    <lineToCover lineNumber="14" covered="true"/>
+    <lineToCover lineNumber="15" covered="true"/>
+    <lineToCover lineNumber="16" covered="true"/>
+    <lineToCover lineNumber="17" covered="true"/>
+  </file>
+</coverage>
+"#;
+        assert_eq!(actual, expected);
+
+        let actual = SonarQubeExport::from_llvm_cov_json_export(&json, Some("lib.rs"), "/ws")
+            .unwrap()
+            .to_xml();
+        assert!(actual.ends_with("<coverage version=\"1\">\n</coverage>\n"));
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+    }
+
+    #[test]
+    fn test_strip_generic_args() {
+        assert_eq!(strip_generic_args("a::b"), "a::b");
+        assert_eq!(strip_generic_args("a::b::<c::D>"), "a::b");
+        assert_eq!(strip_generic_args("a::b::<c::D<E>, F>"), "a::b");
+        assert_eq!(strip_generic_args("<a::B as c::D>::e"), "<a::B as c::D>::e");
+        assert_eq!(strip_generic_args("<a::B as c::D>::e::<F>"), "<a::B as c::D>::e");
+        assert_eq!(strip_generic_args("<a::B<C>>"), "<a::B<C>>");
     }
 
     #[test]
@@ -656,7 +3598,7 @@ mod tests {
         let json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
 
         let ignore_filename_regex = None;
-        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex);
+        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex).unwrap();
 
         let expected: UncoveredLines = UncoveredLines::new();
         assert_eq!(uncovered_lines, expected);
@@ -675,7 +3617,7 @@ mod tests {
 
         // When finding uncovered lines in that report:
         let ignore_filename_regex = None;
-        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex);
+        let uncovered_lines = json.get_uncovered_lines(ignore_filename_regex).unwrap();
 
         // Then make sure the file / line data matches the `llvm-cov report` output:
         let expected: UncoveredLines =
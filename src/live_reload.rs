@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Live reload of the HTML report for --watch.
+//
+// With --html or --open, the report is served over HTTP on localhost, and a script that connects
+// to the server by WebSocket is inserted into each page when it is served. When the report is
+// regenerated, the server sends a message to the connected pages, and they reload themselves.
+// The files of the report are not modified, so they can still be opened directly.
+//
+// This implements only the parts of HTTP/1.1 and WebSocket needed for this, to avoid depending
+// on an HTTP server: one request is handled per connection, and the only message sent to a
+// WebSocket connection is the one to reload the page.
+
+use std::{
+    io::{self, BufRead as _, BufReader, Write as _},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    str,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{digest, fs};
+
+const LIVE_RELOAD_PATH: &str = "/cargo-llvm-cov-live-reload";
+
+const SCRIPT: &str = "<script>
+// Added by cargo-llvm-cov: reload the page when the report is regenerated.
+new WebSocket('ws://' + location.host + '/cargo-llvm-cov-live-reload')
+  .addEventListener('message', () => location.reload());
+</script>";
+
+pub(crate) struct Server {
+    url: String,
+    /// WebSocket connections of the pages to reload.
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Server {
+    /// Starts serving the HTML report in `dir` on an unused port of localhost.
+    pub(crate) fn start(dir: Utf8PathBuf) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let url = format!("http://{}/index.html", listener.local_addr()?);
+        let clients = Arc::new(Mutex::new(vec![]));
+        let server_clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let dir = dir.clone();
+                let clients = Arc::clone(&server_clients);
+                thread::spawn(move || {
+                    // Errors are ignored, as they are usually caused by pages closed while
+                    // loading.
+                    let _ = handle(stream, &dir, &clients);
+                });
+            }
+        });
+        Ok(Self { url, clients })
+    }
+
+    /// Returns the URL of the index page of the report.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Reloads the pages connected to the server. The pages connect again after they are
+    /// reloaded.
+    pub(crate) fn reload(&self) {
+        // An unmasked text frame that contains "reload".
+        // Refs: https://www.rfc-editor.org/rfc/rfc6455#section-5.2
+        let frame = b"\x81\x06reload";
+        for mut client in self.clients.lock().unwrap().drain(..) {
+            let _ = client.write_all(frame);
+        }
+    }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    dir: &Utf8Path,
+    clients: &Mutex<Vec<TcpStream>>,
+) -> io::Result<()> {
+    let mut request_line = String::new();
+    let mut key = None;
+    {
+        let mut reader = BufReader::new(&stream);
+        reader.read_line(&mut request_line)?;
+        let mut line = String::new();
+        while reader.read_line(&mut line)? != 0 && !line.trim_end().is_empty() {
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("Sec-WebSocket-Key") {
+                    key = Some(value.trim().to_owned());
+                }
+            }
+            line.clear();
+        }
+    }
+    let mut request_line = request_line.split(' ');
+    let (method, target) = (request_line.next().unwrap_or_default(), request_line.next());
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+    let path = target.unwrap_or("/").split(['?', '#']).next().unwrap_or_default();
+
+    if path == LIVE_RELOAD_PATH {
+        let Some(key) = key else {
+            return respond(&mut stream, "400 Bad Request", "text/plain", b"");
+        };
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        )?;
+        clients.lock().unwrap().push(stream);
+        return Ok(());
+    }
+
+    let Some(file) = file_path(dir, path) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"");
+    };
+    let Ok(content) = fs::read(&file) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"");
+    };
+    let extension = file.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    if extension == "html" {
+        return respond(&mut stream, "200 OK", content_type(extension), &insert_script(&content));
+    }
+    respond(&mut stream, "200 OK", content_type(extension), &content)
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    // The report may be regenerated at any time, so the pages are not cached.
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Returns the value of `Sec-WebSocket-Accept` header for the given `Sec-WebSocket-Key` header.
+// Refs: https://www.rfc-editor.org/rfc/rfc6455#section-4.2.2
+fn accept_key(key: &str) -> String {
+    const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    digest::to_base64(&digest::sha1(format!("{key}{GUID}").as_bytes()))
+}
+
+/// Returns the file in `dir` that the path of the request refers to, or `None` if the path
+/// refers to a file outside of `dir`.
+fn file_path(dir: &Utf8Path, path: &str) -> Option<PathBuf> {
+    let path = percent_decode(path.strip_prefix('/')?)?;
+    let path = Path::new(&path);
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let mut file = dir.as_std_path().join(path);
+    if file.is_dir() {
+        file.push("index.html");
+    }
+    Some(file)
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            out.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Inserts the script that reloads the page into the head of the HTML page.
+fn insert_script(html: &[u8]) -> Vec<u8> {
+    let mut html = String::from_utf8_lossy(html).into_owned();
+    let pos = html.find("</head>").unwrap_or(html.len());
+    html.insert_str(pos, SCRIPT);
+    html.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read as _, Write as _},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    use camino::Utf8Path;
+
+    use super::{LIVE_RELOAD_PATH, SCRIPT, Server, accept_key, file_path, insert_script};
+    use crate::fs;
+
+    #[test]
+    fn test_accept_key() {
+        // The example in RFC 6455.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_file_path() {
+        let dir = Utf8Path::new("html");
+        assert_eq!(file_path(dir, "/style.css").unwrap(), dir.as_std_path().join("style.css"));
+        assert_eq!(
+            file_path(dir, "/coverage/a%20b/lib.rs.html").unwrap(),
+            dir.as_std_path().join("coverage/a b/lib.rs.html")
+        );
+        assert_eq!(file_path(dir, "/../secret"), None);
+        assert_eq!(file_path(dir, "/a/%2e%2e/%2e%2e/secret"), None);
+        assert_eq!(file_path(dir, "//etc/passwd"), None);
+        assert_eq!(file_path(dir, "/%zz"), None);
+        assert_eq!(file_path(dir, "style.css"), None);
+    }
+
+    #[test]
+    fn test_insert_script() {
+        assert_eq!(
+            String::from_utf8(insert_script(b"<html><head></head><body></body></html>")).unwrap(),
+            format!("<html><head>{SCRIPT}</head><body></body></html>")
+        );
+        assert_eq!(String::from_utf8(insert_script(b"a")).unwrap(), format!("a{SCRIPT}"));
+    }
+
+    #[test]
+    fn test_server() {
+        fn request(addr: &str, request: &str) -> TcpStream {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            stream
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+        fs::write(dir.join("index.html"), "<html><head></head></html>").unwrap();
+        let server = Server::start(dir.to_owned()).unwrap();
+        let addr = server.url().strip_prefix("http://").unwrap().strip_suffix("/index.html");
+        let addr = addr.unwrap();
+
+        let mut response = String::new();
+        request(addr, "GET /index.html HTTP/1.1\r\n\r\n").read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with(&format!("<html><head>{SCRIPT}</head></html>")), "{response}");
+
+        let mut response = String::new();
+        request(addr, "GET /../index.html HTTP/1.1\r\n\r\n").read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{response}");
+
+        let mut client = request(
+            addr,
+            &format!(
+                "GET {LIVE_RELOAD_PATH} HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+            ),
+        );
+        let expected = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                        Connection: Upgrade\r\nSec-WebSocket-Accept: \
+                        s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+        let mut response = vec![0; expected.len()];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(String::from_utf8(response).unwrap(), expected);
+        // Wait for the connection to be registered.
+        while server.clients.lock().unwrap().is_empty() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        server.reload();
+        let mut frame = vec![];
+        client.read_to_end(&mut frame).unwrap();
+        assert_eq!(frame, b"\x81\x06reload");
+    }
+}
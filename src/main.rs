@@ -8,19 +8,27 @@
 // - https://llvm.org/docs/CommandGuide/llvm-cov.html
 
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, hash_map},
     ffi::{OsStr, OsString},
     fmt::Write as _,
-    io::{self, BufRead as _, BufWriter, Read as _, Write as _},
-    path::Path,
+    io::{self, BufWriter, Read as _, Write as _},
+    path::{Path, PathBuf},
     process::ExitCode,
-    time::SystemTime,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{Context as _, Result, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_config2::Flags;
-use cargo_llvm_cov::json::{CodeCovJsonExport, CoverageKind, LlvmCovJsonExport};
+use cargo_llvm_cov::json::{
+    CheckstyleExport, CoberturaExport, CodeCovJsonExport, CoverageKind, GroupCoverage,
+    JacocoExport, LlvmCovJsonExport, RdJsonExport, RunMetadata, SarifExport, SonarQubeExport,
+    UncoveredFunction,
+};
 use regex::Regex;
 use serde_derive::Deserialize;
 use tar::Archive;
@@ -28,12 +36,13 @@ use walkdir::WalkDir;
 
 use crate::{
     cargo::Workspace,
-    cli::{Args, ShowEnvOptions, Subcommand},
+    cli::{Args, ProfileUpdate, ShowEnvOptions, Subcommand},
     context::Context,
-    metadata::Metadata,
+    manifest::Manifest,
+    metadata::{Metadata, PackageId},
     process::ProcessBuilder,
     regex_vec::{RegexVec, RegexVecBuilder},
-    term::Coloring,
+    term::{Coloring, ExitStatus},
 };
 
 #[macro_use]
@@ -42,21 +51,62 @@ mod term;
 #[macro_use]
 mod process;
 
+mod affected;
+mod badge;
+mod binary_id;
 mod cargo;
 mod clean;
 mod cli;
+mod collect;
+mod config;
 mod context;
+mod coveralls;
+mod dashboard;
+mod dedup;
+mod diff;
+mod digest;
+mod doctor;
 mod env;
+mod exclude;
+mod features;
 mod fs;
+mod fuzz;
+mod html;
+mod init;
+mod live_reload;
+mod manifest;
 mod metadata;
+mod overhead;
+mod ratchet;
 mod regex_vec;
+mod reports;
+mod sample;
+mod self_update;
+mod show;
+mod snapshot;
+mod stats;
+mod summary;
+mod timings;
+mod tui;
+mod upload;
+mod wasm;
+mod watch;
+mod wrapper;
 
 fn main() -> ExitCode {
     term::init_coloring();
+    if wrapper::is_wrapper() {
+        return wrapper::run().unwrap_or_else(|e| {
+            error!("{e:#}");
+            ExitCode::FAILURE
+        });
+    }
     if let Err(e) = try_main() {
         error!("{e:#}");
     }
-    if term::error() || term::warn() && env::var_os("CARGO_LLVM_COV_DENY_WARNINGS").is_some() {
+    if term::error() {
+        ExitCode::from(term::exit_status())
+    } else if term::warn() && env::var_os("CARGO_LLVM_COV_DENY_WARNINGS").is_some() {
         ExitCode::FAILURE
     } else {
         ExitCode::SUCCESS
@@ -74,6 +124,11 @@ fn try_main() -> Result<()> {
             stdout.flush()?;
         }
         Subcommand::Clean => clean::run(&mut args)?,
+        Subcommand::Doctor => doctor::run(&mut args)?,
+        Subcommand::Upload => upload::run(&args)?,
+        Subcommand::Collect => collect::run(&mut args)?,
+        Subcommand::SelfUpdate => self_update::run(&args)?,
+        Subcommand::Init => init::run(&mut args)?,
         Subcommand::ShowEnv => {
             let cx = &Context::new(args)?;
             let writer = &mut ShowEnvWriter {
@@ -81,6 +136,12 @@ fn try_main() -> Result<()> {
                 options: cx.args.show_env.clone(),
             };
             set_env(cx, writer, IsNextest(true))?; // Include envs for nextest.
+            // Objects built with these environment variables are not known, so reports must use
+            // all objects in the target directory.
+            if let Some(mut manifest) = Manifest::load(&cx.ws)? {
+                manifest.record_artifacts(None);
+                manifest.save(&cx.ws)?;
+            }
             writer.set("CARGO_LLVM_COV_TARGET_DIR", cx.ws.metadata.target_directory.as_str())?;
             writer.set("CARGO_LLVM_COV_BUILD_DIR", cx.ws.metadata.build_directory().as_str())?;
             writer.writer.flush()?;
@@ -90,11 +151,45 @@ fn try_main() -> Result<()> {
             create_dirs(cx)?;
             generate_report(cx)?;
         }
+        Subcommand::Tui => {
+            let cx = &Context::new(args)?;
+            create_dirs(cx)?;
+            tui::run(cx)?;
+        }
+        Subcommand::Show => {
+            let cx = &Context::new(args)?;
+            create_dirs(cx)?;
+            show::run(cx)?;
+        }
+        Subcommand::Stats => {
+            let cx = &Context::new(args)?;
+            create_dirs(cx)?;
+            stats::run(cx)?;
+        }
         Subcommand::Run => {
             let cx = &Context::new(args)?;
             clean::clean_partial(cx)?;
             create_dirs(cx)?;
-            run_run(cx)?;
+            check_build_env(cx)?;
+            record_metadata(cx)?;
+            let previous = profraw_files_modified(cx)?;
+            with_test_hooks(cx, IsNextest(false), run_run)?;
+            check_profraw_files(cx, &previous)?;
+            record_object_files(cx, None);
+            if !cx.args.cov.no_report {
+                generate_report(cx)?;
+            }
+        }
+        Subcommand::Fuzz => {
+            let cx = &Context::new(args)?;
+            clean::clean_partial(cx)?;
+            create_dirs(cx)?;
+            check_build_env(cx)?;
+            record_metadata(cx)?;
+            let previous = profraw_files_modified(cx)?;
+            fuzz::run(cx)?;
+            check_profraw_files(cx, &previous)?;
+            record_object_files(cx, None);
             if !cx.args.cov.no_report {
                 generate_report(cx)?;
             }
@@ -103,7 +198,12 @@ fn try_main() -> Result<()> {
             let cx = &Context::new(args)?;
             clean::clean_partial(cx)?;
             create_dirs(cx)?;
-            run_nextest(cx)?;
+            check_build_env(cx)?;
+            record_metadata(cx)?;
+            let previous = profraw_files_modified(cx)?;
+            with_test_hooks(cx, IsNextest(true), run_nextest)?;
+            check_profraw_files(cx, &previous)?;
+            record_object_files(cx, None);
             if !cx.args.cov.no_report {
                 generate_report(cx)?;
             }
@@ -118,15 +218,64 @@ fn try_main() -> Result<()> {
             let cx = &Context::new(args)?;
             clean::clean_partial(cx)?;
             create_dirs(cx)?;
-            run_test(cx)?;
-            if !cx.args.cov.no_report {
-                generate_report(cx)?;
+            if cx.args.watch {
+                watch::run(cx, test_and_report)?;
+            } else {
+                test_and_report(cx)?;
             }
         }
     }
     Ok(())
 }
 
+/// Runs tests and generates the report, for `cargo llvm-cov` and `cargo llvm-cov test`.
+fn test_and_report(cx: &Context) -> Result<()> {
+    check_build_env(cx)?;
+    record_metadata(cx)?;
+    if cx.args.timings {
+        timings::clear(cx)?;
+    }
+    let mut instrumented = overhead::Timings::default();
+    if cx.args.measure_overhead {
+        instrumented.build = overhead::build_instrumented(cx)?;
+    }
+    let previous = profraw_files_modified(cx)?;
+    let mut built = None;
+    with_test_hooks(cx, IsNextest(false), |cx| {
+        let start = Instant::now();
+        let res = run_test(cx);
+        instrumented.run = start.elapsed();
+        built = res?;
+        Ok(())
+    })?;
+    // `cargo test --no-run` does not build doctests.
+    let built = built.filter(|_| !cx.args.doctests && !cx.args.doc);
+    check_profraw_files(cx, &previous)?;
+    record_object_files(cx, built.as_ref().map(|built| &built.files));
+    if let Some(built) = built {
+        check_test_binaries_profile_data(cx, &previous, built.test_binaries)?;
+    }
+    let miri = cx.args.miri.then(|| run_miri(cx));
+    let uninstrumented =
+        cx.args.measure_overhead.then(|| overhead::measure_uninstrumented(cx)).transpose()?;
+    let (mut merge_time, mut report_time) = (Duration::ZERO, Duration::ZERO);
+    if !cx.args.cov.no_report {
+        let start = Instant::now();
+        generate_report_timed(cx, &mut merge_time)?;
+        report_time = start.elapsed().saturating_sub(merge_time);
+    }
+    if let Some(res) = miri {
+        report_miri_result(cx, res)?;
+    }
+    if let Some(uninstrumented) = uninstrumented {
+        overhead::print(cx, &instrumented, &uninstrumented)?;
+    }
+    if cx.args.timings {
+        timings::print(cx, &previous, merge_time, report_time)?;
+    }
+    Ok(())
+}
+
 fn create_dirs(cx: &Context) -> Result<()> {
     fs::create_dir_all(&cx.ws.target_dir)?;
 
@@ -162,6 +311,21 @@ impl EnvTarget for ProcessBuilder {
     }
 }
 
+/// Collects environment variables set by [`set_env`].
+#[derive(Default)]
+struct EnvCollector(BTreeMap<String, String>);
+
+impl EnvTarget for EnvCollector {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.0.insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+    fn unset(&mut self, key: &str) -> Result<()> {
+        self.0.remove(key);
+        Ok(())
+    }
+}
+
 struct ShowEnvWriter<W: io::Write> {
     writer: W,
     options: ShowEnvOptions,
@@ -180,15 +344,19 @@ impl<W: io::Write> EnvTarget for ShowEnvWriter<W> {
     }
 }
 
+#[derive(Clone, Copy)]
 struct IsNextest(bool);
 
 fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNextest) -> Result<()> {
     fn push_common_flags(cx: &Context, flags: &mut Flags) {
         if cx.ws.stable_coverage {
-            flags.push("-C");
-            // TODO: if user already set -C instrument-coverage=..., respect it
+            // Flags from RUSTFLAGS or the cargo configuration come first and the last one takes
+            // precedence, so respect -C instrument-coverage=... set by the user.
             // https://doc.rust-lang.org/rustc/instrument-coverage.html#-c-instrument-coverageoptions
-            flags.push("instrument-coverage");
+            if !cargo::enables_instrument_coverage(&flags.flags) {
+                flags.push("-C");
+                flags.push("instrument-coverage");
+            }
         } else {
             flags.push("-Z");
             flags.push("instrument-coverage");
@@ -219,9 +387,27 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
         } else {
             cx.ws.rustc_version.major_minor() < (1, 71)
         };
-        if needs_atomic_counter_workaround {
+        match cx.args.profile_update {
+            Some(ProfileUpdate::Atomic) => {
+                flags.push("-C");
+                flags.push("llvm-args=--instrprof-atomic-counter-update-all");
+            }
+            // rustc does not add its default when the option is passed by the user.
+            Some(ProfileUpdate::Single) => {
+                flags.push("-C");
+                flags.push("llvm-args=--instrprof-atomic-counter-update-all=false");
+            }
+            None if needs_atomic_counter_workaround => {
+                flags.push("-C");
+                flags.push("llvm-args=--instrprof-atomic-counter-update-all");
+            }
+            None => {}
+        }
+        if cx.args.profile_continuous && !cx.ws.target_for_config.triple().contains("-apple-") {
+            // Continuous mode on non-Darwin platforms requires runtime counter relocation.
+            // https://clang.llvm.org/docs/SourceBasedCodeCoverage.html#running-the-instrumented-program
             flags.push("-C");
-            flags.push("llvm-args=--instrprof-atomic-counter-update-all");
+            flags.push("llvm-args=-runtime-counter-relocation");
         }
         if !cx.args.cov.no_cfg_coverage {
             flags.push("--cfg=coverage");
@@ -229,6 +415,16 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
         if cx.ws.rustc_version.nightly && !cx.args.cov.no_cfg_coverage_nightly {
             flags.push("--cfg=coverage_nightly");
         }
+        // Register the cfgs so that code using them does not trigger `unexpected_cfgs` warnings
+        // when built by cargo-llvm-cov. --check-cfg was stabilized in Rust 1.80.
+        let check_cfg_stable = if cx.ws.rustc_version.nightly {
+            cx.ws.rustc_version.major_minor() > (1, 80)
+        } else {
+            cx.ws.rustc_version.major_minor() >= (1, 80)
+        };
+        if check_cfg_stable {
+            flags.push("--check-cfg=cfg(coverage,coverage_nightly)");
+        }
     }
 
     let llvm_profile_file_name =
@@ -256,14 +452,21 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
                 let _ = write!(
                     llvm_profile_file_name,
                     "-%{}m",
-                    // TODO: clamp to 1..=9?
                     // https://doc.rust-lang.org/rustc/instrument-coverage.html#running-the-instrumented-binary-to-generate-raw-coverage-profiling-data
                     // > N must be between 1 and 9
-                    std::thread::available_parallelism().map_or(1, usize::from)
+                    // Processes share the files in the pool and merge their profiles into
+                    // them under a lock, so a smaller pool than the number of threads is
+                    // still correct.
+                    std::thread::available_parallelism().map_or(1, usize::from).clamp(1, 9)
                 );
             } else {
                 llvm_profile_file_name.push_str("-%m");
             }
+            if cx.args.profile_continuous {
+                // %c enables continuous mode: counter updates are synced to the profile
+                // file while the process is running, not only when it exits.
+                llvm_profile_file_name.push_str("%c");
+            }
             llvm_profile_file_name.push_str(".profraw");
             llvm_profile_file_name
         };
@@ -271,6 +474,15 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
 
     let rustflags = &mut cx.ws.config.rustflags(&cx.ws.target_for_config)?.unwrap_or_default();
     push_common_flags(cx, rustflags);
+    if let Some(sanitizer) = cx.args.sanitizer {
+        // This is not pushed by push_common_flags, because build scripts and proc-macros
+        // instrumented by the rustc wrapper must not be built with sanitizers.
+        rustflags.push(format!("-Zsanitizer={}", sanitizer.as_str()));
+    }
+    if cx.args.subcommand == Subcommand::Fuzz {
+        // Set by cargo-fuzz for fuzz targets and the crates they depend on.
+        rustflags.push("--cfg=fuzzing");
+    }
     if cx.args.remap_path_prefix {
         rustflags.push("--remap-path-prefix");
         rustflags.push(format!("{}/=", cx.ws.metadata.workspace_root));
@@ -281,12 +493,19 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
         // https://github.com/dtolnay/trybuild/pull/123
         rustflags.push("--cfg=trybuild_no_target");
     }
+    if wasm::is_wasm(cx) {
+        wasm::push_flags(cx, rustflags)?;
+    }
 
     // https://doc.rust-lang.org/nightly/rustc/instrument-coverage.html#including-doc-tests
     let rustdocflags = &mut cx.ws.config.rustdocflags(&cx.ws.target_for_config)?;
     if cx.args.doctests {
         let rustdocflags = rustdocflags.get_or_insert_with(Flags::default);
         push_common_flags(cx, rustdocflags);
+        if let Some(sanitizer) = cx.args.sanitizer {
+            // Doctests link the crates built with the sanitizer.
+            rustdocflags.push(format!("-Zsanitizer={}", sanitizer.as_str()));
+        }
         rustdocflags.push("-Z");
         rustdocflags.push("unstable-options");
         rustdocflags.push("--persist-doctests");
@@ -313,6 +532,12 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
         }
     }
 
+    if cx.args.override_build_settings {
+        for (key, value) in cargo::build_settings_overrides(&cx.args) {
+            env.set(&key, value)?;
+        }
+    }
+
     if let Some(rustdocflags) = rustdocflags {
         // First, try with RUSTDOCFLAGS because `nextest` subcommand sometimes doesn't work well with encoded flags.
         if let Ok(v) = rustdocflags.encode_space_separated() {
@@ -344,12 +569,89 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
                 Err(_) => std::env::var("CXXFLAGS").unwrap_or_default(),
             },
         };
-        let clang_flags = " -fprofile-instr-generate -fcoverage-mapping -fprofile-update=atomic";
-        cflags.push_str(clang_flags);
-        cxxflags.push_str(clang_flags);
+        let mut clang_flags =
+            " -fprofile-instr-generate -fcoverage-mapping -fprofile-update=atomic".to_owned();
+        if let Some(sanitizer) = cx.args.sanitizer {
+            let _ = write!(clang_flags, " -fsanitize={}", sanitizer.as_str());
+        }
+        cflags.push_str(&clang_flags);
+        cxxflags.push_str(&clang_flags);
         env.set(cflags_key, &cflags)?;
         env.set(cxxflags_key, &cxxflags)?;
     }
+    let exclude_std = is_build_std(cx) && !cx.args.include_std;
+    let host_crates = host_crates_to_instrument(cx);
+    if exclude_std
+        || !cx.args.include_crate.is_empty()
+        || !cx.args.exclude_crate.is_empty()
+        || cx.args.cov.dep_coverage.is_some()
+        || !host_crates.is_empty()
+        || cx.args.timings
+    {
+        // With -Z build-std, RUSTFLAGS are also applied to the standard library crates, so
+        // use cargo-llvm-cov as a rustc wrapper that doesn't instrument them. The wrapper
+        // also skips the crates not selected by --include-crate and --exclude-crate, and
+        // dependencies other than the one specified by --dep-coverage, which are not in the
+        // report anyway. Conversely, it instruments build scripts and proc-macros built for
+        // the host, to which cargo does not pass RUSTFLAGS when --target is used. With
+        // --timings, it also measures the compile time of each crate.
+        let std_src = if exclude_std {
+            Utf8PathBuf::from(cx.ws.rustc_print("sysroot")?).join("lib/rustlib/src/rust/library")
+        } else {
+            Utf8PathBuf::new()
+        };
+        let inner =
+            wrapper::inner_wrapper(cx.ws.config.build.rustc_wrapper.as_deref(), &cx.current_exe);
+        env.set(wrapper::STD_SRC_ENV, std_src.as_str())?;
+        env.set(wrapper::INCLUDE_CRATES_ENV, &cx.args.include_crate.join(","))?;
+        env.set(wrapper::EXCLUDE_CRATES_ENV, &cx.args.exclude_crate.join(","))?;
+        if let Some(dep) = &cx.args.cov.dep_coverage {
+            env.set(wrapper::DEP_COVERAGE_ENV, dep)?;
+        }
+        env.set(wrapper::WORKSPACE_ROOT_ENV, cx.ws.metadata.workspace_root.as_str())?;
+        if !host_crates.is_empty() {
+            let mut host_flags = Flags::default();
+            push_common_flags(cx, &mut host_flags);
+            if cx.args.remap_path_prefix {
+                host_flags.push("--remap-path-prefix");
+                host_flags.push(format!("{}/=", cx.ws.metadata.workspace_root));
+            }
+            if host_flags.flags.iter().any(|flag| flag.contains(['\n', '\r'])) {
+                bail!("flags to instrument build scripts and proc-macros may not contain newlines");
+            }
+            // Flags are passed via a file because environment variables have size limits
+            // (especially on Windows).
+            let host_flags_file = cx.ws.target_dir.join("llvm-cov-host-flags");
+            fs::create_dir_all(&cx.ws.target_dir)?;
+            fs::write(&host_flags_file, host_flags.flags.join("\n"))?;
+            env.set(wrapper::HOST_CRATES_ENV, &host_crates.join(","))?;
+            env.set(wrapper::HOST_FLAGS_FILE_ENV, host_flags_file.as_str())?;
+        }
+        env.set(wrapper::INNER_WRAPPER_ENV, &inner.to_string_lossy())?;
+        env.set(wrapper::RECORDS_DIR_ENV, wrapper::records_dir(&cx.ws.target_dir).as_str())?;
+        if cx.args.timings {
+            env.set(wrapper::TIMINGS_DIR_ENV, timings::instrumented_dir(cx).as_str())?;
+        }
+        if cx.args.verbose > 1 {
+            env.set(wrapper::VERBOSE_ENV, "1")?;
+        }
+        env.set("RUSTC_WRAPPER", &cx.current_exe.to_string_lossy())?;
+    }
+    if cx.args.benches || cx.args.all_targets || !cx.args.bench.is_empty() {
+        // Run benchmarks with custom harnesses in a minimal-iteration mode, because only
+        // whether the code is executed matters for coverage. Criterion and libtest run each
+        // benchmark once under `cargo test`, but divan samples benchmarks if the harness
+        // flags passed by the user (e.g., `-- --bench`) request it.
+        // https://docs.rs/divan/latest/divan/attr.bench.html#sample_count
+        for key in ["DIVAN_SAMPLE_COUNT", "DIVAN_SAMPLE_SIZE"] {
+            if env::var_os(key).is_none() {
+                env.set(key, "1")?;
+            }
+        }
+    }
+    if wasm::is_wasm(cx) {
+        wasm::set_env(cx, env)?;
+    }
     env.set("LLVM_PROFILE_FILE", llvm_profile_file.as_str())?;
     env.set("CARGO_LLVM_COV", "1")?;
     if cx.args.subcommand == Subcommand::ShowEnv {
@@ -358,6 +660,11 @@ fn set_env(cx: &Context, env: &mut dyn EnvTarget, IsNextest(is_nextest): IsNexte
     Ok(())
 }
 
+fn is_build_std(cx: &Context) -> bool {
+    has_z_flag(&cx.args.cargo_args, "build-std")
+        || env::var_os("CARGO_UNSTABLE_BUILD_STD").is_some()
+}
+
 fn has_z_flag(args: &[String], name: &str) -> bool {
     let mut iter = args.iter().map(String::as_str);
     while let Some(mut arg) = iter.next() {
@@ -377,31 +684,130 @@ fn has_z_flag(args: &[String], name: &str) -> bool {
     false
 }
 
-fn run_test(cx: &Context) -> Result<()> {
-    let mut cargo = cx.cargo();
+/// Returns environment variables that affect how crates are built under coverage.
+fn build_env(cx: &Context) -> Result<BTreeMap<String, String>> {
+    let mut env = EnvCollector::default();
+    set_env(cx, &mut env, IsNextest(false))?;
+    let mut build_env: BTreeMap<_, _> =
+        env.0.into_iter().filter(|(key, _)| key.contains("FLAGS")).collect();
+    for key in ["RUSTC", "RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER"] {
+        if let Some(value) = env::var(key)? {
+            build_env.insert(key.to_owned(), value);
+        }
+    }
+    Ok(build_env)
+}
 
-    set_env(cx, &mut cargo, IsNextest(false))?;
+/// Formats differences between two sets of environment variables, one variable per line.
+fn diff_env(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> String {
+    fn fmt_value(value: Option<&String>) -> String {
+        value.map_or_else(|| "(unset)".to_owned(), |v| format!("`{v}`"))
+    }
+    let keys: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+    let mut out = String::new();
+    for key in keys {
+        let (old, new) = (old.get(key), new.get(key));
+        if old != new {
+            let _ = write!(out, "\n    {key}: {} -> {}", fmt_value(old), fmt_value(new));
+        }
+    }
+    out
+}
 
-    cargo.arg("test");
-    if cx.ws.need_doctest_in_workspace && !has_z_flag(&cx.args.cargo_args, "doctest-in-workspace") {
-        // https://github.com/rust-lang/cargo/issues/9427
-        cargo.arg("-Z");
-        cargo.arg("doctest-in-workspace");
+/// Reports changes of the build environment since the last run, since they cause cargo to
+/// rebuild all crates, and records the current one.
+///
+/// This also makes cargo rebuild the crates whose instrumentation by the rustc wrapper has
+/// been changed, since cargo does not track the environment variables of the wrapper.
+fn check_build_env(cx: &Context) -> Result<()> {
+    let mut env = EnvCollector::default();
+    set_env(cx, &mut env, IsNextest(false))?;
+    let invalidated =
+        wrapper::invalidate_changed(&wrapper::records_dir(&cx.ws.target_dir), &env.0)?;
+    if invalidated != 0 {
+        info!(
+            "instrumentation of {invalidated} crates has been changed since the last run, so \
+             cargo will rebuild them"
+        );
     }
+    let build_env = build_env(cx)?;
+    let mut manifest = Manifest::load(&cx.ws)?.unwrap_or_default();
+    if !manifest.build_env.is_empty() {
+        let diff = diff_env(&manifest.build_env, &build_env);
+        if !diff.is_empty() {
+            info!(
+                "build environment has been changed since the last run, so cargo may rebuild \
+                 all crates:{diff}"
+            );
+        }
+    }
+    manifest.build_env = build_env;
+    manifest.save(&cx.ws)
+}
 
-    if cx.args.ignore_run_fail {
-        {
-            let mut cargo = cargo.clone();
-            cargo.arg("--no-run");
-            cargo::test_or_run_args(cx, &mut cargo);
-            if term::verbose() {
-                status!("Running", "{cargo}");
-                cargo.stdout_to_stderr().run()?;
+/// Runs `f` between --pre-test-cmd and --post-test-cmd.
+fn with_test_hooks(
+    cx: &Context,
+    is_nextest: IsNextest,
+    f: impl FnOnce(&Context) -> Result<()>,
+) -> Result<()> {
+    if let Some(pre_test_cmd) = &cx.args.pre_test_cmd {
+        run_hook(cx, "--pre-test-cmd", pre_test_cmd, is_nextest)?;
+    }
+    let res = f(cx);
+    if let Some(post_test_cmd) = &cx.args.post_test_cmd {
+        // Tear down even if tests failed.
+        if let Err(e) = run_hook(cx, "--post-test-cmd", post_test_cmd, is_nextest) {
+            if res.is_err() {
+                warn!("{e:#}");
             } else {
-                // Capture output to prevent duplicate warnings from appearing in two runs.
-                cargo.run_with_output()?;
+                return Err(e);
             }
         }
+    }
+    res
+}
+
+fn run_hook(cx: &Context, flag: &str, command: &str, is_nextest: IsNextest) -> Result<()> {
+    let mut cmd =
+        if cfg!(windows) { cmd!("cmd", "/C", command) } else { cmd!("sh", "-c", command) };
+    set_env(cx, &mut cmd, is_nextest)?;
+    // Commands that copy profraw files from devices or emulators (e.g., `cargo llvm-cov
+    // collect`) need to know where to put them.
+    cmd.env("CARGO_LLVM_COV_TARGET_DIR", &cx.ws.target_dir);
+    cmd.env("CARGO_LLVM_COV_BUILD_DIR", cx.ws.build_dir.as_ref().unwrap_or(&cx.ws.target_dir));
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    stdout_to_stderr(cx, &mut cmd);
+    cmd.run().with_context(|| format!("failed to run {flag} command `{command}`"))?;
+    Ok(())
+}
+
+/// Runs tests, and returns the artifacts that cargo reported if the tests were built by a
+/// separate `cargo test --no-run`.
+fn run_test(cx: &Context) -> Result<Option<BuiltArtifacts>> {
+    if let Some(jobs) = cx.args.test_jobs {
+        return run_test_binaries(cx, jobs).map(Some);
+    }
+    if cx.args.each_feature || cx.args.feature_powerset {
+        // The artifacts of the last feature combination are not all of the objects used.
+        for features in features::runs(cx)? {
+            status!("Running", "tests with `{}`", features.join(" "));
+            run_cargo_test(cx, &features)?;
+        }
+        return Ok(None);
+    }
+    run_cargo_test(cx, &[])
+}
+
+/// Runs `cargo test` with the given feature flags.
+fn run_cargo_test(cx: &Context, features: &[String]) -> Result<Option<BuiltArtifacts>> {
+    let mut cargo = cargo_test(cx)?;
+    cargo.args(features);
+
+    if cx.args.ignore_run_fail {
+        let built = build_tests(cx, features)?;
 
         cargo.arg("--no-fail-fast");
         cargo::test_or_run_args(cx, &mut cargo);
@@ -412,6 +818,7 @@ fn run_test(cx: &Context) -> Result<()> {
         if let Err(e) = cargo.run() {
             warn!("{e:#}");
         }
+        Ok(Some(built))
     } else {
         cargo::test_or_run_args(cx, &mut cargo);
         if term::verbose() {
@@ -419,11 +826,183 @@ fn run_test(cx: &Context) -> Result<()> {
         }
         stdout_to_stderr(cx, &mut cargo);
         cargo.run()?;
+        Ok(None)
     }
+}
+
+/// Returns `cargo test` command with the environment to collect coverage.
+fn cargo_test(cx: &Context) -> Result<ProcessBuilder> {
+    let mut cargo = cx.cargo();
 
+    set_env(cx, &mut cargo, IsNextest(false))?;
+
+    cargo.arg("test");
+    if cx.ws.need_doctest_in_workspace && !has_z_flag(&cx.args.cargo_args, "doctest-in-workspace") {
+        // https://github.com/rust-lang/cargo/issues/9427
+        cargo.arg("-Z");
+        cargo.arg("doctest-in-workspace");
+    }
+    Ok(cargo)
+}
+
+/// Runs tests under Miri, without instrumentation.
+fn run_miri(cx: &Context) -> Result<()> {
+    let mut cargo = cx.cargo();
+    cargo.args(["miri", "test"]);
+    cargo::test_or_run_args(cx, &mut cargo);
+    stdout_to_stderr(cx, &mut cargo);
+    if term::verbose() {
+        status!("Running", "{cargo}");
+    }
+    cargo.run()?;
     Ok(())
 }
 
+/// Reports the result of the Miri run after the coverage report, so that both results
+/// are visible in one summary.
+fn report_miri_result(cx: &Context, res: Result<()>) -> Result<()> {
+    match res {
+        Ok(()) => {
+            status!("Finished", "tests under Miri passed");
+            Ok(())
+        }
+        Err(e) if cx.args.ignore_run_fail => {
+            warn!("tests failed under Miri: {e:#}");
+            Ok(())
+        }
+        Err(e) => Err(e.context("tests failed under Miri")),
+    }
+}
+
+/// Test binary reported by cargo.
+struct TestBinary {
+    name: String,
+    path: Utf8PathBuf,
+    manifest_dir: Utf8PathBuf,
+}
+
+/// Artifacts built by `cargo test --no-run`.
+struct BuiltArtifacts {
+    test_binaries: Vec<TestBinary>,
+    /// Absolute paths of all files that cargo reported as built, including test binaries,
+    /// binaries for integration tests, shared libraries, and build scripts.
+    files: BTreeSet<String>,
+}
+
+/// Builds tests with the given feature flags by `cargo test --no-run`, and returns the
+/// artifacts that cargo reported.
+fn build_tests(cx: &Context, features: &[String]) -> Result<BuiltArtifacts> {
+    let mut cargo = cargo_test(cx)?;
+    cargo.args(features);
+    cargo.args(["--no-run", "--message-format=json-render-diagnostics"]);
+    cargo::test_or_run_args(cx, &mut cargo);
+    if term::verbose() {
+        status!("Running", "{cargo}");
+    }
+    // Capture output to prevent duplicate warnings from appearing in two runs.
+    let out = cargo.read()?;
+    let mut binaries = vec![];
+    let mut files = BTreeSet::new();
+    for line in out.lines() {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(line) else { continue };
+        if msg["reason"] != "compiler-artifact" {
+            continue;
+        }
+        let filenames = msg["filenames"].as_array().into_iter().flatten();
+        files.extend(
+            filenames.chain([&msg["executable"]]).filter_map(|f| f.as_str()).map(str::to_owned),
+        );
+        if msg["profile"]["test"] != true {
+            continue;
+        }
+        let Some(path) = msg["executable"].as_str() else { continue };
+        let package_id = PackageId::from(msg["package_id"].as_str().unwrap_or_default().to_owned());
+        let manifest_dir = match cx.ws.metadata.packages.get(&package_id) {
+            Some(package) => package.manifest_path.parent().unwrap().to_owned(),
+            None => cx.ws.metadata.workspace_root.clone(),
+        };
+        binaries.push(TestBinary {
+            name: msg["target"]["name"].as_str().unwrap_or_default().to_owned(),
+            path: path.into(),
+            manifest_dir,
+        });
+    }
+    Ok(BuiltArtifacts { test_binaries: binaries, files })
+}
+
+/// Builds test binaries and runs up to `jobs` of them concurrently.
+fn run_test_binaries(cx: &Context, jobs: usize) -> Result<BuiltArtifacts> {
+    let built = build_tests(cx, &[])?;
+    let binaries = &built.test_binaries;
+
+    // Give each binary its own profile file name, so that concurrently running binaries
+    // never write the same file.
+    let mut env = EnvCollector::default();
+    set_env(cx, &mut env, IsNextest(false))?;
+    let llvm_profile_file = &env.0["LLVM_PROFILE_FILE"];
+    let llvm_profile_file = llvm_profile_file.strip_suffix(".profraw").unwrap();
+    let fail_fast =
+        !cx.args.ignore_run_fail && !cx.args.cargo_args.iter().any(|arg| arg == "--no-fail-fast");
+    let redirect_stdout = needs_stdout_redirect(cx);
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let errors = Mutex::new(vec![]);
+    std::thread::scope(|s| {
+        for _ in 0..jobs.min(binaries.len()) {
+            s.spawn(|| {
+                while !(fail_fast && failed.load(Ordering::Relaxed)) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(binary) = binaries.get(i) else { break };
+                    let mut cmd = cx.process(binary.path.as_str());
+                    cmd.args(&cx.args.rest)
+                        .dir(binary.manifest_dir.as_std_path())
+                        .env("CARGO_MANIFEST_DIR", binary.manifest_dir.as_str())
+                        .env(
+                            "LLVM_PROFILE_FILE",
+                            format!("{llvm_profile_file}-{}-{i}.profraw", binary.name),
+                        );
+                    if term::verbose() {
+                        status!("Running", "{cmd}");
+                    }
+                    // Capture output to prevent outputs of binaries from being interleaved.
+                    match cmd.run_with_output() {
+                        Ok(output) => {
+                            let mut stderr = io::stderr().lock();
+                            if redirect_stdout {
+                                let _ = stderr.write_all(&output.stdout);
+                            } else {
+                                let _ = io::stdout().lock().write_all(&output.stdout);
+                            }
+                            let _ = stderr.write_all(&output.stderr);
+                        }
+                        Err(e) => {
+                            failed.store(true, Ordering::Relaxed);
+                            errors.lock().unwrap().push(e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let mut errors = errors.into_inner().unwrap();
+    if cx.args.ignore_run_fail {
+        for e in errors {
+            warn!("{e:#}");
+        }
+        return Ok(built);
+    }
+    if errors.is_empty() {
+        return Ok(built);
+    }
+    let first = errors.remove(0);
+    for e in errors {
+        error!("{e:#}");
+    }
+    Err(first)
+}
+
 fn archive_nextest(cx: &Context) -> Result<()> {
     let mut cargo = cx.cargo();
 
@@ -487,7 +1066,10 @@ fn run_run(cx: &Context) -> Result<()> {
 
     set_env(cx, &mut cargo, IsNextest(false))?;
 
-    if cx.args.ignore_run_fail {
+    // The report is generated even if the binary fails only when the user asked for
+    // continuous mode, not when it is enabled automatically by `panic = "abort"`.
+    if cx.args.ignore_run_fail || cx.profile_continuous_passed {
+        // Build separately so that build failures are not ignored.
         {
             let mut cargo = cargo.clone();
             cargo.arg("build");
@@ -508,7 +1090,15 @@ fn run_run(cx: &Context) -> Result<()> {
         }
         stdout_to_stderr(cx, &mut cargo);
         if let Err(e) = cargo.run() {
-            warn!("{e:#}");
+            if cx.args.ignore_run_fail {
+                warn!("{e:#}");
+            } else {
+                // In continuous mode, counters are written to the profile files while the
+                // process is running, so the process that was killed by a signal (e.g., a
+                // server stopped by `kill` or `timeout`) or aborted still has coverage. Generate
+                // the report, but exit with an error.
+                error!("{e:#}");
+            }
         }
     } else {
         cargo.arg("run");
@@ -523,39 +1113,140 @@ fn run_run(cx: &Context) -> Result<()> {
 }
 
 fn stdout_to_stderr(cx: &Context, cargo: &mut ProcessBuilder) {
-    if cx.args.cov.no_report
-        || cx.args.cov.output_dir.is_some()
-        || cx.args.cov.output_path.is_some()
-    {
-        // Do not redirect if unnecessary.
-    } else {
-        // Redirect stdout to stderr as the report is output to stdout by default.
+    if needs_stdout_redirect(cx) {
         cargo.stdout_to_stderr();
     }
 }
 
+/// Returns `true` if stdout of tests needs to be redirected to stderr, as the report is output
+/// to stdout by default.
+fn needs_stdout_redirect(cx: &Context) -> bool {
+    !(cx.args.cov.no_report
+        || cx.args.cov.output_dir.is_some()
+        || cx.args.cov.output_path.is_some())
+}
+
 fn generate_report(cx: &Context) -> Result<()> {
+    generate_report_timed(cx, &mut Duration::ZERO)
+}
+
+/// Same as [`generate_report`], but also sets `merge_time` to the time taken to merge profile
+/// data, for --timings.
+fn generate_report_timed(cx: &Context, merge_time: &mut Duration) -> Result<()> {
+    if cx.args.cov.allow_empty && !has_profile_data(cx)? {
+        info!("no profile data was generated; skipping report (--allow-empty)");
+        return Ok(());
+    }
+    let start = Instant::now();
     merge_profraw(cx).context("failed to merge profile data")?;
+    *merge_time = start.elapsed();
+    if !cx.args.cov.allow_empty && profdata_functions(cx) == Some(0) {
+        term::fail(ExitStatus::NoProfileData);
+        bail!(
+            "merged profile data ({}) contains no functions; the profraw files may have been \
+             generated by binaries that were not instrumented, or by processes that exited \
+             before any instrumented code was run; pass --allow-empty to skip the report instead",
+            cx.ws.profdata_file
+        );
+    }
 
+    check_recorded_object_files(cx)?;
     let object_files = object_files(cx).context("failed to collect object files")?;
+    check_stale_objects(cx, &object_files);
     let ignore_filename_regex = ignore_filename_regex(cx, &object_files)?;
-    let format = Format::from_args(cx);
-    format
-        .generate_report(cx, &object_files, ignore_filename_regex.as_deref())
-        .context("failed to generate report")?;
+    if !retain_mapped_changed_files(cx, &object_files, ignore_filename_regex.as_ref())? {
+        let rev = cx.args.cov.changed_since.as_ref().unwrap();
+        info!("no files in the report have been changed since {rev}; skipping report");
+        return Ok(());
+    }
+    if !cx.args.cov.affected_by.is_empty() {
+        return affected::generate(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    if cx.args.cov.debt {
+        return generate_debt_report(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    if cx.args.cov.uncovered_functions {
+        return generate_uncovered_functions_report(
+            cx,
+            &object_files,
+            ignore_filename_regex.as_ref(),
+        );
+    }
+    if cx.args.cov.missing_examples {
+        return generate_missing_examples_report(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    if cx.args.cov.hotspots {
+        return generate_hotspots_report(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    if cx.args.cov.show_functions {
+        return generate_functions_report(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    if cx.args.cov.module_summary || cx.args.cov.type_summary || cx.args.cov.package_summary {
+        return generate_group_summary_report(cx, &object_files, ignore_filename_regex.as_ref());
+    }
+    // The JSON export used to generate the report, reused by the checks below if any.
+    let json = if cx.args.cov.multiple_formats() {
+        let json = reports::generate(cx, &object_files, ignore_filename_regex.as_deref())
+            .context("failed to generate reports")?;
+        // -summary-only and -skip-functions drop the details needed by the checks below.
+        if cx.args.cov.summary_only || cx.args.cov.skip_functions {
+            None
+        } else {
+            json.map(|mut json| {
+                if cx.args.cov.group_closures {
+                    json.group_closures();
+                }
+                json
+            })
+        }
+    } else if cx.args.cov.file_summary {
+        let json = Format::Json
+            .get_json(cx, &object_files, ignore_filename_regex.as_ref())
+            .context("failed to get json")?;
+        summary::print(cx, &json, ignore_filename_regex.as_deref())?;
+        Some(json)
+    } else if cx.args.cov.csv || cx.args.cov.tsv {
+        let json = Format::Json
+            .get_json(cx, &object_files, ignore_filename_regex.as_ref())
+            .context("failed to get json")?;
+        let separator = if cx.args.cov.csv { ',' } else { '\t' };
+        let out = summary::delimited(cx, &json, &package_coverage(cx, &json), separator);
+        write_report(cx, &out, false)?;
+        Some(json)
+    } else {
+        let format = Format::from_args(cx);
+        format
+            .generate_report(cx, &object_files, ignore_filename_regex.as_deref())
+            .context("failed to generate report")?;
+        None
+    };
 
     if cx.args.cov.fail_under_functions.is_some()
         || cx.args.cov.fail_under_lines.is_some()
         || cx.args.cov.fail_under_regions.is_some()
+        || cx.args.cov.fail_under_branches.is_some()
         || cx.args.cov.fail_uncovered_functions.is_some()
         || cx.args.cov.fail_uncovered_lines.is_some()
         || cx.args.cov.fail_uncovered_regions.is_some()
         || cx.args.cov.show_missing_lines
+        || cx.args.cov.show_missing_lines_by_function
+        || cx.args.cov.show_missing_ranges
+        || cx.args.cov.complexity_weighted && !cx.args.cov.json
+        || cx.args.cov.badges.is_some()
+        || cx.args.cov.dashboard.is_some()
+        || cx.args.cov.ratchet.is_some()
+        || cx.args.cov.save_snapshot.is_some()
+        || cx.args.cov.compare_to.is_some()
+        || cx.changed_lines.is_some()
+        || cx.args.sample_history.is_some()
+        || cx.args.cov.summary_json_to_stderr
     {
-        let format = Format::Json;
-        let json = format
-            .get_json(cx, &object_files, ignore_filename_regex.as_ref())
-            .context("failed to get json")?;
+        let json = match json {
+            Some(json) => json,
+            None => Format::Json
+                .get_json(cx, &object_files, ignore_filename_regex.as_ref())
+                .context("failed to get json")?,
+        };
 
         if let Some(fail_under_functions) = cx.args.cov.fail_under_functions {
             // Handle --fail-under-functions.
@@ -563,102 +1254,1166 @@ fn generate_report(cx: &Context) -> Result<()> {
                 .get_coverage_percent(CoverageKind::Functions)
                 .context("failed to get function coverage")?;
             if functions_percent < fail_under_functions {
-                term::error::set(true);
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+
+        if let Some(fail_under_lines) = cx.args.cov.fail_under_lines {
+            // Handle --fail-under-lines.
+            let lines_percent = json
+                .get_coverage_percent(CoverageKind::Lines)
+                .context("failed to get line coverage")?;
+            if lines_percent < fail_under_lines {
+                term::fail(ExitStatus::Threshold);
             }
         }
 
-        if let Some(fail_under_lines) = cx.args.cov.fail_under_lines {
-            // Handle --fail-under-lines.
-            let lines_percent = json
-                .get_coverage_percent(CoverageKind::Lines)
-                .context("failed to get line coverage")?;
-            if lines_percent < fail_under_lines {
-                term::error::set(true);
-            }
-        }
+        if let Some(fail_under_regions) = cx.args.cov.fail_under_regions {
+            // Handle --fail-under-regions.
+            let regions_percent = json
+                .get_coverage_percent(CoverageKind::Regions)
+                .context("failed to get region coverage")?;
+            if regions_percent < fail_under_regions {
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+
+        if let Some(fail_under_branches) = cx.args.cov.fail_under_branches {
+            // Handle --fail-under-branches.
+            let branches_percent = json
+                .get_coverage_percent(CoverageKind::Branches)
+                .context("failed to get branch coverage")?;
+            if branches_percent < fail_under_branches {
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+
+        if let Some(fail_uncovered_functions) = cx.args.cov.fail_uncovered_functions {
+            // Handle --fail-uncovered-functions.
+            let uncovered =
+                json.count_uncovered_functions().context("failed to count uncovered functions")?;
+            if uncovered > fail_uncovered_functions {
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+        if let Some(fail_uncovered_lines) = cx.args.cov.fail_uncovered_lines {
+            // Handle --fail-uncovered-lines.
+            let uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref())?;
+            let uncovered = uncovered_files
+                .iter()
+                .fold(0_u64, |uncovered, (_, lines)| uncovered + lines.len() as u64);
+
+            if uncovered > fail_uncovered_lines {
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+        if let Some(fail_uncovered_regions) = cx.args.cov.fail_uncovered_regions {
+            // Handle --fail-uncovered-regions.
+            let uncovered =
+                json.count_uncovered_regions().context("failed to count uncovered regions")?;
+            if uncovered > fail_uncovered_regions {
+                term::fail(ExitStatus::Threshold);
+            }
+        }
+
+        if cx.args.cov.show_missing_lines && !cx.args.cov.file_summary {
+            // Handle --show-missing-lines.
+            let uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref())?;
+            if !uncovered_files.is_empty() {
+                let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+                writeln!(stdout, "Uncovered Lines:")?;
+                for (file, lines) in &uncovered_files {
+                    let lines: Vec<_> = lines.iter().map(ToString::to_string).collect();
+                    writeln!(stdout, "{file}: {}", lines.join(", "))?;
+                }
+                stdout.flush()?;
+            }
+        }
+
+        if cx.args.cov.show_missing_lines_by_function {
+            // Handle --show-missing-lines-by-function.
+            let uncovered_functions =
+                json.get_uncovered_lines_by_function(ignore_filename_regex.as_deref())?;
+            if !uncovered_functions.is_empty() {
+                let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+                writeln!(stdout, "Uncovered Lines by Function:")?;
+                for function in &uncovered_functions {
+                    let lines: Vec<_> =
+                        function.uncovered_lines.iter().map(ToString::to_string).collect();
+                    writeln!(
+                        stdout,
+                        "{}:{}: {} ({} lines): {}",
+                        function.location.filename,
+                        function.location.line,
+                        function.location.name,
+                        lines.len(),
+                        lines.join(", ")
+                    )?;
+                }
+                stdout.flush()?;
+            }
+        }
+
+        if cx.args.cov.show_missing_ranges {
+            // Handle --show-missing-ranges.
+            summary::print_missing_ranges(&json, ignore_filename_regex.as_deref())?;
+        }
+
+        if cx.args.cov.complexity_weighted && !cx.args.cov.json {
+            // Handle --complexity-weighted.
+            // In JSON format, this is included in the report instead.
+            let percent = json
+                .get_complexity_weighted_coverage_percent(ignore_filename_regex.as_deref())
+                .context("failed to get complexity-weighted coverage")?;
+            println!("Complexity-weighted coverage: {percent:.2}%");
+        }
+
+        if let Some(changed_lines) = &cx.changed_lines {
+            // Handle --diff and --fail-under-diff.
+            diff::report(cx, &json, changed_lines).context("failed to report patch coverage")?;
+        }
+
+        if let Some(path) = &cx.args.cov.ratchet {
+            // Handle --ratchet.
+            let tolerance = cx.args.cov.ratchet_tolerance.unwrap_or(0.);
+            ratchet::check(&json, path, tolerance).context("failed to check --ratchet")?;
+        }
+
+        if let Some(path) = &cx.args.cov.compare_to {
+            // Handle --compare-to.
+            let tolerance = cx.args.cov.compare_tolerance.unwrap_or(0.);
+            snapshot::compare(cx, &json, path, tolerance)
+                .context("failed to compare coverage with snapshot")?;
+        }
+
+        if let Some(path) = &cx.args.cov.save_snapshot {
+            // Handle --save-snapshot.
+            // This is done after --compare-to, so the same file can be compared and updated.
+            snapshot::save(cx, &json, path).context("failed to save coverage snapshot")?;
+        }
+
+        if let Some(dir) = &cx.args.cov.badges {
+            // Handle --badges.
+            write_badges(cx, &json, dir).context("failed to write badges")?;
+        }
+
+        if let Some(path) = &cx.args.cov.dashboard {
+            // Handle --dashboard.
+            let total = json.get_coverage_percent(CoverageKind::Lines)?;
+            dashboard::write(cx, path, total, &package_line_coverage(cx, &json))
+                .context("failed to write dashboard")?;
+        }
+
+        if cx.args.cov.summary_json_to_stderr {
+            // Handle --summary-json-to-stderr.
+            if let Some(totals) = json.get_totals() {
+                eprintln!("{}", serde_json::to_string(totals)?);
+            }
+        }
+
+        if let Some(path) = &cx.args.sample_history {
+            // Handle --sample-history.
+            sample::merge_history(cx, path, json).context("failed to merge coverage history")?;
+        }
+    }
+
+    // With --watch, the served report is opened instead.
+    if cx.args.cov.open && !cx.args.watch {
+        let path = &cx.args.cov.output_dir.as_ref().unwrap().join("html/index.html");
+        status!("Opening", "{path}");
+        open_report(cx, path.as_str())?;
+    }
+    Ok(())
+}
+
+fn generate_debt_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    let json = Format::Json
+        .get_json(cx, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let debt = json
+        .get_coverage_debt(ignore_filename_regex.map(String::as_str))
+        .context("failed to get coverage debt")?;
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&debt)?
+    } else {
+        let mut out = String::new();
+        let _ = writeln!(out, "{:>7} {:>7} {:>8}  Function", "Regions", "Missed", "Cover");
+        for function in &debt {
+            let cover = (function.regions - function.uncovered_regions) as f64 * 100_f64
+                / function.regions as f64;
+            let _ = writeln!(
+                out,
+                "{:>7} {:>7} {:>7.2}%  {}",
+                function.regions, function.uncovered_regions, cover, function.location
+            );
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+fn generate_uncovered_functions_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    let json = Format::Json
+        .get_json(cx, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let functions = json
+        .get_uncovered_functions(ignore_filename_regex.map(String::as_str))
+        .context("failed to get uncovered functions")?;
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&functions)?
+    } else if cx.args.cov.csv || cx.args.cov.tsv {
+        let separator = if cx.args.cov.csv { ',' } else { '\t' };
+        let mut out = UncoveredFunction::FIELDS.join(separator.encode_utf8(&mut [0; 4]));
+        out.push('\n');
+        for function in &functions {
+            let _ = writeln!(out, "{}", function.to_record(separator));
+        }
+        out
+    } else {
+        let mut out = String::new();
+        for (i, function) in functions.iter().enumerate() {
+            if i == 0 || functions[i - 1].krate != function.krate {
+                let (count, regions) = functions[i..]
+                    .iter()
+                    .take_while(|f| f.krate == function.krate)
+                    .fold((0_usize, 0), |(count, regions), f| (count + 1, regions + f.regions));
+                if i != 0 {
+                    out.push('\n');
+                }
+                let _ = writeln!(
+                    out,
+                    "{} ({count} functions, {regions} regions)\n{:>7}  Function",
+                    function.krate, "Regions"
+                );
+            }
+            let _ = writeln!(out, "{:>7}  {}", function.regions, function.location);
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+fn generate_functions_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    let filter = cx
+        .args
+        .cov
+        .function_filter
+        .as_deref()
+        .map(|re| {
+            Regex::new(re).with_context(|| format!("invalid regex in --function-filter: {re}"))
+        })
+        .transpose()?;
+    let json = Format::Json
+        .get_json(cx, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let mut functions = json
+        .get_function_coverage(ignore_filename_regex.map(String::as_str), filter.as_ref())
+        .context("failed to get function coverage")?;
+    if !cx.args.cov.show_instantiations {
+        for function in &mut functions {
+            function.instantiations.clear();
+        }
+    }
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&functions)?
+    } else {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:>12} {:>7} {:>7} {:>8}  Function",
+            "Count", "Regions", "Missed", "Cover"
+        );
+        let cover = |regions: u64, covered: u64| {
+            if regions == 0 {
+                "-".to_owned()
+            } else {
+                format!("{:.2}%", covered as f64 * 100_f64 / regions as f64)
+            }
+        };
+        for function in &functions {
+            let _ = writeln!(
+                out,
+                "{:>12} {:>7} {:>7} {:>8}  {}",
+                function.count,
+                function.regions,
+                function.regions - function.covered_regions,
+                cover(function.regions, function.covered_regions),
+                function.location
+            );
+            // The merged coverage above is followed by the coverage of each instantiation.
+            for instantiation in &function.instantiations {
+                let _ = writeln!(
+                    out,
+                    "{:>12} {:>7} {:>7} {:>8}    {}",
+                    instantiation.count,
+                    instantiation.regions,
+                    instantiation.regions - instantiation.covered_regions,
+                    cover(instantiation.regions, instantiation.covered_regions),
+                    instantiation.name
+                );
+            }
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+fn generate_hotspots_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    const LIMIT: usize = 20;
+
+    let json = Format::Json
+        .get_json(cx, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let hotspots = json
+        .get_hotspots(ignore_filename_regex.map(String::as_str), LIMIT)
+        .context("failed to get hotspots")?;
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&hotspots)?
+    } else {
+        let mut out = String::new();
+        let _ = writeln!(out, "{:>12}  Function", "Count");
+        for function in &hotspots.functions {
+            let _ = writeln!(out, "{:>12}  {}", function.count, function.location);
+        }
+        let _ = writeln!(out, "\n{:>12}  Line", "Count");
+        for line in &hotspots.lines {
+            let _ = writeln!(out, "{:>12}  {}:{}", line.count, line.filename, line.line);
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+/// Generates the report for --module-summary, --type-summary, and --package-summary.
+fn generate_group_summary_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    fn cover(count: u64, covered: u64) -> f64 {
+        if count == 0 { 0_f64 } else { covered as f64 * 100_f64 / count as f64 }
+    }
+
+    let json = Format::Json
+        .get_json(cx, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let ignore_filename_regex = ignore_filename_regex.map(String::as_str);
+    let (groups, kind) = if cx.args.cov.module_summary {
+        let modules = json
+            .get_module_coverage(ignore_filename_regex)
+            .context("failed to get module coverage")?;
+        (modules, "Module")
+    } else if cx.args.cov.package_summary {
+        (package_coverage(cx, &json), "Package")
+    } else {
+        let types =
+            json.get_type_coverage(ignore_filename_regex).context("failed to get type coverage")?;
+        (types, "Type")
+    };
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&groups)?
+    } else {
+        // Branch coverage is only available when it is enabled.
+        let branch = cx.args.cov.branch || cx.args.cov.mcdc;
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{:>9} {:>7} {:>8} {:>7} {:>7} {:>8} {:>7} {:>7} {:>8}",
+            "Functions",
+            "Missed",
+            "Cover",
+            "Lines",
+            "Missed",
+            "Cover",
+            "Regions",
+            "Missed",
+            "Cover"
+        );
+        if branch {
+            let _ = write!(out, " {:>8} {:>7} {:>8}", "Branches", "Missed", "Cover");
+        }
+        let _ = writeln!(out, "  {kind}");
+        let mut row = |g: &GroupCoverage| {
+            let _ = write!(
+                out,
+                "{:>9} {:>7} {:>7.2}% {:>7} {:>7} {:>7.2}% {:>7} {:>7} {:>7.2}%",
+                g.functions,
+                g.functions - g.covered_functions,
+                cover(g.functions, g.covered_functions),
+                g.lines,
+                g.lines - g.covered_lines,
+                cover(g.lines, g.covered_lines),
+                g.regions,
+                g.regions - g.covered_regions,
+                cover(g.regions, g.covered_regions),
+            );
+            if branch {
+                let _ = write!(
+                    out,
+                    " {:>8} {:>7} {:>7.2}%",
+                    g.branches,
+                    g.branches - g.covered_branches,
+                    cover(g.branches, g.covered_branches)
+                );
+            }
+            let _ = writeln!(out, "  {}", g.name);
+        };
+        for g in &groups {
+            row(g);
+        }
+        // Files outside of the workspace members are not counted in the total.
+        if cx.args.cov.package_summary {
+            let mut total = GroupCoverage {
+                name: "TOTAL".to_owned(),
+                functions: 0,
+                covered_functions: 0,
+                lines: 0,
+                covered_lines: 0,
+                regions: 0,
+                covered_regions: 0,
+                branches: 0,
+                covered_branches: 0,
+            };
+            for g in &groups {
+                add_group_coverage(&mut total, g);
+            }
+            row(&total);
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+/// Writes the report to --output-path if specified, otherwise to stdout.
+///
+/// If `newline` is `true`, a newline is appended when writing to stdout, for reports that do
+/// not end with a newline (e.g., JSON).
+fn write_report(cx: &Context, out: &str, newline: bool) -> Result<()> {
+    if let Some(output_path) = &cx.args.cov.output_path {
+        create_output_path_parent(output_path)?;
+        fs::write(output_path, out)?;
+        eprintln!();
+        status!("Finished", "report saved to {output_path}");
+    } else {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        if newline {
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Creates the parent directory of --output-path, as --output-dir is created if missing.
+fn create_output_path_parent(output_path: &Utf8Path) -> Result<()> {
+    if let Some(dir) = output_path.parent() {
+        // The parent of a file name without directory components is an empty path.
+        if !dir.as_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_badges(cx: &Context, json: &LlvmCovJsonExport, dir: &Utf8Path) -> Result<()> {
+    let packages = package_line_coverage(cx, json)
+        .into_iter()
+        .map(|(name, (count, covered))| {
+            let percent = if count == 0 { 0_f64 } else { covered as f64 * 100_f64 / count as f64 };
+            (name.to_owned(), percent)
+        })
+        .collect();
+    let total = json.get_coverage_percent(CoverageKind::Lines)?;
+    badge::write(dir, total, &packages, cx.args.cov.badge_thresholds.unwrap_or_default())?;
+    status!("Finished", "badges saved to {dir}");
+    Ok(())
+}
+
+/// Returns package name -> (lines, covered lines) of the workspace members.
+fn package_line_coverage<'a>(
+    cx: &'a Context,
+    json: &LlvmCovJsonExport,
+) -> BTreeMap<&'a str, (u64, u64)> {
+    let members = workspace_member_dirs(cx);
+    let mut counts: BTreeMap<&str, (u64, u64)> =
+        members.iter().map(|&(name, _)| (name, (0, 0))).collect();
+    for (filename, (count, covered)) in json.get_line_counts() {
+        let Some(name) = package_of_file(cx, &members, filename) else { continue };
+        let entry = counts.get_mut(name).unwrap();
+        entry.0 += count;
+        entry.1 += covered;
+    }
+    counts
+}
+
+/// Returns the coverage of each workspace member, in the order of the package names.
+fn package_coverage(cx: &Context, json: &LlvmCovJsonExport) -> Vec<GroupCoverage> {
+    let members = workspace_member_dirs(cx);
+    let mut packages: BTreeMap<&str, GroupCoverage> = BTreeMap::new();
+    for file in json.get_file_coverage() {
+        let Some(name) = package_of_file(cx, &members, &file.name) else { continue };
+        let package = packages.entry(name).or_insert_with(|| GroupCoverage {
+            name: name.to_owned(),
+            functions: 0,
+            covered_functions: 0,
+            lines: 0,
+            covered_lines: 0,
+            regions: 0,
+            covered_regions: 0,
+            branches: 0,
+            covered_branches: 0,
+        });
+        add_group_coverage(package, &file);
+    }
+    packages.into_values().collect()
+}
+
+fn add_group_coverage(total: &mut GroupCoverage, g: &GroupCoverage) {
+    total.functions += g.functions;
+    total.covered_functions += g.covered_functions;
+    total.lines += g.lines;
+    total.covered_lines += g.covered_lines;
+    total.regions += g.regions;
+    total.covered_regions += g.covered_regions;
+    total.branches += g.branches;
+    total.covered_branches += g.covered_branches;
+}
+
+/// Returns (package name, package directory) of the workspace members.
+fn workspace_member_dirs(cx: &Context) -> Vec<(&str, &Utf8Path)> {
+    cx.workspace_members
+        .included
+        .iter()
+        .map(|id| {
+            let package = &cx.ws.metadata.packages[id];
+            (package.name.as_str(), package.manifest_path.parent().unwrap())
+        })
+        .collect()
+}
+
+/// Returns the name of the workspace member that contains the given file.
+fn package_of_file<'a>(
+    cx: &Context,
+    members: &[(&'a str, &Utf8Path)],
+    filename: &str,
+) -> Option<&'a str> {
+    // Paths are relative to the workspace root when --remap-path-prefix is used.
+    let path = cx.ws.metadata.workspace_root.join(filename);
+    // Use the innermost package for files of nested packages.
+    members
+        .iter()
+        .filter(|(_, dir)| path.starts_with(dir))
+        .max_by_key(|(_, dir)| dir.as_str().len())
+        .map(|&(name, _)| name)
+}
+
+/// Opens the report at the given path or URL in a browser.
+fn open_report(cx: &Context, path: &str) -> Result<()> {
+    match &cx.ws.config.doc.browser {
+        Some(browser) => {
+            cmd!(&browser.path)
+                .args(&browser.args)
+                .arg(path)
+                .run()
+                .with_context(|| format!("couldn't open report with {}", browser.path.display()))?;
+        }
+        None => opener::open(path).context("couldn't open report")?,
+    }
+    Ok(())
+}
+
+/// Lists public functions that are not executed by any doctest or example.
+///
+/// Profile data of doctests and examples is identified by the binary IDs embedded in profraw
+/// files and merged separately from the other profile data.
+fn generate_missing_examples_report(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<()> {
+    let example_ids: HashSet<String> = object_binary_ids(cx)?
+        .into_iter()
+        .filter(|(path, _)| {
+            let path = Utf8Path::new(path);
+            path.starts_with(&cx.ws.doctests_dir)
+                || path.parent().and_then(Utf8Path::file_name) == Some("examples")
+        })
+        .map(|(_, id)| id)
+        .collect();
+    if example_ids.is_empty() {
+        bail!(
+            "--missing-examples: no doctest or example binaries with binary IDs found; run tests \
+             with --doctests or examples before generating the report (binary IDs are currently \
+             only available on ELF targets when linked with build IDs)"
+        );
+    }
+    let mut profraw_files = vec![];
+    for path in glob::glob(
+        Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw").as_str(),
+    )?
+    .filter_map(Result::ok)
+    {
+        let ids = binary_id::profraw_binary_ids(cx, &path)?;
+        if ids.iter().any(|id| example_ids.contains(id)) {
+            profraw_files.push(path);
+        }
+    }
+    if profraw_files.is_empty() {
+        warn!("--missing-examples: not found profraw files generated by doctests or examples");
+    }
+    let profdata_file = &cx.ws.target_dir.join(format!("{}-examples.profdata", cx.ws.name));
+    let input_files_path = &cx.ws.target_dir.join(format!("{}-examples-profraw-list", cx.ws.name));
+    merge_profraw_files(cx, profraw_files, input_files_path, profdata_file)
+        .context("failed to merge profile data of doctests and examples")?;
+
+    let json = Format::Json
+        .get_json_with_profdata(cx, profdata_file, object_files, ignore_filename_regex)
+        .context("failed to get json")?;
+    let mut sources = HashMap::new();
+    let mut functions = vec![];
+    for function in json
+        .get_unexecuted_functions(ignore_filename_regex.map(String::as_str))
+        .context("failed to get unexecuted functions")?
+    {
+        let source = match sources.entry(function.filename.clone()) {
+            hash_map::Entry::Occupied(e) => e.into_mut(),
+            hash_map::Entry::Vacant(e) => e.insert(fs::read_to_string(
+                cx.ws.metadata.workspace_root.join(&function.filename),
+            )?),
+        };
+        if is_public_fn(source, function.line, &function.name) {
+            functions.push(function);
+        }
+    }
+
+    let out = if cx.args.cov.json {
+        serde_json::to_string(&functions)?
+    } else {
+        let mut out = String::new();
+        for function in &functions {
+            let _ = writeln!(out, "{function}");
+        }
+        out
+    };
+
+    write_report(cx, &out, cx.args.cov.json)
+}
+
+/// Returns `true` if the function starting at the given line is declared with `pub`.
+///
+/// Functions declared with restricted visibility such as `pub(crate)` are not public.
+fn is_public_fn(source: &str, line: u64, name: &str) -> bool {
+    let Some(name) = name.rsplit("::").next() else { return false };
+    let re = Regex::new(&format!(
+        r#"(?-u:\b)pub[ \t]+((const|async|unsafe|safe)[ \t]+|extern[ \t]+("[^"]*"[ \t]+)?)*fn[ \t]+{}(?-u:\b)"#,
+        regex::escape(name)
+    ))
+    .unwrap();
+    // Attributes and multi-line signatures may precede the first region of the function.
+    let line = usize::try_from(line).unwrap_or(usize::MAX);
+    source.lines().skip(line.saturating_sub(4)).take(line.min(4)).any(|l| re.is_match(l))
+}
+
+/// Returns object file path -> binary ID of the current instrumented objects.
+fn object_binary_ids(cx: &Context) -> Result<BTreeMap<String, String>> {
+    let object_files = object_files(cx).context("failed to collect object files")?;
+    Ok(binary_ids(&object_files))
+}
+
+fn binary_ids(object_files: &[OsString]) -> BTreeMap<String, String> {
+    object_files
+        .iter()
+        .filter_map(|f| {
+            let id = binary_id::elf_build_id(Path::new(f))?;
+            Some((f.to_string_lossy().into_owned(), id))
+        })
+        .collect()
+}
+
+/// Records information about the run (the toolchain, etc.) to include in reports, and warns if
+/// the profraw files left by a previous run were generated with another toolchain.
+fn record_metadata(cx: &Context) -> Result<()> {
+    let mut manifest = Manifest::load(&cx.ws)?.unwrap_or_default();
+    let metadata = manifest::collect_metadata(cx)?;
+    // Switching toolchains (e.g., `cargo +nightly llvm-cov`) rebuilds the objects, but
+    // profraw files left by --no-clean are still those written by binaries of the previous
+    // toolchain, whose LLVM version may use another format of profile data.
+    if let Some(prev) = &manifest.metadata {
+        if prev.rustc_version != metadata.rustc_version && !profraw_files_modified(cx)?.is_empty() {
+            warn!(
+                "profraw files in {} were generated by a previous run with another toolchain \
+                 ({}), but this run uses {}; they may fail to be merged or make the report \
+                 inaccurate, so consider removing them with `cargo llvm-cov clean --profraw-only`",
+                cx.ws.target_dir, prev.rustc_version, metadata.rustc_version
+            );
+        }
+    }
+    manifest.metadata = Some(metadata);
+    manifest.save(&cx.ws)
+}
+
+/// Returns information about the run that generated the coverage data to include in reports.
+fn report_metadata(cx: &Context) -> Result<RunMetadata> {
+    // Use the one recorded when tests were run if available, because the report can be
+    // generated later by the report subcommand.
+    let mut metadata = match Manifest::load(&cx.ws)?.and_then(|m| m.metadata) {
+        Some(metadata) => metadata,
+        None => manifest::collect_metadata(cx)?,
+    };
+    if !cx.args.cov.metadata_timestamp {
+        metadata.timestamp = None;
+    }
+    Ok(metadata)
+}
+
+/// Adds information about the run to the index page of the HTML report.
+fn inject_html_metadata(cx: &Context, path: &Utf8Path) -> Result<()> {
+    let metadata = report_metadata(cx)?;
+    let mut rows = vec![];
+    if let Some(commit) = &metadata.commit {
+        rows.push(("Commit", commit.clone()));
+    }
+    if let Some(branch) = &metadata.branch {
+        rows.push(("Branch", branch.clone()));
+    }
+    rows.push(("Rustc", metadata.rustc_version.clone()));
+    if let Some(llvm_version) = &metadata.llvm_version {
+        rows.push(("LLVM", llvm_version.clone()));
+    }
+    rows.push(("Host", metadata.host.clone()));
+    rows.push(("Target", metadata.target.clone()));
+    let mut features = metadata.features.join(", ");
+    for (enabled, flag) in [
+        (metadata.all_features, "--all-features"),
+        (metadata.no_default_features, "--no-default-features"),
+    ] {
+        if enabled {
+            if !features.is_empty() {
+                features.push_str(", ");
+            }
+            features.push_str(flag);
+        }
+    }
+    if !features.is_empty() {
+        rows.push(("Features", features));
+    }
+    if let Some(timestamp) = metadata.timestamp {
+        rows.push(("Tests run at (Unix time)", timestamp.to_string()));
+    }
+
+    let mut table = String::from("<table class='cargo-llvm-cov-metadata'>");
+    for (name, value) in rows {
+        let _ = write!(table, "<tr><td>{name}</td><td>{}</td></tr>", dashboard::escape(&value));
+    }
+    table.push_str("</table>");
+    let mut html = fs::read_to_string(path)?;
+    // Insert after the "Created: <time>" line of the index page, or at the beginning of the body.
+    let pos = match (html.find("</h4>"), html.find("<body>")) {
+        (Some(pos), _) => pos + "</h4>".len(),
+        (None, Some(pos)) => pos + "<body>".len(),
+        (None, None) => {
+            warn!("failed to find where to insert run metadata in {path}");
+            return Ok(());
+        }
+    };
+    html.insert_str(pos, &table);
+    fs::write(path, html)?;
+    Ok(())
+}
+
+/// Records the objects used by the run in the manifest. `artifacts` is the files that cargo
+/// reported as built by the run, if known.
+///
+/// The manifest is only used to diagnose issues at report time, so this warns instead of
+/// failing the run.
+fn record_object_files(cx: &Context, artifacts: Option<&BTreeSet<String>>) {
+    if let Err(e) = try_record_object_files(cx, artifacts) {
+        warn!("failed to record instrumented objects used by this run: {e:#}");
+    }
+}
+
+fn try_record_object_files(cx: &Context, artifacts: Option<&BTreeSet<String>>) -> Result<()> {
+    let profraw_files = profraw_files_modified(cx)?;
+    // There is nothing to report if no profile data was generated (e.g., --allow-empty).
+    if profraw_files.is_empty() {
+        return Ok(());
+    }
+    let mut manifest = Manifest::load(&cx.ws)?.unwrap_or_default();
+    // This is saved first because object_files uses it.
+    manifest.record_artifacts(artifacts);
+    manifest.save(&cx.ws)?;
+    let object_files = object_files(cx).context("failed to collect object files")?;
+    record_binary_ids(cx, &mut manifest, &object_files);
+    // Objects of the previous runs are cleared when their profraw files are removed.
+    manifest
+        .object_files
+        .extend(object_files.iter().map(|f| cx.current_dir.join(f).to_string_lossy().into_owned()));
+    manifest.workspace_root = Some(cx.ws.metadata.workspace_root.to_string());
+    manifest.profraw_files =
+        profraw_files.into_keys().map(|path| path.to_string_lossy().into_owned()).collect();
+    manifest.profdata_file = Some(cx.ws.profdata_file.to_string());
+    manifest.save(&cx.ws)
+}
+
+/// Records the binary IDs of the instrumented objects to verify profraw files at report time.
+///
+/// This reads all objects, so this is only done when --verify-binary-ids is passed.
+fn record_binary_ids(cx: &Context, manifest: &mut Manifest, object_files: &[OsString]) {
+    manifest.binary_ids = if cx.args.cov.verify_binary_ids {
+        binary_ids(object_files)
+    } else {
+        // Do not leave the ones of the previous runs, which are compared with the current
+        // objects at report time.
+        BTreeMap::new()
+    };
+}
 
-        if let Some(fail_under_regions) = cx.args.cov.fail_under_regions {
-            // Handle --fail-under-regions.
-            let regions_percent = json
-                .get_coverage_percent(CoverageKind::Regions)
-                .context("failed to get region coverage")?;
-            if regions_percent < fail_under_regions {
-                term::error::set(true);
-            }
-        }
+/// Warns about objects used by the previous runs that no longer exist, because the profile
+/// data generated by them cannot be mapped to the source code without them.
+fn check_recorded_object_files(cx: &Context) -> Result<()> {
+    let Some(manifest) = Manifest::load(&cx.ws)? else { return Ok(()) };
+    let missing: Vec<_> = manifest
+        .object_files
+        .iter()
+        .filter(|f| !Path::new(f).exists())
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        warn!(
+            "{} instrumented objects used by previous runs no longer exist; coverage collected \
+             by them will be missing from the report (this may occur if they are removed or \
+             rebuilt to a different path, e.g., by `cargo clean`):\n    {}",
+            missing.len(),
+            missing.join("\n    ")
+        );
+    }
+    Ok(())
+}
 
-        if let Some(fail_uncovered_functions) = cx.args.cov.fail_uncovered_functions {
-            // Handle --fail-uncovered-functions.
-            let uncovered =
-                json.count_uncovered_functions().context("failed to count uncovered functions")?;
-            if uncovered > fail_uncovered_functions {
-                term::error::set(true);
-            }
+/// Warns about objects that are older than their source files, e.g., when sources have been
+/// changed after running tests and `report` subcommand is used, since the coverage of the old
+/// code would be shown for the changed code.
+fn check_stale_objects(cx: &Context, object_files: &[OsString]) {
+    let modified = |path: &Path| fs::metadata(path).ok()?.modified().ok();
+    let root = cx.ws.metadata.workspace_root.as_std_path();
+    let mut stale = vec![];
+    for object in object_files {
+        let object = Path::new(object);
+        let Some(built) = modified(object) else { continue };
+        // Cargo writes the dep-info file next to each artifact (e.g., `foo-<hash>.d` for
+        // `foo-<hash>` or `foo-<hash>.exe`). Relative paths in it are relative to the
+        // workspace root.
+        let Ok(dep_info) = fs::read_to_string(object.with_extension("d")) else { continue };
+        if let Some(source) = cargo::dep_info_dependencies(&dep_info)
+            .into_iter()
+            .find(|source| modified(&root.join(source)).is_some_and(|m| m > built))
+        {
+            stale.push(format!("{} (older than {source})", object.display()));
         }
-        if let Some(fail_uncovered_lines) = cx.args.cov.fail_uncovered_lines {
-            // Handle --fail-uncovered-lines.
-            let uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref());
-            let uncovered = uncovered_files
-                .iter()
-                .fold(0_u64, |uncovered, (_, lines)| uncovered + lines.len() as u64);
+    }
+    if !stale.is_empty() {
+        warn!(
+            "{} instrumented objects are older than their source files, so coverage of the \
+             changed code may be shown incorrectly; run tests again to rebuild them:\n    {}",
+            stale.len(),
+            stale.join("\n    ")
+        );
+    }
+}
 
-            if uncovered > fail_uncovered_lines {
-                term::error::set(true);
+fn verify_binary_ids(cx: &Context, profraw_files: &[PathBuf]) -> Result<()> {
+    let current = object_binary_ids(cx)?;
+    if current.is_empty() {
+        warn!(
+            "--verify-binary-ids: no binary IDs found in instrumented objects; binary IDs are \
+             currently only available on ELF targets when linked with build IDs"
+        );
+        return Ok(());
+    }
+
+    let mut mismatches = vec![];
+    if let Some(manifest) = Manifest::load(&cx.ws)? {
+        for (path, id) in &manifest.binary_ids {
+            if current.get(path).is_some_and(|current| current != id) {
+                mismatches.push(format!("{path} has been rebuilt since tests were run"));
             }
         }
-        if let Some(fail_uncovered_regions) = cx.args.cov.fail_uncovered_regions {
-            // Handle --fail-uncovered-regions.
-            let uncovered =
-                json.count_uncovered_regions().context("failed to count uncovered regions")?;
-            if uncovered > fail_uncovered_regions {
-                term::error::set(true);
-            }
+    }
+    let known: HashSet<&str> = current.values().map(String::as_str).collect();
+    for profraw in profraw_files {
+        let ids = binary_id::profraw_binary_ids(cx, profraw)?;
+        if !ids.is_empty() && !ids.iter().any(|id| known.contains(id.as_str())) {
+            mismatches.push(format!(
+                "{} was not generated by any of the current instrumented objects",
+                profraw.display()
+            ));
         }
+    }
+    if !mismatches.is_empty() {
+        bail!(
+            "profile data does not match the current instrumented objects; consider running \
+             tests again:\n    {}",
+            mismatches.join("\n    ")
+        );
+    }
+    Ok(())
+}
 
-        if cx.args.cov.show_missing_lines {
-            // Handle --show-missing-lines.
-            let uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref());
-            if !uncovered_files.is_empty() {
-                let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
-                writeln!(stdout, "Uncovered Lines:")?;
-                for (file, lines) in &uncovered_files {
-                    let lines: Vec<_> = lines.iter().map(ToString::to_string).collect();
-                    writeln!(stdout, "{file}: {}", lines.join(", "))?;
-                }
-                stdout.flush()?;
+/// Warns about test binaries that ran but did not write profile data.
+///
+/// The profiler runtime writes profile data in the exit hook of the process, so processes that
+/// exit without running exit hooks (e.g., by `std::process::abort`, `libc::_exit`, exec, or
+/// signals) lose their counters. Note that `std::process::exit` runs exit hooks. `previous` is
+/// the result of [`profraw_files_modified`] before running tests.
+fn check_test_binaries_profile_data(
+    cx: &Context,
+    previous: &BTreeMap<PathBuf, Option<SystemTime>>,
+    binaries: Vec<TestBinary>,
+) -> Result<()> {
+    // Binary IDs are currently only available on ELF targets.
+    let mut binaries: Vec<_> = binaries
+        .into_iter()
+        .filter_map(|binary| Some((binary_id::elf_build_id(binary.path.as_std_path())?, binary)))
+        .collect();
+    let written: Vec<_> = profraw_files_modified(cx)?
+        .into_iter()
+        .filter(|(path, modified)| previous.get(path) != Some(modified))
+        .map(|(path, _)| path)
+        .collect();
+    // Each process writes its own profraw file (`%p` in LLVM_PROFILE_FILE), so if there are at
+    // least as many files as binaries, all of them have most likely written profile data. Do
+    // not run llvm-profdata for each file in that case.
+    if written.len() >= binaries.len() {
+        return Ok(());
+    }
+    for profraw in written {
+        let ids = match binary_id::profraw_binary_ids(cx, &profraw) {
+            Ok(ids) => ids,
+            Err(e) => {
+                // e.g., a truncated file written by a process that crashed.
+                warn!("failed to read binary IDs from {}: {e:#}", profraw.display());
+                continue;
             }
+        };
+        binaries.retain(|(id, _)| !ids.contains(id));
+        if binaries.is_empty() {
+            return Ok(());
         }
     }
 
-    if cx.args.cov.open {
-        let path = &cx.args.cov.output_dir.as_ref().unwrap().join("html/index.html");
-        status!("Opening", "{path}");
-        open_report(cx, path)?;
+    let mut names = String::new();
+    for (_, binary) in &binaries {
+        let _ = write!(names, "\n    {} ({})", binary.name, binary.path);
     }
+    warn!(
+        "{} test binaries ran but did not write profile data, so their coverage is missing:{names}\n\
+         this happens when the process exits without running exit hooks, e.g., by \
+         `std::process::abort`, `panic = \"abort\"`, `libc::_exit`, exec, or signals; consider \
+         passing --profile-continuous to write counters while the process is running, or calling \
+         `__llvm_profile_write_file` before such exits (see \"Get coverage of processes that \
+         abort or exec\" in the README)",
+        binaries.len()
+    );
     Ok(())
 }
 
-fn open_report(cx: &Context, path: &Utf8Path) -> Result<()> {
-    match &cx.ws.config.doc.browser {
-        Some(browser) => {
-            cmd!(&browser.path)
-                .args(&browser.args)
-                .arg(path)
-                .run()
-                .with_context(|| format!("couldn't open report with {}", browser.path.display()))?;
-        }
-        None => opener::open(path).context("couldn't open report")?,
+/// Returns the profraw files in the target directory and their modification times.
+fn profraw_files_modified(cx: &Context) -> Result<BTreeMap<PathBuf, Option<SystemTime>>> {
+    let pattern =
+        Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw");
+    Ok(glob::glob(pattern.as_str())?
+        .filter_map(Result::ok)
+        .map(|path| {
+            let modified = fs::metadata(&path).ok().and_then(|m| m.modified().ok());
+            (path, modified)
+        })
+        .collect())
+}
+
+/// Checks that running tests or binaries generated profraw files, and reports likely
+/// causes if none were generated, instead of generating an empty report (or a report from
+/// only the profraw files of previous runs). `previous` is the result of
+/// [`profraw_files_modified`] before running them.
+fn check_profraw_files(
+    cx: &Context,
+    previous: &BTreeMap<PathBuf, Option<SystemTime>>,
+) -> Result<()> {
+    let current = profraw_files_modified(cx)?;
+    // With --no-clean, profraw files generated by previous runs are left in the target
+    // directory and merged together with the ones generated by this run. Each process writes
+    // its own file (`%p` and `%m` in LLVM_PROFILE_FILE), so they are not overwritten, but they
+    // may be for binaries that no longer exist or for code that has since been changed.
+    let stale = current
+        .iter()
+        .filter(|&(path, modified)| {
+            modified.is_some() && previous.get(path).is_some_and(|prev| prev == modified)
+        })
+        .count();
+    if stale != current.len() {
+        if stale != 0 && !cx.args.cov.no_report {
+            info!(
+                "{stale} of {} profraw files were generated by previous runs and will also be \
+                 merged into the report; run `cargo llvm-cov clean --profraw-only` to remove them",
+                current.len()
+            );
+        }
+        return Ok(());
     }
-    Ok(())
+
+    let mut hints = String::new();
+    let target = cx.ws.target_for_config.triple();
+    let sysroot = Utf8PathBuf::from(cx.ws.rustc_print("sysroot")?);
+    if !doctor::has_profiler_builtins(
+        sysroot.join("lib/rustlib").join(target).join("lib").as_std_path(),
+    ) {
+        let _ = write!(
+            hints,
+            "\n  - the profiler runtime is not available for {target}; binaries for this target \
+             cannot generate profile data"
+        );
+    }
+    let has_target_selection = cx.args.lib
+        || cx.args.bins
+        || cx.args.examples
+        || cx.args.tests
+        || cx.args.benches
+        || cx.args.doc
+        || !cx.args.bin.is_empty()
+        || !cx.args.example.is_empty()
+        || !cx.args.test.is_empty()
+        || !cx.args.bench.is_empty()
+        || !cx.args.exclude_from_test.is_empty();
+    if has_target_selection || !cx.args.rest.is_empty() {
+        let _ = write!(
+            hints,
+            "\n  - the target selection or test filters may have excluded all test binaries"
+        );
+    }
+    if cx.ws.config.runner(&cx.ws.target_for_config)?.is_some() {
+        let _ = write!(
+            hints,
+            "\n  - a target runner is configured for {target}; the runner must pass \
+             LLVM_PROFILE_FILE to binaries and leave the generated profraw files in {}",
+            cx.ws.target_dir
+        );
+    }
+    let _ = write!(
+        hints,
+        "\n  - binaries with custom harness (`harness = false`) or custom test runners may have \
+         cleared LLVM_PROFILE_FILE, or exited abnormally (e.g., by abort or signal) before profile \
+         data was written"
+    );
+    let _ = write!(
+        hints,
+        "\n  - binaries that are not built by cargo-llvm-cov (e.g., ones spawned by path from \
+         outside of {}) are not instrumented",
+        cx.ws.target_dir
+    );
+
+    if cx.args.cov.allow_empty {
+        info!("no profraw files were generated by running tests or binaries (--allow-empty)");
+        Ok(())
+    } else if cx.args.cov.no_report {
+        warn!(
+            "no profraw files were generated by running tests or binaries; likely causes:{hints}"
+        );
+        Ok(())
+    } else {
+        term::fail(ExitStatus::NoProfileData);
+        bail!("no profraw files were generated by running tests or binaries; likely causes:{hints}")
+    }
+}
+
+/// Returns `true` if there are profraw files to merge, or profile data merged before.
+fn has_profile_data(cx: &Context) -> Result<bool> {
+    if cx.ws.profdata_file.exists()
+        || !cx.args.cov.profraw_glob.is_empty()
+        || cx.args.subcommand.read_nextest_archive()
+    {
+        return Ok(true);
+    }
+    Ok(cx.ws.target_dir.is_dir()
+        && fs::read_dir(&cx.ws.target_dir)?.any(|entry| {
+            entry.is_ok_and(|entry| entry.path().extension().is_some_and(|ext| ext == "profraw"))
+        }))
+}
+
+/// Returns the number of functions in the merged profile data, or `None` if it is unknown.
+fn profdata_functions(cx: &Context) -> Option<u64> {
+    let out = cx.process(&cx.llvm_profdata).arg("show").arg(&cx.ws.profdata_file).read().ok()?;
+    out.lines().find_map(|line| line.trim().strip_prefix("Total functions:")?.trim().parse().ok())
 }
 
 fn merge_profraw(cx: &Context) -> Result<()> {
     // Convert raw profile data.
-    let profraw_files = glob::glob(
+    let mut profraw_files = glob::glob(
         Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw").as_str(),
     )?
     .filter_map(Result::ok)
     .collect::<Vec<_>>();
+    if !cx.args.cov.profraw_glob.is_empty() {
+        // Artifacts from multiple jobs are often downloaded into nested or overlapping
+        // directories, so dedup files matched by multiple patterns.
+        let mut seen: HashSet<_> =
+            profraw_files.iter().filter_map(|path| fs::canonicalize(path).ok()).collect();
+        for pattern in &cx.args.cov.profraw_glob {
+            let mut found = 0;
+            let mut duplicates = 0;
+            for path in glob::glob(pattern)
+                .with_context(|| format!("invalid pattern in --profraw-glob: {pattern}"))?
+                .filter_map(Result::ok)
+            {
+                if !path.is_file() {
+                    continue;
+                }
+                if seen.insert(fs::canonicalize(&path)?) {
+                    profraw_files.push(path);
+                    found += 1;
+                } else {
+                    duplicates += 1;
+                }
+            }
+            if found == 0 && duplicates == 0 {
+                warn!("no profraw files matched --profraw-glob pattern '{pattern}'");
+            } else if duplicates == 0 {
+                info!("found {found} profraw files matching '{pattern}'");
+            } else {
+                info!(
+                    "found {found} profraw files matching '{pattern}' ({duplicates} duplicates skipped)"
+                );
+            }
+        }
+    }
+    if cx.args.cov.verify_binary_ids && !profraw_files.is_empty() {
+        verify_binary_ids(cx, &profraw_files)?;
+    }
     if profraw_files.is_empty() {
         if cx.ws.profdata_file.exists() {
             return Ok(());
@@ -669,6 +2424,102 @@ fn merge_profraw(cx: &Context) -> Result<()> {
             cx.ws.target_dir
         );
     }
+    let input_files_path = &cx.ws.target_dir.join(format!("{}-profraw-list", cx.ws.name));
+    // `cargo llvm-cov report` is often called multiple times for the results of the same run
+    // to generate reports in different formats, so reuse the profile data if none of the
+    // profraw files have changed since the last merge.
+    if matches!(cx.args.subcommand, Subcommand::Report { .. })
+        && cx.args.cov.failure_mode.is_none()
+        && cx.llvm_profdata_flags.is_none()
+        && is_profdata_up_to_date(&cx.ws.profdata_file, input_files_path, &profraw_files)
+    {
+        if term::verbose() {
+            info!("reusing {} because profraw files have not changed", cx.ws.profdata_file);
+        }
+        return Ok(());
+    }
+    merge_profraw_files(cx, profraw_files, input_files_path, &cx.ws.profdata_file)
+}
+
+/// Returns `true` if `profdata_file` was merged from the same list of profraw files, and
+/// none of them have been modified since then.
+fn is_profdata_up_to_date(
+    profdata_file: &Utf8Path,
+    input_files_path: &Utf8Path,
+    profraw_files: &[PathBuf],
+) -> bool {
+    let modified = |path: &Path| fs::metadata(path).ok()?.modified().ok();
+    let Some(merged) = modified(profdata_file.as_std_path()) else { return false };
+    let Ok(input_files) = fs::read_to_string(input_files_path) else { return false };
+    input_files.lines().count() == profraw_files.len()
+        && input_files.lines().zip(profraw_files).all(|(input, path)| {
+            path.to_str() == Some(input) && modified(path).is_some_and(|m| m <= merged)
+        })
+}
+
+/// Merges the profraw files into `profdata_file`.
+///
+/// If merging fails and --failure-mode is not passed, merging is retried without the files
+/// that cannot be read, e.g., ones truncated by a process that crashed or was killed while
+/// writing them, so that the report can still be generated from the others.
+fn merge_profraw_files(
+    cx: &Context,
+    profraw_files: Vec<PathBuf>,
+    input_files_path: &Utf8Path,
+    profdata_file: &Utf8Path,
+) -> Result<()> {
+    let Err(e) = run_profdata_merge(cx, &profraw_files, input_files_path, profdata_file) else {
+        return Ok(());
+    };
+    // None of the files can be read by llvm-profdata of another LLVM version.
+    if let Some(hint) = llvm_version_mismatch(cx) {
+        return Err(e.context(hint));
+    }
+    if cx.args.cov.failure_mode.is_some() {
+        return Err(e);
+    }
+    let (valid, invalid): (Vec<_>, Vec<_>) = profraw_files.into_iter().partition(|path| {
+        cx.process(&cx.llvm_profdata).arg("show").arg(path).run_with_output().is_ok()
+    });
+    if invalid.is_empty() || valid.is_empty() {
+        return Err(e);
+    }
+    let mut list = String::new();
+    for path in &invalid {
+        let _ = write!(list, "\n  {}", path.display());
+    }
+    warn!(
+        "skipped {} of {} profraw files that cannot be merged; this may occur if processes \
+         crashed or were killed while writing profile data:{list}",
+        invalid.len(),
+        invalid.len() + valid.len()
+    );
+    run_profdata_merge(cx, &valid, input_files_path, profdata_file)
+}
+
+/// Returns a hint to fix the problem if llvm-profdata uses another major version of LLVM than
+/// rustc, since the format of profraw files depends on the LLVM version.
+fn llvm_version_mismatch(cx: &Context) -> Option<String> {
+    let rustc = doctor::llvm_major_version(&cx.ws.rustc().arg("-vV").read().ok()?)?;
+    let tools =
+        doctor::llvm_major_version(&cx.process(&cx.llvm_profdata).arg("--version").read().ok()?)?;
+    if rustc == tools {
+        return None;
+    }
+    let fix = if env::var_os("LLVM_PROFDATA").is_some() {
+        "set LLVM_COV and LLVM_PROFDATA to llvm-tools of the same LLVM version as rustc"
+    } else {
+        "reinstall llvm-tools with `rustup component add llvm-tools-preview`"
+    };
+    Some(format!("llvm-profdata uses LLVM {tools}, but rustc uses LLVM {rustc}; {fix}"))
+}
+
+fn run_profdata_merge(
+    cx: &Context,
+    profraw_files: &[PathBuf],
+    input_files_path: &Utf8Path,
+    profdata_file: &Utf8Path,
+) -> Result<()> {
     let mut input_files = String::new();
     for path in profraw_files {
         input_files.push_str(path.to_str().with_context(|| {
@@ -679,19 +2530,14 @@ fn merge_profraw(cx: &Context) -> Result<()> {
         })?);
         input_files.push('\n');
     }
-    let input_files_path = &cx.ws.target_dir.join(format!("{}-profraw-list", cx.ws.name));
     fs::write(input_files_path, input_files)?;
     let mut cmd = cx.process(&cx.llvm_profdata);
-    cmd.args(["merge", "-sparse"])
-        .arg("-f")
-        .arg(input_files_path)
-        .arg("-o")
-        .arg(&cx.ws.profdata_file);
+    cmd.args(["merge", "-sparse"]).arg("-f").arg(input_files_path).arg("-o").arg(profdata_file);
     if let Some(mode) = &cx.args.cov.failure_mode {
         cmd.arg(format!("-failure-mode={mode}"));
     }
     if let Some(flags) = &cx.llvm_profdata_flags {
-        cmd.args(flags.split(' ').filter(|s| !s.trim_start().is_empty()));
+        cmd.args(flags);
     }
     if term::verbose() {
         status!("Running", "{cmd}");
@@ -700,6 +2546,21 @@ fn merge_profraw(cx: &Context) -> Result<()> {
     Ok(())
 }
 
+/// Returns the kinds of the crates built for the host that cargo-llvm-cov instruments by
+/// using itself as a rustc wrapper. See wrapper module for details.
+fn host_crates_to_instrument(cx: &Context) -> Vec<&'static str> {
+    let mut kinds = vec![];
+    if cx.args.target.is_some() && !cx.args.coverage_target_only {
+        if cx.args.cov.include_build_script {
+            kinds.push("build-script");
+        }
+        if cx.args.cov.include_proc_macros {
+            kinds.push("proc-macro");
+        }
+    }
+    kinds
+}
+
 fn object_files(cx: &Context) -> Result<Vec<OsString>> {
     fn walk_target_dir<'a>(
         cx: &'a Context,
@@ -759,6 +2620,10 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
         if ext == "d" || ext == "rlib" || ext == "rmeta" || f.ends_with(".cargo-lock") {
             return false;
         }
+        if wasm::is_wasm(cx) {
+            // Wasm modules are not supported by llvm-cov, see wasm::object_files.
+            return false;
+        }
         let target_is_windows = cx.ws.target_for_config.triple().contains("-windows");
         if target_is_windows
             && !(ext.eq_ignore_ascii_case("exe") || ext.eq_ignore_ascii_case("dll"))
@@ -788,6 +2653,20 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
     }
 
     let re = pkg_hash_re(&cx.ws)?;
+    // If the files built by the runs are known, objects in the target directory that are not
+    // among them (e.g., test binaries built with another set of features) are left over from
+    // previous builds, and only make their code appear as not covered.
+    let artifacts = if cx.args.subcommand.read_nextest_archive() {
+        None
+    } else {
+        Manifest::load(&cx.ws)?.and_then(|m| m.artifacts).filter(|a| !a.is_empty())
+    };
+    let is_built = |f: &Path| {
+        artifacts.as_ref().is_none_or(|a| f.to_str().is_some_and(|f| a.contains(f)))
+            // Shared libraries loaded at runtime (e.g., plugins loaded by libloading) may be
+            // built by the tests themselves, which cargo does not report.
+            || is_shared_library(f)
+    };
     let mut files = vec![];
     let mut searched_dir = String::new();
     // To support testing binary crate like tests that use the CARGO_BIN_EXE
@@ -857,14 +2736,9 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
             }
         }
     }
+    // Directories of the artifacts built for the host when --target is used.
+    let mut host_dirs = vec![];
     if !auto_detect_profile {
-        // https://doc.rust-lang.org/nightly/cargo/reference/build-cache.html
-        if let Some(target) = &cx.args.target {
-            target_dir.push(target);
-            if let Some(build_dir) = &mut build_dir {
-                build_dir.push(target);
-            }
-        }
         // https://doc.rust-lang.org/nightly/cargo/reference/profiles.html#custom-profiles
         let profile = match cx.args.cargo_profile.as_deref() {
             None if cx.args.release => "release",
@@ -872,6 +2746,19 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
             None | Some("dev" | "test") => "debug",
             Some(p) => p,
         };
+        // https://doc.rust-lang.org/nightly/cargo/reference/build-cache.html
+        if let Some(target) = &cx.args.target {
+            if !host_crates_to_instrument(cx).is_empty() {
+                host_dirs.push(target_dir.join(profile));
+                if let Some(build_dir) = build_dir.as_ref().filter(|&d| *d != target_dir) {
+                    host_dirs.push(build_dir.join(profile));
+                }
+            }
+            target_dir.push(target);
+            if let Some(build_dir) = &mut build_dir {
+                build_dir.push(target);
+            }
+        }
         target_dir.push(profile);
         if let Some(build_dir) = &mut build_dir {
             build_dir.push(profile);
@@ -879,7 +2766,7 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
     }
     for f in walk_target_dir(cx, &target_dir) {
         let f = f.path();
-        if is_object(cx, f) {
+        if is_object(cx, f) && is_built(f) {
             if let Some(file_stem) = fs::file_stem_recursive(f).unwrap().to_str() {
                 if re.is_match(file_stem) {
                     files.push(make_relative(cx, f).to_owned().into_os_string());
@@ -887,12 +2774,17 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
             }
         }
     }
+    if wasm::is_wasm(cx) {
+        for f in wasm::object_files(cx, &target_dir, &re)? {
+            files.push(make_relative(cx, &f).to_owned().into_os_string());
+        }
+    }
     searched_dir.push_str(target_dir.as_str());
     if let Some(build_dir) = &build_dir {
         if target_dir != *build_dir {
             for f in walk_target_dir(cx, build_dir) {
                 let f = f.path();
-                if is_object(cx, f) {
+                if is_object(cx, f) && is_built(f) {
                     if let Some(file_stem) = fs::file_stem_recursive(f).unwrap().to_str() {
                         if re.is_match(file_stem) {
                             files.push(make_relative(cx, f).to_owned().into_os_string());
@@ -904,10 +2796,25 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
             searched_dir.push_str(build_dir.as_str());
         }
     }
+    for host_dir in &host_dirs {
+        for f in walk_target_dir(cx, host_dir) {
+            let f = f.path();
+            if is_object(cx, f) && is_built(f) {
+                if let Some(file_stem) = fs::file_stem_recursive(f).unwrap().to_str() {
+                    if re.is_match(file_stem) {
+                        files.push(make_relative(cx, f).to_owned().into_os_string());
+                    }
+                }
+            }
+        }
+        searched_dir.push(',');
+        searched_dir.push_str(host_dir.as_str());
+    }
     if cx.args.doctests {
         for f in glob::glob(
             Utf8Path::new(&glob::Pattern::escape(cx.ws.doctests_dir.as_str()))
-                .join("*/rust_out")
+                // rust_out.exe on Windows
+                .join("*/rust_out*")
                 .as_str(),
         )?
         .filter_map(Result::ok)
@@ -959,15 +2866,30 @@ fn object_files(cx: &Context) -> Result<Vec<OsString>> {
     files.sort_unstable();
 
     if files.is_empty() {
+        let mut hint = String::new();
+        if let Some(manifest) = Manifest::load(&cx.ws)? {
+            let diff = diff_env(&manifest.build_env, &build_env(cx)?);
+            if !manifest.build_env.is_empty() && !diff.is_empty() {
+                hint = format!(
+                    "\nnote: build environment differs from the one when tests were last run:{diff}"
+                );
+            }
+        }
         bail!(
             "not found object files (searched directories: {searched_dir}); this may occur if \
              show-env subcommand is used incorrectly (see docs or other warnings), or unsupported \
-             commands or configs are used",
+             commands or configs are used{hint}",
         );
     }
     Ok(files)
 }
 
+/// Returns `true` if the given file is a shared library (e.g., a `cdylib` crate).
+fn is_shared_library(f: &Path) -> bool {
+    f.extension()
+        .is_some_and(|ext| ext == "so" || ext == "dylib" || ext.eq_ignore_ascii_case("dll"))
+}
+
 fn pkg_hash_re(ws: &Workspace) -> Result<RegexVec> {
     let mut targets = BTreeSet::new();
     for id in &ws.metadata.workspace_members {
@@ -1018,6 +2940,16 @@ enum Format {
     Cobertura,
     /// `llvm-cov show -format=lcov` later converted to Codecov JSON
     Codecov,
+    /// `llvm-cov export -format=text` later converted to reviewdog RDJSON
+    RdJson,
+    /// `llvm-cov export -format=text` later converted to Checkstyle XML
+    Checkstyle,
+    /// `llvm-cov export -format=text` later converted to Coveralls JSON
+    Coveralls,
+    /// `llvm-cov export -format=text` later converted to SARIF
+    Sarif,
+    Jacoco,
+    SonarQube,
     /// `llvm-cov show -format=text`
     Text,
     /// `llvm-cov show -format=html`
@@ -1034,6 +2966,18 @@ impl Format {
             Self::Cobertura
         } else if cx.args.cov.codecov {
             Self::Codecov
+        } else if cx.args.cov.rdjson {
+            Self::RdJson
+        } else if cx.args.cov.checkstyle {
+            Self::Checkstyle
+        } else if cx.args.cov.coveralls {
+            Self::Coveralls
+        } else if cx.args.cov.sarif {
+            Self::Sarif
+        } else if cx.args.cov.jacoco {
+            Self::Jacoco
+        } else if cx.args.cov.sonarqube {
+            Self::SonarQube
         } else if cx.args.cov.text {
             Self::Text
         } else if cx.args.cov.html {
@@ -1046,15 +2990,35 @@ impl Format {
     const fn llvm_cov_args(self) -> &'static [&'static str] {
         match self {
             Self::None => &["report"],
-            Self::Json | Self::Codecov => &["export", "-format=text"],
-            Self::LCov | Self::Cobertura => &["export", "-format=lcov"],
+            Self::Json
+            | Self::Cobertura
+            | Self::Codecov
+            | Self::RdJson
+            | Self::Checkstyle
+            | Self::Coveralls
+            | Self::Sarif
+            | Self::Jacoco
+            | Self::SonarQube => &["export", "-format=text"],
+            Self::LCov => &["export", "-format=lcov"],
             Self::Text => &["show", "-format=text"],
             Self::Html => &["show", "-format=html"],
         }
     }
 
     fn use_color(self, cx: &Context) -> Option<&'static str> {
-        if matches!(self, Self::Json | Self::LCov | Self::Html) {
+        if matches!(
+            self,
+            Self::Json
+                | Self::LCov
+                | Self::Cobertura
+                | Self::RdJson
+                | Self::Checkstyle
+                | Self::Coveralls
+                | Self::Sarif
+                | Self::Jacoco
+                | Self::SonarQube
+                | Self::Html
+        ) {
             // `llvm-cov export` doesn't have `-use-color` flag.
             // https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export
             // Color output cannot be disabled when generating html.
@@ -1070,12 +3034,13 @@ impl Format {
         }
     }
 
-    fn generate_report(
+    /// Returns the `llvm-cov` command to generate the report of this format.
+    fn llvm_cov_cmd(
         self,
         cx: &Context,
         object_files: &[OsString],
         ignore_filename_regex: Option<&str>,
-    ) -> Result<()> {
+    ) -> ProcessBuilder {
         let mut cmd = cx.process(&cx.llvm_cov);
 
         cmd.args(self.llvm_cov_args());
@@ -1086,6 +3051,9 @@ impl Format {
             cmd.arg("-ignore-filename-regex");
             cmd.arg(ignore_filename_regex);
         }
+        if let Some(changed_files) = &cx.changed_files {
+            cmd.args(changed_files.borrow().iter().map(|f| f.as_os_str()));
+        }
 
         match self {
             Self::Text | Self::Html => {
@@ -1116,7 +3084,16 @@ impl Format {
                     }
                 }
             }
-            Self::Json | Self::LCov | Self::Cobertura | Self::Codecov => {
+            Self::Json
+            | Self::LCov
+            | Self::Cobertura
+            | Self::Codecov
+            | Self::RdJson
+            | Self::Checkstyle
+            | Self::Coveralls
+            | Self::Sarif
+            | Self::Jacoco
+            | Self::SonarQube => {
                 if cx.args.cov.summary_only {
                     cmd.arg("-summary-only");
                 }
@@ -1127,57 +3104,40 @@ impl Format {
             Self::None => {}
         }
 
+        cmd.args(&cx.path_equivalence);
         if let Some(flags) = &cx.llvm_cov_flags {
-            cmd.args(flags.split(' ').filter(|s| !s.trim_start().is_empty()));
+            cmd.args(flags);
         }
+        cmd
+    }
 
-        if cx.args.cov.cobertura {
-            if term::verbose() {
-                status!("Running", "{cmd}");
-            }
-            let lcov = cmd.read()?;
-            // Convert to XML
-            let cdata = lcov2cobertura::parse_lines(
-                lcov.as_bytes().lines(),
-                &cx.ws.metadata.workspace_root,
-                &[],
-            )?;
-            let demangler = lcov2cobertura::RustDemangler::new();
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .context("SystemTime before UNIX EPOCH!")?
-                .as_secs();
-            let out = lcov2cobertura::coverage_to_string(&cdata, now, demangler)?;
-
-            if let Some(output_path) = &cx.args.cov.output_path {
-                fs::write(output_path, out)?;
-                eprintln!();
-                status!("Finished", "report saved to {output_path}");
-            } else {
-                // write XML to stdout
-                println!("{out}");
-            }
-            return Ok(());
-        }
+    fn generate_report(
+        self,
+        cx: &Context,
+        object_files: &[OsString],
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<()> {
+        let cmd = self.llvm_cov_cmd(cx, object_files, ignore_filename_regex);
 
-        if cx.args.cov.codecov {
+        if matches!(
+            self,
+            Self::Cobertura
+                | Self::Codecov
+                | Self::RdJson
+                | Self::Checkstyle
+                | Self::Coveralls
+                | Self::Sarif
+                | Self::Jacoco
+                | Self::SonarQube
+        ) {
             if term::verbose() {
                 status!("Running", "{cmd}");
             }
             let cov = cmd.read()?;
-            let cov: LlvmCovJsonExport = serde_json::from_str(&cov)?;
-            let cov = CodeCovJsonExport::from_llvm_cov_json_export(cov, ignore_filename_regex);
-            let out = serde_json::to_string(&cov)?;
-
-            if let Some(output_path) = &cx.args.cov.output_path {
-                fs::write(output_path, out)?;
-                eprintln!();
-                status!("Finished", "report saved to {output_path}");
-            } else {
-                // write JSON to stdout
-                println!("{out}");
-            }
-            return Ok(());
+            let mut cov: LlvmCovJsonExport = serde_json::from_str(&cov)?;
+            exclude::json(cx, &mut cov)?;
+            let (out, newline) = self.convert_json(cx, cov, ignore_filename_regex)?;
+            return write_report(cx, &out, newline);
         }
 
         if let Some(output_path) = &cx.args.cov.output_path {
@@ -1186,10 +3146,13 @@ impl Format {
             }
 
             let out = cmd.read()?;
+            create_output_path_parent(output_path)?;
             if self == Self::Json {
                 let mut cov = serde_json::from_str::<LlvmCovJsonExport>(&out)?;
-                cov.inject(cx.ws.current_manifest.clone());
-                fs::write(output_path, serde_json::to_string(&cov)?)?;
+                exclude::json(cx, &mut cov)?;
+                fs::write(output_path, self.convert_json(cx, cov, ignore_filename_regex)?.0)?;
+            } else if self == Self::LCov {
+                fs::write(output_path, exclude::lcov(cx, out)?)?;
             } else {
                 fs::write(output_path, out)?;
             }
@@ -1206,17 +3169,28 @@ impl Format {
         if self == Self::Json {
             let out = cmd.read()?;
             let mut cov = serde_json::from_str::<LlvmCovJsonExport>(&out)?;
+            exclude::json(cx, &mut cov)?;
             cov.inject(cx.ws.current_manifest.clone());
+            cov.inject_metadata(report_metadata(cx)?);
+            if cx.args.cov.complexity_weighted {
+                cov.inject_complexity_weighted_coverage(ignore_filename_regex)?;
+            }
 
             let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written many times.
             serde_json::to_writer(&mut stdout, &cov)?;
             stdout.flush()?;
+        } else if self == Self::LCov {
+            print!("{}", exclude::lcov(cx, cmd.read()?)?);
         } else {
             cmd.run()?;
         }
 
         if matches!(self, Self::Html | Self::Text) {
             if let Some(output_dir) = &cx.args.cov.output_dir {
+                if self == Self::Html {
+                    inject_html_metadata(cx, &output_dir.join("html/index.html"))?;
+                    html::improve_accessibility(&output_dir.join("html"))?;
+                }
                 eprintln!();
                 if self == Self::Html {
                     status!("Finished", "report saved to {}", output_dir.join("html"));
@@ -1228,12 +3202,100 @@ impl Format {
         Ok(())
     }
 
+    /// Converts the result of `llvm-cov export -format=text` (after [`exclude::json`]) to the
+    /// report of this format. Returns the report and whether a newline should be appended when
+    /// the report is printed to stdout.
+    fn convert_json(
+        self,
+        cx: &Context,
+        mut cov: LlvmCovJsonExport,
+        ignore_filename_regex: Option<&str>,
+    ) -> Result<(String, bool)> {
+        let now = || -> Result<u64> {
+            Ok(SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .context("SystemTime before UNIX EPOCH!")?
+                .as_secs())
+        };
+        let workspace_root = cx.ws.metadata.workspace_root.as_str();
+        let report = match self {
+            Self::Json => {
+                cov.inject(cx.ws.current_manifest.clone());
+                cov.inject_metadata(report_metadata(cx)?);
+                if cx.args.cov.complexity_weighted {
+                    cov.inject_complexity_weighted_coverage(ignore_filename_regex)?;
+                }
+                (serde_json::to_string(&cov)?, false)
+            }
+            // This is used only when multiple reports are generated at once, as --lcov alone
+            // uses `llvm-cov export -format=lcov`.
+            Self::LCov => (cov.to_lcov(), false),
+            Self::Cobertura => {
+                let cobertura = CoberturaExport::from_llvm_cov_json_export(
+                    &cov,
+                    ignore_filename_regex,
+                    workspace_root,
+                    now()?,
+                )?;
+                (cobertura.to_xml(), false)
+            }
+            Self::SonarQube => {
+                let sonarqube = SonarQubeExport::from_llvm_cov_json_export(
+                    &cov,
+                    ignore_filename_regex,
+                    workspace_root,
+                )?;
+                (sonarqube.to_xml(), false)
+            }
+            Self::RdJson => {
+                let rdjson = RdJsonExport::from_llvm_cov_json_export(&cov, ignore_filename_regex)?;
+                (serde_json::to_string(&rdjson)?, true)
+            }
+            Self::Checkstyle => {
+                let checkstyle =
+                    CheckstyleExport::from_llvm_cov_json_export(&cov, ignore_filename_regex)?;
+                (checkstyle.to_xml(), false)
+            }
+            Self::Coveralls => (coveralls::export(cx, &cov)?, true),
+            Self::Sarif => {
+                let sarif = SarifExport::from_llvm_cov_json_export(&cov, ignore_filename_regex)?;
+                (serde_json::to_string(&sarif)?, true)
+            }
+            Self::Jacoco => {
+                let jacoco = JacocoExport::from_llvm_cov_json_export(
+                    &cov,
+                    ignore_filename_regex,
+                    workspace_root,
+                    now()?,
+                )?;
+                (jacoco.to_xml(), false)
+            }
+            Self::Codecov => {
+                let codecov =
+                    CodeCovJsonExport::from_llvm_cov_json_export(cov, ignore_filename_regex)?;
+                (serde_json::to_string(&codecov)?, true)
+            }
+            Self::None | Self::Text | Self::Html => bail!("{self:?} is not generated from JSON"),
+        };
+        Ok(report)
+    }
+
     /// Generates JSON to perform further analysis on it.
     fn get_json(
         self,
         cx: &Context,
         object_files: &[OsString],
         ignore_filename_regex: Option<&String>,
+    ) -> Result<LlvmCovJsonExport> {
+        self.get_json_with_profdata(cx, &cx.ws.profdata_file, object_files, ignore_filename_regex)
+    }
+
+    fn get_json_with_profdata(
+        self,
+        cx: &Context,
+        profdata_file: &Utf8Path,
+        object_files: &[OsString],
+        ignore_filename_regex: Option<&String>,
     ) -> Result<LlvmCovJsonExport> {
         if let Self::Json = self {
         } else {
@@ -1242,22 +3304,69 @@ impl Format {
 
         let mut cmd = cx.process(&cx.llvm_cov);
         cmd.args(self.llvm_cov_args());
-        cmd.arg(format!("-instr-profile={}", cx.ws.profdata_file));
+        cmd.arg(format!("-instr-profile={profdata_file}"));
         cmd.args(object_files.iter().flat_map(|f| [OsStr::new("-object"), f]));
         if let Some(ignore_filename_regex) = ignore_filename_regex {
             cmd.arg("-ignore-filename-regex");
             cmd.arg(ignore_filename_regex);
         }
+        cmd.args(&cx.path_equivalence);
+        if let Some(changed_files) = &cx.changed_files {
+            cmd.args(changed_files.borrow().iter().map(|f| f.as_os_str()));
+        }
         if term::verbose() {
             status!("Running", "{cmd}");
         }
         let cmd_out = cmd.read()?;
-        let json = serde_json::from_str::<LlvmCovJsonExport>(&cmd_out)
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&cmd_out)
             .context("failed to parse json from llvm-cov")?;
+        exclude::json(cx, &mut json)?;
+        if cx.args.cov.group_closures {
+            json.group_closures();
+        }
         Ok(json)
     }
 }
 
+/// Removes the files that are not in the coverage mapping from the files changed since the
+/// revision specified by `--changed-since`, and returns whether any changed files remain.
+///
+/// llvm-cov warns about the given source files that are not in the coverage mapping, and
+/// reports all files if none of them are.
+fn retain_mapped_changed_files(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&String>,
+) -> Result<bool> {
+    let Some(changed_files) = &cx.changed_files else { return Ok(true) };
+    if changed_files.borrow().is_empty() {
+        return Ok(false);
+    }
+    let mut cmd = cx.process(&cx.llvm_cov);
+    cmd.args(["export", "-summary-only"]);
+    cmd.arg(format!("-instr-profile={}", cx.ws.profdata_file));
+    cmd.args(object_files.iter().flat_map(|f| [OsStr::new("-object"), f]));
+    if let Some(ignore_filename_regex) = ignore_filename_regex {
+        cmd.arg("-ignore-filename-regex");
+        cmd.arg(ignore_filename_regex);
+    }
+    cmd.args(&cx.path_equivalence);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    let json = serde_json::from_str::<LlvmCovJsonExport>(&cmd.read()?)
+        .context("failed to parse json from llvm-cov")?;
+    // File names are relative if --remap-path-prefix is used.
+    let mapped: BTreeSet<_> = json
+        .data
+        .iter()
+        .flat_map(|export| &export.files)
+        .map(|file| cx.ws.metadata.workspace_root.join(&file.filename))
+        .collect();
+    changed_files.borrow_mut().retain(|file| mapped.contains(file));
+    Ok(!changed_files.borrow().is_empty())
+}
+
 fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Option<String>> {
     // On Windows, we should escape the separator.
     const SEPARATOR: &str = if cfg!(windows) { "\\\\" } else { "/" };
@@ -1282,7 +3391,7 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
 
     let mut out = Out::default();
 
-    if let Some(ignore_filename) = &cx.args.cov.ignore_filename_regex {
+    for ignore_filename in &cx.args.cov.ignore_filename_regex {
         out.push(ignore_filename);
     }
     if !cx.args.cov.disable_default_ignore_filename_regex {
@@ -1308,7 +3417,8 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
                 "{SEPARATOR}registry{SEPARATOR}src{SEPARATOR}index\\.crates\\.io-[0-9a-f]+{SEPARATOR}[0-9A-Za-z-_]+-[0-9]+\\.[0-9]+\\.[0-9]+(-[0-9A-Za-z\\.-]+)?(\\+[0-9A-Za-z\\.-]+)?{SEPARATOR}"
             ))?;
             let dep_re = Regex::new(&format!(
-                "{SEPARATOR}registry{SEPARATOR}src{SEPARATOR}index\\.crates\\.io-[0-9a-f]+{SEPARATOR}{dep}-[0-9]+\\.[0-9]+\\.[0-9]+(-[0-9A-Za-z\\.-]+)?(\\+[0-9A-Za-z\\.-]+)?{SEPARATOR}"
+                "{SEPARATOR}registry{SEPARATOR}src{SEPARATOR}index\\.crates\\.io-[0-9a-f]+{SEPARATOR}{dep}-[0-9]+\\.[0-9]+\\.[0-9]+(-[0-9A-Za-z\\.-]+)?(\\+[0-9A-Za-z\\.-]+)?{SEPARATOR}",
+                dep = regex::escape(dep)
             ))?;
             let mut set = BTreeSet::new();
             for data in &json.data {
@@ -1356,8 +3466,13 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
                 let path = format!("^{path}{SEPARATOR}(registry|git){SEPARATOR}");
                 out.push(path);
             }
-            if let Some(path) = env::rustup_home_with_cwd(&cx.current_dir) {
-                out.push_abs_path(path.join("toolchains"));
+            if !cx.args.include_std {
+                if let Some(path) = env::rustup_home_with_cwd(&cx.current_dir) {
+                    out.push_abs_path(path.join("toolchains"));
+                }
+                // Sources of the standard library built by -Z build-std are in the sysroot,
+                // which is not in the rustup home for toolchains not managed by rustup.
+                out.push_abs_path(cx.ws.rustc_print("sysroot")?);
             }
             for path in resolve_excluded_paths(cx) {
                 out.push_abs_path(path);
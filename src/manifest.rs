@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Information about the last run of tests, used to diagnose issues at report time.
+//
+// This is also read by external tools (see "Run manifest" in the README), so fields should
+// only be added.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::SystemTime,
+};
+
+use anyhow::{Context as _, Result};
+use cargo_llvm_cov::json::RunMetadata;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{cargo::Workspace, context::Context, fs};
+
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    /// Object file path -> hex-encoded binary ID, recorded when tests were run.
+    #[serde(default)]
+    pub(crate) binary_ids: BTreeMap<String, String>,
+    /// Absolute paths of the instrumented objects, accumulated across runs until the profraw
+    /// files are cleaned.
+    #[serde(default)]
+    pub(crate) object_files: BTreeSet<String>,
+    /// Absolute paths of the files that cargo reported as built by the runs since the profraw
+    /// files were cleaned, or `None` if they are not known for some of the runs (e.g., runs by
+    /// nextest, runs of `cargo test` without a separate `cargo test --no-run`, or builds with
+    /// the environment of show-env subcommand).
+    ///
+    /// If this is `Some`, objects in the target directory that are not listed here are left
+    /// over from previous builds and not passed to llvm-cov.
+    #[serde(default)]
+    pub(crate) artifacts: Option<BTreeSet<String>>,
+    /// Environment variables that affect how crates are built, recorded when tests were run.
+    #[serde(default)]
+    pub(crate) build_env: BTreeMap<String, String>,
+    /// Information about the run, recorded when tests were run.
+    #[serde(default)]
+    pub(crate) metadata: Option<RunMetadata>,
+    /// Absolute path to the workspace root, recorded when tests were run.
+    #[serde(default)]
+    pub(crate) workspace_root: Option<String>,
+    /// Absolute paths of the profraw files in the target directory, recorded when tests were
+    /// run.
+    #[serde(default)]
+    pub(crate) profraw_files: BTreeSet<String>,
+    /// Absolute path to the file that the profraw files are merged into at report time.
+    #[serde(default)]
+    pub(crate) profdata_file: Option<String>,
+    /// Functions executed by each instrumented object, computed by --affected-by.
+    #[serde(default)]
+    pub(crate) object_coverage: Option<ObjectCoverage>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ObjectCoverage {
+    /// Absolute paths of the profraw files that this was computed from.
+    pub(crate) profraw_files: BTreeSet<String>,
+    /// --ignore-filename-regex (including the default patterns) used to compute this.
+    pub(crate) ignore_filename_regex: Option<String>,
+    /// Object file path -> file name -> names of the functions executed by the object.
+    pub(crate) objects: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Manifest {
+    /// Loads the manifest of the last run, if exists.
+    pub(crate) fn load(ws: &Workspace) -> Result<Option<Self>> {
+        if !ws.manifest_file.exists() {
+            return Ok(None);
+        }
+        let s = fs::read_to_string(&ws.manifest_file)?;
+        let manifest = serde_json::from_str(&s)
+            .with_context(|| format!("failed to parse {}", ws.manifest_file))?;
+        Ok(Some(manifest))
+    }
+
+    /// Records the files that cargo reported as built by the current run, or `None` if they
+    /// are not known. This must be called before the objects of the current run are added to
+    /// `object_files`.
+    pub(crate) fn record_artifacts(&mut self, artifacts: Option<&BTreeSet<String>>) {
+        // `object_files` is cleared when the profraw files are removed.
+        let fresh = self.object_files.is_empty();
+        self.artifacts = match artifacts {
+            Some(artifacts) if fresh => Some(artifacts.clone()),
+            Some(artifacts) => self.artifacts.take().map(|mut recorded| {
+                recorded.extend(artifacts.iter().cloned());
+                recorded
+            }),
+            None => None,
+        };
+    }
+
+    pub(crate) fn save(&self, ws: &Workspace) -> Result<()> {
+        fs::write(&ws.manifest_file, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Collects information about the current run.
+pub(crate) fn collect_metadata(cx: &Context) -> Result<RunMetadata> {
+    let root = cx.ws.metadata.workspace_root.as_std_path();
+    let git =
+        |args: &[&str]| cmd!("git").args(args).dir(root).read().ok().filter(|s| !s.is_empty());
+    let rustc = cx.ws.rustc().arg("-vV").read()?;
+    let field = |name: &str| {
+        rustc.lines().find_map(|line| Some(line.strip_prefix(name)?.trim().to_owned()))
+    };
+    let mut metadata = RunMetadata {
+        commit: git(&["rev-parse", "HEAD"]),
+        // `--abbrev-ref` prints `HEAD` in detached `HEAD` state.
+        branch: git(&["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD"),
+        rustc_version: rustc.lines().next().unwrap_or_default().to_owned(),
+        llvm_version: field("LLVM version:"),
+        host: field("host:").unwrap_or_default(),
+        target: cx.ws.target_for_config.triple().to_owned(),
+        timestamp: Some(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs()),
+        ..RunMetadata::default()
+    };
+    let mut args = cx.args.cargo_args.iter();
+    while let Some(arg) = args.next() {
+        let features = match arg.as_str() {
+            "--all-features" => {
+                metadata.all_features = true;
+                continue;
+            }
+            "--no-default-features" => {
+                metadata.no_default_features = true;
+                continue;
+            }
+            "--features" | "-F" => args.next().map(String::as_str),
+            _ => arg.strip_prefix("--features=").or_else(|| arg.strip_prefix("-F")),
+        };
+        if let Some(features) = features {
+            metadata
+                .features
+                .extend(features.split([' ', ',']).filter(|f| !f.is_empty()).map(str::to_owned));
+        }
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::Manifest;
+
+    #[test]
+    fn test_record_artifacts() {
+        let set = |files: &[&str]| files.iter().map(|&f| f.to_owned()).collect::<BTreeSet<_>>();
+
+        let mut manifest = Manifest::default();
+        manifest.record_artifacts(Some(&set(&["/t/a"])));
+        assert_eq!(manifest.artifacts, Some(set(&["/t/a"])));
+        // Artifacts of runs with --no-clean are accumulated.
+        manifest.object_files.insert("/t/a".to_owned());
+        manifest.record_artifacts(Some(&set(&["/t/b"])));
+        assert_eq!(manifest.artifacts, Some(set(&["/t/a", "/t/b"])));
+        // Once a run with unknown artifacts is included, all objects are used.
+        manifest.record_artifacts(None);
+        assert_eq!(manifest.artifacts, None);
+        manifest.record_artifacts(Some(&set(&["/t/c"])));
+        assert_eq!(manifest.artifacts, None);
+        // Until the profraw files (and the objects used by them) are cleaned.
+        manifest.object_files.clear();
+        manifest.record_artifacts(Some(&set(&["/t/c"])));
+        assert_eq!(manifest.artifacts, Some(set(&["/t/c"])));
+    }
+}
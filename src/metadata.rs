@@ -35,6 +35,9 @@ pub(crate) struct Metadata {
     pub(crate) target_directory: Utf8PathBuf,
     /// This is always `None` if running with a version of Cargo older than 1.91.
     build_directory: Option<Utf8PathBuf>,
+    /// `[workspace.metadata.llvm-cov]` table, or `[package.metadata.llvm-cov]` table of the
+    /// root package if the former is not present.
+    pub(crate) llvm_cov_config: Option<Value>,
 }
 
 impl Metadata {
@@ -60,14 +63,25 @@ impl Metadata {
             .into_iter()
             .map(|v| into_string(v).ok_or("workspace_members"))
             .collect::<Result<_, _>>()?;
+        let workspace_root: Utf8PathBuf = map.remove_string("workspace_root")?;
+        let mut llvm_cov_config = match map.get_mut("metadata") {
+            Some(Value::Object(metadata)) => metadata.remove("llvm-cov"),
+            _ => None,
+        };
+        let mut packages = map.remove_array("packages")?;
+        if llvm_cov_config.is_none() {
+            let root_manifest = workspace_root.join("Cargo.toml");
+            llvm_cov_config = packages.iter_mut().find_map(|package| {
+                if package.get("manifest_path")?.as_str()? != root_manifest.as_str() {
+                    return None;
+                }
+                package.get_mut("metadata")?.as_object_mut()?.remove("llvm-cov")
+            });
+        }
         Ok(Self {
-            packages: map
-                .remove_array("packages")?
-                .into_iter()
-                .map(Package::from_value)
-                .collect::<Result<_, _>>()?,
+            packages: packages.into_iter().map(Package::from_value).collect::<Result<_, _>>()?,
             workspace_members,
-            workspace_root: map.remove_string("workspace_root")?,
+            workspace_root,
             target_directory: map.remove_string("target_directory")?,
             // This field was added in Rust 1.91.
             build_directory: if map.contains_key("build_directory") {
@@ -75,18 +89,31 @@ impl Metadata {
             } else {
                 None
             },
+            llvm_cov_config,
         })
     }
 
     pub(crate) fn build_directory(&self) -> &Utf8Path {
         self.build_directory.as_deref().unwrap_or(&self.target_directory)
     }
+
+    /// Changes the target directory as `--target-dir` does. The build directory is changed
+    /// together unless it is configured separately.
+    pub(crate) fn set_target_directory(&mut self, target_directory: Utf8PathBuf) {
+        if self.build_directory.as_ref() == Some(&self.target_directory) {
+            self.build_directory = None;
+        }
+        self.target_directory = target_directory;
+    }
 }
 
 pub(crate) struct Package {
     /// The name of the package.
     pub(crate) name: String,
     pub(crate) targets: Vec<Target>,
+    /// Features provided by the package, including the implicit features of optional
+    /// dependencies.
+    pub(crate) features: Vec<String>,
     /// Absolute path to this package's manifest.
     pub(crate) manifest_path: Utf8PathBuf,
 }
@@ -103,6 +130,7 @@ impl Package {
                 .into_iter()
                 .map(Target::from_value)
                 .collect::<Result<_, _>>()?,
+            features: map.remove_object("features")?.into_iter().map(|(name, _)| name).collect(),
             manifest_path: map.remove_string("manifest_path")?,
         }))
     }
@@ -131,18 +159,14 @@ fn into_string<S: From<String>>(value: Value) -> Option<S> {
 fn into_array(value: Value) -> Option<Vec<Value>> {
     if let Value::Array(array) = value { Some(array) } else { None }
 }
-// fn into_object(value: Value) -> Option<Object> {
-//     if let Value::Object(object) = value {
-//         Some(object)
-//     } else {
-//         None
-//     }
-// }
+fn into_object(value: Value) -> Option<Object> {
+    if let Value::Object(object) = value { Some(object) } else { None }
+}
 
 trait ObjectExt {
     fn remove_string<S: From<String>>(&mut self, key: &'static str) -> ParseResult<S>;
     fn remove_array(&mut self, key: &'static str) -> ParseResult<Vec<Value>>;
-    // fn remove_object(&mut self, key: &'static str) -> ParseResult<Object>;
+    fn remove_object(&mut self, key: &'static str) -> ParseResult<Object>;
     // fn remove_nullable<T>(
     //     &mut self,
     //     key: &'static str,
@@ -157,9 +181,9 @@ impl ObjectExt for Object {
     fn remove_array(&mut self, key: &'static str) -> ParseResult<Vec<Value>> {
         self.remove(key).and_then(into_array).ok_or(key)
     }
-    // fn remove_object(&mut self, key: &'static str) -> ParseResult<Object> {
-    //     self.remove(key).and_then(into_object).ok_or(key)
-    // }
+    fn remove_object(&mut self, key: &'static str) -> ParseResult<Object> {
+        self.remove(key).and_then(into_object).ok_or(key)
+    }
     // fn remove_nullable<T>(
     //     &mut self,
     //     key: &'static str,
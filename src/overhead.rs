@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Measures the overhead of instrumentation for --measure-overhead.
+//
+// Tests are built and run separately so that build time and run time can be compared
+// independently. Tests without instrumentation are built in the normal target directory,
+// so that artifacts of cargo-llvm-cov are not overwritten.
+
+use std::{
+    fmt::Write as _,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+
+use crate::{
+    cargo, cargo_test, context::Context, object_files, process::ProcessBuilder, stats,
+    stdout_to_stderr, term, timings,
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct Timings {
+    pub(crate) build: Duration,
+    pub(crate) run: Duration,
+}
+
+/// Builds tests with instrumentation before running them, and returns the build time.
+pub(crate) fn build_instrumented(cx: &Context) -> Result<Duration> {
+    let mut cargo = cargo_test(cx)?;
+    cargo.arg("--no-run");
+    cargo::test_or_run_args(cx, &mut cargo);
+    time(cx, &mut cargo).context("failed to build tests with instrumentation")
+}
+
+/// Builds and runs tests without instrumentation.
+pub(crate) fn measure_uninstrumented(cx: &Context) -> Result<Timings> {
+    let target_dir = &cx.ws.metadata.target_directory;
+    let build_dir = cx.ws.build_dir.is_some().then(|| cx.ws.metadata.build_directory());
+    let cargo = |no_run: bool| {
+        let mut cargo = cx.cargo();
+        cargo.arg("test");
+        if no_run {
+            cargo.arg("--no-run");
+        }
+        cargo::test_or_run_args_in(cx, &mut cargo, target_dir, build_dir);
+        if cx.args.timings {
+            timings::set_baseline_env(cx, &mut cargo);
+        }
+        cargo
+    };
+
+    let build =
+        time(cx, &mut cargo(true)).context("failed to build tests without instrumentation")?;
+    let run = match time(cx, &mut cargo(false)) {
+        Ok(run) => run,
+        Err(e) if cx.args.ignore_run_fail => {
+            warn!("{e:#}");
+            Duration::ZERO
+        }
+        Err(e) => return Err(e.context("failed to run tests without instrumentation")),
+    };
+    Ok(Timings { build, run })
+}
+
+/// Prints the overhead of instrumentation.
+pub(crate) fn print(cx: &Context, instrumented: &Timings, uninstrumented: &Timings) -> Result<()> {
+    // Only compare binaries that were built both with and without instrumentation.
+    let (mut size, mut base_size) = (0, 0);
+    for object in object_files(cx).context("failed to collect object files")? {
+        let object = Path::new(&object);
+        if let Some(base) = stats::uninstrumented_object(cx, object) {
+            size += stats::file_size(object)?;
+            base_size += stats::file_size(&base)?;
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<12} {:>14} {:>14} {:>9}",
+        "", "Instrumented", "Uninstrumented", "Overhead"
+    );
+    for (name, a, b) in [
+        ("Build time", instrumented.build, uninstrumented.build),
+        ("Run time", instrumented.run, uninstrumented.run),
+    ] {
+        let _ = writeln!(
+            out,
+            "{name:<12} {:>14} {:>14} {:>9}",
+            format!("{:.2}s", a.as_secs_f64()),
+            format!("{:.2}s", b.as_secs_f64()),
+            overhead(a.as_secs_f64(), b.as_secs_f64())
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{:<12} {:>14} {:>14} {:>9}",
+        "Binary size",
+        stats::format_size(size),
+        stats::format_size(base_size),
+        overhead(size as f64, base_size as f64)
+    );
+    eprintln!();
+    status!("Measured", "overhead of instrumentation");
+    eprint!("{out}");
+    Ok(())
+}
+
+fn time(cx: &Context, cargo: &mut ProcessBuilder) -> Result<Duration> {
+    if term::verbose() {
+        status!("Running", "{cargo}");
+    }
+    stdout_to_stderr(cx, cargo);
+    let start = Instant::now();
+    cargo.run()?;
+    Ok(start.elapsed())
+}
+
+pub(crate) fn overhead(instrumented: f64, uninstrumented: f64) -> String {
+    if uninstrumented == 0. {
+        "-".to_owned()
+    } else {
+        format!("{:+.1}%", (instrumented / uninstrumented - 1.) * 100.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::overhead;
+
+    #[test]
+    fn test_overhead() {
+        assert_eq!(overhead(150., 100.), "+50.0%");
+        assert_eq!(overhead(100., 100.), "+0.0%");
+        assert_eq!(overhead(90., 100.), "-10.0%");
+        assert_eq!(overhead(1., 0.), "-");
+    }
+}
@@ -36,6 +36,8 @@ pub(crate) struct ProcessBuilder {
     /// The working directory where the process will execute.
     dir: Option<PathBuf>,
     stdout_to_stderr: bool,
+    /// Bytes to write to the process's stdin. Not included in display.
+    stdin: Option<Vec<u8>>,
     /// `true` to include environment variables in display.
     display_env_vars: Cell<bool>,
 }
@@ -57,6 +59,7 @@ impl ProcessBuilder {
             env: BTreeMap::new(),
             dir: None,
             stdout_to_stderr: false,
+            stdin: None,
             display_env_vars: Cell::new(false),
         };
         this.env_remove("LLVM_COV_FLAGS");
@@ -103,6 +106,12 @@ impl ProcessBuilder {
         self
     }
 
+    /// Writes the given bytes to the process's stdin.
+    pub(crate) fn stdin_bytes(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.stdin = Some(bytes.into());
+        self
+    }
+
     /// Enables environment variables display.
     pub(crate) fn display_env_vars(&mut self) -> &mut Self {
         self.display_env_vars.set(true);
@@ -176,6 +185,9 @@ impl ProcessBuilder {
         if self.stdout_to_stderr {
             cmd = cmd.stdout_to_stderr();
         }
+        if let Some(bytes) = &self.stdin {
+            cmd = cmd.stdin_bytes(bytes.clone());
+        }
 
         cmd
     }
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --ratchet: an automatically tightening threshold.
+//
+// The recorded coverage is only updated upward, so the threshold never loosens
+// unless the file is edited manually.
+
+use anyhow::{Context as _, Result};
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{CoverageKind, LlvmCovJsonExport};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    fs,
+    term::{self, ExitStatus},
+};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Recorded {
+    functions: f64,
+    lines: f64,
+    regions: f64,
+}
+
+pub(crate) fn check(json: &LlvmCovJsonExport, path: &Utf8Path, tolerance: f64) -> Result<()> {
+    // Round to avoid rewriting the file due to noise in the last digits.
+    let get =
+        |kind| -> Result<f64> { Ok((json.get_coverage_percent(kind)? * 100.).floor() / 100.) };
+    let current = Recorded {
+        functions: get(CoverageKind::Functions)?,
+        lines: get(CoverageKind::Lines)?,
+        regions: get(CoverageKind::Regions)?,
+    };
+    if !path.exists() {
+        save(path, current)?;
+        info!("--ratchet: recorded the current coverage to {path}");
+        return Ok(());
+    }
+    let recorded: Recorded = serde_json::from_str(&fs::read_to_string(path)?)
+        .with_context(|| format!("failed to parse {path}"))?;
+
+    let mut decreased = false;
+    for (name, recorded, current) in [
+        ("function", recorded.functions, current.functions),
+        ("line", recorded.lines, current.lines),
+        ("region", recorded.regions, current.regions),
+    ] {
+        if current < recorded - tolerance {
+            term::fail(ExitStatus::Threshold);
+            error!(
+                "{name} coverage decreased from {recorded:.2}% to {current:.2}% (recorded in {path})"
+            );
+            decreased = true;
+        }
+    }
+    if decreased {
+        return Ok(());
+    }
+    let updated = Recorded {
+        functions: recorded.functions.max(current.functions),
+        lines: recorded.lines.max(current.lines),
+        regions: recorded.regions.max(current.regions),
+    };
+    if updated != recorded {
+        save(path, updated)?;
+        info!("--ratchet: coverage improved; updated {path}");
+    }
+    Ok(())
+}
+
+fn save(path: &Utf8Path, recorded: Recorded) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut s = serde_json::to_string_pretty(&recorded)?;
+    s.push('\n');
+    fs::write(path, s)?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Generates the reports of multiple format flags (e.g., `--lcov --html --json`) in one run.
+//
+// Each report is written to a file in --output-dir. The reports converted from the JSON export
+// (including --lcov, which is usually generated by `llvm-cov export -format=lcov`) share one
+// `llvm-cov export`, and only --text and --html need their own `llvm-cov show`. The shared
+// export is returned so that the checks after the report (e.g., --fail-under-lines) do not need
+// to run `llvm-cov export` again.
+
+use std::ffi::OsString;
+
+use anyhow::{Context as _, Result};
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+use crate::{Format, context::Context, exclude, fs, package_coverage, summary, term};
+
+pub(crate) fn generate(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&str>,
+) -> Result<Option<LlvmCovJsonExport>> {
+    let cov = &cx.args.cov;
+    let output_dir = cov.output_dir.as_ref().unwrap();
+    let exports: Vec<_> = [
+        (cov.json, Format::Json, "coverage.json"),
+        (cov.lcov, Format::LCov, "lcov.info"),
+        (cov.cobertura, Format::Cobertura, "cobertura.xml"),
+        (cov.codecov, Format::Codecov, "codecov.json"),
+        (cov.rdjson, Format::RdJson, "rdjson.json"),
+        (cov.checkstyle, Format::Checkstyle, "checkstyle.xml"),
+        (cov.coveralls, Format::Coveralls, "coveralls.json"),
+        (cov.sarif, Format::Sarif, "coverage.sarif"),
+        (cov.jacoco, Format::Jacoco, "jacoco.xml"),
+        (cov.sonarqube, Format::SonarQube, "sonarqube.xml"),
+    ]
+    .into_iter()
+    .filter(|&(flag, ..)| flag)
+    .map(|(_, format, filename)| (format, filename))
+    .collect();
+    let delimited: Vec<_> = [(cov.csv, ',', "summary.csv"), (cov.tsv, '\t', "summary.tsv")]
+        .into_iter()
+        .filter(|&(flag, ..)| flag)
+        .map(|(_, separator, filename)| (separator, filename))
+        .collect();
+
+    let mut export = None;
+    if !exports.is_empty() || !delimited.is_empty() {
+        let cmd = Format::Json.llvm_cov_cmd(cx, object_files, ignore_filename_regex);
+        if term::verbose() {
+            status!("Running", "{cmd}");
+        }
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&cmd.read()?)
+            .context("failed to parse json from llvm-cov")?;
+        exclude::json(cx, &mut json)?;
+
+        eprintln!();
+        for (format, filename) in exports {
+            let (out, _) = format.convert_json(cx, json.clone(), ignore_filename_regex)?;
+            let path = output_dir.join(filename);
+            fs::write(&path, out)?;
+            status!("Finished", "report saved to {path}");
+        }
+        for (separator, filename) in delimited {
+            let out = summary::delimited(cx, &json, &package_coverage(cx, &json), separator);
+            let path = output_dir.join(filename);
+            fs::write(&path, out)?;
+            status!("Finished", "report saved to {path}");
+        }
+        export = Some(json);
+    }
+
+    for (flag, format) in [(cov.text, Format::Text), (cov.html, Format::Html)] {
+        if flag {
+            format.generate_report(cx, object_files, ignore_filename_regex)?;
+        }
+    }
+    Ok(export)
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Measures coverage of only a subset of the workspace members in each run, to keep
+// coverage runs of large workspaces fast, and merges the results into a history file,
+// so that coverage of all packages is available across runs.
+
+use std::time::SystemTime;
+
+use anyhow::{Context as _, Result, bail};
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{CoverageKind, LlvmCovJsonExport};
+
+use crate::{cli::Args, context::Context, env, fs, metadata::Metadata};
+
+/// Excludes the workspace members that are not selected by --sample-packages from both
+/// the test and report.
+pub(crate) fn apply(args: &mut Args, metadata: &Metadata) -> Result<()> {
+    let Some(spec) = &args.sample_packages else { return Ok(()) };
+    let mut packages: Vec<_> = metadata
+        .workspace_members
+        .iter()
+        .map(|id| metadata.packages[id].name.as_str())
+        .filter(|name| {
+            !args.exclude.iter().any(|n| n == name)
+                && !args.exclude_from_report.iter().any(|n| n == name)
+        })
+        .collect();
+    packages.sort_unstable();
+    packages.dedup();
+    let seed = match env::var("CARGO_LLVM_COV_SAMPLE_SEED")? {
+        Some(seed) => seed.parse().with_context(|| {
+            format!("CARGO_LLVM_COV_SAMPLE_SEED must be a non-negative integer, but found `{seed}`")
+        })?,
+        None => SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs() / 86400,
+    };
+    let sampled = select(spec, &packages, seed)?;
+    info!(
+        "--sample-packages: measuring coverage of {} of {} packages: {}",
+        sampled.len(),
+        packages.len(),
+        sampled.join(", ")
+    );
+    let excluded: Vec<_> = packages
+        .iter()
+        .filter(|name| !sampled.contains(name))
+        .map(|&name| name.to_owned())
+        .collect();
+    for name in excluded {
+        args.cargo_args.push("--exclude".to_owned());
+        args.cargo_args.push(name.clone());
+        args.exclude.push(name);
+    }
+    Ok(())
+}
+
+/// Selects packages from `packages` (sorted by name) according to the value of
+/// --sample-packages.
+fn select<'a>(spec: &str, packages: &[&'a str], seed: u64) -> Result<Vec<&'a str>> {
+    if let Ok(fraction) = spec.parse::<f64>() {
+        if !(fraction > 0. && fraction <= 1.) {
+            bail!(
+                "--sample-packages must be a fraction in (0, 1] or a comma-separated list of packages"
+            )
+        }
+        let len = packages.len();
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        // Split packages into chunks of the given fraction and select one of them in rotation.
+        let size = (1..=len).find(|&n| n as f64 >= len as f64 * fraction).unwrap_or(len);
+        let chunks: Vec<_> = packages.chunks(size).collect();
+        let index = usize::try_from(seed % chunks.len() as u64).unwrap();
+        return Ok(chunks[index].to_vec());
+    }
+    let mut sampled = vec![];
+    for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let Some(&package) = packages.iter().find(|&&p| p == name) else {
+            bail!("--sample-packages: `{name}` is not a workspace member to be measured")
+        };
+        if !sampled.contains(&package) {
+            sampled.push(package);
+        }
+    }
+    if sampled.is_empty() {
+        bail!("--sample-packages: no packages specified")
+    }
+    Ok(sampled)
+}
+
+/// Merges the coverage of this run into the coverage history in `path`.
+///
+/// Coverage of files in the packages measured in this run is replaced, and coverage of
+/// other files is kept from the previous runs.
+pub(crate) fn merge_history(
+    cx: &Context,
+    path: &Utf8Path,
+    mut json: LlvmCovJsonExport,
+) -> Result<()> {
+    if path.exists() {
+        let history = serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("failed to parse {path}"))?;
+        let root = &cx.ws.metadata.workspace_root;
+        let members: Vec<_> = cx
+            .ws
+            .metadata
+            .workspace_members
+            .iter()
+            .map(|id| (id, cx.ws.metadata.packages[id].manifest_path.parent().unwrap()))
+            .collect();
+        json.merge_history(history, |filename| {
+            let path = root.join(filename);
+            // Files belong to the innermost package that contains them.
+            let package = members
+                .iter()
+                .filter(|(_, dir)| path.starts_with(dir))
+                .max_by_key(|(_, dir)| dir.as_str().len());
+            package.is_none_or(|(id, _)| !cx.workspace_members.included.contains(*id))
+        })?;
+    }
+    if let Some(dir) = path.parent() {
+        if !dir.as_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    fs::write(path, serde_json::to_string(&json)?)?;
+    let lines = json.get_coverage_percent(CoverageKind::Lines)?;
+    status!("Finished", "coverage history saved to {path} (line coverage: {lines:.2}%)");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select;
+
+    #[test]
+    fn test_select() {
+        let packages = ["a", "b", "c", "d", "e"];
+        assert_eq!(select("0.4", &packages, 0).unwrap(), ["a", "b"]);
+        assert_eq!(select("0.4", &packages, 1).unwrap(), ["c", "d"]);
+        assert_eq!(select("0.4", &packages, 2).unwrap(), ["e"]);
+        assert_eq!(select("0.4", &packages, 3).unwrap(), ["a", "b"]);
+        assert_eq!(select("1", &packages, 7).unwrap(), packages);
+        assert_eq!(select("0.01", &packages, 3).unwrap(), ["d"]);
+        assert_eq!(select("e, a,a", &packages, 0).unwrap(), ["e", "a"]);
+        assert!(select("0", &packages, 0).is_err());
+        assert!(select("1.5", &packages, 0).is_err());
+        assert!(select("a,f", &packages, 0).is_err());
+        assert!(select(",", &packages, 0).is_err());
+    }
+}
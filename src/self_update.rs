@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Updates cargo-llvm-cov to a prebuilt binary from the GitHub Releases page.
+//
+// This uses curl and tar to avoid depending on an HTTP client, TLS implementation, and gzip
+// decoder. The downloaded archive is verified against the SHA-256 digest of the release asset
+// reported by the GitHub API before it is extracted. The digest is served by the same origin as
+// the archive, so this detects corrupted downloads, but not a compromised release; signatures
+// are not verified.
+//
+// Without --version, releases older than the current version (e.g., when the latest release is
+// yanked) are not installed.
+
+use std::{cmp::Ordering, env::consts, fmt::Write as _, path::Path};
+
+use anyhow::{Context as _, Result, bail};
+use serde_derive::Deserialize;
+
+use crate::{
+    cli::Args,
+    digest::{sha256, to_hex},
+    env, fs, term,
+};
+
+const REPOSITORY: &str = "taiki-e/cargo-llvm-cov";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    digest: Option<String>,
+}
+
+pub(crate) fn run(args: &Args) -> Result<()> {
+    let Some(target) = host_target() else {
+        bail!("prebuilt binaries are not available for {}-{}", consts::ARCH, consts::OS);
+    };
+    let tag = match &args.self_update.version {
+        Some(version) => format!("tags/v{}", version.strip_prefix('v').unwrap_or(version)),
+        None => "latest".to_owned(),
+    };
+    let release = curl(&format!("https://api.github.com/repos/{REPOSITORY}/releases/{tag}"))?
+        .read()
+        .with_context(|| format!("failed to fetch release information of {REPOSITORY}"))?;
+    let release: Release = serde_json::from_str(&release)?;
+    let version = release.tag_name.strip_prefix('v').unwrap_or(&release.tag_name);
+    if args.self_update.version.is_none() {
+        let current = env!("CARGO_PKG_VERSION");
+        match cmp_versions(version, current) {
+            Some(Ordering::Greater) => {}
+            Some(Ordering::Equal) => {
+                info!("cargo-llvm-cov is already up to date ({version})");
+                return Ok(());
+            }
+            Some(Ordering::Less) => bail!(
+                "the latest release ({version}) is older than the current version ({current}); \
+                 pass `--version {version}` to downgrade"
+            ),
+            None => bail!("failed to parse the version of release {}", release.tag_name),
+        }
+    }
+
+    let name = format!("cargo-llvm-cov-{target}.tar.gz");
+    let Some(asset) = release.assets.iter().find(|asset| asset.name == name) else {
+        bail!("{name} not found in release {}", release.tag_name);
+    };
+    let Some(digest) = asset.digest.as_deref().and_then(|d| d.strip_prefix("sha256:")) else {
+        bail!("SHA-256 digest of {name} is not available; refusing to install unverified binary");
+    };
+
+    let current_exe = fs::canonicalize(std::env::current_exe()?)?;
+    let exe_dir = current_exe.parent().unwrap();
+    // Use a directory next to the executable, because files cannot be renamed across file
+    // systems.
+    let tmp_dir = exe_dir.join(".cargo-llvm-cov-self-update");
+    fs::remove_dir_all(&tmp_dir)?;
+    fs::create_dir_all(&tmp_dir)?;
+    let res = install(&current_exe, &tmp_dir, asset, digest);
+    // The old executable cannot be removed while it is running on Windows, so it is removed
+    // at the next update.
+    let _ = fs::remove_dir_all(&tmp_dir);
+    res?;
+    status!("Updated", "cargo-llvm-cov to {version} ({})", current_exe.display());
+    Ok(())
+}
+
+fn install(current_exe: &Path, tmp_dir: &Path, asset: &Asset, digest: &str) -> Result<()> {
+    let archive = tmp_dir.join(&asset.name);
+    let mut cmd = curl(&asset.browser_download_url)?;
+    cmd.arg("--output").arg(&archive);
+    cmd.run().with_context(|| format!("failed to download {}", asset.name))?;
+    let actual = to_hex(&sha256(&fs::read(&archive)?));
+    if !actual.eq_ignore_ascii_case(digest) {
+        bail!("checksum mismatch for {}: expected {digest}, found {actual}", asset.name);
+    }
+
+    let mut cmd = cmd!("tar", "xzf", &archive, "-C", tmp_dir);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    cmd.run().with_context(|| format!("failed to extract {}", asset.name))?;
+    let new_exe = tmp_dir.join(current_exe.file_name().unwrap());
+    if !new_exe.is_file() {
+        bail!("{} not found in {}", new_exe.file_name().unwrap().to_string_lossy(), asset.name);
+    }
+
+    // The running executable cannot be overwritten on Windows, but can be renamed.
+    let old_exe = tmp_dir.join("old");
+    fs::rename(current_exe, &old_exe)?;
+    if let Err(e) = fs::rename(&new_exe, current_exe) {
+        fs::rename(&old_exe, current_exe)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn curl(url: &str) -> Result<crate::process::ProcessBuilder> {
+    // The URL and token are passed via stdin as in upload subcommand.
+    let mut config = format!("url = \"{url}\"\n");
+    config.push_str("header = \"Accept: application/vnd.github+json\"\n");
+    if let Some(token) = env::var("GITHUB_TOKEN")?.filter(|token| !token.is_empty()) {
+        // Avoid the rate limit of unauthenticated requests in CI.
+        let _ = writeln!(config, "header = \"Authorization: Bearer {token}\"");
+    }
+    let mut cmd = cmd!("curl", "--proto", "=https", "--tlsv1.2", "--fail", "--silent");
+    cmd.args(["--show-error", "--location", "--config", "-"]).stdin_bytes(config);
+    if term::verbose() {
+        status!("Running", "{cmd} ({url})");
+    }
+    Ok(cmd)
+}
+
+/// Compares versions in `MAJOR.MINOR.PATCH[-PRE][+BUILD]` format by the precedence of Semantic
+/// Versioning, or returns `None` if either of them is not in this format.
+// Refs: https://semver.org/#spec-item-11
+fn cmp_versions(a: &str, b: &str) -> Option<Ordering> {
+    fn parse(version: &str) -> Option<([u64; 3], Option<&str>)> {
+        let version = version.split_once('+').map_or(version, |(version, _build)| version);
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+        let mut numbers = core.split('.').map(|n| n.parse().ok());
+        let core = [numbers.next()??, numbers.next()??, numbers.next()??];
+        if numbers.next().is_some() {
+            return None;
+        }
+        Some((core, pre))
+    }
+    fn cmp_identifiers(a: &str, b: &str) -> Ordering {
+        match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            // Numeric identifiers have lower precedence than alphanumeric identifiers.
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        }
+    }
+
+    let (a_core, a_pre) = parse(a)?;
+    let (b_core, b_pre) = parse(b)?;
+    Some(a_core.cmp(&b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        // A pre-release version has lower precedence than the normal version.
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            let (mut a, mut b) = (a.split('.'), b.split('.'));
+            loop {
+                match (a.next(), b.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(a), Some(b)) => match cmp_identifiers(a, b) {
+                        Ordering::Equal => {}
+                        ordering => return ordering,
+                    },
+                }
+            }
+        }
+    }))
+}
+
+/// Returns the target triple of the prebuilt binary for the current platform.
+fn host_target() -> Option<&'static str> {
+    let musl = cfg!(target_env = "musl");
+    Some(match (consts::ARCH, consts::OS) {
+        ("x86_64", "linux") if musl => "x86_64-unknown-linux-musl",
+        ("x86_64", "linux") => "x86_64-unknown-linux-gnu",
+        ("aarch64", "linux") if musl => "aarch64-unknown-linux-musl",
+        ("aarch64", "linux") => "aarch64-unknown-linux-gnu",
+        ("powerpc64", "linux") if cfg!(target_endian = "little") => {
+            if musl {
+                "powerpc64le-unknown-linux-musl"
+            } else {
+                "powerpc64le-unknown-linux-gnu"
+            }
+        }
+        ("riscv64", "linux") if musl => "riscv64gc-unknown-linux-musl",
+        ("riscv64", "linux") => "riscv64gc-unknown-linux-gnu",
+        ("s390x", "linux") => "s390x-unknown-linux-gnu",
+        ("x86_64", "macos") => "x86_64-apple-darwin",
+        ("aarch64", "macos") => "aarch64-apple-darwin",
+        ("x86_64", "windows") => "x86_64-pc-windows-msvc",
+        ("x86_64", "freebsd") => "x86_64-unknown-freebsd",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::cmp_versions;
+
+    #[test]
+    fn test_cmp_versions() {
+        assert_eq!(cmp_versions("0.6.16", "0.6.16"), Some(Ordering::Equal));
+        assert_eq!(cmp_versions("0.6.16", "0.6.9"), Some(Ordering::Greater));
+        assert_eq!(cmp_versions("0.6.9", "0.7.0"), Some(Ordering::Less));
+        assert_eq!(cmp_versions("1.0.0", "0.99.99"), Some(Ordering::Greater));
+        assert_eq!(cmp_versions("1.0.0-alpha", "1.0.0"), Some(Ordering::Less));
+        assert_eq!(cmp_versions("1.0.0-alpha.1", "1.0.0-alpha"), Some(Ordering::Greater));
+        assert_eq!(cmp_versions("1.0.0-alpha.beta", "1.0.0-alpha.1"), Some(Ordering::Greater));
+        assert_eq!(cmp_versions("1.0.0-rc.11", "1.0.0-rc.2"), Some(Ordering::Greater));
+        assert_eq!(cmp_versions("1.0.0+build", "1.0.0"), Some(Ordering::Equal));
+        assert_eq!(cmp_versions("1.0", "1.0.0"), None);
+        assert_eq!(cmp_versions("1.0.0.0", "1.0.0"), None);
+        assert_eq!(cmp_versions("latest", "1.0.0"), None);
+    }
+}
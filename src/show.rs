@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// show subcommand: the source of the given files annotated with the execution count of each
+// line, like the source view of the tui subcommand, but printed to stdout.
+//
+// This is faster than generating the HTML report when checking the coverage of a few files.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write as _},
+    path::Path,
+};
+
+use anyhow::{Context as _, Result, bail};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{
+    Format, context::Context, fs, ignore_filename_regex, merge_profraw, object_files, term,
+};
+
+pub(crate) fn run(cx: &Context) -> Result<()> {
+    let patterns = cx
+        .args
+        .show
+        .files
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+            glob::Pattern::new(pattern).with_context(|| format!("invalid pattern `{pattern}`"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    merge_profraw(cx).context("failed to merge profile data")?;
+    let object_files = object_files(cx).context("failed to collect object files")?;
+    let ignore_filename_regex = ignore_filename_regex(cx, &object_files)?;
+    let json = Format::Json
+        .get_json(cx, &object_files, ignore_filename_regex.as_ref())
+        .context("failed to get json")?;
+
+    let root = &cx.ws.metadata.workspace_root;
+    let bases = [cx.current_dir.as_path(), root.as_std_path()];
+    let mut stdout = term::stdout();
+    let mut found = false;
+    for (&filename, lines) in &json.get_line_execution_counts() {
+        // Paths are relative to the workspace root when --remap-path-prefix is used.
+        let path = root.join(filename);
+        if !patterns.iter().any(|pattern| is_match(pattern, path.as_std_path(), &bases)) {
+            continue;
+        }
+        if found {
+            writeln!(stdout)?;
+        }
+        found = true;
+        let name = path.strip_prefix(root).unwrap_or(&path);
+        let text = fs::read_to_string(&path)?;
+        render(&mut stdout, name.as_str(), &text, lines)?;
+    }
+    stdout.flush()?;
+    if !found {
+        bail!("no files with coverage data match {}", cx.args.show.files.join(", "));
+    }
+    Ok(())
+}
+
+/// Returns `true` if the pattern matches the path, or the path relative to one of `bases`.
+fn is_match(pattern: &glob::Pattern, path: &Path, bases: &[&Path]) -> bool {
+    let options =
+        glob::MatchOptions { require_literal_separator: true, ..glob::MatchOptions::new() };
+    pattern.matches_path_with(path, options)
+        || bases.iter().any(|base| {
+            path.strip_prefix(base).is_ok_and(|path| pattern.matches_path_with(path, options))
+        })
+}
+
+fn render(
+    w: &mut impl WriteColor,
+    name: &str,
+    text: &str,
+    lines: &BTreeMap<u64, u64>,
+) -> io::Result<()> {
+    let covered = lines.values().filter(|&&count| count != 0).count();
+    let percent = if lines.is_empty() { 100. } else { covered as f64 * 100. / lines.len() as f64 };
+    w.set_color(ColorSpec::new().set_bold(true))?;
+    write!(w, "{name}  {covered}/{} lines ({percent:.2}%)", lines.len())?;
+    w.reset()?;
+    writeln!(w)?;
+    for (i, text) in text.lines().enumerate() {
+        let text = text.replace('\t', "    ");
+        match lines.get(&(i as u64 + 1)) {
+            Some(0) => {
+                w.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                write!(w, "{:>7}✗ {text}", 0)?;
+                w.reset()?;
+                writeln!(w)?;
+            }
+            Some(count) => {
+                w.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                write!(w, "{count:>7}")?;
+                w.reset()?;
+                writeln!(w, "  {text}")?;
+            }
+            None => writeln!(w, "{:>7}  {text}", "")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
+    use termcolor::NoColor;
+
+    use super::{is_match, render};
+
+    #[test]
+    fn test_is_match() {
+        let pattern = |s: &str| glob::Pattern::new(s).unwrap();
+        let bases = [Path::new("/ws/crates/a"), Path::new("/ws")];
+        let path = Path::new("/ws/crates/a/src/lib.rs");
+        assert!(is_match(&pattern("src/lib.rs"), path, &bases));
+        assert!(is_match(&pattern("crates/a/src/lib.rs"), path, &bases));
+        assert!(is_match(&pattern("/ws/crates/a/src/lib.rs"), path, &bases));
+        assert!(is_match(&pattern("crates/**/*.rs"), path, &bases));
+        assert!(!is_match(&pattern("crates/*.rs"), path, &bases));
+        assert!(!is_match(&pattern("src/main.rs"), path, &bases));
+    }
+
+    #[test]
+    fn test_render() {
+        let lines = BTreeMap::from([(1, 2), (2, 0)]);
+        let mut out = NoColor::new(vec![]);
+        render(&mut out, "src/lib.rs", "fn f() {\n\tg();\n}\n", &lines).unwrap();
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "src/lib.rs  1/2 lines (50.00%)\n      2  fn f() {\n      0✗     g();\n         }\n"
+        );
+    }
+}
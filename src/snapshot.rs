@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --save-snapshot/--compare-to: per-file coverage recorded in a file and compared with later
+// runs.
+//
+// Unlike --ratchet, the snapshot is only written when requested, so it can be kept as the
+// baseline of a branch and compared with the coverage of each change.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufWriter, Write as _},
+};
+
+use anyhow::{Context as _, Result};
+use camino::Utf8Path;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    context::Context,
+    fs,
+    term::{self, ExitStatus},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct Counts {
+    functions: u64,
+    covered_functions: u64,
+    lines: u64,
+    covered_lines: u64,
+    regions: u64,
+    covered_regions: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Snapshot {
+    total: Counts,
+    /// File name relative to the workspace root -> counts
+    files: BTreeMap<String, Counts>,
+}
+
+impl Snapshot {
+    fn new(json: &LlvmCovJsonExport, root: &Utf8Path) -> Self {
+        let mut snapshot = Self::default();
+        for file in json.get_file_coverage() {
+            let counts = Counts {
+                functions: file.functions,
+                covered_functions: file.covered_functions,
+                lines: file.lines,
+                covered_lines: file.covered_lines,
+                regions: file.regions,
+                covered_regions: file.covered_regions,
+            };
+            let total = &mut snapshot.total;
+            total.functions += counts.functions;
+            total.covered_functions += counts.covered_functions;
+            total.lines += counts.lines;
+            total.covered_lines += counts.covered_lines;
+            total.regions += counts.regions;
+            total.covered_regions += counts.covered_regions;
+            // Use relative paths so that snapshots can be compared between checkouts.
+            let name = Utf8Path::new(&file.name)
+                .strip_prefix(root)
+                .map_or_else(|_| file.name.clone(), ToString::to_string);
+            snapshot.files.insert(name, counts);
+        }
+        snapshot
+    }
+}
+
+pub(crate) fn save(cx: &Context, json: &LlvmCovJsonExport, path: &Utf8Path) -> Result<()> {
+    let snapshot = Snapshot::new(json, &cx.ws.metadata.workspace_root);
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut s = serde_json::to_string_pretty(&snapshot)?;
+    s.push('\n');
+    fs::write(path, s)?;
+    status!("Finished", "coverage snapshot saved to {path}");
+    Ok(())
+}
+
+/// Prints the changes of the coverage since the snapshot, and sets the exit status if the
+/// total coverage or the line coverage of any file decreased by more than `tolerance`
+/// percentage points.
+pub(crate) fn compare(
+    cx: &Context,
+    json: &LlvmCovJsonExport,
+    path: &Utf8Path,
+    tolerance: f64,
+) -> Result<()> {
+    let previous: Snapshot = serde_json::from_str(&fs::read_to_string(path)?)
+        .with_context(|| format!("failed to parse {path}"))?;
+    let current = Snapshot::new(json, &cx.ws.metadata.workspace_root);
+    let (changes, regressions) = diff(&previous, &current, tolerance);
+
+    let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+    writeln!(stdout, "Coverage Changes (compared to {path}):")?;
+    for change in changes {
+        writeln!(stdout, "{change}")?;
+    }
+    stdout.flush()?;
+    drop(stdout);
+
+    for regression in regressions {
+        term::fail(ExitStatus::Threshold);
+        error!("{regression} (recorded in {path})");
+    }
+    Ok(())
+}
+
+/// Returns the lines describing the changes, and the regressions beyond `tolerance`.
+fn diff(previous: &Snapshot, current: &Snapshot, tolerance: f64) -> (Vec<String>, Vec<String>) {
+    let mut changes = vec![];
+    let mut regressions = vec![];
+    let mut check = |name: &str, prev: Option<f64>, cur: Option<f64>| {
+        if let (Some(prev), Some(cur)) = (prev, cur) {
+            if cur < prev - tolerance {
+                regressions.push(format!("{name} decreased from {prev:.2}% to {cur:.2}%"));
+            }
+        }
+    };
+
+    // Only line coverage is compared for each file, to keep the output short.
+    for (name, cur) in &current.files {
+        let cur_percent = percent(cur.lines, cur.covered_lines);
+        match previous.files.get(name) {
+            Some(prev) => {
+                let prev_percent = percent(prev.lines, prev.covered_lines);
+                if let (Some(p), Some(c)) = (prev_percent, cur_percent) {
+                    if p != c {
+                        changes.push(format!("{name}: {p:.2}% -> {c:.2}% ({:+.2})", c - p));
+                    }
+                }
+                check(&format!("line coverage of {name}"), prev_percent, cur_percent);
+            }
+            None => {
+                if let Some(c) = cur_percent {
+                    changes.push(format!("{name}: {c:.2}% (new)"));
+                }
+            }
+        }
+    }
+    for name in previous.files.keys() {
+        if !current.files.contains_key(name) {
+            changes.push(format!("{name}: removed"));
+        }
+    }
+
+    let (prev, cur) = (&previous.total, &current.total);
+    let mut total = vec![];
+    for (kind, prev, cur) in [
+        (
+            "function",
+            percent(prev.functions, prev.covered_functions),
+            percent(cur.functions, cur.covered_functions),
+        ),
+        ("line", percent(prev.lines, prev.covered_lines), percent(cur.lines, cur.covered_lines)),
+        (
+            "region",
+            percent(prev.regions, prev.covered_regions),
+            percent(cur.regions, cur.covered_regions),
+        ),
+    ] {
+        let fmt = |p: Option<f64>| p.map_or_else(|| "-".to_owned(), |p| format!("{p:.2}%"));
+        total.push(format!("{kind}s {} -> {}", fmt(prev), fmt(cur)));
+        check(&format!("total {kind} coverage"), prev, cur);
+    }
+    changes.push(format!("TOTAL: {}", total.join(", ")));
+    (changes, regressions)
+}
+
+fn percent(count: u64, covered: u64) -> Option<f64> {
+    (count != 0).then(|| covered as f64 * 100. / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counts, Snapshot, diff};
+
+    fn snapshot(files: &[(&str, u64, u64)]) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        for &(name, lines, covered_lines) in files {
+            let counts = Counts { lines, covered_lines, ..Counts::default() };
+            snapshot.total.lines += lines;
+            snapshot.total.covered_lines += covered_lines;
+            snapshot.files.insert(name.to_owned(), counts);
+        }
+        snapshot
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = snapshot(&[("a.rs", 4, 4), ("b.rs", 4, 2), ("c.rs", 2, 1)]);
+        let current = snapshot(&[("a.rs", 4, 3), ("b.rs", 4, 2), ("d.rs", 2, 2)]);
+        let (changes, regressions) = diff(&previous, &current, 0.);
+        assert_eq!(changes, [
+            "a.rs: 100.00% -> 75.00% (-25.00)",
+            "d.rs: 100.00% (new)",
+            "c.rs: removed",
+            "TOTAL: functions - -> -, lines 70.00% -> 70.00%, regions - -> -",
+        ]);
+        assert_eq!(regressions, ["line coverage of a.rs decreased from 100.00% to 75.00%"]);
+
+        let (_, regressions) = diff(&previous, &current, 25.);
+        assert!(regressions.is_empty());
+        let current = snapshot(&[("a.rs", 4, 2), ("b.rs", 4, 2)]);
+        let (_, regressions) = diff(&previous, &current, 0.);
+        assert_eq!(regressions, [
+            "line coverage of a.rs decreased from 100.00% to 50.00%",
+            "total line coverage decreased from 70.00% to 50.00%",
+        ]);
+    }
+}
@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Prints statistics about the instrumentation to help users understand the cost of
+// coverage collection.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result};
+use camino::Utf8Path;
+
+use crate::{context::Context, fs, merge_profraw, object_files, term};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CrateStats {
+    functions: u64,
+    counters: u64,
+}
+
+pub(crate) fn run(cx: &Context) -> Result<()> {
+    let profraw_files: Vec<_> = glob::glob(
+        Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw").as_str(),
+    )?
+    .filter_map(Result::ok)
+    .collect();
+    let profraw_size = profraw_files.iter().map(|path| file_size(path)).sum::<Result<u64>>()?;
+    merge_profraw(cx).context("failed to merge profile data")?;
+
+    let mut cmd = cx.process(&cx.llvm_profdata);
+    cmd.args(["show", "--all-functions"]).arg(&cx.ws.profdata_file);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    let crates = crate_stats(&cmd.read()?);
+    let object_files = object_files(cx).context("failed to collect object files")?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<40} {:>12} {:>12}", "Crate", "Functions", "Counters");
+    let mut total = CrateStats::default();
+    for (name, stats) in &crates {
+        let _ = writeln!(out, "{name:<40} {:>12} {:>12}", stats.functions, stats.counters);
+        total.functions += stats.functions;
+        total.counters += stats.counters;
+    }
+    let _ = writeln!(out, "{:<40} {:>12} {:>12}", "TOTAL", total.functions, total.counters);
+
+    let _ = writeln!(out, "\n{:<40} {:>12} {:>12}", "Profile data", "Files", "Size");
+    let _ = writeln!(
+        out,
+        "{:<40} {:>12} {:>12}",
+        "profraw",
+        profraw_files.len(),
+        format_size(profraw_size)
+    );
+    let _ = writeln!(
+        out,
+        "{:<40} {:>12} {:>12}",
+        "profdata",
+        1,
+        format_size(file_size(cx.ws.profdata_file.as_std_path())?)
+    );
+
+    let _ = writeln!(
+        out,
+        "\n{:<40} {:>12} {:>14} {:>9}",
+        "Binary", "Instrumented", "Uninstrumented", "Overhead"
+    );
+    let mut missing = false;
+    for object in &object_files {
+        let object = Path::new(object);
+        let name = object.strip_prefix(&cx.ws.metadata.target_directory).unwrap_or(object);
+        let size = file_size(object)?;
+        let (base_size, overhead) = match uninstrumented_object(cx, object) {
+            Some(base) => {
+                let base_size = file_size(&base)?;
+                let overhead = (size as f64 / base_size.max(1) as f64 - 1.) * 100.;
+                (format_size(base_size), format!("{overhead:+.1}%"))
+            }
+            None => {
+                missing = true;
+                ("-".to_owned(), "-".to_owned())
+            }
+        };
+        let _ = writeln!(
+            out,
+            "{:<40} {:>12} {base_size:>14} {overhead:>9}",
+            name.display(),
+            format_size(size)
+        );
+    }
+
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{out}")?;
+    stdout.flush()?;
+    if missing {
+        info!(
+            "uninstrumented binaries to compare with were not found in {}; build them without \
+             cargo-llvm-cov (e.g., `cargo test --no-run`) to show the binary size overhead",
+            cx.ws.metadata.target_directory
+        );
+    }
+    Ok(())
+}
+
+/// Counts the instrumented functions and counters per crate from the output of
+/// `llvm-profdata show --all-functions`.
+fn crate_stats(s: &str) -> BTreeMap<String, CrateStats> {
+    let mut crates: BTreeMap<String, CrateStats> = BTreeMap::new();
+    let mut current = None;
+    for line in s.lines() {
+        if let Some(name) = line.strip_prefix("  ").and_then(|l| l.strip_suffix(':')) {
+            if !name.starts_with(' ') {
+                let name = crate_name(name);
+                crates.entry(name.clone()).or_default().functions += 1;
+                current = Some(name);
+                continue;
+            }
+        }
+        if let Some(counters) = line.trim_start().strip_prefix("Counters: ") {
+            let stats = current.as_ref().and_then(|name| crates.get_mut(name));
+            if let (Some(stats), Ok(counters)) = (stats, counters.parse::<u64>()) {
+                stats.counters += counters;
+            }
+        } else if !line.starts_with(' ') {
+            // The summary after the list of functions.
+            current = None;
+        }
+    }
+    crates
+}
+
+/// Returns the name of the crate that defines the given function.
+fn crate_name(name: &str) -> String {
+    // Names of functions with internal linkage are prefixed with the file name
+    // (`<file>;<name>`, or `<file>:<name>` in old LLVM).
+    let name = name.rsplit([';', ':']).next().unwrap_or(name);
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    match demangled.trim_start_matches('<').split_once("::") {
+        Some((krate, _)) => krate.to_owned(),
+        None => "(other)".to_owned(),
+    }
+}
+
+/// Returns the binary built without instrumentation in the (non-cargo-llvm-cov) target
+/// directory that corresponds to the given instrumented binary.
+pub(crate) fn uninstrumented_object(cx: &Context, object: &Path) -> Option<PathBuf> {
+    let (rel, base) = if let Ok(rel) = object.strip_prefix(&cx.ws.target_dir) {
+        (rel, cx.ws.metadata.target_directory.as_path())
+    } else {
+        let build_dir = cx.ws.build_dir.as_ref()?;
+        (object.strip_prefix(build_dir).ok()?, cx.ws.metadata.build_directory())
+    };
+    let dir = base.as_std_path().join(rel.parent()?);
+    let name = strip_hash(rel.file_name()?.to_str()?);
+    // The hash in the file name may differ between instrumented and uninstrumented builds,
+    // so compare names without it and use the most recently built one.
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_str().is_some_and(|n| strip_hash(n) == name))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Removes the `-<hash>` suffix cargo appends to the file stem, e.g., `foo-0123456789abcdef`.
+fn strip_hash(file_name: &str) -> String {
+    let (stem, ext) = match file_name.split_once('.') {
+        Some((stem, ext)) => (stem, Some(ext)),
+        None => (file_name, None),
+    };
+    let stem = match stem.rsplit_once('-') {
+        Some((name, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            name
+        }
+        _ => stem,
+    };
+    match ext {
+        Some(ext) => format!("{stem}.{ext}"),
+        None => stem.to_owned(),
+    }
+}
+
+pub(crate) fn file_size(path: &Path) -> Result<u64> {
+    Ok(fs::metadata(path)?.len())
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 { format!("{bytes} B") } else { format!("{size:.1} {}", UNITS[unit]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrateStats, crate_name, crate_stats, format_size, strip_hash};
+
+    #[test]
+    fn test_crate_stats() {
+        let out = "\
+Counters:
+  _RNvCs1234_3foo4main:
+    Hash: 0x0000000000000001
+    Counters: 3
+    Function count: 1
+  src/lib.rs;_RNvNtCs5678_3bar5inner3baz:
+    Hash: 0x0000000000000002
+    Counters: 2
+    Function count: 0
+  _RNvCs1234_3foo6helper:
+    Hash: 0x0000000000000003
+    Counters: 1
+    Function count: 4
+Instrumentation level: Front-end
+Functions shown: 3
+Total functions: 3
+Maximum function count: 4
+Maximum internal block count: 1
+";
+        let crates = crate_stats(out);
+        assert_eq!(crates.len(), 2);
+        assert_eq!(crates["foo"], CrateStats { functions: 2, counters: 4 });
+        assert_eq!(crates["bar"], CrateStats { functions: 1, counters: 2 });
+    }
+
+    #[test]
+    fn test_crate_name() {
+        assert_eq!(crate_name("_RNvCs1234_3foo4main"), "foo");
+        assert_eq!(crate_name("src/lib.rs:_RNvCs1234_3foo4main"), "foo");
+        assert_eq!(crate_name("_ZN3foo4main17h0123456789abcdefE"), "foo");
+        assert_eq!(crate_name("c_function"), "(other)");
+    }
+
+    #[test]
+    fn test_strip_hash() {
+        assert_eq!(strip_hash("foo-0123456789abcdef"), "foo");
+        assert_eq!(strip_hash("foo-0123456789abcdef.exe"), "foo.exe");
+        assert_eq!(strip_hash("foo-bar"), "foo-bar");
+        assert_eq!(strip_hash("foo"), "foo");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
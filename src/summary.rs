@@ -0,0 +1,443 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --file-summary: table of the coverage of each file, rendered from the JSON export.
+//
+// Unlike the summary printed by `llvm-cov report`, rows can be sorted by coverage, and
+// uncovered lines can be listed as ranges in the last column (like the report of coverage.py).
+//
+// --csv/--tsv: the same numbers as the table, with a row for each file and workspace member.
+//
+// --show-missing-ranges: uncovered line ranges with the functions that contain them, one per
+// line, for CI logs where the HTML report is not available.
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    io::{self, BufWriter, Write as _},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use anyhow::{Error, Result, bail};
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{GroupCoverage, LlvmCovJsonExport, escape_field};
+use termcolor::{Color, ColorSpec, NoColor, WriteColor};
+
+use crate::{context::Context, term};
+
+const GREEN: f64 = 80.;
+const YELLOW: f64 = 50.;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum SortBy {
+    #[default]
+    Name,
+    Lines,
+    Functions,
+    Regions,
+    Branches,
+}
+
+impl FromStr for SortBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Self::Name),
+            "lines" => Ok(Self::Lines),
+            "functions" => Ok(Self::Functions),
+            "regions" => Ok(Self::Regions),
+            "branches" => Ok(Self::Branches),
+            _ => bail!(
+                "--sort-by must be name, lines, functions, regions, or branches, but found `{s}`"
+            ),
+        }
+    }
+}
+
+pub(crate) fn print(
+    cx: &Context,
+    json: &LlvmCovJsonExport,
+    ignore_filename_regex: Option<&str>,
+) -> Result<()> {
+    let root = &cx.ws.metadata.workspace_root;
+    let missing = if cx.args.cov.show_missing_lines {
+        let uncovered_files = json.get_uncovered_lines(ignore_filename_regex)?;
+        Some(
+            uncovered_files
+                .into_iter()
+                .map(|(file, lines)| (file, ranges(&lines)))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    } else {
+        None
+    };
+    let mut files: Vec<_> = json
+        .get_file_coverage()
+        .into_iter()
+        .map(|mut file| {
+            let missing = missing.as_ref().map(|m| m.get(&file.name).cloned().unwrap_or_default());
+            if let Ok(name) = Utf8Path::new(&file.name).strip_prefix(root) {
+                file.name = name.to_string();
+            }
+            (file, missing)
+        })
+        .collect();
+    sort(&mut files, cx.args.cov.sort_by.unwrap_or_default());
+    // Branch coverage is only available when it is enabled.
+    let branch = cx.args.cov.branch || cx.args.cov.mcdc;
+
+    if cx.args.cov.output_path.is_some() {
+        let mut out = NoColor::new(vec![]);
+        render(&mut out, &files, branch)?;
+        crate::write_report(cx, &String::from_utf8(out.into_inner())?, false)?;
+    } else {
+        let mut stdout = term::stdout();
+        render(&mut stdout, &files, branch)?;
+    }
+    Ok(())
+}
+
+/// Returns the coverage summary in CSV (if `separator` is `,`) or TSV (if `separator` is `\t`)
+/// format, for --csv and --tsv.
+pub(crate) fn delimited(
+    cx: &Context,
+    json: &LlvmCovJsonExport,
+    packages: &[GroupCoverage],
+    separator: char,
+) -> String {
+    let root = &cx.ws.metadata.workspace_root;
+    let mut files = json.get_file_coverage();
+    for file in &mut files {
+        if let Ok(name) = Utf8Path::new(&file.name).strip_prefix(root) {
+            file.name = name.to_string();
+        }
+    }
+    render_delimited(&files, packages, separator)
+}
+
+fn render_delimited(
+    files: &[GroupCoverage],
+    packages: &[GroupCoverage],
+    separator: char,
+) -> String {
+    let sep = &*separator.to_string();
+    let mut out = [
+        "kind",
+        "name",
+        "lines",
+        "covered_lines",
+        "lines_percent",
+        "functions",
+        "covered_functions",
+        "functions_percent",
+        "regions",
+        "covered_regions",
+        "regions_percent",
+        "branches",
+        "covered_branches",
+        "branches_percent",
+    ]
+    .join(sep);
+    out.push('\n');
+    let total = total(files.iter());
+    let rows = files
+        .iter()
+        .map(|f| ("file", f))
+        .chain(packages.iter().map(|f| ("package", f)))
+        .chain([("total", &total)]);
+    for (kind, f) in rows {
+        let mut fields = vec![kind.to_owned(), escape_field(&f.name, separator)];
+        for (count, covered) in [
+            (f.lines, f.covered_lines),
+            (f.functions, f.covered_functions),
+            (f.regions, f.covered_regions),
+            (f.branches, f.covered_branches),
+        ] {
+            fields.push(count.to_string());
+            fields.push(covered.to_string());
+            // Empty if there is no coverage of the kind, like `-` in the table.
+            fields.push(percent(count, covered).map(|p| format!("{p:.2}")).unwrap_or_default());
+        }
+        out.push_str(&fields.join(sep));
+        out.push('\n');
+    }
+    out
+}
+
+fn total<'a>(files: impl Iterator<Item = &'a GroupCoverage>) -> GroupCoverage {
+    let mut total = GroupCoverage {
+        name: "TOTAL".to_owned(),
+        functions: 0,
+        covered_functions: 0,
+        lines: 0,
+        covered_lines: 0,
+        regions: 0,
+        covered_regions: 0,
+        branches: 0,
+        covered_branches: 0,
+    };
+    for f in files {
+        total.functions += f.functions;
+        total.covered_functions += f.covered_functions;
+        total.lines += f.lines;
+        total.covered_lines += f.covered_lines;
+        total.regions += f.regions;
+        total.covered_regions += f.covered_regions;
+        total.branches += f.branches;
+        total.covered_branches += f.covered_branches;
+    }
+    total
+}
+
+/// Sorts files by name, or by coverage in ascending order (least covered first).
+fn sort(files: &mut [(GroupCoverage, Option<String>)], sort_by: SortBy) {
+    let key = |f: &GroupCoverage| match sort_by {
+        SortBy::Name => None,
+        SortBy::Lines => percent(f.lines, f.covered_lines),
+        SortBy::Functions => percent(f.functions, f.covered_functions),
+        SortBy::Regions => percent(f.regions, f.covered_regions),
+        SortBy::Branches => percent(f.branches, f.covered_branches),
+    };
+    files.sort_by(|(a, _), (b, _)| {
+        // Files without the coverage of the kind are placed last.
+        match (key(a), key(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+fn render(
+    w: &mut impl WriteColor,
+    files: &[(GroupCoverage, Option<String>)],
+    branch: bool,
+) -> io::Result<()> {
+    let total = total(files.iter().map(|(f, _)| f));
+    let counts = |f: &GroupCoverage| {
+        let mut counts = vec![
+            (f.lines, f.covered_lines),
+            (f.functions, f.covered_functions),
+            (f.regions, f.covered_regions),
+        ];
+        if branch {
+            counts.push((f.branches, f.covered_branches));
+        }
+        counts
+    };
+    let mut headers = vec!["Lines", "Functions", "Regions"];
+    if branch {
+        headers.push("Branches");
+    }
+    let show_missing = files.iter().any(|(_, missing)| missing.is_some());
+
+    // Width of the file name column and the `covered/count` part of each column.
+    let rows = files.iter().map(|(f, _)| f).chain([&total]);
+    let name_width = rows.clone().map(|f| f.name.len()).max().unwrap_or(0).max("Filename".len());
+    let mut widths = vec![0; headers.len()];
+    for f in rows {
+        for (width, (count, covered)) in widths.iter_mut().zip(counts(f)) {
+            *width = (*width).max(format!("{covered}/{count}").len());
+        }
+    }
+
+    write!(w, "{:<name_width$}", "Filename")?;
+    for (header, width) in headers.iter().zip(&widths) {
+        // `covered/count` and percent (`100.00%`)
+        write!(w, "  {header:>0$}", width + 8)?;
+    }
+    if show_missing {
+        write!(w, "  Missing")?;
+    }
+    writeln!(w)?;
+    let separator = name_width + widths.iter().map(|width| width + 10).sum::<usize>();
+    writeln!(w, "{}", "-".repeat(separator))?;
+
+    let row = |w: &mut dyn WriteColor, f: &GroupCoverage, missing: &str| -> io::Result<()> {
+        write!(w, "{:<name_width$}", f.name)?;
+        for (width, (count, covered)) in widths.iter().zip(counts(f)) {
+            write!(w, "  {:>width$} ", format!("{covered}/{count}"))?;
+            match percent(count, covered) {
+                Some(p) => {
+                    let color = if p >= GREEN {
+                        Color::Green
+                    } else if p >= YELLOW {
+                        Color::Yellow
+                    } else {
+                        Color::Red
+                    };
+                    w.set_color(ColorSpec::new().set_fg(Some(color)))?;
+                    write!(w, "{:>7}", format!("{p:.2}%"))?;
+                    w.reset()?;
+                }
+                None => write!(w, "{:>7}", "-")?,
+            }
+        }
+        if !missing.is_empty() {
+            write!(w, "  {missing}")?;
+        }
+        writeln!(w)
+    };
+    for (f, missing) in files {
+        row(w, f, missing.as_deref().unwrap_or_default())?;
+    }
+    writeln!(w, "{}", "-".repeat(separator))?;
+    row(w, &total, "")?;
+    w.flush()
+}
+
+fn percent(count: u64, covered: u64) -> Option<f64> {
+    (count != 0).then(|| covered as f64 * 100. / count as f64)
+}
+
+/// Prints the uncovered line ranges of each file and the functions that contain them.
+pub(crate) fn print_missing_ranges(
+    json: &LlvmCovJsonExport,
+    ignore_filename_regex: Option<&str>,
+) -> Result<()> {
+    let functions = json.get_uncovered_lines_by_function(ignore_filename_regex)?;
+    if functions.is_empty() {
+        return Ok(());
+    }
+    let mut ranges: Vec<_> = functions
+        .iter()
+        .flat_map(|f| coalesce(&f.uncovered_lines).into_iter().map(move |range| (f, range)))
+        .collect();
+    ranges.sort_by(|(a, x), (b, y)| {
+        a.location
+            .filename
+            .cmp(&b.location.filename)
+            .then(x.start().cmp(y.start()))
+            .then(a.location.name.cmp(&b.location.name))
+    });
+
+    let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+    writeln!(stdout, "Uncovered Ranges:")?;
+    for (f, range) in ranges {
+        writeln!(stdout, "{}:{}: {}", f.location.filename, format_range(&range), f.location.name)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Coalesces sorted line numbers into ranges of consecutive lines.
+pub(crate) fn coalesce(lines: &[u64]) -> Vec<RangeInclusive<u64>> {
+    let mut ranges: Vec<RangeInclusive<u64>> = vec![];
+    for &line in lines {
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == line => *range = *range.start()..=line,
+            _ => ranges.push(line..=line),
+        }
+    }
+    ranges
+}
+
+fn format_range(range: &RangeInclusive<u64>) -> String {
+    if range.start() == range.end() {
+        range.start().to_string()
+    } else {
+        format!("{}-{}", range.start(), range.end())
+    }
+}
+
+/// Formats sorted line numbers as ranges, e.g., `1-3, 5`.
+fn ranges(lines: &[u64]) -> String {
+    coalesce(lines).iter().map(format_range).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::GroupCoverage;
+    use termcolor::NoColor;
+
+    use super::{SortBy, coalesce, ranges, render, render_delimited, sort};
+
+    fn file(name: &str, lines: (u64, u64), functions: (u64, u64)) -> GroupCoverage {
+        GroupCoverage {
+            name: name.to_owned(),
+            functions: functions.0,
+            covered_functions: functions.1,
+            lines: lines.0,
+            covered_lines: lines.1,
+            regions: 0,
+            covered_regions: 0,
+            branches: 0,
+            covered_branches: 0,
+        }
+    }
+
+    #[test]
+    fn test_coalesce() {
+        assert_eq!(coalesce(&[]), []);
+        assert_eq!(coalesce(&[1, 2, 3, 5, 7, 8]), [1..=3, 5..=5, 7..=8]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(ranges(&[]), "");
+        assert_eq!(ranges(&[3]), "3");
+        assert_eq!(ranges(&[1, 2, 3, 5, 7, 8]), "1-3, 5, 7-8");
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut files = vec![
+            (file("a.rs", (4, 4), (1, 1)), None),
+            (file("b.rs", (4, 1), (0, 0)), None),
+            (file("c.rs", (4, 2), (2, 0)), None),
+        ];
+        sort(&mut files, SortBy::Lines);
+        let names: Vec<_> = files.iter().map(|(f, _)| f.name.as_str()).collect();
+        assert_eq!(names, ["b.rs", "c.rs", "a.rs"]);
+        sort(&mut files, SortBy::Functions);
+        let names: Vec<_> = files.iter().map(|(f, _)| f.name.as_str()).collect();
+        assert_eq!(names, ["c.rs", "a.rs", "b.rs"]);
+        sort(&mut files, SortBy::Name);
+        let names: Vec<_> = files.iter().map(|(f, _)| f.name.as_str()).collect();
+        assert_eq!(names, ["a.rs", "b.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn test_render() {
+        let files = vec![
+            (file("src/lib.rs", (11, 8), (5, 4)), Some("7-9".to_owned())),
+            (file("src/main.rs", (2, 2), (1, 1)), Some(String::new())),
+        ];
+        let mut out = NoColor::new(vec![]);
+        render(&mut out, &files, false).unwrap();
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "\
+Filename             Lines    Functions      Regions  Missing
+----------------------------------------------------
+src/lib.rs    8/11  72.73%  4/5  80.00%  0/0       -  7-9
+src/main.rs    2/2 100.00%  1/1 100.00%  0/0       -
+----------------------------------------------------
+TOTAL        10/13  76.92%  5/6  83.33%  0/0       -
+"
+        );
+    }
+
+    #[test]
+    fn test_render_delimited() {
+        let files = [file("src/lib.rs", (11, 8), (5, 4)), file("src/a,b.rs", (2, 2), (0, 0))];
+        let packages = [file("t", (13, 10), (5, 4))];
+        assert_eq!(
+            render_delimited(&files, &packages, ','),
+            "\
+kind,name,lines,covered_lines,lines_percent,functions,covered_functions,functions_percent,regions,covered_regions,regions_percent,branches,covered_branches,branches_percent
+file,src/lib.rs,11,8,72.73,5,4,80.00,0,0,,0,0,
+file,\"src/a,b.rs\",2,2,100.00,0,0,,0,0,,0,0,
+package,t,13,10,76.92,5,4,80.00,0,0,,0,0,
+total,TOTAL,13,10,76.92,5,4,80.00,0,0,,0,0,
+"
+        );
+        assert!(
+            render_delimited(&files, &packages, '\t')
+                .ends_with("total\tTOTAL\t13\t10\t76.92\t5\t4\t80.00\t0\t0\t\t0\t0\t\n")
+        );
+    }
+}
@@ -7,7 +7,9 @@ use std::{
 };
 
 use anyhow::Error;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor as _};
+use termcolor::{
+    BufferedStandardStream, Color, ColorChoice, ColorSpec, StandardStream, WriteColor as _,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
@@ -74,6 +76,15 @@ fn coloring() -> ColorChoice {
     }
 }
 
+/// Returns stdout that is colored if the coloring is enabled and stdout is a terminal.
+pub(crate) fn stdout() -> BufferedStandardStream {
+    let choice = match coloring() {
+        ColorChoice::Auto if !io::stdout().is_terminal() => ColorChoice::Never,
+        choice => choice,
+    };
+    BufferedStandardStream::stdout(choice)
+}
+
 macro_rules! global_flag {
     ($name:ident: $value:ty = $ty:ident::new($($default:expr)?)) => {
         pub(crate) mod $name {
@@ -105,6 +116,34 @@ global_flag!(verbose: bool = AtomicBool::new(false));
 global_flag!(error: bool = AtomicBool::new(false));
 global_flag!(warn: bool = AtomicBool::new(false));
 
+/// Exit status of cargo-llvm-cov when it fails, so that scripts can tell why it failed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub(crate) enum ExitStatus {
+    /// Tests failed, or other errors occurred.
+    Failure = 1,
+    /// Coverage did not meet a threshold, e.g., --fail-under-lines or --ratchet.
+    Threshold = 2,
+    /// No profile data was generated or found.
+    NoProfileData = 3,
+    /// llvm-cov or llvm-profdata was not found.
+    ToolchainMissing = 4,
+}
+
+static EXIT_STATUS: AtomicU8 = AtomicU8::new(0);
+/// Marks cargo-llvm-cov as failed. Only the first failure determines the exit status.
+pub(crate) fn fail(status: ExitStatus) {
+    error::set(true);
+    let _ = EXIT_STATUS.compare_exchange(0, status as u8, Ordering::Relaxed, Ordering::Relaxed);
+}
+/// Returns the exit status of the first failure.
+pub(crate) fn exit_status() -> u8 {
+    match EXIT_STATUS.load(Ordering::Relaxed) {
+        0 => ExitStatus::Failure as u8,
+        status => status,
+    }
+}
+
 pub(crate) fn print_status(status: &str, color: Option<Color>, justified: bool) -> StandardStream {
     let mut stream = StandardStream::stderr(coloring());
     let _ = stream.set_color(ColorSpec::new().set_bold(true).set_fg(color));
@@ -123,7 +162,7 @@ pub(crate) fn print_status(status: &str, color: Option<Color>, justified: bool)
 macro_rules! error {
     ($($msg:expr),* $(,)?) => {{
         use std::io::Write as _;
-        crate::term::error::set(true);
+        crate::term::fail(crate::term::ExitStatus::Failure);
         let mut stream = crate::term::print_status("error", Some(termcolor::Color::Red), false);
         let _ = writeln!(stream, $($msg),*);
     }};
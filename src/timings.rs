@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Timing and size report for --timings, to decide which crates to exclude from
+// instrumentation (e.g., by --exclude-crate).
+//
+// The rustc wrapper measures the compile time of each crate and writes it to the timings
+// directory, so only the crates compiled in this run are in the report. With
+// --measure-overhead, the build without instrumentation also uses the wrapper, so that the
+// compile time of each crate can be compared with the one without instrumentation.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde_derive::Serialize;
+
+use crate::{
+    context::Context,
+    fs, overhead,
+    process::ProcessBuilder,
+    profraw_files_modified, stats,
+    wrapper::{self, CrateTiming},
+};
+
+#[derive(Debug, Serialize)]
+struct Report {
+    crates: Vec<CrateReport>,
+    profraw_files: usize,
+    profraw_size: u64,
+    merge_seconds: f64,
+    report_seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CrateReport {
+    crate_name: String,
+    package_name: Option<String>,
+    kind: String,
+    instrumented: bool,
+    seconds: f64,
+    /// Compile time without instrumentation, if the crate was compiled by --measure-overhead.
+    baseline_seconds: Option<f64>,
+}
+
+fn dir(cx: &Context) -> Utf8PathBuf {
+    cx.ws.target_dir.join("llvm-cov-timings")
+}
+
+/// Returns the directory where the rustc wrapper writes the compile time of each crate.
+pub(crate) fn instrumented_dir(cx: &Context) -> Utf8PathBuf {
+    dir(cx).join("instrumented")
+}
+
+fn baseline_dir(cx: &Context) -> Utf8PathBuf {
+    dir(cx).join("baseline")
+}
+
+/// Removes the compile times recorded by previous runs.
+pub(crate) fn clear(cx: &Context) -> Result<()> {
+    let dir = dir(cx);
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Uses the rustc wrapper for the build without instrumentation of --measure-overhead, only to
+/// measure the compile time of each crate.
+pub(crate) fn set_baseline_env(cx: &Context, cmd: &mut ProcessBuilder) {
+    let inner =
+        wrapper::inner_wrapper(cx.ws.config.build.rustc_wrapper.as_deref(), &cx.current_exe);
+    cmd.env(wrapper::STD_SRC_ENV, "");
+    cmd.env(wrapper::INNER_WRAPPER_ENV, inner);
+    cmd.env(wrapper::TIMINGS_DIR_ENV, baseline_dir(cx).as_str());
+    cmd.env("RUSTC_WRAPPER", &cx.current_exe);
+}
+
+/// Prints the report, and writes it to `timings.json` in the output directory. `previous` is
+/// the result of [`profraw_files_modified`] before running tests.
+pub(crate) fn print(
+    cx: &Context,
+    previous: &BTreeMap<PathBuf, Option<SystemTime>>,
+    merge: Duration,
+    report: Duration,
+) -> Result<()> {
+    let (mut profraw_files, mut profraw_size) = (0, 0);
+    for (path, modified) in profraw_files_modified(cx)? {
+        if previous.get(&path) != Some(&modified) {
+            profraw_files += 1;
+            profraw_size += stats::file_size(&path)?;
+        }
+    }
+    let report = Report {
+        crates: crates(&read(&instrumented_dir(cx))?, &read(&baseline_dir(cx))?),
+        profraw_files,
+        profraw_size,
+        merge_seconds: merge.as_secs_f64(),
+        report_seconds: report.as_secs_f64(),
+    };
+
+    fs::create_dir_all(&cx.ws.output_dir)?;
+    let path = cx.ws.output_dir.join("timings.json");
+    fs::write(&path, serde_json::to_string(&report)?)?;
+    eprintln!();
+    status!("Measured", "compile time of {} crates (saved to {path})", report.crates.len());
+    eprint!("{}", render(&report));
+    Ok(())
+}
+
+fn read(dir: &Utf8Path) -> Result<Vec<CrateTiming>> {
+    let mut timings = vec![];
+    if !dir.is_dir() {
+        return Ok(timings);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Ok(timing) = serde_json::from_str(&fs::read_to_string(&path)?) {
+            timings.push(timing);
+        }
+    }
+    Ok(timings)
+}
+
+/// Sums up the compile time of the crates with the same name and kind (e.g., built for both the
+/// host and the target), and sorts them by the compile time.
+fn crates(instrumented: &[CrateTiming], baseline: &[CrateTiming]) -> Vec<CrateReport> {
+    let key = |t: &CrateTiming| (t.package_name.clone(), t.crate_name.clone(), t.kind.clone());
+    let mut baseline_seconds: BTreeMap<_, f64> = BTreeMap::new();
+    for timing in baseline {
+        *baseline_seconds.entry(key(timing)).or_default() += timing.seconds;
+    }
+    let mut crates: BTreeMap<_, CrateReport> = BTreeMap::new();
+    for timing in instrumented {
+        let report = crates.entry(key(timing)).or_insert_with(|| CrateReport {
+            crate_name: timing.crate_name.clone(),
+            package_name: timing.package_name.clone(),
+            kind: timing.kind.clone(),
+            instrumented: false,
+            seconds: 0.,
+            baseline_seconds: baseline_seconds.get(&key(timing)).copied(),
+        });
+        report.instrumented |= timing.instrumented;
+        report.seconds += timing.seconds;
+    }
+    let mut crates: Vec<_> = crates.into_values().collect();
+    crates.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+    crates
+}
+
+fn render(report: &Report) -> String {
+    let width = report.crates.iter().map(|c| c.crate_name.len()).max().unwrap_or(0).max(5);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<width$} {:<12} {:>12} {:>12} {:>12} {:>9}",
+        "Crate", "Kind", "Instrumented", "Compile time", "Baseline", "Overhead"
+    );
+    let seconds = |s: f64| format!("{s:.2}s");
+    let (mut total, mut total_baseline) = (0., 0.);
+    for c in &report.crates {
+        total += c.seconds;
+        total_baseline += c.baseline_seconds.unwrap_or(0.);
+        let _ = writeln!(
+            out,
+            "{:<width$} {:<12} {:>12} {:>12} {:>12} {:>9}",
+            c.crate_name,
+            c.kind,
+            if c.instrumented { "yes" } else { "no" },
+            seconds(c.seconds),
+            c.baseline_seconds.map_or_else(|| "-".to_owned(), seconds),
+            c.baseline_seconds.map_or_else(|| "-".to_owned(), |b| overhead::overhead(c.seconds, b)),
+        );
+    }
+    let has_baseline = report.crates.iter().any(|c| c.baseline_seconds.is_some());
+    let _ = writeln!(
+        out,
+        "{:<width$} {:<12} {:>12} {:>12} {:>12} {:>9}",
+        "Total",
+        "",
+        "",
+        seconds(total),
+        if has_baseline { seconds(total_baseline) } else { "-".to_owned() },
+        if has_baseline { overhead::overhead(total, total_baseline) } else { "-".to_owned() },
+    );
+    let _ = writeln!(
+        out,
+        "\nProfraw files: {} ({})",
+        report.profraw_files,
+        stats::format_size(report.profraw_size)
+    );
+    let _ = writeln!(out, "Merge time: {}", seconds(report.merge_seconds));
+    let _ = writeln!(out, "Report time: {}", seconds(report.report_seconds));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Report, crates, render};
+    use crate::wrapper::CrateTiming;
+
+    fn timing(crate_name: &str, kind: &str, instrumented: bool, seconds: f64) -> CrateTiming {
+        CrateTiming {
+            crate_name: crate_name.to_owned(),
+            package_name: Some(crate_name.to_owned()),
+            kind: kind.to_owned(),
+            instrumented,
+            seconds,
+        }
+    }
+
+    #[test]
+    fn test_crates() {
+        let instrumented = [
+            timing("foo", "lib", true, 1.),
+            timing("foo", "test", true, 3.),
+            timing("dep", "lib", false, 1.5),
+            timing("dep", "lib", false, 0.5),
+        ];
+        let baseline = [timing("foo", "lib", false, 0.5)];
+        let crates = crates(&instrumented, &baseline);
+        let crates: Vec<_> = crates
+            .iter()
+            .map(|c| (&*c.crate_name, &*c.kind, c.instrumented, c.seconds, c.baseline_seconds))
+            .collect();
+        assert_eq!(crates, [
+            ("foo", "test", true, 3., None),
+            ("dep", "lib", false, 2., None),
+            ("foo", "lib", true, 1., Some(0.5)),
+        ]);
+    }
+
+    #[test]
+    fn test_render() {
+        let report = Report {
+            crates: crates(&[timing("foo", "lib", true, 1.5)], &[timing("foo", "lib", false, 1.)]),
+            profraw_files: 2,
+            profraw_size: 2048,
+            merge_seconds: 0.25,
+            report_seconds: 0.5,
+        };
+        assert_eq!(
+            render(&report),
+            "Crate Kind         Instrumented Compile time     Baseline  Overhead\n\
+             foo   lib                   yes        1.50s        1.00s    +50.0%\n\
+             Total                                  1.50s        1.00s    +50.0%\n\
+             \n\
+             Profraw files: 2 (2.0 KiB)\n\
+             Merge time: 0.25s\n\
+             Report time: 0.50s\n"
+        );
+    }
+}
@@ -0,0 +1,655 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Interactive coverage browser for terminal-only environments (e.g., over SSH).
+//
+// This does not use a TUI library: the terminal is switched to non-canonical mode by
+// stty(1), and the screen is drawn with ANSI escape sequences.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, IsTerminal as _, Read, Write as _},
+};
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::{
+    Format, context::Context, fs, ignore_filename_regex, merge_profraw, object_files,
+    retain_mapped_changed_files, term::Coloring,
+};
+
+const HEADER_LINES: usize = 2;
+const FOOTER_LINES: usize = 1;
+
+pub(crate) fn run(cx: &Context) -> Result<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!("tui subcommand requires an interactive terminal");
+    }
+
+    merge_profraw(cx).context("failed to merge profile data")?;
+    let object_files = object_files(cx).context("failed to collect object files")?;
+    let ignore_filename_regex = ignore_filename_regex(cx, &object_files)?;
+    if !retain_mapped_changed_files(cx, &object_files, ignore_filename_regex.as_ref())? {
+        let rev = cx.args.cov.changed_since.as_ref().unwrap();
+        info!("no files in the report have been changed since {rev}; nothing to browse");
+        return Ok(());
+    }
+    let json = Format::Json
+        .get_json(cx, &object_files, ignore_filename_regex.as_ref())
+        .context("failed to get json")?;
+
+    let mut app = App::new(cx, &json.get_line_execution_counts());
+    if app.files.is_empty() {
+        bail!("no files with coverage data found");
+    }
+
+    let _terminal = Terminal::enter()?;
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    loop {
+        let (height, width) = terminal_size();
+        let screen = app.draw(height, width);
+        stdout.write_all(screen.as_bytes())?;
+        stdout.flush()?;
+        if !app.handle(read_key(&mut stdin)?) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Restores the terminal state when dropped.
+struct Terminal {
+    saved: String,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        let saved = cmd!("stty", "-g")
+            .read()
+            .context("tui subcommand requires stty(1) to control the terminal")?;
+        // Reads return after 0.1 seconds even if no key is pressed, to distinguish the
+        // Escape key from escape sequences.
+        cmd!("stty", "-icanon", "-echo", "-isig", "min", "0", "time", "1").run()?;
+        // Use the alternate screen and hide the cursor.
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = cmd!("stty", &self.saved).run();
+    }
+}
+
+/// Returns the number of rows and columns of the terminal.
+fn terminal_size() -> (usize, usize) {
+    let size = cmd!("stty", "size").read().ok().and_then(|size| {
+        let (rows, cols) = size.trim().split_once(' ')?;
+        Some((rows.parse().ok()?, cols.parse().ok()?))
+    });
+    match size {
+        Some((rows, cols)) if rows > HEADER_LINES + FOOTER_LINES && cols > 0 => (rows, cols),
+        _ => (24, 80),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Interrupt,
+    Char(char),
+    Unknown,
+}
+
+fn read_byte(stdin: &mut impl Read) -> Result<Option<u8>> {
+    let mut buf = [0];
+    Ok(if stdin.read(&mut buf)? == 0 { None } else { Some(buf[0]) })
+}
+
+fn read_key(stdin: &mut impl Read) -> Result<Key> {
+    let b = loop {
+        if let Some(b) = read_byte(stdin)? {
+            break b;
+        }
+    };
+    Ok(match b {
+        0x1B => match read_byte(stdin)? {
+            None => Key::Esc,
+            Some(b'[' | b'O') => {
+                let mut param = String::new();
+                loop {
+                    match read_byte(stdin)? {
+                        Some(b @ b'0'..=b'9') => param.push(b.into()),
+                        Some(b'A') => break Key::Up,
+                        Some(b'B') => break Key::Down,
+                        Some(b'C') => break Key::Right,
+                        Some(b'D') => break Key::Left,
+                        Some(b'H') => break Key::Home,
+                        Some(b'F') => break Key::End,
+                        Some(b'~') => {
+                            break match param.as_str() {
+                                "1" | "7" => Key::Home,
+                                "4" | "8" => Key::End,
+                                "5" => Key::PageUp,
+                                "6" => Key::PageDown,
+                                _ => Key::Unknown,
+                            };
+                        }
+                        _ => break Key::Unknown,
+                    }
+                }
+            }
+            Some(_) => Key::Unknown,
+        },
+        b'\r' | b'\n' => Key::Enter,
+        0x7F | 0x08 => Key::Backspace,
+        0x03 | 0x04 => Key::Interrupt,
+        0..=0x7F => Key::Char(b.into()),
+        _ => {
+            // Decode a multi-byte UTF-8 character.
+            let mut bytes = vec![b];
+            let len = match b {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                _ => 4,
+            };
+            while bytes.len() < len {
+                let Some(b) = read_byte(stdin)? else { break };
+                bytes.push(b);
+            }
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Key::Unknown, Key::Char)
+        }
+    })
+}
+
+/// A row of the package list or the file list.
+struct Row {
+    name: String,
+    count: u64,
+    covered: u64,
+}
+
+impl Row {
+    fn percent(&self) -> f64 {
+        if self.count == 0 { 100. } else { self.covered as f64 * 100. / self.count as f64 }
+    }
+}
+
+struct File {
+    row: Row,
+    package: usize,
+    path: String,
+    lines: BTreeMap<u64, u64>,
+}
+
+struct Source {
+    file: usize,
+    text: Vec<String>,
+    /// 0-based index of the selected line.
+    cursor: usize,
+    top: usize,
+}
+
+#[derive(Default)]
+struct List {
+    selected: usize,
+    top: usize,
+}
+
+enum View {
+    Packages,
+    Files(usize),
+    Source(Source),
+}
+
+struct App {
+    packages: Vec<Row>,
+    files: Vec<File>,
+    view: View,
+    package_list: List,
+    file_list: List,
+    range: (f64, f64),
+    /// Sort the lists by coverage in ascending order (least covered first) instead of name.
+    sort_by_coverage: bool,
+    prompt: Option<String>,
+    message: Option<String>,
+    color: bool,
+    /// Number of lines of the body of the last drawn screen.
+    page: usize,
+}
+
+impl App {
+    fn new(cx: &Context, counts: &BTreeMap<&str, BTreeMap<u64, u64>>) -> Self {
+        let root = &cx.ws.metadata.workspace_root;
+        let members: Vec<_> = cx
+            .workspace_members
+            .included
+            .iter()
+            .map(|id| {
+                let package = &cx.ws.metadata.packages[id];
+                (package.name.as_str(), package.manifest_path.parent().unwrap())
+            })
+            .collect();
+        let mut packages: BTreeMap<&str, Vec<File>> = BTreeMap::new();
+        for (&filename, lines) in counts {
+            // Paths are relative to the workspace root when --remap-path-prefix is used.
+            let path = root.join(filename);
+            // Use the innermost package for files of nested packages.
+            let package = members
+                .iter()
+                .filter(|(_, dir)| path.starts_with(dir))
+                .max_by_key(|(_, dir)| dir.as_str().len())
+                .map_or("(other)", |&(name, _)| name);
+            let name = path.strip_prefix(root).unwrap_or(&path).to_string();
+            let row = Row {
+                name,
+                count: lines.len() as u64,
+                covered: lines.values().filter(|&&count| count != 0).count() as u64,
+            };
+            packages.entry(package).or_default().push(File {
+                row,
+                package: 0,
+                path: path.into_string(),
+                lines: lines.clone(),
+            });
+        }
+        let mut app = App {
+            packages: vec![],
+            files: vec![],
+            view: View::Packages,
+            package_list: List::default(),
+            file_list: List::default(),
+            range: (0., 100.),
+            sort_by_coverage: false,
+            prompt: None,
+            message: None,
+            color: cx.args.color != Some(Coloring::Never),
+            page: 1,
+        };
+        for (name, files) in packages {
+            let package = app.packages.len();
+            app.packages.push(Row {
+                name: name.to_owned(),
+                count: files.iter().map(|f| f.row.count).sum(),
+                covered: files.iter().map(|f| f.row.covered).sum(),
+            });
+            app.files.extend(files.into_iter().map(|f| File { package, ..f }));
+        }
+        if app.packages.len() == 1 {
+            app.view = View::Files(0);
+        }
+        app
+    }
+
+    fn in_range(&self, row: &Row) -> bool {
+        let percent = row.percent();
+        self.range.0 <= percent && percent <= self.range.1
+    }
+
+    /// Returns the indices of the rows of the current list that match the filter, in the
+    /// order they are shown.
+    fn visible(&self) -> Vec<usize> {
+        let (mut visible, row): (Vec<_>, fn(&Self, usize) -> &Row) = match self.view {
+            View::Packages => (
+                (0..self.packages.len()).filter(|&i| self.in_range(&self.packages[i])).collect(),
+                |app, i| &app.packages[i],
+            ),
+            View::Files(package) => (
+                (0..self.files.len())
+                    .filter(|&i| {
+                        self.files[i].package == package && self.in_range(&self.files[i].row)
+                    })
+                    .collect(),
+                |app, i| &app.files[i].row,
+            ),
+            View::Source(_) => return vec![],
+        };
+        if self.sort_by_coverage {
+            // Stable sort keeps rows with the same coverage in name order.
+            visible.sort_by(|&a, &b| row(self, a).percent().total_cmp(&row(self, b).percent()));
+        }
+        visible
+    }
+
+    /// Handles a key press. Returns `false` if the browser should exit.
+    fn handle(&mut self, key: Key) -> bool {
+        self.message = None;
+        if key == Key::Interrupt {
+            return false;
+        }
+        if let Some(prompt) = &mut self.prompt {
+            match key {
+                Key::Char(c) => prompt.push(c),
+                Key::Backspace => {
+                    prompt.pop();
+                }
+                Key::Esc => self.prompt = None,
+                Key::Enter => {
+                    match parse_range(prompt) {
+                        Ok(range) => {
+                            self.range = range;
+                            self.package_list = List::default();
+                            self.file_list = List::default();
+                        }
+                        Err(e) => self.message = Some(format!("{e:#}")),
+                    }
+                    self.prompt = None;
+                }
+                _ => {}
+            }
+            return true;
+        }
+        if let View::Source(source) = &mut self.view {
+            let file = &self.files[source.file];
+            let last = source.text.len().saturating_sub(1);
+            let page = self.page;
+            match key {
+                Key::Up | Key::Char('k') => source.cursor = source.cursor.saturating_sub(1),
+                Key::Down | Key::Char('j') => source.cursor = (source.cursor + 1).min(last),
+                Key::PageUp | Key::Char('b') => source.cursor = source.cursor.saturating_sub(page),
+                Key::PageDown | Key::Char(' ') => source.cursor = (source.cursor + page).min(last),
+                Key::Home | Key::Char('g') => source.cursor = 0,
+                Key::End | Key::Char('G') => source.cursor = last,
+                Key::Char(c @ ('n' | 'p' | ']' | '[')) => {
+                    let forward = matches!(c, 'n' | ']');
+                    let line = source.cursor as u64 + 1;
+                    match next_uncovered_region(&file.lines, line, forward) {
+                        Some(line) => {
+                            source.cursor = usize::try_from(line - 1).unwrap().min(last);
+                            // Show a few lines before the region.
+                            source.top = source.cursor.saturating_sub(3);
+                        }
+                        None => {
+                            let dir = if forward { "below" } else { "above" };
+                            self.message = Some(format!("no uncovered regions {dir}"));
+                        }
+                    }
+                }
+                Key::Left | Key::Esc | Key::Char('h' | 'q') => {
+                    self.view = View::Files(file.package);
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        let visible = self.visible();
+        let page = self.page;
+        let list = match self.view {
+            View::Packages => &mut self.package_list,
+            _ => &mut self.file_list,
+        };
+        let last = visible.len().saturating_sub(1);
+        match key {
+            Key::Up | Key::Char('k') => list.selected = list.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => list.selected = (list.selected + 1).min(last),
+            Key::PageUp | Key::Char('b') => list.selected = list.selected.saturating_sub(page),
+            Key::PageDown | Key::Char(' ') => list.selected = (list.selected + page).min(last),
+            Key::Home | Key::Char('g') => list.selected = 0,
+            Key::End | Key::Char('G') => list.selected = last,
+            Key::Char('f') => self.prompt = Some(String::new()),
+            Key::Char('s') => {
+                self.sort_by_coverage = !self.sort_by_coverage;
+                self.package_list = List::default();
+                self.file_list = List::default();
+            }
+            Key::Char('q') => return false,
+            Key::Enter | Key::Right | Key::Char('l') => {
+                let Some(&index) = visible.get(list.selected) else { return true };
+                match self.view {
+                    View::Packages => {
+                        self.file_list = List::default();
+                        self.view = View::Files(index);
+                    }
+                    _ => self.open(index),
+                }
+            }
+            Key::Left | Key::Esc | Key::Char('h') => {
+                if let View::Files(package) = self.view {
+                    if self.packages.len() > 1 {
+                        self.view = View::Packages;
+                        self.package_list.selected = self
+                            .visible()
+                            .iter()
+                            .position(|&i| i == package)
+                            .unwrap_or(self.package_list.selected);
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn open(&mut self, file: usize) {
+        let path = &self.files[file].path;
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let text: Vec<_> = text.lines().map(|line| line.replace('\t', "    ")).collect();
+                let mut source = Source { file, text, cursor: 0, top: 0 };
+                // Start at the first uncovered region if any.
+                if let Some(line) = next_uncovered_region(&self.files[file].lines, 0, true) {
+                    source.cursor =
+                        usize::try_from(line - 1).unwrap().min(source.text.len().saturating_sub(1));
+                    source.top = source.cursor.saturating_sub(3);
+                }
+                self.view = View::Source(source);
+            }
+            Err(e) => self.message = Some(format!("{e:#}")),
+        }
+    }
+
+    fn draw(&mut self, height: usize, width: usize) -> String {
+        let body = height - HEADER_LINES - FOOTER_LINES;
+        self.page = body.max(1);
+        let mut screen = String::from("\x1b[H");
+        let color = self.color;
+        let line = |screen: &mut String, style: &str, text: &str| {
+            let text = truncate(text, width);
+            if color && !style.is_empty() {
+                let _ = write!(screen, "{style}{text}\x1b[0m\x1b[K\r\n");
+            } else {
+                let _ = write!(screen, "{text}\x1b[K\r\n");
+            }
+        };
+
+        if let View::Source(source) = &mut self.view {
+            let file = &self.files[source.file];
+            let row = &file.row;
+            line(
+                &mut screen,
+                "\x1b[1;7m",
+                &format!(
+                    " {}  {}/{} lines ({:.2}%)",
+                    row.name,
+                    row.covered,
+                    row.count,
+                    row.percent()
+                ),
+            );
+            line(&mut screen, "\x1b[1m", "   Count  Source");
+            scroll(&mut source.top, source.cursor, body);
+            for (i, text) in source.text.iter().enumerate().skip(source.top).take(body) {
+                let count = file.lines.get(&(i as u64 + 1));
+                let cursor = if i == source.cursor { '>' } else { ' ' };
+                let (mark, style) = match count {
+                    Some(0) => ('✗', "\x1b[31m"),
+                    Some(_) => (' ', "\x1b[32m"),
+                    None => (' ', ""),
+                };
+                let count = count.map(u64::to_string).unwrap_or_default();
+                let style = if i == source.cursor { "\x1b[1m" } else { style };
+                line(&mut screen, style, &format!("{cursor}{count:>7}{mark} {text}"));
+            }
+            for _ in source.text.len().saturating_sub(source.top)..body {
+                line(&mut screen, "", "");
+            }
+        } else {
+            let visible = self.visible();
+            let (title, rows, list): (_, Vec<_>, _) = match self.view {
+                View::Packages => {
+                    ("packages".to_owned(), self.packages.iter().collect(), &mut self.package_list)
+                }
+                View::Files(package) => (
+                    format!("files in {}", self.packages[package].name),
+                    self.files.iter().map(|f| &f.row).collect(),
+                    &mut self.file_list,
+                ),
+                View::Source(_) => unreachable!(),
+            };
+            let (min, max) = self.range;
+            let sort = if self.sort_by_coverage { ", sorted by coverage" } else { "" };
+            line(
+                &mut screen,
+                "\x1b[1;7m",
+                &format!(" cargo llvm-cov tui: {title}  (coverage {min}%-{max}%{sort})"),
+            );
+            line(&mut screen, "\x1b[1m", "       Lines    Cover  Name");
+            list.selected = list.selected.min(visible.len().saturating_sub(1));
+            scroll(&mut list.top, list.selected, body);
+            for (n, &i) in visible.iter().enumerate().skip(list.top).take(body) {
+                let row = rows[i];
+                let cursor = if n == list.selected { '>' } else { ' ' };
+                let percent = row.percent();
+                let style = if n == list.selected {
+                    "\x1b[1;7m"
+                } else if percent < 80. {
+                    "\x1b[31m"
+                } else if percent < 100. {
+                    "\x1b[33m"
+                } else {
+                    "\x1b[32m"
+                };
+                let cover = if row.count == 0 { "-".to_owned() } else { format!("{percent:.2}%") };
+                line(
+                    &mut screen,
+                    style,
+                    &format!(
+                        "{cursor}{:>11} {cover:>8}  {}",
+                        format!("{}/{}", row.covered, row.count),
+                        row.name
+                    ),
+                );
+            }
+            for _ in visible.len().saturating_sub(list.top)..body {
+                line(&mut screen, "", "");
+            }
+        }
+
+        let footer = if let Some(prompt) = &self.prompt {
+            format!("coverage range (e.g. 0-80, -80, 50-): {prompt}")
+        } else if let Some(message) = &self.message {
+            message.clone()
+        } else if let View::Source(_) = self.view {
+            "j/k: move  space/b: page  n/p: next/previous uncovered region  h: back  ^C: quit"
+                .to_owned()
+        } else {
+            "j/k: move  l/enter: open  h: back  f: filter by coverage  s: sort  q: quit".to_owned()
+        };
+        let _ = write!(screen, "{}\x1b[K\x1b[J", truncate(&footer, width));
+        screen
+    }
+}
+
+/// Adjusts `top` so that `cursor` is in the `height` lines from `top`.
+fn scroll(top: &mut usize, cursor: usize, height: usize) {
+    if cursor < *top {
+        *top = cursor;
+    } else if cursor >= *top + height {
+        *top = cursor + 1 - height;
+    }
+}
+
+fn truncate(s: &str, width: usize) -> &str {
+    s.char_indices().nth(width).map_or(s, |(i, _)| &s[..i])
+}
+
+/// Parses a coverage range such as `0-80`, `-80` and `50-`. A single number is treated as
+/// the maximum, and an empty string resets the range.
+fn parse_range(s: &str) -> Result<(f64, f64)> {
+    let s = s.trim();
+    let (min, max) = s.split_once('-').unwrap_or(("", s));
+    let parse = |s: &str, default| -> Result<f64> {
+        let s = s.trim().trim_end_matches('%');
+        if s.is_empty() {
+            return Ok(default);
+        }
+        s.parse().with_context(|| format!("invalid coverage range `{s}`"))
+    };
+    let range = (parse(min, 0.)?, parse(max, 100.)?);
+    if !(0. <= range.0 && range.0 <= range.1 && range.1 <= 100.) {
+        bail!("coverage range must be within 0-100");
+    }
+    Ok(range)
+}
+
+/// Returns the first line of the uncovered region after (or before if `forward` is
+/// `false`) `line`.
+///
+/// Consecutive uncovered lines, ignoring lines that have no coverage mapping, are treated
+/// as a region.
+fn next_uncovered_region(lines: &BTreeMap<u64, u64>, line: u64, forward: bool) -> Option<u64> {
+    let mut starts = vec![];
+    let mut prev_uncovered = false;
+    for (&l, &count) in lines {
+        if count == 0 && !prev_uncovered {
+            starts.push(l);
+        }
+        prev_uncovered = count == 0;
+    }
+    if forward {
+        starts.into_iter().find(|&l| l > line)
+    } else {
+        starts.into_iter().rev().find(|&l| l < line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{next_uncovered_region, parse_range};
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("").unwrap(), (0., 100.));
+        assert_eq!(parse_range("0-80").unwrap(), (0., 80.));
+        assert_eq!(parse_range("-80").unwrap(), (0., 80.));
+        assert_eq!(parse_range("80").unwrap(), (0., 80.));
+        assert_eq!(parse_range("50-").unwrap(), (50., 100.));
+        assert_eq!(parse_range(" 50% - 90% ").unwrap(), (50., 90.));
+        assert!(parse_range("80-50").is_err());
+        assert!(parse_range("0-120").is_err());
+        assert!(parse_range("a-b").is_err());
+    }
+
+    #[test]
+    fn test_next_uncovered_region() {
+        // Lines 3-6 (5 has no coverage mapping) and 9 are uncovered.
+        let lines = BTreeMap::from([(1, 1), (2, 1), (3, 0), (4, 0), (6, 0), (7, 2), (9, 0)]);
+        assert_eq!(next_uncovered_region(&lines, 0, true), Some(3));
+        assert_eq!(next_uncovered_region(&lines, 3, true), Some(9));
+        assert_eq!(next_uncovered_region(&lines, 9, true), None);
+        assert_eq!(next_uncovered_region(&lines, 9, false), Some(3));
+        assert_eq!(next_uncovered_region(&lines, 4, false), Some(3));
+        assert_eq!(next_uncovered_region(&lines, 3, false), None);
+    }
+}
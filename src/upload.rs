@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Uploads a generated report to an arbitrary HTTP endpoint.
+//
+// With --coveralls, the report generated by --coveralls is sent to the Coveralls Jobs API as
+// a multipart form as the API expects, along with the repository token.
+//
+// This uses curl to avoid depending on an HTTP client and TLS implementation.
+// The URL and headers are passed to curl via stdin (`--config -`) instead of
+// arguments, so that tokens expanded from environment variables do not appear
+// in the process list or in error messages. The form is also passed via stdin, so that
+// the file name is not interpreted by curl's `--form` syntax (e.g., `;type=`).
+//
+// curl honors the proxy environment variables (HTTPS_PROXY, NO_PROXY, etc.) itself.
+//
+// With --retry, curl retries on transient errors (timeouts and HTTP 408, 429, 500, 502, 503,
+// and 504) with exponential backoff. Requests with a method that is not idempotent (e.g., POST
+// used by --coveralls) may have been processed by the server when these errors are returned,
+// so they are sent with an `Idempotency-Key` header derived from the report, which servers
+// that support it use to ignore the retried requests.
+//
+// With --upload-dry-run, nothing is sent and the body of the request is written to a file
+// instead, for environments without network access. The multipart form of --coveralls is built
+// here in the same way as curl does, as curl cannot write a request without sending it.
+
+use std::fmt::Write as _;
+
+use anyhow::{Context as _, Result, bail};
+use camino::Utf8Path;
+
+use crate::{cli::Args, digest, env, fs, term};
+
+pub(crate) const COVERALLS_URL: &str = "https://coveralls.io/api/v1/jobs";
+
+pub(crate) fn run(args: &Args) -> Result<()> {
+    let options = &args.upload;
+    if !options.file.is_file() {
+        bail!("{} not found; generate the report before uploading it", options.file);
+    }
+
+    let mut config = String::new();
+    let url = expand_env_vars(&options.url).context("failed to expand --url")?;
+    let _ = writeln!(config, "url = \"{}\"", escape(&url));
+    for header in &options.headers {
+        let header = expand_env_vars(header).context("failed to expand --header")?;
+        let _ = writeln!(config, "header = \"{}\"", escape(&header));
+    }
+    let token = if options.coveralls { env::var("COVERALLS_REPO_TOKEN")? } else { None };
+
+    // The URL and headers are expanded above even with --upload-dry-run, so that errors in them
+    // are reported before the file is uploaded from another machine.
+    if let Some(path) = &options.dry_run {
+        let report = fs::read(&options.file)?;
+        if options.coveralls {
+            let boundary = boundary(&report);
+            let form = multipart_form(&boundary, &options.file, &report, token.as_deref());
+            fs::write(path, form)?;
+            status!(
+                "Wrote",
+                "{path} instead of uploading {} to {} (--upload-dry-run); send it with \
+                 `Content-Type: multipart/form-data; boundary={boundary}`",
+                options.file,
+                options.url
+            );
+        } else {
+            fs::write(path, report)?;
+            status!(
+                "Wrote",
+                "{path} instead of uploading {} to {} (--upload-dry-run)",
+                options.file,
+                options.url
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(cacert) = &options.cacert {
+        let _ = writeln!(config, "cacert = \"{}\"", escape(cacert.as_str()));
+    }
+    if options.retry > 0 {
+        // Without `retry-delay`, curl doubles the delay between retries, starting at one second.
+        let _ = writeln!(config, "retry = {}", options.retry);
+        // The request is not sent when the connection is refused, so it is safe to retry.
+        let _ = writeln!(config, "retry-connrefused");
+        if !is_idempotent(&options.method)
+            && !options.headers.iter().any(|h| is_header(h, "Idempotency-Key"))
+        {
+            let key = digest::to_hex(&digest::sha256(&fs::read(&options.file)?));
+            let _ = writeln!(config, "header = \"Idempotency-Key: {key}\"");
+        }
+    }
+    if options.coveralls {
+        let _ = writeln!(config, "form = \"{}\"", escape(&form_file("json_file", &options.file)));
+        // `form-string` is not interpreted by curl's `--form` syntax (e.g., `@` and `;type=`).
+        if let Some(token) = &token {
+            let _ = writeln!(config, "form-string = \"repo_token={}\"", escape(token));
+        }
+    }
+
+    let mut cmd = cmd!("curl", "--fail", "--silent", "--show-error", "--request", &options.method);
+    if !options.coveralls {
+        cmd.arg("--upload-file").arg(&options.file);
+    }
+    cmd.args(["--config", "-"]).stdin_bytes(config);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    cmd.run().with_context(|| format!("failed to upload {} to {}", options.file, options.url))?;
+    status!("Uploaded", "{} to {}", options.file, options.url);
+    Ok(())
+}
+
+/// Replaces `${VAR}` with the value of the environment variable `VAR`.
+fn expand_env_vars(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            bail!("unclosed `${{` in `{s}`");
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        match env::var(name)? {
+            Some(value) => out.push_str(&value),
+            None => bail!("environment variable {name} is not set"),
+        }
+        rest = &rest[start + 2 + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Escapes a string for use in a double-quoted string in curl config.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// Returns the value of `--form` that uploads the file as the given form field.
+///
+/// The file name is quoted, as curl otherwise treats `;` and `,` in it as separators.
+fn form_file(name: &str, file: &Utf8Path) -> String {
+    format!("{name}=@\"{}\"", file.as_str().replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns `true` if the HTTP method is idempotent, that is, sending the same request multiple
+/// times has the same effect as sending it once.
+// Refs: https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2
+fn is_idempotent(method: &str) -> bool {
+    ["GET", "HEAD", "PUT", "DELETE", "OPTIONS", "TRACE"]
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Returns `true` if the header in `NAME: VALUE` format has the given name.
+fn is_header(header: &str, name: &str) -> bool {
+    header.split_once(':').is_some_and(|(n, _)| n.trim().eq_ignore_ascii_case(name))
+}
+
+/// Returns the boundary of the multipart form that contains the given file.
+///
+/// This contains the digest of the file, so it does not appear in the file.
+fn boundary(file: &[u8]) -> String {
+    format!("cargo-llvm-cov-{}", &digest::to_hex(&digest::sha256(file))[..32])
+}
+
+/// Returns the multipart form that curl sends for `form_file("json_file", file)` and the
+/// `repo_token` field.
+// Refs: https://www.rfc-editor.org/rfc/rfc7578
+fn multipart_form(boundary: &str, file: &Utf8Path, content: &[u8], token: Option<&str>) -> Vec<u8> {
+    // Like curl, `"`, CR, and LF in the file name are percent-encoded.
+    let file_name = file
+        .file_name()
+        .unwrap_or_default()
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    let mut form = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"json_file\"; \
+         filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+    )
+    .into_bytes();
+    form.extend_from_slice(content);
+    form.extend_from_slice(b"\r\n");
+    if let Some(token) = token {
+        form.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"repo_token\"\r\n\r\n\
+                 {token}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    form.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    form
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+
+    use super::{
+        boundary, escape, expand_env_vars, form_file, is_header, is_idempotent, multipart_form,
+    };
+
+    #[test]
+    fn test_expand_env_vars() {
+        assert_eq!(expand_env_vars("https://example.com").unwrap(), "https://example.com");
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(expand_env_vars("a${PATH}b").unwrap(), format!("a{path}b"));
+        expand_env_vars("${CARGO_LLVM_COV_UPLOAD_TEST_UNSET}").unwrap_err();
+        expand_env_vars("${PATH").unwrap_err();
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(escape("a\nb"), r"a\nb");
+    }
+
+    #[test]
+    fn test_form_file() {
+        assert_eq!(
+            form_file("json_file", Utf8Path::new("coveralls.json")),
+            r#"json_file=@"coveralls.json""#
+        );
+        assert_eq!(
+            form_file("json_file", Utf8Path::new(r#"a;type=text/html,"b\c".json"#)),
+            r#"json_file=@"a;type=text/html,\"b\\c\".json""#
+        );
+        // In curl config, the value is escaped again.
+        assert_eq!(
+            escape(&form_file("json_file", Utf8Path::new(r#"a"b.json"#))),
+            r#"json_file=@\"a\\\"b.json\""#
+        );
+    }
+
+    #[test]
+    fn test_multipart_form() {
+        let boundary = boundary(b"{}");
+        assert_eq!(boundary, "cargo-llvm-cov-44136fa355b3678a1146ad16f7e8649e");
+        let form = multipart_form(&boundary, Utf8Path::new("a/b\"c.json"), b"{}", Some("t"));
+        assert_eq!(
+            String::from_utf8(form).unwrap(),
+            format!(
+                "--{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"json_file\"; filename=\"b%22c.json\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n\
+                 {{}}\r\n\
+                 --{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"repo_token\"\r\n\r\n\
+                 t\r\n\
+                 --{boundary}--\r\n"
+            )
+        );
+        let form = multipart_form(&boundary, Utf8Path::new("c.json"), b"{}", None);
+        assert!(!String::from_utf8(form).unwrap().contains("repo_token"));
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent("PUT"));
+        assert!(is_idempotent("get"));
+        assert!(!is_idempotent("POST"));
+        assert!(!is_idempotent("PATCH"));
+    }
+
+    #[test]
+    fn test_is_header() {
+        assert!(is_header("Idempotency-Key: a", "Idempotency-Key"));
+        assert!(is_header("idempotency-key:a", "Idempotency-Key"));
+        assert!(!is_header("X-Idempotency-Key: a", "Idempotency-Key"));
+    }
+}
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coverage of wasm32-unknown-unknown tests run by wasm-bindgen-test.
+//
+// There is no profiler runtime for wasm32-unknown-unknown, so the profile data is
+// accumulated by the minicov crate in the wasm module, and wasm-bindgen-test-runner writes
+// it as profraw files after running tests on Node.js or in a headless browser.
+// llvm-cov cannot read coverage mappings from wasm modules, so the LLVM IR emitted by rustc
+// is compiled to object files with clang, which are passed to llvm-cov instead.
+// https://wasm-bindgen.github.io/wasm-bindgen/wasm-bindgen-test/coverage.html
+//
+// Coverage of wasm32-wasip1 tests (experimental).
+//
+// There is no profiler runtime for wasm32-wasip1 either, and test harnesses have no hook to
+// write the profile data accumulated by minicov, so tests write it to the directory specified
+// by the CARGO_LLVM_COV_WASI_PROFRAW_DIR environment variable (see README). Tests are run by
+// wasmtime (unless a runner is configured for the target), which is given access to that
+// directory. Object files are generated from the LLVM IR in the same way as above.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use camino::Utf8Path;
+use cargo_config2::Flags;
+use walkdir::WalkDir;
+
+use crate::{EnvTarget, context::Context, env, fs, regex_vec::RegexVec, term};
+
+pub(crate) const TARGET: &str = "wasm32-unknown-unknown";
+pub(crate) const WASI_TARGET: &str = "wasm32-wasip1";
+
+/// Directory in the guest to which wasmtime maps the target directory.
+const WASI_PROFRAW_DIR: &str = "/cargo-llvm-cov-profraw";
+
+/// Returns `true` if the coverage of tests run by wasm-bindgen-test-runner is collected.
+fn is_wasm_bindgen(cx: &Context) -> bool {
+    cx.ws.target_for_config.triple() == TARGET
+}
+
+/// Returns `true` if the coverage of tests for wasm32-wasip1 is collected.
+fn is_wasi(cx: &Context) -> bool {
+    cx.ws.target_for_config.triple() == WASI_TARGET
+}
+
+/// Returns `true` if the coverage of wasm modules is collected by minicov, instead of the
+/// profiler runtime.
+pub(crate) fn is_wasm(cx: &Context) -> bool {
+    is_wasm_bindgen(cx) || is_wasi(cx)
+}
+
+pub(crate) fn push_flags(cx: &Context, flags: &mut Flags) -> Result<()> {
+    if !cx.ws.rustc_version.nightly {
+        bail!("coverage of {} requires nightly toolchain", cx.ws.target_for_config.triple());
+    }
+    flags.push("-Z");
+    flags.push("no-profiler-runtime");
+    flags.push("--emit=llvm-ir");
+    if is_wasm_bindgen(cx) {
+        flags.push("--cfg=wasm_bindgen_unstable_test_coverage");
+    }
+    Ok(())
+}
+
+pub(crate) fn set_env(cx: &Context, env: &mut dyn EnvTarget) -> Result<()> {
+    let has_runner = cx.ws.config.runner(&cx.ws.target_for_config)?.is_some();
+    if is_wasi(cx) {
+        if has_runner {
+            // The configured runner is responsible for giving access to this directory.
+            env.set("CARGO_LLVM_COV_WASI_PROFRAW_DIR", cx.ws.target_dir.as_str())?;
+        } else {
+            // Runners are split by whitespace.
+            if cx.ws.target_dir.as_str().contains(char::is_whitespace) {
+                bail!(
+                    "target directory {} contains whitespace; configure a runner for \
+                     {WASI_TARGET} that gives access to it instead",
+                    cx.ws.target_dir
+                );
+            }
+            env.set(
+                "CARGO_TARGET_WASM32_WASIP1_RUNNER",
+                &format!(
+                    "wasmtime run --dir={}::{WASI_PROFRAW_DIR} \
+                     --env=CARGO_LLVM_COV_WASI_PROFRAW_DIR={WASI_PROFRAW_DIR}",
+                    cx.ws.target_dir
+                ),
+            )?;
+        }
+        return Ok(());
+    }
+    if !has_runner {
+        env.set("CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER", "wasm-bindgen-test-runner")?;
+    }
+    env.set("WASM_BINDGEN_UNSTABLE_TEST_PROFRAW_OUT", cx.ws.target_dir.as_str())?;
+    env.set("WASM_BINDGEN_UNSTABLE_TEST_PROFRAW_PREFIX", &format!("{}-wasm-", cx.ws.name))?;
+    Ok(())
+}
+
+/// Compiles the LLVM IR of the crates that match `re` in the given target directory to
+/// object files, and returns the paths to them.
+pub(crate) fn object_files(
+    cx: &Context,
+    target_dir: &Utf8Path,
+    re: &RegexVec,
+) -> Result<Vec<PathBuf>> {
+    let clang = env::var_os("CLANG").unwrap_or_else(|| "clang".into());
+    let mut files = vec![];
+    for entry in
+        WalkDir::new(target_dir.join("deps")).max_depth(1).into_iter().filter_map(Result::ok)
+    {
+        let ll = entry.path();
+        if ll.extension().is_none_or(|ext| ext != "ll") {
+            continue;
+        }
+        if !ll.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| re.is_match(stem)) {
+            continue;
+        }
+        let object = ll.with_extension("o");
+        if !is_up_to_date(ll, &object) {
+            let mut cmd = cx.process(&clang);
+            cmd.arg(ll).args(["-Wno-override-module", "-c", "-o"]).arg(&object);
+            if term::verbose() {
+                status!("Running", "{cmd}");
+            }
+            cmd.run_with_output().with_context(|| {
+                format!(
+                    "failed to compile {} to an object file; make sure clang with the same or \
+                     newer LLVM version as rustc is installed, or set CLANG environment variable",
+                    ll.display()
+                )
+            })?;
+        }
+        files.push(object);
+    }
+    Ok(files)
+}
+
+fn is_up_to_date(ll: &Path, object: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).ok()?.modified().ok();
+    match (modified(ll), modified(object)) {
+        (Some(ll), Some(object)) => object >= ll,
+        _ => false,
+    }
+}
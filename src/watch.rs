@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// --watch: re-run tests and regenerate the report when sources or manifests of workspace members
+// are changed.
+//
+// All tests are re-run on each change: which tests are affected by a change is only known after
+// building them, and cargo already skips rebuilding the crates that are not affected.
+//
+// This does not use a file system notification library: the modification times of the files
+// are polled, which is cheap enough for source trees and works the same on all platforms.
+//
+// With --html or --open, the HTML report is served with live reload (see live_reload.rs), and
+// --open opens the served report only once, instead of opening the files after each run.
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use walkdir::WalkDir;
+
+use crate::{clean, context::Context, live_reload, open_report};
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+pub(crate) fn run(cx: &Context, f: impl Fn(&Context) -> Result<()>) -> Result<()> {
+    let server = if cx.args.cov.html {
+        let dir = cx.args.cov.output_dir.as_ref().unwrap().join("html");
+        Some(live_reload::Server::start(dir)?)
+    } else {
+        None
+    };
+    let mut opened = false;
+    loop {
+        // Take the snapshot before running tests, so changes during the run are not missed.
+        let modified = modified_files(cx);
+        if let Err(e) = f(cx) {
+            // Keep watching, since the next change may fix the build or tests.
+            error!("{e:#}");
+        }
+        if let Some(server) = &server {
+            server.reload();
+            if cx.args.cov.open && !opened {
+                status!("Opening", "{}", server.url());
+                open_report(cx, server.url())?;
+                opened = true;
+            }
+        }
+        eprintln!();
+        if let Some(server) = &server {
+            status!("Serving", "HTML report at {} with live reload", server.url());
+        }
+        status!(
+            "Watching",
+            "for changes in {} (press Ctrl-C to stop)",
+            cx.ws.metadata.workspace_root
+        );
+        while modified_files(cx) == modified {
+            thread::sleep(INTERVAL);
+        }
+        // Unlike the first run, build artifacts are kept so that tests are rebuilt
+        // incrementally, and only the profraw files of the previous run are removed.
+        if !cx.args.no_clean {
+            clean::clean_profraw(cx)?;
+        }
+    }
+}
+
+/// Returns the modification times of the sources and manifests of workspace members, except for
+/// hidden files and the files written by builds and reports.
+fn modified_files(cx: &Context) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    let metadata = &cx.ws.metadata;
+    let mut ignored = vec![
+        metadata.target_directory.as_std_path().to_owned(),
+        metadata.build_directory().as_std_path().to_owned(),
+        cx.ws.target_dir.as_std_path().to_owned(),
+    ];
+    // Output paths are relative to the current directory, not the workspace root.
+    if let Some(output_dir) = &cx.args.cov.output_dir {
+        ignored.push(cx.current_dir.join(output_dir));
+    }
+    if let Some(output_path) = &cx.args.cov.output_path {
+        ignored.push(cx.current_dir.join(output_path));
+    }
+    let mut package_dirs: Vec<_> = metadata
+        .workspace_members
+        .iter()
+        .filter_map(|id| metadata.packages[id].manifest_path.parent())
+        .map(|dir| dir.as_std_path())
+        .collect();
+    package_dirs.sort_unstable();
+    // Packages in the directories of other packages are walked with them.
+    package_dirs.dedup_by(|dir, prev| dir.starts_with(prev));
+    let mut files: BTreeMap<_, _> = ["Cargo.toml", "Cargo.lock"]
+        .into_iter()
+        .map(|name| metadata.workspace_root.as_std_path().join(name))
+        .map(|path| {
+            let modified = path.metadata().ok().and_then(|m| m.modified().ok());
+            (path, modified)
+        })
+        .collect();
+    for dir in package_dirs {
+        files.extend(
+            WalkDir::new(dir)
+                .into_iter()
+                .filter_entry(|e| {
+                    e.depth() == 0
+                        || (!e.file_name().to_str().is_some_and(|name| name.starts_with('.'))
+                            && !ignored.iter().any(|path| e.path().starts_with(path)))
+                })
+                .filter_map(Result::ok)
+                .filter(|e| {
+                    e.file_type().is_file()
+                        && (e.path().extension().is_some_and(|ext| ext == "rs")
+                            || e.file_name() == "Cargo.toml")
+                })
+                .map(|e| {
+                    let modified = e.metadata().ok().and_then(|m| m.modified().ok());
+                    (e.into_path(), modified)
+                }),
+        );
+    }
+    files
+}
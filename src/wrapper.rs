@@ -0,0 +1,802 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// rustc wrapper used with `-Z build-std`, --include-crate, --exclude-crate,
+// --dep-coverage, and --include-build-script/--include-proc-macros with --target.
+//
+// With `-Z build-std`, RUSTFLAGS are also passed to the standard library crates built by
+// cargo, so cargo-llvm-cov sets itself as RUSTC_WRAPPER and removes -C instrument-coverage
+// from the rustc invocations for these crates. The same is done for the crates that are
+// not selected by --include-crate and --exclude-crate, and for dependencies other than the
+// one specified by --dep-coverage.
+//
+// Conversely, cargo does not pass RUSTFLAGS to build scripts and proc-macros when --target
+// is used, so the wrapper adds the instrumentation flags to them.
+//
+// Cargo does not know about these environment variables, so it does not rebuild crates when
+// they change (e.g., after toggling --dep-coverage). The wrapper records how each crate is
+// instrumented, and cargo-llvm-cov removes the outputs of the crates whose instrumentation
+// changed before the next build, so that cargo rebuilds only them (and their dependents).
+//
+// With --timings, the wrapper is also used to measure the compile time of each crate, which
+// is written to the directory specified by TIMINGS_DIR_ENV. See timings.rs for more.
+//
+// A rustc wrapper set by the user (RUSTC_WRAPPER or build.rustc-wrapper, e.g., sccache) is not
+// replaced, but chained: cargo invokes `<RUSTC_WRAPPER> [<RUSTC_WORKSPACE_WRAPPER>] <rustc>
+// <args>...`, so cargo-llvm-cov passes the user's wrapper by INNER_WRAPPER_ENV, and the wrapper
+// invokes `<inner wrapper> <rustc> <modified args>...` (or `<rustc> <modified args>...` if there
+// is none). The workspace wrapper, if any, is passed through as the first argument.
+//
+// trybuild runs cargo for UI tests with the environment of the test, so the wrapper is also
+// used for the crates of the test cases. These are compared against the expected diagnostics
+// and are not part of the report, so the wrapper removes the instrumentation from them and
+// does not record the crates built in trybuild's target directory. Users can also set
+// CARGO_LLVM_COV_SKIP_WRAPPER to pass the rustc invocations through unchanged.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+    time::Instant,
+};
+
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{env, fs};
+
+/// Path to the sources of the standard library. This is set (to an empty string if the
+/// standard library is instrumented) when cargo-llvm-cov is used as a rustc wrapper.
+pub(crate) const STD_SRC_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_STD_SRC";
+/// Comma-separated names of the crates passed by --include-crate.
+pub(crate) const INCLUDE_CRATES_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_INCLUDE_CRATES";
+/// Comma-separated names of the crates passed by --exclude-crate.
+pub(crate) const EXCLUDE_CRATES_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_EXCLUDE_CRATES";
+/// Package name passed by --dep-coverage.
+pub(crate) const DEP_COVERAGE_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_DEP_COVERAGE";
+/// Path to the workspace root.
+pub(crate) const WORKSPACE_ROOT_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_WORKSPACE_ROOT";
+/// Comma-separated kinds of the crates built for the host to instrument (`build-script`
+/// and `proc-macro`). This is set when --target is used.
+pub(crate) const HOST_CRATES_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_HOST_CRATES";
+/// Path to the file that contains flags to instrument the crates selected by
+/// `HOST_CRATES_ENV`, one per line (like rustc's `@path` argument files).
+pub(crate) const HOST_FLAGS_FILE_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_HOST_FLAGS_FILE";
+/// The rustc wrapper set by the user, which is called by the wrapper. See [`inner_wrapper`].
+pub(crate) const INNER_WRAPPER_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_INNER";
+/// Directory where the wrapper records how each crate is instrumented.
+pub(crate) const RECORDS_DIR_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_RECORDS_DIR";
+/// Directory where the wrapper writes the compile time of each crate. This is set when
+/// --timings is passed.
+pub(crate) const TIMINGS_DIR_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_TIMINGS_DIR";
+/// Set to print how the wrapper changes the instrumentation of each crate. This is set when
+/// -vv is passed.
+pub(crate) const VERBOSE_ENV: &str = "__CARGO_LLVM_COV_RUSTC_WRAPPER_VERBOSE";
+/// Set by the user to pass the rustc invocations to rustc (or the inner wrapper) as is.
+const SKIP_WRAPPER_ENV: &str = "CARGO_LLVM_COV_SKIP_WRAPPER";
+
+/// How the wrapper changes the instrumentation of a crate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Instrumentation {
+    /// The flags are passed to rustc as is.
+    Unchanged,
+    /// `-C instrument-coverage` is removed.
+    Removed,
+    /// The flags to instrument build scripts and proc-macros built for the host are added.
+    HostFlags,
+}
+
+impl Instrumentation {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Unchanged => "flags are unchanged",
+            Self::Removed => "-C instrument-coverage is removed",
+            Self::HostFlags => "flags to instrument the crate built for the host are added",
+        }
+    }
+}
+
+/// Settings of the wrapper, from the environment variables set by cargo-llvm-cov.
+struct Selection {
+    std_src: String,
+    include: String,
+    exclude: String,
+    dep: Option<String>,
+    workspace_root: String,
+    host_crates: String,
+}
+
+impl Selection {
+    fn new(var: impl Fn(&str) -> Result<Option<String>>) -> Result<Self> {
+        Ok(Self {
+            std_src: var(STD_SRC_ENV)?.unwrap_or_default(),
+            include: var(INCLUDE_CRATES_ENV)?.unwrap_or_default(),
+            exclude: var(EXCLUDE_CRATES_ENV)?.unwrap_or_default(),
+            dep: var(DEP_COVERAGE_ENV)?,
+            workspace_root: var(WORKSPACE_ROOT_ENV)?.unwrap_or_default(),
+            host_crates: var(HOST_CRATES_ENV)?.unwrap_or_default(),
+        })
+    }
+
+    /// Returns how the crate compiled by the given rustc arguments is instrumented.
+    fn instrumentation(
+        &self,
+        args: &[OsString],
+        package_name: Option<&str>,
+        manifest_dir: &Path,
+    ) -> Instrumentation {
+        let workspace_root = Path::new(&self.workspace_root);
+        if is_trybuild_path(manifest_dir)
+            || is_std_crate(args, Path::new(&self.std_src))
+            || !should_instrument(args, &self.include, &self.exclude)
+            || self.dep.as_deref().is_some_and(|dep| {
+                !is_dep_or_workspace_member(dep, workspace_root, package_name, manifest_dir)
+            })
+        {
+            Instrumentation::Removed
+        } else if is_host_crate(args, &self.host_crates)
+            && is_workspace_member(workspace_root, manifest_dir)
+        {
+            Instrumentation::HostFlags
+        } else {
+            Instrumentation::Unchanged
+        }
+    }
+}
+
+/// How a crate was instrumented when it was built last time.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    /// The rustc arguments that the instrumentation depends on.
+    args: Vec<String>,
+    package_name: Option<String>,
+    manifest_dir: PathBuf,
+    /// `--out-dir` of the crate.
+    out_dir: PathBuf,
+    /// File stem of the outputs of the crate (crate name and `-C extra-filename`).
+    stem: String,
+    instrumentation: Instrumentation,
+}
+
+impl Record {
+    fn new(
+        args: &[OsString],
+        package_name: Option<&str>,
+        manifest_dir: &Path,
+        instrumentation: Instrumentation,
+    ) -> Option<Self> {
+        let crate_name = arg_value(args, "--crate-name")?;
+        let extra_filename = codegen_value(args, "extra-filename")?;
+        let out_dir = arg_value(args, "--out-dir")?;
+        let mut recorded = vec![];
+        for name in ["--crate-name", "--crate-type", "--target"] {
+            if let Some(value) = arg_value(args, name) {
+                recorded.push(name.to_owned());
+                recorded.push(value.to_owned());
+            }
+        }
+        // Source files, which are used to check whether the crate is in the standard library.
+        recorded.extend(
+            args.iter()
+                .filter_map(|arg| arg.to_str())
+                .filter(|arg| !arg.starts_with('-') && arg.ends_with(".rs"))
+                .map(str::to_owned),
+        );
+        Some(Self {
+            args: recorded,
+            package_name: package_name.map(str::to_owned),
+            manifest_dir: manifest_dir.to_owned(),
+            out_dir: PathBuf::from(out_dir),
+            stem: format!("{crate_name}{extra_filename}"),
+            instrumentation,
+        })
+    }
+
+    /// Returns `true` if the given file name is one of the outputs of the crate (e.g.,
+    /// `libfoo-0123.rlib`, `foo-0123.d`, or `foo-0123` for binaries).
+    fn is_output(&self, file_name: &str) -> bool {
+        let is_output = |name: &str| {
+            name.strip_prefix(&self.stem)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        };
+        is_output(file_name) || file_name.strip_prefix("lib").is_some_and(is_output)
+    }
+}
+
+/// Compile time of a crate, written for --timings.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CrateTiming {
+    pub(crate) crate_name: String,
+    pub(crate) package_name: Option<String>,
+    /// `test`, `build-script`, or the crate type (e.g., `lib` or `bin`).
+    pub(crate) kind: String,
+    /// Whether the crate was compiled with `-C instrument-coverage`.
+    pub(crate) instrumented: bool,
+    pub(crate) seconds: f64,
+}
+
+impl CrateTiming {
+    fn new(args: &[OsString], package_name: Option<&str>, seconds: f64) -> Option<Self> {
+        let crate_name = arg_value(args, "--crate-name")?;
+        let kind = if args.iter().any(|arg| arg == "--test") {
+            "test"
+        } else if crate_name == "build_script_build" {
+            "build-script"
+        } else {
+            arg_value(args, "--crate-type").unwrap_or("lib")
+        };
+        Some(Self {
+            crate_name: crate_name.to_owned(),
+            package_name: package_name.map(str::to_owned),
+            kind: kind.to_owned(),
+            instrumented: has_instrument_coverage(args),
+            seconds,
+        })
+    }
+}
+
+/// Returns `true` if this process is invoked by cargo as a rustc wrapper.
+pub(crate) fn is_wrapper() -> bool {
+    // The environment variable is also inherited by tests, which may run cargo-llvm-cov as a
+    // cargo subcommand. It is set to an empty string unless `-Z build-std` is used, so
+    // env::var_os, which ignores empty values, cannot be used here.
+    std::env::var_os(STD_SRC_ENV).is_some()
+        && std::env::args_os().nth(1).is_some_and(|arg| arg != "llvm-cov")
+}
+
+pub(crate) fn run() -> Result<ExitCode> {
+    let mut args = std::env::args_os().skip(1);
+    let rustc = args.next().context("rustc wrapper requires the path to rustc")?;
+    let mut args: Vec<_> = args.collect();
+    let selection = Selection::new(env::var)?;
+    // Set by cargo for rustc invocations.
+    let package_name = env::var("CARGO_PKG_NAME")?;
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default());
+    let skip = env::var_os(SKIP_WRAPPER_ENV).is_some();
+    let instrumentation = if skip {
+        Instrumentation::Unchanged
+    } else {
+        selection.instrumentation(&args, package_name.as_deref(), &manifest_dir)
+    };
+    let inner = env::var_os(INNER_WRAPPER_ENV);
+    if let Some(crate_name) = arg_value(&args, "--crate-name") {
+        if env::var_os(VERBOSE_ENV).is_some() {
+            // Cargo forwards the lines of rustc's stderr that are not JSON messages.
+            let reason = if skip { format!(" ({SKIP_WRAPPER_ENV} is set)") } else { String::new() };
+            let inner = match &inner {
+                Some(inner) => format!(" (calling {})", Path::new(inner).display()),
+                None => String::new(),
+            };
+            info!(
+                "rustc wrapper: {crate_name} (package {}): {}{reason}{inner}",
+                package_name.as_deref().unwrap_or("unknown"),
+                instrumentation.description()
+            );
+        }
+    }
+    if let Some(dir) = env::var_os(RECORDS_DIR_ENV).filter(|_| !skip) {
+        // Crates built by trybuild are not removed by cargo-llvm-cov, and may have the same
+        // stems as the crates built by cargo-llvm-cov.
+        if let Some(record) =
+            Record::new(&args, package_name.as_deref(), &manifest_dir, instrumentation)
+                .filter(|record| !is_trybuild_path(&record.out_dir))
+        {
+            let dir = Path::new(&dir);
+            fs::create_dir_all(dir)?;
+            fs::write(dir.join(format!("{}.json", record.stem)), serde_json::to_string(&record)?)?;
+        }
+    }
+    match instrumentation {
+        Instrumentation::Unchanged => {}
+        Instrumentation::Removed => remove_instrument_coverage(&mut args),
+        Instrumentation::HostFlags => args.extend(host_flags()?),
+    }
+    let mut cmd = match inner {
+        Some(wrapper) => {
+            let mut cmd = Command::new(resolve_program(wrapper));
+            cmd.arg(rustc);
+            cmd
+        }
+        None => Command::new(resolve_program(rustc)),
+    };
+    let start = Instant::now();
+    let status = cmd.args(&args).status().context("failed to run rustc")?;
+    // Crates built by trybuild are not built by cargo-llvm-cov.
+    if let Some(dir) = env::var_os(TIMINGS_DIR_ENV).filter(|_| !is_trybuild_path(&manifest_dir)) {
+        let seconds = start.elapsed().as_secs_f64();
+        // Each crate is compiled once per build, and the outputs of a crate have the same stem.
+        if let (Some(timing), Some(extra_filename)) = (
+            CrateTiming::new(&args, package_name.as_deref(), seconds),
+            codegen_value(&args, "extra-filename"),
+        ) {
+            let dir = Path::new(&dir);
+            fs::create_dir_all(dir)?;
+            fs::write(
+                dir.join(format!("{}{extra_filename}.json", timing.crate_name)),
+                serde_json::to_string(&timing)?,
+            )?;
+        }
+    }
+    Ok(status
+        .code()
+        .and_then(|code| u8::try_from(code).ok())
+        .map_or(ExitCode::FAILURE, ExitCode::from))
+}
+
+/// Returns the rustc wrapper set by the user that the wrapper calls, or an empty path if there
+/// is none.
+///
+/// cargo-llvm-cov itself is ignored: the environment printed by `cargo llvm-cov show-env` sets
+/// it as RUSTC_WRAPPER, and calling it from the wrapper would recurse forever.
+pub(crate) fn inner_wrapper<'a>(configured: Option<&'a Path>, current_exe: &Path) -> &'a Path {
+    let Some(wrapper) = configured else { return Path::new("") };
+    let is_self = if wrapper.components().count() == 1 {
+        // Found in PATH.
+        wrapper.file_stem() == current_exe.file_stem()
+    } else {
+        wrapper == current_exe
+            || fs::canonicalize(wrapper).ok().is_some_and(|wrapper| {
+                fs::canonicalize(current_exe).is_ok_and(|exe| exe == wrapper)
+            })
+    };
+    if is_self { Path::new("") } else { wrapper }
+}
+
+/// Returns the directory where the wrapper records how each crate is instrumented.
+pub(crate) fn records_dir(target_dir: &Utf8Path) -> Utf8PathBuf {
+    target_dir.join("llvm-cov-wrapper-records")
+}
+
+/// Removes the outputs of the crates recorded in `dir` whose instrumentation is changed by the
+/// given environment variables (set by cargo-llvm-cov for the next build), so that cargo
+/// rebuilds them. Returns the number of such crates.
+pub(crate) fn invalidate_changed(dir: &Utf8Path, env: &BTreeMap<String, String>) -> Result<usize> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let selection = Selection::new(|key| Ok(env.get(key).cloned()))?;
+    let mut invalidated = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Records written by other versions of cargo-llvm-cov are ignored.
+        let Ok(mut record) = serde_json::from_str::<Record>(&fs::read_to_string(&path)?) else {
+            continue;
+        };
+        let args: Vec<_> = record.args.iter().map(OsString::from).collect();
+        let instrumentation =
+            selection.instrumentation(&args, record.package_name.as_deref(), &record.manifest_dir);
+        if instrumentation == record.instrumentation {
+            continue;
+        }
+        if record.out_dir.is_dir() {
+            for output in fs::read_dir(&record.out_dir)? {
+                let output = output?;
+                if output.file_name().to_str().is_some_and(|name| record.is_output(name)) {
+                    fs::remove_file(output.path())?;
+                }
+            }
+        }
+        // The outputs may not be rebuilt by the next build (e.g., if the crate is no longer
+        // used), so record the new instrumentation now.
+        record.instrumentation = instrumentation;
+        fs::write(&path, serde_json::to_string(&record)?)?;
+        invalidated += 1;
+    }
+    Ok(invalidated)
+}
+
+/// Returns the flags to instrument build scripts and proc-macros built for the host.
+fn host_flags() -> Result<Vec<OsString>> {
+    let Some(path) = env::var_os(HOST_FLAGS_FILE_ENV) else { return Ok(vec![]) };
+    let flags = fs::read_to_string(Path::new(&path))?;
+    Ok(split_flags(&flags).map(OsString::from).collect())
+}
+
+fn split_flags(flags: &str) -> impl Iterator<Item = &str> {
+    flags.lines().filter(|f| !f.is_empty())
+}
+
+/// Resolves batch file shims (e.g., `sccache.cmd` installed by a package manager) on Windows,
+/// because `Command` only tries `.exe` extension when the program has no extension.
+fn resolve_program(program: OsString) -> OsString {
+    if cfg!(windows) {
+        let path = env::var_os("PATH");
+        if let Some(program) = find_program(Path::new(&program), path.as_deref()) {
+            return program.into_os_string();
+        }
+    }
+    program
+}
+
+/// Finds the program with `.exe`, `.cmd`, or `.bat` extension, in the given PATH if the
+/// program is a file name, or next to it otherwise.
+fn find_program(program: &Path, path: Option<&OsStr>) -> Option<PathBuf> {
+    if program.as_os_str().is_empty() || program.extension().is_some() {
+        return None;
+    }
+    let dirs = if program.components().count() > 1 {
+        vec![PathBuf::new()]
+    } else {
+        std::env::split_paths(path?).collect()
+    };
+    dirs.iter()
+        .flat_map(|dir| ["exe", "cmd", "bat"].map(|ext| dir.join(program).with_extension(ext)))
+        .find(|path| path.is_file())
+}
+
+/// Returns `true` if the crate compiled by the given rustc arguments is in the sources of the
+/// standard library.
+fn is_std_crate(args: &[OsString], std_src: &Path) -> bool {
+    // Crate names are not checked, because crates in the workspace or dependencies may have
+    // the same names as the crates of the standard library (e.g., `core`).
+    !std_src.as_os_str().is_empty()
+        && args.iter().map(Path::new).any(|path| {
+            path.extension().is_some_and(|ext| ext == "rs") && path.starts_with(std_src)
+        })
+}
+
+/// Returns `false` if the crate compiled by the given rustc arguments is not in the
+/// comma-separated `include` list (if it is not empty) or is in the `exclude` list.
+fn should_instrument(args: &[OsString], include: &str, exclude: &str) -> bool {
+    let Some(crate_name) = arg_value(args, "--crate-name") else {
+        // e.g., `rustc -vV` invoked by cargo
+        return true;
+    };
+    // Package names can contain hyphens, but crate names passed to rustc cannot.
+    let contains = |list: &str| list.split(',').any(|name| name.replace('-', "_") == crate_name);
+    (include.is_empty() || contains(include)) && !contains(exclude)
+}
+
+/// Returns `true` if the package compiled by the current rustc invocation is the dependency
+/// specified by --dep-coverage or a package in the workspace.
+///
+/// Crates in the workspace are instrumented because the profile runtime is only linked
+/// to instrumented binaries.
+fn is_dep_or_workspace_member(
+    dep: &str,
+    workspace_root: &Path,
+    package_name: Option<&str>,
+    manifest_dir: &Path,
+) -> bool {
+    package_name == Some(dep) || is_workspace_member(workspace_root, manifest_dir)
+}
+
+/// Returns `true` if the given path is in the directory where trybuild generates the crates of
+/// the test cases and builds them (`<target-dir>/tests/trybuild`).
+fn is_trybuild_path(path: &Path) -> bool {
+    let components: Vec<_> = path.components().map(|c| c.as_os_str()).collect();
+    components.windows(2).any(|w| w[0] == "tests" && w[1] == "trybuild")
+}
+
+fn is_workspace_member(workspace_root: &Path, manifest_dir: &Path) -> bool {
+    !workspace_root.as_os_str().is_empty() && manifest_dir.starts_with(workspace_root)
+}
+
+/// Returns `true` if the crate compiled by the given rustc arguments is built for the host
+/// and is one of the given comma-separated kinds.
+fn is_host_crate(args: &[OsString], kinds: &str) -> bool {
+    if kinds.is_empty() || arg_value(args, "--target").is_some() {
+        return false;
+    }
+    kinds.split(',').any(|kind| match kind {
+        // Cargo uses the same crate name for all build scripts.
+        "build-script" => arg_value(args, "--crate-name") == Some("build_script_build"),
+        "proc-macro" => arg_value(args, "--crate-type") == Some("proc-macro"),
+        _ => false,
+    })
+}
+
+/// Returns the value of the given rustc option (`<name> <value>` or `<name>=<value>`).
+fn arg_value<'a>(args: &'a [OsString], name: &str) -> Option<&'a str> {
+    let mut args = args.iter().map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        let Some(arg) = arg else { continue };
+        if arg == name {
+            return args.next().flatten();
+        }
+        if let Some(value) = arg.strip_prefix(name).and_then(|v| v.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Returns the value of the given codegen option (`-C <name>=<value>` or `-C<name>=<value>`).
+fn codegen_value<'a>(args: &'a [OsString], name: &str) -> Option<&'a str> {
+    let mut args = args.iter().map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        let flag = match arg {
+            Some("-C") => args.next().flatten(),
+            Some(arg) => arg.strip_prefix("-C"),
+            None => None,
+        };
+        if let Some(value) = flag.and_then(|f| f.strip_prefix(name)?.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Returns `true` if the given rustc arguments enable `-C instrument-coverage`.
+fn has_instrument_coverage(args: &[OsString]) -> bool {
+    let mut enabled = false;
+    let mut args = args.iter().map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        let flag = match arg {
+            Some("-C") => args.next().flatten(),
+            Some(arg) => arg.strip_prefix("-C"),
+            None => None,
+        };
+        // The last one takes precedence, as in rustc.
+        match flag.and_then(|flag| flag.strip_prefix("instrument-coverage")) {
+            Some("") => enabled = true,
+            Some(value) => {
+                if let Some(value) = value.strip_prefix('=') {
+                    enabled = !matches!(value, "n" | "no" | "off" | "false");
+                }
+            }
+            None => {}
+        }
+    }
+    enabled
+}
+
+/// Removes `-C instrument-coverage` and `-Z coverage-options` from the given rustc arguments.
+fn remove_instrument_coverage(args: &mut Vec<OsString>) {
+    let mut i = 0;
+    while i < args.len() {
+        let (len, flag) = match args[i].to_str() {
+            Some("-C" | "-Z") => (2, args.get(i + 1).and_then(|arg| arg.to_str())),
+            Some(arg) => (1, arg.strip_prefix("-C").or_else(|| arg.strip_prefix("-Z"))),
+            None => (1, None),
+        };
+        let name = flag.map(|flag| flag.split_once('=').map_or(flag, |(name, _)| name));
+        if matches!(name, Some("instrument-coverage" | "coverage-options")) {
+            args.drain(i..(i + len).min(args.len()));
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, ffi::OsString, path::Path};
+
+    use camino::Utf8Path;
+
+    use super::{
+        CrateTiming, DEP_COVERAGE_ENV, Instrumentation, Record, WORKSPACE_ROOT_ENV, codegen_value,
+        find_program, has_instrument_coverage, inner_wrapper, invalidate_changed,
+        is_dep_or_workspace_member, is_host_crate, is_std_crate, is_trybuild_path,
+        remove_instrument_coverage, should_instrument, split_flags,
+    };
+    use crate::fs;
+
+    #[test]
+    fn test_is_std_crate() {
+        let std_src = Path::new("/sysroot/lib/rustlib/src/rust/library");
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert!(is_std_crate(
+            &args(&[
+                "--crate-name",
+                "core",
+                "/sysroot/lib/rustlib/src/rust/library/core/src/lib.rs"
+            ]),
+            std_src
+        ));
+        assert!(!is_std_crate(&args(&["--crate-name", "core", "src/lib.rs"]), std_src));
+        assert!(!is_std_crate(
+            &args(&["/sysroot/lib/rustlib/src/rust/library/core/src/lib.rs"]),
+            Path::new("")
+        ));
+    }
+
+    #[test]
+    fn test_should_instrument() {
+        let args = |name: &str| {
+            ["--crate-name", name, "src/lib.rs"].iter().map(OsString::from).collect::<Vec<_>>()
+        };
+        assert!(should_instrument(&args("foo"), "", ""));
+        assert!(should_instrument(&args("foo_bar"), "foo-bar,baz", ""));
+        assert!(!should_instrument(&args("build_script_build"), "foo", ""));
+        assert!(!should_instrument(&args("foo_bar"), "", "baz,foo-bar"));
+        assert!(!should_instrument(&args("foo"), "foo", "foo"));
+        assert!(should_instrument(&[OsString::from("-vV")], "foo", ""));
+    }
+
+    #[test]
+    fn test_is_dep_or_workspace_member() {
+        let ws = Path::new("/ws");
+        let registry = Path::new("/home/a/.cargo/registry/src/index.crates.io-0123/foo-1.0.0");
+        assert!(is_dep_or_workspace_member("foo", ws, Some("foo"), registry));
+        assert!(is_dep_or_workspace_member("foo", ws, Some("ws"), Path::new("/ws")));
+        assert!(is_dep_or_workspace_member("foo", ws, Some("a"), Path::new("/ws/member/a")));
+        assert!(!is_dep_or_workspace_member("foo", ws, Some("bar"), registry));
+        assert!(!is_dep_or_workspace_member("foo", Path::new(""), Some("bar"), registry));
+    }
+
+    #[test]
+    fn test_is_trybuild_path() {
+        assert!(is_trybuild_path(Path::new("/ws/target/tests/trybuild/foo")));
+        assert!(is_trybuild_path(Path::new("/ws/target/tests/trybuild/debug/deps")));
+        assert!(!is_trybuild_path(Path::new("/ws/tests/ui")));
+        assert!(!is_trybuild_path(Path::new("/ws/target/llvm-cov-target/debug/deps")));
+    }
+
+    #[test]
+    fn test_is_host_crate() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let build_script = args(&["--crate-name", "build_script_build", "--crate-type", "bin"]);
+        let proc_macro = args(&["--crate-name", "foo", "--crate-type", "proc-macro"]);
+        let target =
+            args(&["--crate-name", "build_script_build", "--target=x86_64-pc-windows-gnu"]);
+        assert!(is_host_crate(&build_script, "build-script,proc-macro"));
+        assert!(!is_host_crate(&build_script, "proc-macro"));
+        assert!(is_host_crate(&proc_macro, "proc-macro"));
+        assert!(!is_host_crate(&proc_macro, ""));
+        assert!(!is_host_crate(&target, "build-script"));
+    }
+
+    #[test]
+    fn test_codegen_value() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let a = args(&["-C", "opt-level=3", "-Cextra-filename=-0123", "-C", "metadata=4567"]);
+        assert_eq!(codegen_value(&a, "extra-filename"), Some("-0123"));
+        assert_eq!(codegen_value(&a, "metadata"), Some("4567"));
+        assert_eq!(codegen_value(&a, "extra"), None);
+        assert_eq!(codegen_value(&args(&["--crate-name", "foo"]), "metadata"), None);
+    }
+
+    #[test]
+    fn test_has_instrument_coverage() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert!(has_instrument_coverage(&args(&["-C", "instrument-coverage"])));
+        assert!(has_instrument_coverage(&args(&["-Cinstrument-coverage=all"])));
+        assert!(!has_instrument_coverage(&args(&[
+            "-C",
+            "instrument-coverage",
+            "-C",
+            "instrument-coverage=off"
+        ])));
+        assert!(!has_instrument_coverage(&args(&["-C", "instrument-coverages"])));
+        assert!(!has_instrument_coverage(&args(&["--crate-name", "foo"])));
+    }
+
+    #[test]
+    fn test_crate_timing() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let timing = CrateTiming::new(
+            &args(&["--crate-name", "foo", "--crate-type", "lib", "-C", "instrument-coverage"]),
+            Some("foo"),
+            1.5,
+        )
+        .unwrap();
+        assert_eq!(timing.kind, "lib");
+        assert!(timing.instrumented);
+        let timing = CrateTiming::new(&args(&["--crate-name", "foo", "--test"]), None, 1.).unwrap();
+        assert_eq!(timing.kind, "test");
+        assert!(!timing.instrumented);
+        let timing = CrateTiming::new(
+            &args(&["--crate-name", "build_script_build", "--crate-type", "bin"]),
+            None,
+            1.,
+        )
+        .unwrap();
+        assert_eq!(timing.kind, "build-script");
+        assert!(CrateTiming::new(&args(&["-vV"]), None, 1.).is_none());
+    }
+
+    #[test]
+    fn test_invalidate_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("deps");
+        let records = Utf8Path::from_path(dir.path()).unwrap().join("records");
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::create_dir_all(&records).unwrap();
+        for file in ["libfoo-01.rlib", "libfoo-01.rmeta", "foo-01.d", "libfoo-012.rlib", "bar-01"] {
+            fs::write(out_dir.join(file), "").unwrap();
+        }
+        let args: Vec<_> = ["--crate-name", "foo", "-C", "extra-filename=-01", "--out-dir"]
+            .iter()
+            .map(OsString::from)
+            .chain([out_dir.clone().into_os_string()])
+            .collect();
+        let registry = Path::new("/registry/foo-1.0.0");
+        // Built with `--dep-coverage bar`.
+        let record = Record::new(&args, Some("foo"), registry, Instrumentation::Removed).unwrap();
+        assert!(record.is_output("libfoo-01.rlib"));
+        assert!(record.is_output("foo-01"));
+        assert!(!record.is_output("libfoo-012.rlib"));
+        fs::write(records.join("foo-01.json"), serde_json::to_string(&record).unwrap()).unwrap();
+
+        let mut env = BTreeMap::new();
+        env.insert(WORKSPACE_ROOT_ENV.to_owned(), "/ws".to_owned());
+        env.insert(DEP_COVERAGE_ENV.to_owned(), "baz".to_owned());
+        assert_eq!(invalidate_changed(&records, &env).unwrap(), 0);
+        // Built with `--dep-coverage foo`.
+        env.insert(DEP_COVERAGE_ENV.to_owned(), "foo".to_owned());
+        assert_eq!(invalidate_changed(&records, &env).unwrap(), 1);
+        let mut remaining: Vec<_> = fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, ["bar-01", "libfoo-012.rlib"]);
+        // The new instrumentation is recorded.
+        assert_eq!(invalidate_changed(&records, &env).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_remove_instrument_coverage() {
+        let mut args: Vec<_> = [
+            "--crate-name",
+            "std",
+            "-C",
+            "instrument-coverage",
+            "-Zcoverage-options=branch",
+            "-C",
+            "opt-level=3",
+            "-Cinstrument-coverage",
+            "--cfg=coverage",
+        ]
+        .iter()
+        .map(OsString::from)
+        .collect();
+        remove_instrument_coverage(&mut args);
+        assert_eq!(args, ["--crate-name", "std", "-C", "opt-level=3", "--cfg=coverage"]);
+    }
+
+    #[test]
+    fn test_find_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(a.join("sccache.cmd"), "").unwrap();
+        fs::write(b.join("sccache.exe"), "").unwrap();
+        let path = std::env::join_paths([&a, &b]).unwrap();
+        let find = |program: &str| find_program(Path::new(program), Some(path.as_os_str()));
+        assert_eq!(find("sccache"), Some(a.join("sccache.cmd")));
+        assert_eq!(find("rustc"), None);
+        assert_eq!(find("sccache.exe"), None);
+        assert_eq!(find(b.join("sccache").to_str().unwrap()), Some(b.join("sccache.exe")));
+        assert_eq!(find_program(Path::new("sccache"), None), None);
+    }
+
+    #[test]
+    fn test_inner_wrapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("cargo-llvm-cov");
+        fs::write(&exe, "").unwrap();
+        let sccache = Path::new("sccache");
+        assert_eq!(inner_wrapper(None, &exe), Path::new(""));
+        assert_eq!(inner_wrapper(Some(sccache), &exe), sccache);
+        assert_eq!(
+            inner_wrapper(Some(Path::new("/usr/bin/sccache")), &exe),
+            Path::new("/usr/bin/sccache")
+        );
+        assert_eq!(inner_wrapper(Some(&exe), &exe), Path::new(""));
+        assert_eq!(
+            inner_wrapper(Some(&dir.path().join(".").join("cargo-llvm-cov")), &exe),
+            Path::new("")
+        );
+        assert_eq!(inner_wrapper(Some(Path::new("cargo-llvm-cov")), &exe), Path::new(""));
+    }
+
+    #[test]
+    fn test_split_flags() {
+        fn flags(flags: &str) -> Vec<&str> {
+            split_flags(flags).collect()
+        }
+        assert_eq!(flags("-C\ninstrument-coverage\r\n--remap-path-prefix\n/a b/=\n"), [
+            "-C",
+            "instrument-coverage",
+            "--remap-path-prefix",
+            "/a b/="
+        ]);
+        assert!(flags("").is_empty());
+    }
+}
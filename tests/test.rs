@@ -4,7 +4,14 @@
 
 mod auxiliary;
 
-use std::path::Path;
+use std::{
+    io::{BufRead as _, BufReader},
+    path::Path,
+    process::Stdio,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
 
 use cargo_config2::Flags;
 use fs_err as fs;
@@ -12,7 +19,22 @@ use test_helper::cli::CommandExt as _;
 
 use self::auxiliary::*;
 
-const SUBCOMMANDS: &[&str] = &["", "run", "report", "clean", "show-env", "nextest"];
+const SUBCOMMANDS: &[&str] = &[
+    "",
+    "run",
+    "fuzz",
+    "report",
+    "clean",
+    "show-env",
+    "nextest",
+    "doctor",
+    "upload",
+    "tui",
+    "show",
+    "collect",
+    "stats",
+    "self-update",
+];
 
 fn test_set() -> Vec<(&'static str, &'static [&'static str])> {
     let mut set: Vec<(&'static str, &'static [&'static str])> = vec![
@@ -104,6 +126,134 @@ fn bin_crate() {
     }
 }
 
+#[test]
+fn run_profile_continuous_abort() {
+    let workspace_root = test_project("bin_crate");
+    fs::write(
+        workspace_root.path().join("src/main.rs"),
+        "fn main() {\n    if std::env::args().count() > 1 {\n        std::process::abort();\n    }\n}\n",
+    )
+    .unwrap();
+    // The report is generated from the counters synced before the abort, but the failure of
+    // the binary is still reported by the exit status.
+    cargo_llvm_cov("run")
+        .args(["--color", "never", "--profile-continuous", "--", "1"])
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stdout_contains("TOTAL");
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)] // binary IDs are only available on ELF targets
+fn test_binaries_without_profile_data() {
+    let workspace_root = test_project("bin_crate");
+    fs::write(
+        workspace_root.path().join("tests/abort.rs"),
+        "#[test]\nfn abort() {\n    std::process::abort();\n}\n",
+    )
+    .unwrap();
+    for args in [&["--ignore-run-fail"][..], &["--ignore-run-fail", "--test-jobs", "2"]] {
+        cargo_llvm_cov("")
+            .args(["--color", "never"])
+            .args(args)
+            .current_dir(workspace_root.path())
+            .assert_success()
+            .stderr_contains("1 test binaries ran but did not write profile data")
+            .stderr_contains("\n    abort (");
+    }
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)] // binary IDs are only available on ELF targets
+fn verify_binary_ids() {
+    let other = test_project("real1");
+    cargo_llvm_cov("")
+        .args(["--color", "never", "--no-report"])
+        .current_dir(other.path())
+        .assert_success();
+    let workspace_root = test_project("bin_crate");
+    cargo_llvm_cov("run")
+        .args(["--color", "never", "--no-report", "--verify-binary-ids"])
+        .current_dir(workspace_root.path())
+        .assert_success();
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--verify-binary-ids"])
+        .current_dir(workspace_root.path())
+        .assert_success();
+
+    // Profile data generated by binaries of another workspace is rejected.
+    let profraw = fs::read_dir(other.path().join("target/llvm-cov-target"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension() == Some(std::ffi::OsStr::new("profraw")))
+        .unwrap();
+    fs::copy(profraw, workspace_root.path().join("target/llvm-cov-target/stale.profraw")).unwrap();
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--verify-binary-ids"])
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stderr_contains(
+            "stale.profraw was not generated by any of the current instrumented objects",
+        );
+}
+
+#[test]
+fn watch() {
+    let workspace_root = test_project("real1");
+    let mut child = cargo_llvm_cov("")
+        .args(["--color", "never", "--watch", "--lcov", "--output-path", "lcov.info"])
+        .current_dir(workspace_root.path())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stderr.lines() {
+            if line.unwrap().trim_start().starts_with("Watching") {
+                if tx.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let res = wait_watch_runs(&rx, workspace_root.path());
+    child.kill().unwrap();
+    child.wait().unwrap();
+    res.unwrap();
+}
+
+fn wait_watch_runs(rx: &mpsc::Receiver<()>, workspace_root: &Path) -> Result<(), String> {
+    let timeout = Duration::from_secs(300);
+    rx.recv_timeout(timeout).map_err(|e| format!("first run did not finish: {e}"))?;
+    // Writing the report must not trigger another run.
+    if rx.recv_timeout(Duration::from_secs(3)).is_ok() {
+        return Err("re-ran without changes".to_owned());
+    }
+    if !workspace_root.join("lcov.info").exists() {
+        return Err("report was not written".to_owned());
+    }
+    let lib = workspace_root.join("src/lib.rs");
+    let mut src = fs::read_to_string(&lib).unwrap();
+    src.push('\n');
+    fs::write(&lib, src).unwrap();
+    rx.recv_timeout(timeout).map_err(|e| format!("did not re-run after changes: {e}"))
+}
+
+// 1.88 fixed bug in report generation, so the latest report is not the same as the old report.
+#[rustversion::attr(before(1.88), ignore)]
+#[test]
+fn target() {
+    // Use the host target, which is always installed. With --target, artifacts are placed in
+    // `<target-dir>/<triple>/debug`, and host artifacts (e.g., build scripts) in
+    // `<target-dir>/debug`.
+    let rustc = std::process::Command::new("rustc").arg("-vV").output().unwrap();
+    let rustc = String::from_utf8(rustc.stdout).unwrap();
+    let host = rustc.lines().find_map(|line| line.strip_prefix("host: ")).unwrap();
+    run("bin_crate", "target", &["--target", host], &[]);
+    run("bin_crate", "coverage_target_only", &["--target", host, "--coverage-target-only"], &[]);
+}
+
 // 1.88 fixed bug in report generation, so the latest report is not the same as the old report.
 #[rustversion::attr(before(1.88), ignore)]
 #[test]
@@ -328,6 +478,274 @@ fn show_env() {
         .arg("--with-pwsh-env-prefix")
         .assert_failure()
         .stderr_contains("may not be used together with");
+    cargo_llvm_cov("show-env")
+        .arg("--with-fish-set-prefix")
+        .assert_success()
+        .stdout_contains("set -gx CARGO_LLVM_COV '1'");
+    cargo_llvm_cov("show-env")
+        .arg("--github-env")
+        .assert_success()
+        .stdout_contains("\nCARGO_LLVM_COV=1\n");
+    cargo_llvm_cov("show-env")
+        .arg("--github-env")
+        .arg("--with-fish-set-prefix")
+        .assert_failure()
+        .stderr_contains("may not be used together with");
+}
+
+#[test]
+fn show_env_rustflags() {
+    let workspace_root = test_project("real1");
+    let config = workspace_root.path().join(".cargo/config.toml");
+    fs::create_dir_all(config.parent().unwrap()).unwrap();
+    fs::write(&config, "[build]\nrustflags = [\"--cfg=from_build\"]\n").unwrap();
+    cargo_llvm_cov("show-env")
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains("--cfg=from_build")
+        .stdout_contains("instrument-coverage");
+    // target.<cfg>.rustflags takes precedence over build.rustflags, and
+    // -C instrument-coverage=... set by the user is respected.
+    fs::write(
+        &config,
+        "[build]\nrustflags = [\"--cfg=from_build\"]\n\
+         [target.'cfg(all())']\nrustflags = [\"--cfg=from_target\", \"-Cinstrument-coverage=yes\"]\n",
+    )
+    .unwrap();
+    cargo_llvm_cov("show-env")
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains("--cfg=from_target -Cinstrument-coverage=yes")
+        .stdout_not_contains("--cfg=from_build")
+        .stdout_not_contains("-C instrument-coverage");
+    // RUSTFLAGS takes precedence over the cargo configuration.
+    cargo_llvm_cov("show-env")
+        .current_dir(workspace_root.path())
+        .env("RUSTFLAGS", "--cfg=from_env")
+        .assert_success()
+        .stdout_contains("--cfg=from_env -C instrument-coverage")
+        .stdout_not_contains("--cfg=from_target");
+}
+
+#[test]
+fn init() {
+    let workspace_root = test_project("real1");
+    let manifest_path = workspace_root.path().join("Cargo.toml");
+    cargo_llvm_cov("init")
+        .arg("--ci-workflow")
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stderr_contains("[workspace.metadata.llvm-cov] table");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    assert!(manifest.contains("[workspace.metadata.llvm-cov.presets]"));
+    assert!(workspace_root.path().join(".github/workflows/coverage.yml").is_file());
+    cargo_llvm_cov("init")
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stderr_contains("already has cargo-llvm-cov configuration");
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), manifest);
+    cargo_llvm_cov("report")
+        .arg("--ci-workflow")
+        .assert_failure()
+        .stderr_contains("invalid option '--ci-workflow' for subcommand 'report'");
+}
+
+#[test]
+fn output_path_parent() {
+    let workspace_root = test_project("real1");
+    cargo_llvm_cov("")
+        .args(["--color", "never", "--lcov", "--output-path", "coverage/lcov/lcov.info"])
+        .current_dir(workspace_root.path())
+        .assert_success();
+    assert!(workspace_root.path().join("coverage/lcov/lcov.info").is_file());
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--checkstyle", "--output-path", "coverage/checkstyle.xml"])
+        .current_dir(workspace_root.path())
+        .assert_success();
+    assert!(workspace_root.path().join("coverage/checkstyle.xml").is_file());
+}
+
+#[test]
+fn fail_under_config() {
+    let workspace_root = test_project("real1");
+    let manifest_path = workspace_root.path().join("Cargo.toml");
+    let mut manifest = fs::read_to_string(&manifest_path).unwrap();
+    manifest.push_str("\n[workspace.metadata.llvm-cov.fail-under]\nlines = 90\n");
+    fs::write(&manifest_path, manifest).unwrap();
+    cargo_llvm_cov("").arg("--summary-only").current_dir(workspace_root.path()).assert_failure();
+    cargo_llvm_cov("report")
+        .args(["--summary-only", "--fail-under-lines", "50"])
+        .current_dir(workspace_root.path())
+        .assert_success();
+    cargo_llvm_cov("report")
+        .args(["--fail-under-branches", "50"])
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stderr_contains(
+            "--fail-under-branches can only be used together with either --branch or --mcdc",
+        );
+}
+
+#[test]
+fn multiple_formats() {
+    let workspace_root = test_project("real1");
+    let output_dir = workspace_root.path().join("reports");
+    cargo_llvm_cov("")
+        .args(["--lcov", "--json", "--cobertura", "--html", "--output-dir"])
+        .arg(&output_dir)
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stderr_contains("report saved to");
+    let lcov = fs::read_to_string(output_dir.join("lcov.info")).unwrap();
+    assert!(lcov.contains("SF:") && lcov.contains("DA:"));
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(output_dir.join("coverage.json")).unwrap())
+            .unwrap();
+    assert_eq!(json["type"], "llvm.coverage.json.export");
+    assert!(fs::read_to_string(output_dir.join("cobertura.xml")).unwrap().contains("<coverage"));
+    assert!(output_dir.join("html/index.html").exists());
+}
+
+#[test]
+fn exit_status() {
+    let workspace_root = test_project("real1");
+    cargo_llvm_cov("").arg("--summary-only").current_dir(workspace_root.path()).assert_success();
+    let output = cargo_llvm_cov("report")
+        .args(["--summary-only", "--fail-under-lines", "100", "--summary-json-to-stderr"])
+        .current_dir(workspace_root.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let totals: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert!(totals["lines"]["percent"].as_f64().unwrap() < 100.);
+}
+
+#[test]
+fn ignore_filename_regex_config() {
+    let workspace_root = test_project("real1");
+    let manifest_path = workspace_root.path().join("Cargo.toml");
+    let mut manifest = fs::read_to_string(&manifest_path).unwrap();
+    manifest.push_str("\n[workspace.metadata.llvm-cov]\nignore-filename-regex = [\"member2\"]\n");
+    fs::write(&manifest_path, manifest).unwrap();
+    cargo_llvm_cov("")
+        .args(["--color", "never", "--workspace", "--ignore-filename-regex", "member1/src"])
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains("src/lib.rs")
+        .stdout_not_contains("member1")
+        .stdout_not_contains("member2");
+}
+
+#[test]
+fn changed_since() {
+    let workspace_root = test_project("real1");
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=a", "-c", "user.email=a@example.com"])
+            .args(args)
+            .current_dir(workspace_root.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "initial"]);
+    let lib = workspace_root.path().join("member1/src/lib.rs");
+    let s = fs::read_to_string(&lib).unwrap();
+    fs::write(&lib, s.replace("        2 => {}\n", "        2 => {}\n        4 => {}\n")).unwrap();
+    // Neither is in the coverage mapping.
+    fs::write(workspace_root.path().join("notes.md"), "notes\n").unwrap();
+    fs::create_dir(workspace_root.path().join("scripts")).unwrap();
+    fs::write(workspace_root.path().join("scripts/gen.rs"), "fn main() {}\n").unwrap();
+    let root = workspace_root.path().to_str().unwrap();
+    cargo_llvm_cov("")
+        .args(["--color", "never", "--workspace", "--json", "--changed-since", "HEAD"])
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains("member1/src/lib.rs")
+        .stdout_not_contains("member2")
+        .stdout_not_contains(format!("{root}/src/lib.rs"))
+        .stderr_not_contains("isn't covered");
+    git(&["checkout", "--quiet", "--", "member1/src/lib.rs"]);
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--json", "--changed-since", "HEAD"])
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_not_contains("src/lib.rs")
+        .stderr_contains("no files in the report have been changed since HEAD");
+}
+
+#[test]
+fn diff() {
+    let workspace_root = test_project("real1");
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=a", "-c", "user.email=a@example.com"])
+            .args(args)
+            .current_dir(workspace_root.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "initial"]);
+    let lib = workspace_root.path().join("src/lib.rs");
+    let s = fs::read_to_string(&lib).unwrap();
+    fs::write(&lib, s.replace("        2 => {}\n", "        2 => {}\n        4 => {}\n")).unwrap();
+    cargo_llvm_cov("")
+        .args(["--color", "never", "--diff", "HEAD"])
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains("Patch Coverage: 0.00% (0 of 1 changed lines)")
+        .stdout_contains("src/lib.rs: 9");
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--diff", "HEAD", "--fail-under-diff", "50"])
+        .current_dir(workspace_root.path())
+        .assert_failure();
+    cargo_llvm_cov("report")
+        .args(["--fail-under-diff", "50"])
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stderr_contains("--fail-under-diff can only be used together with --diff");
+    cargo_llvm_cov("report")
+        .args(["--color", "never", "--diff", "HEAD", "--github-annotations"])
+        .env("GITHUB_WORKSPACE", workspace_root.path())
+        .current_dir(workspace_root.path())
+        .assert_success()
+        .stdout_contains(
+            "::warning file=src/lib.rs,line=9,endLine=9,title=Patch Coverage::Changed line is not covered by tests",
+        );
+    cargo_llvm_cov("report")
+        .args(["--github-annotations"])
+        .current_dir(workspace_root.path())
+        .assert_failure()
+        .stderr_contains("--github-annotations can only be used together with --diff");
+}
+
+#[test]
+fn build_settings_warnings() {
+    cargo_llvm_cov("show-env")
+        .env("CARGO_PROFILE_DEV_OPT_LEVEL", "1")
+        .assert_failure()
+        .stderr_contains("`opt-level = 1` (`CARGO_PROFILE_DEV_OPT_LEVEL` environment variable)");
+    cargo_llvm_cov("show-env")
+        .env("CARGO_PROFILE_DEV_OPT_LEVEL", "1")
+        .arg("--no-build-settings-warnings")
+        .assert_success();
+    cargo_llvm_cov("show-env")
+        .env("CARGO_PROFILE_DEV_LTO", "true")
+        .assert_failure()
+        .stderr_contains("`lto = true` (`CARGO_PROFILE_DEV_LTO` environment variable)");
+    cargo_llvm_cov("show-env")
+        .env("CARGO_PROFILE_DEV_LTO", "true")
+        .arg("--override-build-settings")
+        .assert_success()
+        .stdout_contains("CARGO_PROFILE_DEV_LTO=false")
+        .stdout_contains("CARGO_PROFILE_TEST_LTO=false")
+        .stdout_contains("CARGO_INCREMENTAL=0");
 }
 
 #[test]
@@ -405,13 +823,170 @@ fn invalid_arg() {
             }
         }
         if !matches!(subcommand, "" | "test" | "run" | "nextest" | "nextest-archive" | "show-env") {
-            for arg in ["--no-cfg-coverage", "--no-cfg-coverage-nightly"] {
+            for arg in [
+                "--no-cfg-coverage",
+                "--no-cfg-coverage-nightly",
+                "--profile-continuous",
+                "--sanitizer=address",
+                "--profile-update=atomic",
+            ] {
+                cargo_llvm_cov(subcommand).arg(arg).assert_failure().stderr_contains(format!(
+                    "invalid option '{}' for subcommand '{subcommand}'",
+                    arg.split_once('=').map_or(arg, |(flag, _)| flag)
+                ));
+            }
+        }
+        if subcommand != "report" {
+            cargo_llvm_cov(subcommand)
+                .arg("--profraw-glob=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--profraw-glob'");
+            cargo_llvm_cov(subcommand)
+                .arg("--debt")
+                .assert_failure()
+                .stderr_contains("invalid option '--debt'");
+            cargo_llvm_cov(subcommand)
+                .arg("--missing-examples")
+                .assert_failure()
+                .stderr_contains("invalid option '--missing-examples'");
+            cargo_llvm_cov(subcommand)
+                .arg("--hotspots")
+                .assert_failure()
+                .stderr_contains("invalid option '--hotspots'");
+            cargo_llvm_cov(subcommand)
+                .arg("--show-functions")
+                .assert_failure()
+                .stderr_contains("invalid option '--show-functions'");
+            cargo_llvm_cov(subcommand)
+                .arg("--affected-by=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--affected-by'");
+            cargo_llvm_cov(subcommand)
+                .arg("--uncovered-functions")
+                .assert_failure()
+                .stderr_contains("invalid option '--uncovered-functions'");
+            cargo_llvm_cov(subcommand)
+                .arg("--module-summary")
+                .assert_failure()
+                .stderr_contains("invalid option '--module-summary'");
+            cargo_llvm_cov(subcommand)
+                .arg("--type-summary")
+                .assert_failure()
+                .stderr_contains("invalid option '--type-summary'");
+            cargo_llvm_cov(subcommand)
+                .arg("--package-summary")
+                .assert_failure()
+                .stderr_contains("invalid option '--package-summary'");
+        }
+        if !matches!(subcommand, "" | "test" | "run" | "nextest") {
+            for arg in [
+                "--pre-test-cmd=v",
+                "--post-test-cmd=v",
+                "--sample-packages=v",
+                "--sample-history=v",
+            ] {
                 cargo_llvm_cov(subcommand).arg(arg).assert_failure().stderr_contains(format!(
                     "invalid option '{}' for subcommand '{subcommand}'",
                     arg.strip_suffix("=v").unwrap_or(arg)
                 ));
             }
         }
+        for arg in [
+            "--url=v",
+            "--method=v",
+            "--header=v",
+            "--file=v",
+            "--cacert=v",
+            "--upload-dry-run=v",
+            "--retry=1",
+        ] {
+            cargo_llvm_cov(subcommand)
+                .arg(arg)
+                .assert_failure()
+                .stderr_contains(format!("invalid option '{}'", arg.split_once('=').unwrap().0));
+        }
+        if subcommand != "nextest" {
+            cargo_llvm_cov(subcommand)
+                .arg("--dashboard=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--dashboard'");
+        }
+        if !matches!(subcommand, "" | "test") {
+            cargo_llvm_cov(subcommand)
+                .arg("--test-jobs=1")
+                .assert_failure()
+                .stderr_contains("invalid option '--test-jobs'");
+            cargo_llvm_cov(subcommand)
+                .arg("--miri")
+                .assert_failure()
+                .stderr_contains("invalid option '--miri'");
+            cargo_llvm_cov(subcommand)
+                .arg("--measure-overhead")
+                .assert_failure()
+                .stderr_contains("invalid option '--measure-overhead'");
+            cargo_llvm_cov(subcommand)
+                .arg("--watch")
+                .assert_failure()
+                .stderr_contains("invalid option '--watch'");
+            cargo_llvm_cov(subcommand)
+                .arg("--each-feature")
+                .assert_failure()
+                .stderr_contains("invalid option '--each-feature'");
+        }
+        if matches!(subcommand, "show-env" | "clean") {
+            cargo_llvm_cov(subcommand)
+                .arg("--changed-since=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--changed-since'");
+            cargo_llvm_cov(subcommand)
+                .arg("--verify-binary-ids")
+                .assert_failure()
+                .stderr_contains("invalid option '--verify-binary-ids'");
+            cargo_llvm_cov(subcommand)
+                .arg("--allow-empty")
+                .assert_failure()
+                .stderr_contains("invalid option '--allow-empty'");
+            cargo_llvm_cov(subcommand)
+                .arg("--metadata-timestamp")
+                .assert_failure()
+                .stderr_contains("invalid option '--metadata-timestamp'");
+            cargo_llvm_cov(subcommand)
+                .arg("--badges=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--badges'");
+            cargo_llvm_cov(subcommand)
+                .arg("--ratchet=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--ratchet'");
+            cargo_llvm_cov(subcommand)
+                .arg("--save-snapshot=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--save-snapshot'");
+            cargo_llvm_cov(subcommand)
+                .arg("--compare-to=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--compare-to'");
+            cargo_llvm_cov(subcommand)
+                .arg("--diff=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--diff'");
+            cargo_llvm_cov(subcommand)
+                .arg("--exclude-panic-lines")
+                .assert_failure()
+                .stderr_contains("invalid option '--exclude-panic-lines'");
+            cargo_llvm_cov(subcommand)
+                .arg("--exclude-test-code")
+                .assert_failure()
+                .stderr_contains("invalid option '--exclude-test-code'");
+            cargo_llvm_cov(subcommand)
+                .arg("--exclude-cfg=v")
+                .assert_failure()
+                .stderr_contains("invalid option '--exclude-cfg'");
+            cargo_llvm_cov(subcommand)
+                .arg("--group-closures")
+                .assert_failure()
+                .stderr_contains("invalid option '--group-closures'");
+        }
         if !matches!(subcommand, "" | "test" | "nextest" | "nextest-archive" | "clean") {
             for arg in ["--workspace", "--all"] {
                 cargo_llvm_cov(subcommand).arg(arg).assert_failure().stderr_contains(format!(
@@ -427,12 +1002,370 @@ fn invalid_arg() {
     }
 }
 
+#[test]
+fn invalid_arg_badge_thresholds() {
+    cargo_llvm_cov("")
+        .arg("--badge-thresholds=60,80")
+        .assert_failure()
+        .stderr_contains("--badge-thresholds can only be used together with --badges");
+    cargo_llvm_cov("")
+        .args(["--badges=v", "--badge-thresholds=80,60"])
+        .assert_failure()
+        .stderr_contains("--badge-thresholds must satisfy 0 <= LOW < HIGH <= 100");
+}
+
+#[test]
+fn invalid_arg_fuzz() {
+    cargo_llvm_cov("fuzz")
+        .assert_failure()
+        .stderr_contains("the name of the fuzz target is required for fuzz subcommand");
+    cargo_llvm_cov("fuzz")
+        .args(["target", "--bin=v"])
+        .assert_failure()
+        .stderr_contains("invalid option '--bin' for subcommand 'fuzz'");
+    cargo_llvm_cov("fuzz")
+        .args(["target", "--no-run"])
+        .assert_failure()
+        .stderr_contains("invalid option '--no-run' for subcommand 'fuzz'");
+}
+
+#[test]
+fn invalid_arg_sanitizer() {
+    cargo_llvm_cov("")
+        .arg("--sanitizer=thread")
+        .assert_failure()
+        .stderr_contains("--sanitizer must be address or leak, but found `thread`");
+}
+
+#[test]
+fn invalid_arg_path_equivalence() {
+    cargo_llvm_cov("report")
+        .arg("--path-equivalence=vendor/foo")
+        .assert_failure()
+        .stderr_contains("--path-equivalence must be <FROM>,<TO>, but found `vendor/foo`");
+    cargo_llvm_cov("report")
+        .args(["--path-equivalence=vendor/foo,foo", "--html"])
+        .assert_failure()
+        .stderr_contains("may not be used together with --html");
+    cargo_llvm_cov("report")
+        .arg("--path-equivalence=vendor/foo,foo")
+        .assert_failure()
+        .stderr_contains(
+            "may not be used together with the summary printed when no format is specified",
+        );
+}
+
+#[test]
+fn invalid_arg_summary_json_to_stderr() {
+    cargo_llvm_cov("show-env")
+        .arg("--summary-json-to-stderr")
+        .assert_failure()
+        .stderr_contains("invalid option '--summary-json-to-stderr' for subcommand 'show-env'");
+}
+
+#[test]
+fn invalid_arg_profile_update() {
+    cargo_llvm_cov("")
+        .arg("--profile-update=relaxed")
+        .assert_failure()
+        .stderr_contains("--profile-update must be atomic or single, but found `relaxed`");
+}
+
+#[test]
+fn invalid_arg_coverage_level() {
+    cargo_llvm_cov("")
+        .arg("--coverage-level=condition")
+        .assert_failure()
+        .stderr_contains("--coverage-level must be block, branch, or mcdc, but found `condition`");
+    cargo_llvm_cov("")
+        .args(["--coverage-level=block", "--branch"])
+        .assert_failure()
+        .stderr_contains("--coverage-level may not be used together with --branch");
+}
+
+#[test]
+fn invalid_arg_file_summary() {
+    cargo_llvm_cov("")
+        .arg("--sort-by=lines")
+        .assert_failure()
+        .stderr_contains("--sort-by can only be used together with --file-summary");
+    cargo_llvm_cov("")
+        .args(["--file-summary", "--sort-by=percent"])
+        .assert_failure()
+        .stderr_contains("--sort-by must be name, lines, functions, regions, or branches");
+    cargo_llvm_cov("")
+        .args(["--file-summary", "--lcov"])
+        .assert_failure()
+        .stderr_contains("--file-summary may not be used together with --lcov");
+}
+
+#[test]
+fn invalid_arg_each_feature() {
+    cargo_llvm_cov("")
+        .args(["--each-feature", "--features", "a"])
+        .assert_failure()
+        .stderr_contains("--each-feature may not be used together with --features");
+    cargo_llvm_cov("")
+        .args(["--feature-powerset", "--no-default-features"])
+        .assert_failure()
+        .stderr_contains("--feature-powerset may not be used together with --no-default-features");
+    cargo_llvm_cov("")
+        .args(["--each-feature", "--feature-powerset"])
+        .assert_failure()
+        .stderr_contains("--each-feature may not be used together with --feature-powerset");
+    cargo_llvm_cov("")
+        .args(["--depth", "2"])
+        .assert_failure()
+        .stderr_contains("--depth can only be used together with --feature-powerset");
+    cargo_llvm_cov("")
+        .args(["--feature-powerset", "--depth", "0"])
+        .assert_failure()
+        .stderr_contains("--depth must be greater than 0");
+}
+
+#[test]
+fn invalid_arg_coveralls() {
+    cargo_llvm_cov("")
+        .args(["--coveralls", "--summary-only"])
+        .assert_failure()
+        .stderr_contains("--coveralls may not be used together with --summary-only");
+    cargo_llvm_cov("upload")
+        .args(["--coveralls", "--method=PUT", "--file=v"])
+        .assert_failure()
+        .stderr_contains("--coveralls may not be used together with --method");
+}
+
+#[test]
+fn upload_dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("lcov.info"), "TN:\n").unwrap();
+    cargo_llvm_cov("upload")
+        .args(["--url", "https://example.com/${CARGO_LLVM_COV_UPLOAD_TEST_UNSET}"])
+        .args(["--file", "lcov.info", "--upload-dry-run", "payload"])
+        .current_dir(dir)
+        .assert_failure()
+        .stderr_contains("environment variable CARGO_LLVM_COV_UPLOAD_TEST_UNSET is not set");
+    cargo_llvm_cov("upload")
+        .args([
+            "--url",
+            "https://example.com",
+            "--file",
+            "lcov.info",
+            "--upload-dry-run",
+            "payload",
+        ])
+        .current_dir(dir)
+        .assert_success();
+    assert_eq!(fs::read_to_string(dir.join("payload")).unwrap(), "TN:\n");
+}
+
+#[test]
+fn invalid_arg_sarif() {
+    cargo_llvm_cov("")
+        .args(["--sarif", "--summary-only"])
+        .assert_failure()
+        .stderr_contains("--sarif may not be used together with --summary-only");
+    cargo_llvm_cov("")
+        .args(["--sarif", "--json", "--output-path=v"])
+        .assert_failure()
+        .stderr_contains(
+            "--output-path may not be used together with multiple format flags (--json, --sarif)",
+        );
+}
+
+#[test]
+fn invalid_arg_affected_by() {
+    cargo_llvm_cov("report")
+        .args(["--affected-by", "src/lib.rs", "--lcov"])
+        .assert_failure()
+        .stderr_contains("--affected-by may not be used together with --lcov");
+    cargo_llvm_cov("report")
+        .args(["--affected-by", ""])
+        .assert_failure()
+        .stderr_contains("empty string is not allowed in --affected-by");
+}
+
+#[test]
+fn invalid_arg_uncovered_functions() {
+    cargo_llvm_cov("report")
+        .args(["--uncovered-functions", "--debt"])
+        .assert_failure()
+        .stderr_contains("--uncovered-functions may not be used together with --debt");
+    cargo_llvm_cov("report")
+        .args(["--uncovered-functions", "--csv", "--json"])
+        .assert_failure()
+        .stderr_contains("--csv may not be used together with --json");
+}
+
+#[test]
+fn invalid_arg_report_modes() {
+    cargo_llvm_cov("report")
+        .args(["--debt", "--package-summary"])
+        .assert_failure()
+        .stderr_contains("--package-summary may not be used together with --debt");
+    cargo_llvm_cov("report")
+        .args(["--debt", "--fail-under-lines", "80"])
+        .assert_failure()
+        .stderr_contains("--debt may not be used together with --fail-under-lines");
+    cargo_llvm_cov("report")
+        .args(["--debt", "--ratchet", "coverage.json"])
+        .assert_failure()
+        .stderr_contains("--debt may not be used together with --ratchet");
+    cargo_llvm_cov("report")
+        .args(["--missing-examples", "--fail-uncovered-functions", "0"])
+        .assert_failure()
+        .stderr_contains(
+            "--missing-examples may not be used together with --fail-uncovered-functions",
+        );
+    cargo_llvm_cov("report")
+        .args(["--hotspots", "--cobertura"])
+        .assert_failure()
+        .stderr_contains("--hotspots may not be used together with --cobertura");
+    cargo_llvm_cov("report")
+        .args(["--file-summary", "--json"])
+        .assert_failure()
+        .stderr_contains("--file-summary may not be used together with --json");
+}
+
+#[test]
+fn invalid_arg_csv() {
+    cargo_llvm_cov("")
+        .args(["--tsv", "--csv", "--output-path=v"])
+        .assert_failure()
+        .stderr_contains(
+            "--output-path may not be used together with multiple format flags (--csv, --tsv)",
+        );
+    cargo_llvm_cov("")
+        .args(["--csv", "--lcov", "--file-summary"])
+        .assert_failure()
+        .stderr_contains("--csv may not be used together with --lcov");
+    cargo_llvm_cov("")
+        .args(["--csv", "--summary-only"])
+        .assert_failure()
+        .stderr_contains("--csv may not be used together with --summary-only");
+}
+
+#[test]
+fn invalid_arg_jacoco() {
+    cargo_llvm_cov("")
+        .args(["--jacoco", "--summary-only"])
+        .assert_failure()
+        .stderr_contains("--jacoco may not be used together with --summary-only");
+    cargo_llvm_cov("")
+        .args(["--jacoco", "--cobertura", "--debt"])
+        .assert_failure()
+        .stderr_contains("--jacoco may not be used together with --cobertura");
+}
+
+#[test]
+fn invalid_arg_sonarqube() {
+    cargo_llvm_cov("")
+        .args(["--sonarqube", "--summary-only"])
+        .assert_failure()
+        .stderr_contains("--sonarqube may not be used together with --summary-only");
+    cargo_llvm_cov("")
+        .args(["--sonarqube", "--jacoco", "--output-path=v"])
+        .assert_failure()
+        .stderr_contains(
+            "--output-path may not be used together with multiple format flags (--jacoco, \
+             --sonarqube)",
+        );
+}
+
+#[test]
+fn invalid_arg_tui() {
+    for arg in ["--json", "--html", "--output-dir=v", "--badges=v", "--fail-under-lines=1"] {
+        cargo_llvm_cov("tui").arg(arg).assert_failure().stderr_contains(format!(
+            "invalid option '{}' for subcommand 'tui'",
+            arg.split_once('=').map_or(arg, |(flag, _)| flag)
+        ));
+    }
+}
+
+#[test]
+fn invalid_arg_show() {
+    cargo_llvm_cov("show")
+        .assert_failure()
+        .stderr_contains("at least one file is required for show subcommand");
+    cargo_llvm_cov("show")
+        .arg("")
+        .assert_failure()
+        .stderr_contains("empty string is not allowed in the files of show subcommand");
+    for arg in ["--lcov", "--html", "--output-path=v", "--fail-under-lines=1"] {
+        cargo_llvm_cov("show").args([arg, "src/lib.rs"]).assert_failure().stderr_contains(format!(
+            "invalid option '{}' for subcommand 'show'",
+            arg.split_once('=').map_or(arg, |(flag, _)| flag)
+        ));
+    }
+}
+
+#[test]
+fn invalid_arg_stats() {
+    for arg in ["--lcov", "--open", "--output-path=v", "--fail-under-regions=1"] {
+        cargo_llvm_cov("stats").arg(arg).assert_failure().stderr_contains(format!(
+            "invalid option '{}' for subcommand 'stats'",
+            arg.split_once('=').map_or(arg, |(flag, _)| flag)
+        ));
+    }
+}
+
+#[test]
+fn invalid_arg_collect() {
+    cargo_llvm_cov("collect")
+        .args(["--chip", "nRF52840_xxAA", "--address", "0x20000000", "--size", "1024"])
+        .assert_failure()
+        .stderr_contains("--probe-rs is required for collect subcommand");
+    cargo_llvm_cov("collect")
+        .args(["--probe-rs", "--chip", "nRF52840_xxAA", "--symbol", "COVERAGE"])
+        .assert_failure()
+        .stderr_contains("--symbol can only be used together with --elf");
+    cargo_llvm_cov("collect")
+        .args(["--probe-rs", "--chip", "nRF52840_xxAA", "--address", "0x20000000"])
+        .assert_failure()
+        .stderr_contains("either --symbol or both --address and --size are required");
+    cargo_llvm_cov("collect")
+        .args(["--probe-rs", "--chip", "nRF52840_xxAA", "--address", "0xzz", "--size", "1024"])
+        .assert_failure()
+        .stderr_contains("invalid value '0xzz' for --address");
+    cargo_llvm_cov("report")
+        .arg("--probe-rs")
+        .assert_failure()
+        .stderr_contains("invalid option '--probe-rs' for subcommand 'report'");
+}
+
+#[test]
+fn invalid_arg_self_update() {
+    cargo_llvm_cov("self-update")
+        .args(["--version", ""])
+        .assert_failure()
+        .stderr_contains("empty string is not allowed in --version");
+    cargo_llvm_cov("self-update")
+        .arg("--ratchet=ratchet.json")
+        .assert_failure()
+        .stderr_contains("invalid option '--ratchet' for subcommand 'self-update'");
+    cargo_llvm_cov("report")
+        .args(["--version", "0.6.0"])
+        .assert_failure()
+        .stderr_contains("invalid option '--version' for subcommand 'report'");
+}
+
+#[test]
+fn invalid_arg_timings() {
+    for subcommand in ["report", "clean", "show-env"] {
+        cargo_llvm_cov(subcommand)
+            .arg("--timings")
+            .assert_failure()
+            .stderr_contains(format!("invalid option '--timings' for subcommand '{subcommand}'"));
+    }
+}
+
 #[test]
 fn invalid_arg_no_passthrough() {
     // These subcommands don't allow passthrough args.
     // In other subcommands, if passthrough args are invalid,
     // it will be detected by cargo or cargo-nextest.
-    for subcommand in ["report", "clean", "show-env"] {
+    for subcommand in ["report", "clean", "show-env", "tui", "stats", "init", "self-update"] {
         cargo_llvm_cov(subcommand)
             .arg("-a")
             .assert_failure()
@@ -445,7 +1378,16 @@ fn invalid_arg_no_passthrough() {
             .arg("c")
             .assert_failure()
             .stderr_contains("unexpected argument \"c\"");
+        // Options before the subcommand are passed through only if the subcommand allows it.
+        cargo_llvm_cov("")
+            .args(["--b", subcommand])
+            .assert_failure()
+            .stderr_contains(format!("invalid option '--b' for subcommand '{subcommand}'"));
     }
+    cargo_llvm_cov("")
+        .args(["--target-dir", ""])
+        .assert_failure()
+        .stderr_contains("empty string is not allowed in --target-dir");
 }
 
 #[test]